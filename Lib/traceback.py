@@ -502,6 +502,7 @@ class TracebackException:
         self.__context__ = context
         self.__suppress_context__ = \
             exc_value.__suppress_context__ if exc_value else False
+        self.__notes__ = getattr(exc_value, '__notes__', None)
         # TODO: locals.
         self.stack = StackSummary.extract(
             walk_tb(exc_traceback), limit=limit, lookup_lines=lookup_lines,
@@ -564,9 +565,18 @@ class TracebackException:
 
         if not issubclass(self.exc_type, SyntaxError):
             yield _format_final_exc_line(stype, self._str)
-            return
-
-        # It was a syntax error; show exactly where the problem was found.
+        else:
+            yield from self._format_syntax_error(stype)
+        if isinstance(self.__notes__, (list, tuple)):
+            for note in self.__notes__:
+                note = _some_str(note)
+                yield from [line + '\n' for line in note.split('\n')]
+        elif self.__notes__ is not None:
+            yield '{}\n'.format(repr(self.__notes__))
+
+    def _format_syntax_error(self, stype):
+        """Format SyntaxError exceptions (internal helper)."""
+        # Show exactly where the problem was found.
         filename = self.filename or "<string>"
         lineno = str(self.lineno) or '?'
         yield '  File "{}", line {}\n'.format(filename, lineno)
@@ -43,6 +43,8 @@ ClassMethodDescriptorType = type(dict.__dict__['fromkeys'])
 
 ModuleType = type(sys)
 
+GenericAlias = type(list[int])
+
 # try:
 #     raise TypeError
 # except TypeError:
@@ -0,0 +1,12 @@
+
+class Point:
+    def __init__(self, x):
+        self.x = x
+
+
+point = Point(1)
+total = 0
+for i in range(1000000):
+    total += point.x
+
+assert total == 1000000
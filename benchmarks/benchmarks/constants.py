@@ -0,0 +1,10 @@
+# Load the same bytes and string constants on every iteration of a loop.
+
+n = 100000
+total = 0
+i = 0
+while i < n:
+    total = total + len(b'bytes') + len('constant')
+    i = i + 1
+
+assert total == 1300000
@@ -0,0 +1,18 @@
+# Raise and catch an exception on every iteration of a loop, reading where it
+# was raised from its traceback.
+
+def fail(i):
+    raise ValueError(i)
+
+n = 20000
+total = 0
+i = 0
+while i < n:
+    try:
+        fail(i)
+    except ValueError as e:
+        code = e.__traceback__.tb_next.tb_frame.f_code
+        total = total + len(code.co_filename) + len(code.co_name)
+    i = i + 1
+
+assert total > n * len('fail')
@@ -0,0 +1,10 @@
+# The same loop as in local_variables.py, but on module level names.
+
+n = 100000
+total = 0
+i = 0
+while i < n:
+    total = total + i * i
+    i = i + 1
+
+assert total == 333328333350000
@@ -0,0 +1,12 @@
+# The same loop as in global_variables.py, but on the locals of a function.
+
+def sum_of_squares(n):
+    total = 0
+    i = 0
+    while i < n:
+        total = total + i * i
+        i = i + 1
+    return total
+
+
+assert sum_of_squares(100000) == 333328333350000
@@ -0,0 +1,14 @@
+
+class Counter:
+    def __init__(self):
+        self.count = 0
+
+    def increment(self, amount):
+        self.count += amount
+
+
+counter = Counter()
+for i in range(100000):
+    counter.increment(1)
+
+assert counter.count == 100000
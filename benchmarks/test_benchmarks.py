@@ -18,6 +18,11 @@ pythons = [
 benchmarks = [
     ['benchmarks/nbody.py'],
     ['benchmarks/mandelbrot.py'],
+    ['benchmarks/method_calls.py'],
+    ['benchmarks/attribute_access.py'],
+    ['benchmarks/local_variables.py'],
+    ['benchmarks/global_variables.py'],
+    ['benchmarks/constants.py'],
 ]
 
 exe_ids = ['cpython', 'rustpython']
@@ -6,18 +6,38 @@ use num_bigint::BigInt;
 use num_complex::Complex64;
 use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet};
+use std::error::Error;
 use std::fmt;
+use std::rc::Rc;
 
 /// Sourcode location.
 #[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
 pub struct Location {
     row: usize,
     column: usize,
+    /// Where the source span ends, just after its last character. A location
+    /// without a span ends where it starts.
+    end_row: usize,
+    end_column: usize,
 }
 
 impl Location {
     pub fn new(row: usize, column: usize) -> Self {
-        Location { row, column }
+        Location {
+            row,
+            column,
+            end_row: row,
+            end_column: column,
+        }
+    }
+
+    /// Extend this location into a span ending at the given position.
+    pub fn with_end(self, end_row: usize, end_column: usize) -> Self {
+        Location {
+            end_row,
+            end_column,
+            ..self
+        }
     }
 
     pub fn row(&self) -> usize {
@@ -27,6 +47,14 @@ impl Location {
     pub fn column(&self) -> usize {
         self.column
     }
+
+    pub fn end_row(&self) -> usize {
+        self.end_row
+    }
+
+    pub fn end_column(&self) -> usize {
+        self.end_column
+    }
 }
 
 /// Primary container of a single code object. Each python function has
@@ -34,17 +62,31 @@ impl Location {
 #[derive(Clone, PartialEq, Serialize, Deserialize)]
 pub struct CodeObject {
     pub instructions: Vec<Instruction>,
-    /// Jump targets.
+    /// Jump targets. The compiler resolves every label to the offset of its
+    /// target when it finishes a code object, leaving this an identity map
+    /// which is only used for disassembly.
     pub label_map: HashMap<Label, usize>,
     pub locations: Vec<Location>,
     pub arg_names: Vec<String>, // Names of positional arguments
     pub varargs: Varargs,       // *args or *
     pub kwonlyarg_names: Vec<String>,
+    /// How many of the positional arguments are before a `/`, so can't be passed by keyword.
+    pub posonlyarg_count: usize,
     pub varkeywords: Varargs, // **kwargs or **
     pub source_path: String,
     pub first_line_number: usize,
     pub obj_name: String, // Name of the object that created this code object
     pub is_generator: bool,
+    pub is_coroutine: bool,    // Defined with `async def`
+    pub is_class_body: bool,   // Runs the body of a class statement
+    pub max_stacksize: usize,  // The deepest the value stack gets, computed by the compiler
+    pub varnames: Vec<String>, // Local names of a function, stored in the frame's fast slots
+    pub cellvars: Vec<String>, // Local names which are referenced by nested scopes
+    pub freevars: Vec<String>, // Names which are taken from the closure
+    /// The source text this code was compiled from, for showing source lines
+    /// when the code does not come from a file. Not serialized.
+    #[serde(skip)]
+    pub source: Option<Rc<str>>,
 }
 
 bitflags! {
@@ -53,11 +95,39 @@ bitflags! {
         const HAS_DEFAULTS = 0x01;
         const HAS_KW_ONLY_DEFAULTS = 0x02;
         const HAS_ANNOTATIONS = 0x04;
+        const HAS_CLOSURE = 0x08;
     }
 }
 
 pub type Label = usize;
 
+/// Starts serialized code objects. The last byte is the version of the
+/// format, to be bumped whenever `CodeObject` or `Instruction` changes.
+pub const MAGIC_NUMBER: [u8; 4] = *b"RPc\x02";
+
+#[derive(Debug, PartialEq)]
+pub enum CodeDeserializeError {
+    /// The data doesn't start with `MAGIC_NUMBER`, so it was written by
+    /// another version or isn't a code object at all.
+    BadMagicNumber,
+    Malformed,
+}
+
+impl fmt::Display for CodeDeserializeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CodeDeserializeError::BadMagicNumber => write!(f, "bad magic number"),
+            CodeDeserializeError::Malformed => write!(f, "malformed code object"),
+        }
+    }
+}
+
+impl Error for CodeDeserializeError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        None
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum NameScope {
     Local,
@@ -99,6 +169,36 @@ pub enum Instruction {
     DeleteName {
         name: String,
     },
+    /// Load the local of a function at index `idx` of `varnames`.
+    LoadFast {
+        idx: usize,
+    },
+    StoreFast {
+        idx: usize,
+    },
+    DeleteFast {
+        idx: usize,
+    },
+    /// Load the contents of the cell for a name in `cellvars` or `freevars`.
+    LoadDeref {
+        name: String,
+    },
+    /// Load a free variable in a class body, from the class namespace if it is
+    /// set there, otherwise from its cell.
+    LoadClassDeref {
+        name: String,
+    },
+    StoreDeref {
+        name: String,
+    },
+    DeleteDeref {
+        name: String,
+    },
+    /// Load the cell itself, to build the closure of a nested function.
+    LoadClosure {
+        name: String,
+    },
+    BinarySubscr,
     StoreSubscript,
     DeleteSubscript,
     StoreAttr {
@@ -120,6 +220,11 @@ pub enum Instruction {
     LoadAttr {
         name: String,
     },
+    /// Look up a method on the top of the stack, without creating a bound method.
+    /// Pushes either the unbound function and the object, or None and the attribute.
+    LoadMethod {
+        name: String,
+    },
     CompareOperation {
         op: ComparisonOperator,
     },
@@ -129,6 +234,20 @@ pub enum Instruction {
     },
     Duplicate,
     GetIter,
+    /// Replace the value on top of the stack with the iterator that a
+    /// `yield from` on it delegates to, which is a generator itself.
+    GetYieldFromIter,
+    /// Replace the value on top of the stack with the iterator that an
+    /// `await` on it drives.
+    GetAwaitable,
+    /// Replace the value on top of the stack with its `__aiter__()`.
+    GetAIter,
+    /// Push the awaitable returned by `__anext__()` of the asynchronous
+    /// iterator on top of the stack, keeping the iterator.
+    GetANext,
+    /// Handle the exception which ended the awaiting of `__anext__()` in an
+    /// `async for`: `StopAsyncIteration` ends the loop, anything else is raised.
+    EndAsyncFor,
     Pass,
     Continue,
     Break,
@@ -159,12 +278,22 @@ pub enum Instruction {
     CallFunction {
         typ: CallType,
     },
+    /// Call a method loaded by `LoadMethod` with `amount` positional arguments.
+    CallMethod {
+        amount: usize,
+    },
     ForIter {
         target: Label,
     },
     ReturnValue,
     YieldValue,
-    YieldFrom,
+    /// Send the value on top of the stack into the iterator below it, which a
+    /// `yield from` or an `await` delegates to. A value the iterator yields
+    /// replaces the sent one, to be yielded in turn. Once the iterator is done,
+    /// its return value replaces both, and execution continues at `target`.
+    Send {
+        target: Label,
+    },
     SetupLoop {
         start: Label,
         end: Label,
@@ -172,13 +301,41 @@ pub enum Instruction {
     SetupExcept {
         handler: Label,
     },
+    SetupFinally {
+        handler: Label,
+    },
+    /// Enter a finally block, without returning, excepting, just because we are there.
+    EnterFinally,
+    /// Marker bytecode for the end of a finally sequence.
+    /// When this bytecode is executed, the eval loop does one of those things:
+    /// - Continue at a certain bytecode position
+    /// - Propagate the exception
+    /// - Return from a function
+    /// - Do nothing at all, just continue
+    EndFinally,
     SetupWith {
         end: Label,
     },
     CleanupWith {
         end: Label,
     },
+    /// Call `__aenter__` on the context manager on top of the stack, keeping
+    /// the manager and pushing the awaitable it returns.
+    BeforeAsyncWith,
+    /// Enter an `async with` block, taking the context manager from under
+    /// the awaited result of `__aenter__`.
+    SetupAsyncWith {
+        end: Label,
+    },
+    /// Leave an `async with` block normally, pushing the awaitable returned
+    /// by `__aexit__(None, None, None)`.
+    CleanupAsyncWith,
+    /// Pop the awaited result of `__aexit__` and suppress the exception being
+    /// handled if it is true.
+    EndAsyncWith,
     PopBlock,
+    /// Push the builtin `AssertionError`, regardless of what the name is bound to.
+    LoadAssertionError,
     Raise {
         argc: usize,
     },
@@ -188,6 +345,7 @@ pub enum Instruction {
     BuildTuple {
         size: usize,
         unpack: bool,
+        for_call: bool,
     },
     BuildList {
         size: usize,
@@ -200,6 +358,11 @@ pub enum Instruction {
     BuildMap {
         size: usize,
         unpack: bool,
+        for_call: bool,
+    },
+    /// Build a dict from `size` values and the tuple of their keys above them.
+    BuildConstKeyMap {
+        size: usize,
     },
     BuildSlice {
         size: usize,
@@ -214,6 +377,7 @@ pub enum Instruction {
         i: usize,
     },
     PrintExpr,
+    SetupAnnotations,
     LoadBuildClass,
     UnpackSequence {
         size: usize,
@@ -223,9 +387,9 @@ pub enum Instruction {
         after: usize,
     },
     Unpack,
+    /// Format a value with the format spec above it on the stack.
     FormatValue {
         conversion: Option<ConversionFlag>,
-        spec: String,
     },
     PopException,
     Reverse {
@@ -280,7 +444,6 @@ pub enum BinaryOperator {
     Modulo,
     Add,
     Subtract,
-    Subscript,
     Lshift,
     Rshift,
     And,
@@ -326,6 +489,7 @@ impl CodeObject {
             label_map: HashMap::new(),
             locations: Vec::new(),
             arg_names,
+            posonlyarg_count: 0,
             varargs,
             kwonlyarg_names,
             varkeywords,
@@ -333,9 +497,48 @@ impl CodeObject {
             first_line_number,
             obj_name,
             is_generator: false,
+            is_coroutine: false,
+            is_class_body: false,
+            max_stacksize: 0,
+            varnames: Vec::new(),
+            cellvars: Vec::new(),
+            freevars: Vec::new(),
+            source: None,
         }
     }
 
+    /// Retain the source text on this code object and all the code objects
+    /// nested in its constants.
+    pub fn set_source(&mut self, source: Rc<str>) {
+        for instruction in self.instructions.iter_mut() {
+            if let Instruction::LoadConst {
+                value: Constant::Code { code },
+            } = instruction
+            {
+                code.set_source(source.clone());
+            }
+        }
+        self.source = Some(source);
+    }
+
+    /// Serialize this code object, prefixed with `MAGIC_NUMBER` so that
+    /// `from_bytes` can reject bytecode written by an incompatible version.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut data = MAGIC_NUMBER.to_vec();
+        bincode::serialize_into(&mut data, self).expect("Failed to serialize code object");
+        data
+    }
+
+    /// Load a code object written by `to_bytes`. The result isn't verified,
+    /// see `verify::verify`.
+    pub fn from_bytes(data: &[u8]) -> Result<CodeObject, CodeDeserializeError> {
+        if !data.starts_with(&MAGIC_NUMBER) {
+            return Err(CodeDeserializeError::BadMagicNumber);
+        }
+        bincode::deserialize(&data[MAGIC_NUMBER.len()..])
+            .map_err(|_| CodeDeserializeError::Malformed)
+    }
+
     pub fn get_constants(&self) -> impl Iterator<Item = &Constant> {
         self.instructions.iter().filter_map(|x| {
             if let Instruction::LoadConst { value } = x {
@@ -346,14 +549,78 @@ impl CodeObject {
         })
     }
 
+    /// The global, attribute and imported names used by the instructions, each
+    /// once, in order of first use.
+    pub fn get_names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = Vec::new();
+        for instruction in &self.instructions {
+            let used: Vec<&String> = match instruction {
+                Instruction::Import { name, symbols, .. } => {
+                    name.iter().chain(symbols.iter()).collect()
+                }
+                Instruction::ImportFrom { name }
+                | Instruction::LoadName { name, .. }
+                | Instruction::StoreName { name, .. }
+                | Instruction::DeleteName { name }
+                | Instruction::StoreAttr { name }
+                | Instruction::DeleteAttr { name }
+                | Instruction::LoadAttr { name }
+                | Instruction::LoadMethod { name } => vec![name],
+                _ => continue,
+            };
+            for name in used {
+                if !names.contains(&name.as_str()) {
+                    names.push(name);
+                }
+            }
+        }
+        names
+    }
+
+    /// The names of the arguments followed by the other locals in fast slots,
+    /// like CPython's `co_varnames`. Arguments are listed even if they are
+    /// captured by an inner function, which makes them cells instead.
+    pub fn get_varnames(&self) -> Vec<&str> {
+        let varargs = [&self.varargs, &self.varkeywords];
+        let star_names = varargs.iter().filter_map(|varargs| match varargs {
+            Varargs::Named(name) => Some(name),
+            _ => None,
+        });
+        let mut names: Vec<&str> = self
+            .arg_names
+            .iter()
+            .chain(self.kwonlyarg_names.iter())
+            .chain(star_names)
+            .map(String::as_str)
+            .collect();
+        for name in &self.varnames {
+            if !names.contains(&name.as_str()) {
+                names.push(name);
+            }
+        }
+        names
+    }
+
     fn display_inner(
         &self,
         f: &mut fmt::Formatter,
         expand_codeobjects: bool,
+        line_numbers: bool,
         level: usize,
     ) -> fmt::Result {
         let label_targets: HashSet<&usize> = self.label_map.values().collect();
+        let mut last_row = None;
         for (offset, instruction) in self.instructions.iter().enumerate() {
+            if line_numbers {
+                // Like CPython's dis, show the line number where a new line starts:
+                let row = self.locations[offset].row();
+                if last_row == Some(row) {
+                    write!(f, "    ")?;
+                } else {
+                    write!(f, "{:>4}", row)?;
+                    last_row = Some(row);
+                }
+            }
             let arrow = if label_targets.contains(&offset) {
                 ">>"
             } else {
@@ -363,7 +630,7 @@ impl CodeObject {
                 write!(f, "          ")?;
             }
             write!(f, "{} {:5} ", arrow, offset)?;
-            instruction.fmt_dis(f, &self.label_map, expand_codeobjects, level)?;
+            instruction.fmt_dis(f, self, expand_codeobjects, level)?;
         }
         Ok(())
     }
@@ -372,7 +639,19 @@ impl CodeObject {
         struct Display<'a>(&'a CodeObject);
         impl fmt::Display for Display<'_> {
             fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-                self.0.display_inner(f, true, 1)
+                self.0.display_inner(f, true, false, 1)
+            }
+        }
+        Display(self)
+    }
+
+    /// The disassembly with the source line of each line's first instruction in
+    /// front, as shown by the `dis` module.
+    pub fn display_line_numbers<'a>(&'a self) -> impl fmt::Display + 'a {
+        struct Display<'a>(&'a CodeObject);
+        impl fmt::Display for Display<'_> {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                self.0.display_inner(f, false, true, 1)
             }
         }
         Display(self)
@@ -381,18 +660,145 @@ impl CodeObject {
 
 impl fmt::Display for CodeObject {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        self.display_inner(f, false, 1)
+        self.display_inner(f, false, false, 1)
     }
 }
 
 impl Instruction {
+    /// The change in depth of the value stack by this instruction. For
+    /// instructions which can jump, `jump` selects whether the effect is taken
+    /// towards the jump target or when continuing with the next instruction.
+    pub fn stack_effect(&self, jump: bool) -> isize {
+        match self {
+            Import { .. } | ImportFrom { .. } => 1,
+            ImportStar => -1,
+            LoadName { .. }
+            | LoadFast { .. }
+            | LoadDeref { .. }
+            | LoadClassDeref { .. }
+            | LoadClosure { .. }
+            | LoadConst { .. }
+            | LoadBuildClass => 1,
+            StoreName { .. } | StoreFast { .. } | StoreDeref { .. } => -1,
+            DeleteName { .. } | DeleteFast { .. } | DeleteDeref { .. } => 0,
+            BinarySubscr => -1,
+            StoreSubscript => -3,
+            DeleteSubscript => -2,
+            StoreAttr { .. } => -2,
+            DeleteAttr { .. } => -1,
+            UnaryOperation { .. } | LoadAttr { .. } => 0,
+            BinaryOperation { .. } | CompareOperation { .. } => -1,
+            LoadMethod { .. } => 1,
+            Pop => -1,
+            Rotate { .. } | Reverse { .. } => 0,
+            Duplicate => 1,
+            GetIter | GetAwaitable | GetAIter | Pass | Continue | Break | Jump { .. } => 0,
+            GetYieldFromIter => 0,
+            JumpIfTrue { .. } | JumpIfFalse { .. } => -1,
+            JumpIfTrueOrPop { .. } | JumpIfFalseOrPop { .. } => {
+                if jump {
+                    0
+                } else {
+                    -1
+                }
+            }
+            MakeFunction { flags } => {
+                let optional = [
+                    FunctionOpArg::HAS_DEFAULTS,
+                    FunctionOpArg::HAS_KW_ONLY_DEFAULTS,
+                    FunctionOpArg::HAS_ANNOTATIONS,
+                    FunctionOpArg::HAS_CLOSURE,
+                ]
+                .iter()
+                .filter(|flag| flags.contains(**flag))
+                .count();
+                -1 - optional as isize
+            }
+            CallFunction { typ } => match typ {
+                CallType::Positional(count) => -(*count as isize),
+                CallType::Keyword(count) => -1 - (*count as isize),
+                CallType::Ex(has_kwargs) => -1 - (*has_kwargs as isize),
+            },
+            CallMethod { amount } => -1 - (*amount as isize),
+            ForIter { .. } => {
+                if jump {
+                    -1
+                } else {
+                    1
+                }
+            }
+            ReturnValue => -1,
+            YieldValue => 0,
+            Send { .. } => {
+                if jump {
+                    -1
+                } else {
+                    0
+                }
+            }
+            SetupLoop { .. } | SetupFinally { .. } | EnterFinally | EndFinally => 0,
+            // The handler is entered with the exception pushed.
+            SetupExcept { .. } => {
+                if jump {
+                    1
+                } else {
+                    0
+                }
+            }
+            // A suppressed exception continues at the end without the result of `__enter__`.
+            SetupWith { .. } => {
+                if jump {
+                    -1
+                } else {
+                    0
+                }
+            }
+            CleanupWith { .. } | PopBlock | PopException => 0,
+            BeforeAsyncWith | CleanupAsyncWith | GetANext => 1,
+            // Pops the exception and the asynchronous iterator.
+            EndAsyncFor => -2,
+            // When unwinding, the end is reached with the `__aexit__` awaitable pushed.
+            SetupAsyncWith { .. } | EndAsyncWith => -1,
+            LoadAssertionError => 1,
+            Raise { argc } => -(*argc as isize),
+            BuildString { size }
+            | BuildTuple { size, .. }
+            | BuildList { size, .. }
+            | BuildSet { size, .. }
+            | BuildSlice { size } => 1 - (*size as isize),
+            BuildMap { size, unpack, .. } => {
+                if *unpack {
+                    1 - (*size as isize)
+                } else {
+                    1 - 2 * (*size as isize)
+                }
+            }
+            // Pops the values and the keys tuple.
+            BuildConstKeyMap { size } => -(*size as isize),
+            ListAppend { .. } | SetAdd { .. } => -1,
+            MapAdd { .. } => -2,
+            PrintExpr => -1,
+            SetupAnnotations => 0,
+            UnpackSequence { size } => *size as isize - 1,
+            UnpackEx { before, after } => (*before + *after) as isize,
+            // The number of values is only known at runtime.
+            Unpack => 0,
+            FormatValue { .. } => -1,
+        }
+    }
+
     fn fmt_dis(
         &self,
         f: &mut fmt::Formatter,
-        label_map: &HashMap<Label, usize>,
+        code: &CodeObject,
         expand_codeobjects: bool,
         level: usize,
     ) -> fmt::Result {
+        let label_map = &code.label_map;
+        let varname = |idx: &usize| match code.varnames.get(*idx) {
+            Some(name) => name.as_str(),
+            None => "?",
+        };
         macro_rules! w {
             ($variant:ident) => {
                 write!(f, "{:20}\n", stringify!($variant))
@@ -431,6 +837,15 @@ impl Instruction {
             LoadName { name, scope } => w!(LoadName, name, format!("{:?}", scope)),
             StoreName { name, scope } => w!(StoreName, name, format!("{:?}", scope)),
             DeleteName { name } => w!(DeleteName, name),
+            LoadFast { idx } => w!(LoadFast, idx, varname(idx)),
+            StoreFast { idx } => w!(StoreFast, idx, varname(idx)),
+            DeleteFast { idx } => w!(DeleteFast, idx, varname(idx)),
+            LoadDeref { name } => w!(LoadDeref, name),
+            LoadClassDeref { name } => w!(LoadClassDeref, name),
+            StoreDeref { name } => w!(StoreDeref, name),
+            DeleteDeref { name } => w!(DeleteDeref, name),
+            LoadClosure { name } => w!(LoadClosure, name),
+            BinarySubscr => w!(BinarySubscr),
             StoreSubscript => w!(StoreSubscript),
             DeleteSubscript => w!(DeleteSubscript),
             StoreAttr { name } => w!(StoreAttr, name),
@@ -438,7 +853,7 @@ impl Instruction {
             LoadConst { value } => match value {
                 Constant::Code { code } if expand_codeobjects => {
                     writeln!(f, "LoadConst ({:?}):", code)?;
-                    code.display_inner(f, true, level + 1)?;
+                    code.display_inner(f, true, false, level + 1)?;
                     Ok(())
                 }
                 _ => w!(LoadConst, value),
@@ -446,11 +861,17 @@ impl Instruction {
             UnaryOperation { op } => w!(UnaryOperation, format!("{:?}", op)),
             BinaryOperation { op, inplace } => w!(BinaryOperation, format!("{:?}", op), inplace),
             LoadAttr { name } => w!(LoadAttr, name),
+            LoadMethod { name } => w!(LoadMethod, name),
             CompareOperation { op } => w!(CompareOperation, format!("{:?}", op)),
             Pop => w!(Pop),
             Rotate { amount } => w!(Rotate, amount),
             Duplicate => w!(Duplicate),
             GetIter => w!(GetIter),
+            GetYieldFromIter => w!(GetYieldFromIter),
+            GetAwaitable => w!(GetAwaitable),
+            GetAIter => w!(GetAIter),
+            GetANext => w!(GetANext),
+            EndAsyncFor => w!(EndAsyncFor),
             Pass => w!(Pass),
             Continue => w!(Continue),
             Break => w!(Break),
@@ -461,31 +882,50 @@ impl Instruction {
             JumpIfFalseOrPop { target } => w!(JumpIfFalseOrPop, label_map[target]),
             MakeFunction { flags } => w!(MakeFunction, format!("{:?}", flags)),
             CallFunction { typ } => w!(CallFunction, format!("{:?}", typ)),
+            CallMethod { amount } => w!(CallMethod, amount),
             ForIter { target } => w!(ForIter, label_map[target]),
             ReturnValue => w!(ReturnValue),
             YieldValue => w!(YieldValue),
-            YieldFrom => w!(YieldFrom),
+            Send { target } => w!(Send, label_map[target]),
             SetupLoop { start, end } => w!(SetupLoop, label_map[start], label_map[end]),
-            SetupExcept { handler } => w!(SetupExcept, handler),
-            SetupWith { end } => w!(SetupWith, end),
-            CleanupWith { end } => w!(CleanupWith, end),
+            SetupExcept { handler } => w!(SetupExcept, label_map[handler]),
+            SetupFinally { handler } => w!(SetupFinally, label_map[handler]),
+            EnterFinally => w!(EnterFinally),
+            EndFinally => w!(EndFinally),
+            SetupWith { end } => w!(SetupWith, label_map[end]),
+            CleanupWith { end } => w!(CleanupWith, label_map[end]),
+            BeforeAsyncWith => w!(BeforeAsyncWith),
+            SetupAsyncWith { end } => w!(SetupAsyncWith, label_map[end]),
+            CleanupAsyncWith => w!(CleanupAsyncWith),
+            EndAsyncWith => w!(EndAsyncWith),
             PopBlock => w!(PopBlock),
+            LoadAssertionError => w!(LoadAssertionError),
             Raise { argc } => w!(Raise, argc),
             BuildString { size } => w!(BuildString, size),
-            BuildTuple { size, unpack } => w!(BuildTuple, size, unpack),
+            BuildTuple {
+                size,
+                unpack,
+                for_call,
+            } => w!(BuildTuple, size, unpack, for_call),
             BuildList { size, unpack } => w!(BuildList, size, unpack),
             BuildSet { size, unpack } => w!(BuildSet, size, unpack),
-            BuildMap { size, unpack } => w!(BuildMap, size, unpack),
+            BuildMap {
+                size,
+                unpack,
+                for_call,
+            } => w!(BuildMap, size, unpack, for_call),
+            BuildConstKeyMap { size } => w!(BuildConstKeyMap, size),
             BuildSlice { size } => w!(BuildSlice, size),
             ListAppend { i } => w!(ListAppend, i),
             SetAdd { i } => w!(SetAdd, i),
             MapAdd { i } => w!(MapAdd, i),
             PrintExpr => w!(PrintExpr),
+            SetupAnnotations => w!(SetupAnnotations),
             LoadBuildClass => w!(LoadBuildClass),
             UnpackSequence { size } => w!(UnpackSequence, size),
             UnpackEx { before, after } => w!(UnpackEx, before, after),
             Unpack => w!(Unpack),
-            FormatValue { spec, .. } => w!(FormatValue, spec), // TODO: write conversion
+            FormatValue { conversion } => w!(FormatValue, format!("{:?}", conversion)),
             PopException => w!(PopException),
             Reverse { amount } => w!(Reverse, amount),
         }
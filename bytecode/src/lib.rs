@@ -2,3 +2,4 @@
 #![doc(html_root_url = "https://docs.rs/rustpython-bytecode/")]
 
 pub mod bytecode;
+pub mod verify;
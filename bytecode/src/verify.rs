@@ -0,0 +1,574 @@
+//! Check that a code object is well-formed before running it. The frame
+//! trusts its code completely, so bytecode from an untrusted source, such as
+//! `marshal.loads`, should be verified first.
+
+use crate::bytecode::{CallType, CodeObject, Constant, Instruction};
+use std::error::Error;
+use std::fmt;
+
+#[derive(Debug, PartialEq)]
+pub struct VerifyError {
+    pub error: VerifyErrorType,
+    /// The name of the code object containing the offending instruction.
+    pub obj_name: String,
+    pub offset: usize,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum VerifyErrorType {
+    /// A jump to a label which is not in the label map or past the last instruction.
+    InvalidJumpTarget(usize),
+    /// An instruction takes more values than the stack holds.
+    StackUnderflow { needed: usize, depth: usize },
+    /// The stack gets deeper than the code object's `max_stacksize`.
+    StackOverflow,
+    /// Paths reaching the same instruction disagree on the stack depth.
+    InconsistentStackDepth,
+    /// A block is popped when none is set up.
+    BlockUnderflow,
+    /// A `break` or `continue` without a loop block.
+    OutsideLoop,
+    /// Paths reaching the same instruction disagree on the block nesting.
+    InconsistentBlockDepth,
+    /// A fast local which is not in `varnames`.
+    InvalidLocal(usize),
+    /// A name which is in neither `cellvars` nor `freevars`.
+    InvalidCell(String),
+    /// A raise with more than three values, or a slice of other than two or three.
+    InvalidArgumentCount(usize),
+    /// Execution runs past the last instruction.
+    MissingReturn,
+    /// There is not exactly one location for each instruction.
+    LocationCount {
+        locations: usize,
+        instructions: usize,
+    },
+}
+
+impl fmt::Display for VerifyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.error {
+            VerifyErrorType::InvalidJumpTarget(target) => {
+                write!(f, "invalid jump target {}", target)
+            }
+            VerifyErrorType::StackUnderflow { needed, depth } => write!(
+                f,
+                "instruction needs {} values on the stack, but it holds {}",
+                needed, depth
+            ),
+            VerifyErrorType::StackOverflow => write!(f, "stack deeper than max_stacksize"),
+            VerifyErrorType::InconsistentStackDepth => write!(f, "inconsistent stack depth"),
+            VerifyErrorType::BlockUnderflow => write!(f, "no block to pop"),
+            VerifyErrorType::OutsideLoop => write!(f, "'break' or 'continue' outside a loop"),
+            VerifyErrorType::InconsistentBlockDepth => write!(f, "inconsistent block nesting"),
+            VerifyErrorType::InvalidLocal(idx) => write!(f, "invalid local variable {}", idx),
+            VerifyErrorType::InvalidCell(name) => write!(f, "invalid cell variable '{}'", name),
+            VerifyErrorType::InvalidArgumentCount(count) => {
+                write!(f, "invalid argument count {}", count)
+            }
+            VerifyErrorType::MissingReturn => write!(f, "code runs past its last instruction"),
+            VerifyErrorType::LocationCount {
+                locations,
+                instructions,
+            } => write!(
+                f,
+                "{} locations for {} instructions",
+                locations, instructions
+            ),
+        }?;
+
+        write!(f, " at offset {} in {}", self.offset, self.obj_name)
+    }
+}
+
+impl Error for VerifyError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        None
+    }
+}
+
+/// Verify `code` and the code objects nested in its constants, by following
+/// every path through the instructions with the same stack effects the
+/// compiler uses to compute `max_stacksize`.
+pub fn verify(code: &CodeObject) -> Result<(), VerifyError> {
+    let fail = |error, offset| {
+        Err(VerifyError {
+            error,
+            obj_name: code.obj_name.clone(),
+            offset,
+        })
+    };
+
+    if code.locations.len() != code.instructions.len() {
+        let error = VerifyErrorType::LocationCount {
+            locations: code.locations.len(),
+            instructions: code.instructions.len(),
+        };
+        return fail(error, 0);
+    }
+
+    // The stack and block depth at which each instruction is reached:
+    let mut seen: Vec<Option<(usize, usize)>> = vec![None; code.instructions.len()];
+    let mut pending = vec![(0, State::default())];
+    while let Some((mut offset, mut state)) = pending.pop() {
+        loop {
+            let instruction = match code.instructions.get(offset) {
+                Some(instruction) => instruction,
+                None => return fail(VerifyErrorType::MissingReturn, offset),
+            };
+            match seen[offset] {
+                None => seen[offset] = Some((state.depth, state.blocks)),
+                Some((depth, _)) if depth != state.depth => {
+                    return fail(VerifyErrorType::InconsistentStackDepth, offset);
+                }
+                Some((_, blocks)) if blocks != state.blocks => {
+                    return fail(VerifyErrorType::InconsistentBlockDepth, offset);
+                }
+                Some(_) => break,
+            }
+            if state.depth > code.max_stacksize {
+                return fail(VerifyErrorType::StackOverflow, offset);
+            }
+
+            for &target in jump_targets(instruction).iter().flatten() {
+                if !code.label_map.contains_key(&target) || target >= code.instructions.len() {
+                    return fail(VerifyErrorType::InvalidJumpTarget(target), offset);
+                }
+            }
+            match instruction {
+                Instruction::LoadFast { idx }
+                | Instruction::StoreFast { idx }
+                | Instruction::DeleteFast { idx }
+                    if *idx >= code.varnames.len() =>
+                {
+                    return fail(VerifyErrorType::InvalidLocal(*idx), offset);
+                }
+                Instruction::LoadDeref { name }
+                | Instruction::LoadClassDeref { name }
+                | Instruction::StoreDeref { name }
+                | Instruction::DeleteDeref { name }
+                | Instruction::LoadClosure { name }
+                    if !code.cellvars.contains(name) && !code.freevars.contains(name) =>
+                {
+                    return fail(VerifyErrorType::InvalidCell(name.clone()), offset);
+                }
+                Instruction::Raise { argc: count } if *count > 3 => {
+                    return fail(VerifyErrorType::InvalidArgumentCount(*count), offset);
+                }
+                Instruction::BuildSlice { size: count } if *count != 2 && *count != 3 => {
+                    return fail(VerifyErrorType::InvalidArgumentCount(*count), offset);
+                }
+                Instruction::LoadConst {
+                    value: Constant::Code { code },
+                } => verify(code)?,
+                _ => {}
+            }
+
+            let needed = stack_inputs(instruction);
+            if needed > state.depth {
+                let depth = state.depth;
+                return fail(VerifyErrorType::StackUnderflow { needed, depth }, offset);
+            }
+            if let Some((target, blocks)) = jump(instruction) {
+                let depth = state.depth as isize + instruction.stack_effect(true);
+                let target_state = State {
+                    depth: depth as usize,
+                    blocks: state.blocks + blocks,
+                    loops: state.loops.clone(),
+                };
+                pending.push((target, target_state));
+            }
+            match instruction {
+                Instruction::SetupLoop { end, .. } => {
+                    state.loops.push((*end, state.depth, state.blocks));
+                }
+                // Like the other ways of leaving a block, `break` is followed
+                // to the end of the loop without running finally clauses.
+                Instruction::Break | Instruction::Continue => match state.loops.pop() {
+                    Some((end, depth, blocks)) => {
+                        if let Instruction::Break = instruction {
+                            let loops = state.loops.clone();
+                            pending.push((
+                                end,
+                                State {
+                                    depth,
+                                    blocks,
+                                    loops,
+                                },
+                            ));
+                        }
+                    }
+                    None => return fail(VerifyErrorType::OutsideLoop, offset),
+                },
+                _ => {}
+            }
+            state.depth = (state.depth as isize + instruction.stack_effect(false)) as usize;
+            match block_effect(instruction) {
+                1 => state.blocks += 1,
+                -1 if state.blocks == 0 => return fail(VerifyErrorType::BlockUnderflow, offset),
+                -1 => {
+                    state.blocks -= 1;
+                    let blocks = state.blocks;
+                    state
+                        .loops
+                        .retain(|&(_, _, loop_blocks)| loop_blocks < blocks);
+                }
+                _ => {}
+            }
+
+            match instruction {
+                Instruction::Jump { .. }
+                | Instruction::ReturnValue
+                | Instruction::Raise { .. }
+                | Instruction::Break
+                | Instruction::Continue => break,
+                _ => offset += 1,
+            }
+        }
+    }
+    Ok(())
+}
+
+/// What is known about the frame when it reaches an instruction.
+#[derive(Clone, Default)]
+struct State {
+    depth: usize,
+    blocks: usize,
+    /// The end of each loop being run, with the stack and block depth there.
+    loops: Vec<(usize, usize, usize)>,
+}
+
+/// The labels an instruction refers to.
+fn jump_targets(instruction: &Instruction) -> [Option<usize>; 2] {
+    match instruction {
+        Instruction::Jump { target }
+        | Instruction::JumpIfTrue { target }
+        | Instruction::JumpIfFalse { target }
+        | Instruction::JumpIfTrueOrPop { target }
+        | Instruction::JumpIfFalseOrPop { target }
+        | Instruction::ForIter { target }
+        | Instruction::Send { target }
+        | Instruction::SetupExcept { handler: target }
+        | Instruction::SetupFinally { handler: target }
+        | Instruction::SetupWith { end: target }
+        | Instruction::CleanupWith { end: target }
+        | Instruction::SetupAsyncWith { end: target } => [Some(*target), None],
+        Instruction::SetupLoop { start, end } => [Some(*start), Some(*end)],
+        _ => [None, None],
+    }
+}
+
+/// Where an instruction can continue other than the next instruction,
+/// directly or when the block it sets up is unwound, and how many more
+/// blocks are set up there than before the instruction.
+fn jump(instruction: &Instruction) -> Option<(usize, usize)> {
+    match instruction {
+        Instruction::Jump { target }
+        | Instruction::JumpIfTrue { target }
+        | Instruction::JumpIfFalse { target }
+        | Instruction::JumpIfTrueOrPop { target }
+        | Instruction::JumpIfFalseOrPop { target }
+        | Instruction::ForIter { target }
+        | Instruction::Send { target } => Some((*target, 0)),
+        // A suppressed exception leaves the with block:
+        Instruction::SetupWith { end } => Some((*end, 0)),
+        // The handler runs in a block replacing the one set up:
+        Instruction::SetupExcept { handler }
+        | Instruction::SetupFinally { handler }
+        | Instruction::SetupAsyncWith { end: handler } => Some((*handler, 1)),
+        _ => None,
+    }
+}
+
+/// The change in the number of blocks when continuing with the next instruction.
+fn block_effect(instruction: &Instruction) -> isize {
+    match instruction {
+        Instruction::SetupLoop { .. }
+        | Instruction::SetupExcept { .. }
+        | Instruction::SetupFinally { .. }
+        | Instruction::SetupWith { .. }
+        | Instruction::SetupAsyncWith { .. }
+        | Instruction::EnterFinally => 1,
+        Instruction::PopBlock
+        | Instruction::EndFinally
+        | Instruction::CleanupWith { .. }
+        | Instruction::PopException
+        | Instruction::EndAsyncFor => -1,
+        _ => 0,
+    }
+}
+
+/// The number of values an instruction takes from, or looks at on, the stack.
+fn stack_inputs(instruction: &Instruction) -> usize {
+    match instruction {
+        Instruction::ImportFrom { .. }
+        | Instruction::ImportStar
+        | Instruction::StoreName { .. }
+        | Instruction::StoreFast { .. }
+        | Instruction::StoreDeref { .. }
+        | Instruction::DeleteAttr { .. }
+        | Instruction::UnaryOperation { .. }
+        | Instruction::LoadAttr { .. }
+        | Instruction::LoadMethod { .. }
+        | Instruction::Pop
+        | Instruction::Duplicate
+        | Instruction::GetIter
+        | Instruction::GetYieldFromIter
+        | Instruction::GetAwaitable
+        | Instruction::GetAIter
+        | Instruction::GetANext
+        | Instruction::JumpIfTrue { .. }
+        | Instruction::JumpIfFalse { .. }
+        | Instruction::JumpIfTrueOrPop { .. }
+        | Instruction::JumpIfFalseOrPop { .. }
+        | Instruction::ForIter { .. }
+        | Instruction::ReturnValue
+        | Instruction::YieldValue
+        | Instruction::SetupWith { .. }
+        | Instruction::BeforeAsyncWith
+        | Instruction::EndAsyncWith
+        | Instruction::PrintExpr
+        | Instruction::UnpackSequence { .. }
+        | Instruction::UnpackEx { .. }
+        | Instruction::Unpack => 1,
+        Instruction::BinarySubscr
+        | Instruction::DeleteSubscript
+        | Instruction::StoreAttr { .. }
+        | Instruction::BinaryOperation { .. }
+        | Instruction::CompareOperation { .. }
+        | Instruction::Send { .. }
+        | Instruction::SetupAsyncWith { .. }
+        | Instruction::EndAsyncFor
+        | Instruction::FormatValue { .. } => 2,
+        Instruction::StoreSubscript => 3,
+        Instruction::Rotate { amount } | Instruction::Reverse { amount } => *amount,
+        Instruction::Raise { argc } => *argc,
+        Instruction::BuildString { size }
+        | Instruction::BuildTuple { size, .. }
+        | Instruction::BuildList { size, .. }
+        | Instruction::BuildSet { size, .. }
+        | Instruction::BuildSlice { size } => *size,
+        Instruction::BuildMap { size, unpack, .. } => {
+            if *unpack {
+                *size
+            } else {
+                2 * *size
+            }
+        }
+        Instruction::BuildConstKeyMap { size } => *size + 1,
+        Instruction::ListAppend { i } | Instruction::SetAdd { i } => *i + 1,
+        Instruction::MapAdd { i } => *i + 2,
+        Instruction::CallFunction { typ } => match typ {
+            CallType::Positional(count) => *count + 1,
+            CallType::Keyword(count) => *count + 2,
+            CallType::Ex(has_kwargs) => 2 + *has_kwargs as usize,
+        },
+        Instruction::CallMethod { amount } => *amount + 2,
+        // The function object replaces the code, name and optional values:
+        Instruction::MakeFunction { .. } => (1 - instruction.stack_effect(false)) as usize,
+        _ => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{verify, VerifyErrorType};
+    use crate::bytecode::{CodeObject, Constant, Instruction, Varargs};
+
+    fn make_code(instructions: Vec<Instruction>, labels: &[usize]) -> CodeObject {
+        let mut code = CodeObject::new(
+            vec![],
+            Varargs::None,
+            vec![],
+            Varargs::None,
+            "<test>".to_string(),
+            1,
+            "test".to_string(),
+        );
+        code.locations = vec![Default::default(); instructions.len()];
+        code.instructions = instructions;
+        code.label_map = labels.iter().map(|&offset| (offset, offset)).collect();
+        code.max_stacksize = 2;
+        code
+    }
+
+    fn verify_error(instructions: Vec<Instruction>, labels: &[usize]) -> VerifyErrorType {
+        verify(&make_code(instructions, labels)).unwrap_err().error
+    }
+
+    fn load_none() -> Instruction {
+        Instruction::LoadConst {
+            value: Constant::None,
+        }
+    }
+
+    #[test]
+    fn test_valid_loop() {
+        let code = make_code(
+            vec![
+                Instruction::SetupLoop { start: 1, end: 5 },
+                Instruction::LoadConst {
+                    value: Constant::Boolean { value: true },
+                },
+                Instruction::JumpIfFalse { target: 4 },
+                Instruction::Break,
+                Instruction::PopBlock,
+                load_none(),
+                Instruction::ReturnValue,
+            ],
+            &[1, 4, 5],
+        );
+        assert_eq!(verify(&code), Ok(()));
+    }
+
+    #[test]
+    fn test_invalid_jump_target() {
+        let error = verify_error(vec![Instruction::Jump { target: 7 }], &[]);
+        assert_eq!(error, VerifyErrorType::InvalidJumpTarget(7));
+        let error = verify_error(vec![Instruction::Jump { target: 0 }], &[]);
+        assert_eq!(error, VerifyErrorType::InvalidJumpTarget(0));
+    }
+
+    #[test]
+    fn test_stack_underflow() {
+        let error = verify_error(vec![load_none(), Instruction::Reverse { amount: 2 }], &[]);
+        assert_eq!(
+            error,
+            VerifyErrorType::StackUnderflow {
+                needed: 2,
+                depth: 1
+            }
+        );
+        let error = verify_error(vec![Instruction::ReturnValue], &[]);
+        assert_eq!(
+            error,
+            VerifyErrorType::StackUnderflow {
+                needed: 1,
+                depth: 0
+            }
+        );
+    }
+
+    #[test]
+    fn test_stack_overflow() {
+        let error = verify_error(
+            vec![
+                load_none(),
+                load_none(),
+                load_none(),
+                Instruction::ReturnValue,
+            ],
+            &[],
+        );
+        assert_eq!(error, VerifyErrorType::StackOverflow);
+    }
+
+    #[test]
+    fn test_inconsistent_stack_depth() {
+        // The loop pushes a value each time around:
+        let error = verify_error(vec![load_none(), Instruction::Jump { target: 0 }], &[0]);
+        assert_eq!(error, VerifyErrorType::InconsistentStackDepth);
+    }
+
+    #[test]
+    fn test_block_nesting() {
+        let error = verify_error(vec![Instruction::PopBlock], &[]);
+        assert_eq!(error, VerifyErrorType::BlockUnderflow);
+        let error = verify_error(vec![Instruction::Break], &[]);
+        assert_eq!(error, VerifyErrorType::OutsideLoop);
+        let error = verify_error(
+            vec![
+                Instruction::SetupExcept { handler: 0 },
+                Instruction::Jump { target: 0 },
+            ],
+            &[0],
+        );
+        assert_eq!(error, VerifyErrorType::InconsistentBlockDepth);
+    }
+
+    #[test]
+    fn test_operands() {
+        let error = verify_error(vec![Instruction::LoadFast { idx: 0 }], &[]);
+        assert_eq!(error, VerifyErrorType::InvalidLocal(0));
+        let load_deref = Instruction::LoadDeref {
+            name: "x".to_string(),
+        };
+        let error = verify_error(vec![load_deref.clone()], &[]);
+        assert_eq!(error, VerifyErrorType::InvalidCell("x".to_string()));
+        let mut code = make_code(vec![load_deref, Instruction::ReturnValue], &[]);
+        code.freevars = vec!["x".to_string()];
+        assert_eq!(verify(&code), Ok(()));
+        let error = verify_error(
+            vec![load_none(), load_none(), Instruction::Raise { argc: 4 }],
+            &[],
+        );
+        assert_eq!(error, VerifyErrorType::InvalidArgumentCount(4));
+        let error = verify_error(vec![load_none(), Instruction::BuildSlice { size: 1 }], &[]);
+        assert_eq!(error, VerifyErrorType::InvalidArgumentCount(1));
+        let error = verify_error(vec![load_none()], &[]);
+        assert_eq!(error, VerifyErrorType::MissingReturn);
+    }
+
+    #[test]
+    fn test_location_count() {
+        let mut code = make_code(vec![load_none(), Instruction::ReturnValue], &[]);
+        code.locations.pop();
+        assert_eq!(
+            verify(&code).unwrap_err().error,
+            VerifyErrorType::LocationCount {
+                locations: 1,
+                instructions: 2
+            }
+        );
+    }
+
+    #[test]
+    fn test_nested_code() {
+        let mut nested = make_code(vec![Instruction::Pop], &[]);
+        nested.obj_name = "nested".to_string();
+        let error = verify(&make_code(
+            vec![
+                Instruction::LoadConst {
+                    value: Constant::Code {
+                        code: Box::new(nested),
+                    },
+                },
+                Instruction::ReturnValue,
+            ],
+            &[],
+        ))
+        .unwrap_err();
+        assert_eq!(error.obj_name, "nested");
+        assert_eq!(
+            error.error,
+            VerifyErrorType::StackUnderflow {
+                needed: 1,
+                depth: 0
+            }
+        );
+
+        let mut nested = make_code(vec![load_none(), Instruction::ReturnValue], &[]);
+        nested.obj_name = "nested".to_string();
+        nested.locations.clear();
+        let error = verify(&make_code(
+            vec![
+                Instruction::LoadConst {
+                    value: Constant::Code {
+                        code: Box::new(nested),
+                    },
+                },
+                Instruction::ReturnValue,
+            ],
+            &[],
+        ))
+        .unwrap_err();
+        assert_eq!(error.obj_name, "nested");
+        assert_eq!(
+            error.error,
+            VerifyErrorType::LocationCount {
+                locations: 0,
+                instructions: 2
+            }
+        );
+    }
+}
@@ -7,13 +7,17 @@
 
 use crate::error::{CompileError, CompileErrorType};
 use crate::output_stream::{CodeObjectStream, OutputStream};
-use crate::peephole::PeepholeOptimizer;
+use crate::peephole::{self, PeepholeOptimizer};
 use crate::symboltable::{
-    make_symbol_table, statements_to_symbol_table, Symbol, SymbolScope, SymbolTable,
+    has_future_annotations, make_symbol_table, statements_to_symbol_table, Symbol, SymbolScope,
+    SymbolTable, SymbolTableType,
 };
+use crate::unparse::unparse_annotation;
 use num_complex::Complex64;
 use rustpython_bytecode::bytecode::{self, CallType, CodeObject, Instruction, Varargs};
 use rustpython_parser::{ast, parser};
+use std::collections::HashMap;
+use std::rc::Rc;
 
 type BasicOutputStream = PeepholeOptimizer<CodeObjectStream>;
 
@@ -24,9 +28,15 @@ struct Compiler<O: OutputStream = BasicOutputStream> {
     nxt_label: usize,
     source_path: Option<String>,
     current_source_location: ast::Location,
+    /// Where the source of the node being compiled ends.
+    current_source_end: ast::Location,
     current_qualified_path: Option<String>,
     in_loop: bool,
     in_function_def: bool,
+    in_async_function: bool,
+    /// Whether `from __future__ import annotations` is in effect, so that
+    /// annotations are stored as strings instead of being evaluated.
+    future_annotations: bool,
     optimize: u8,
 }
 
@@ -37,7 +47,7 @@ pub fn compile(
     source_path: String,
     optimize: u8,
 ) -> Result<CodeObject, CompileError> {
-    match mode {
+    let mut code = match mode {
         Mode::Exec => {
             let ast = parser::parse_program(source)?;
             compile_program(ast, source_path, optimize)
@@ -50,7 +60,9 @@ pub fn compile(
             let ast = parser::parse_program(source)?;
             compile_program_single(ast, source_path, optimize)
         }
-    }
+    }?;
+    code.set_source(Rc::from(source));
+    Ok(code)
 }
 
 /// A helper function for the shared code of the different compile functions
@@ -153,9 +165,12 @@ impl<O: OutputStream> Compiler<O> {
             nxt_label: 0,
             source_path: None,
             current_source_location: ast::Location::default(),
+            current_source_end: ast::Location::default(),
             current_qualified_path: None,
             in_loop: false,
             in_function_def: false,
+            in_async_function: false,
+            future_annotations: false,
             optimize,
         }
     }
@@ -178,7 +193,30 @@ impl<O: OutputStream> Compiler<O> {
     }
 
     fn pop_code_object(&mut self) -> CodeObject {
-        self.output_stack.pop().unwrap().into()
+        let mut code: CodeObject = self.output_stack.pop().unwrap().into();
+        // Record which names of the scope live in fast slots and cells:
+        if let Some(table) = self.symbol_table_stack.last() {
+            code.varnames = table
+                .symbols
+                .values()
+                .filter(|symbol| Self::is_fast(table, symbol))
+                .map(|symbol| symbol.name.clone())
+                .collect();
+            for symbol in table.symbols.values() {
+                match symbol.scope {
+                    SymbolScope::Cell => code.cellvars.push(symbol.name.clone()),
+                    SymbolScope::Free | SymbolScope::Nonlocal => {
+                        code.freevars.push(symbol.name.clone())
+                    }
+                    _ if symbol.is_free_class => code.freevars.push(symbol.name.clone()),
+                    _ => {}
+                }
+            }
+        }
+        peephole::optimize_code(&mut code);
+        code.max_stacksize = max_stackdepth(&code);
+        resolve_labels(&mut code);
+        code
     }
 
     fn compile_program(
@@ -190,6 +228,10 @@ impl<O: OutputStream> Compiler<O> {
         self.symbol_table_stack.push(symbol_table);
 
         let (statements, doc) = get_doc(&program.statements);
+        self.future_annotations = has_future_annotations(statements);
+        if find_ann(statements) {
+            self.emit(Instruction::SetupAnnotations);
+        }
         if let Some(value) = doc {
             self.emit(Instruction::LoadConst {
                 value: bytecode::Constant::String { value },
@@ -218,6 +260,11 @@ impl<O: OutputStream> Compiler<O> {
     ) -> Result<(), CompileError> {
         self.symbol_table_stack.push(symbol_table);
 
+        self.future_annotations = has_future_annotations(&program.statements);
+        if find_ann(&program.statements) {
+            self.emit(Instruction::SetupAnnotations);
+        }
+
         let mut emitted_return = false;
 
         for (i, statement) in program.statements.iter().enumerate() {
@@ -282,12 +329,61 @@ impl<O: OutputStream> Compiler<O> {
         match symbol.scope {
             SymbolScope::Global => bytecode::NameScope::Global,
             SymbolScope::Nonlocal => bytecode::NameScope::NonLocal,
+            // A name a function neither binds nor takes from an enclosing
+            // function can only be a global or a builtin, except for the
+            // `__class__` of methods, which is found through the class scope:
+            SymbolScope::Unknown if self.in_function_scope() && name != "__class__" => {
+                bytecode::NameScope::Global
+            }
             SymbolScope::Unknown => bytecode::NameScope::Local,
             SymbolScope::Local => bytecode::NameScope::Local,
+            SymbolScope::Free | SymbolScope::Cell => bytecode::NameScope::Local,
         }
     }
 
+    fn in_function_scope(&self) -> bool {
+        match self.symbol_table_stack.last() {
+            Some(table) => table.typ == SymbolTableType::Function,
+            None => false,
+        }
+    }
+
+    /// The locals of a function are kept in the frame instead of a dict, unless
+    /// a nested scope refers to them.
+    fn is_fast(table: &SymbolTable, symbol: &Symbol) -> bool {
+        table.typ == SymbolTableType::Function
+            && match symbol.scope {
+                SymbolScope::Local => true,
+                _ => false,
+            }
+    }
+
+    /// The position of `name` in the `varnames` of the current code object.
+    fn fast_index(&self, name: &str) -> Option<usize> {
+        let table = self.symbol_table_stack.last().unwrap();
+        table
+            .symbols
+            .values()
+            .filter(|symbol| Self::is_fast(table, symbol))
+            .position(|symbol| symbol.name == name)
+    }
+
     fn load_name(&mut self, name: &str) {
+        if let Some(idx) = self.fast_index(name) {
+            self.emit(Instruction::LoadFast { idx });
+            return;
+        }
+        let symbol = self.lookup_name(name);
+        if symbol.is_deref() {
+            let in_class = self.symbol_table_stack.last().unwrap().typ == SymbolTableType::Class;
+            let name = name.to_string();
+            if let (true, SymbolScope::Free) = (in_class, &symbol.scope) {
+                self.emit(Instruction::LoadClassDeref { name });
+            } else {
+                self.emit(Instruction::LoadDeref { name });
+            }
+            return;
+        }
         let scope = self.scope_for_name(name);
         self.emit(Instruction::LoadName {
             name: name.to_string(),
@@ -296,6 +392,16 @@ impl<O: OutputStream> Compiler<O> {
     }
 
     fn store_name(&mut self, name: &str) {
+        if let Some(idx) = self.fast_index(name) {
+            self.emit(Instruction::StoreFast { idx });
+            return;
+        }
+        if self.lookup_name(name).is_deref() {
+            self.emit(Instruction::StoreDeref {
+                name: name.to_string(),
+            });
+            return;
+        }
         let scope = self.scope_for_name(name);
         self.emit(Instruction::StoreName {
             name: name.to_string(),
@@ -303,9 +409,26 @@ impl<O: OutputStream> Compiler<O> {
         });
     }
 
+    /// Push the cells which a nested code object takes from the current scope as
+    /// a tuple, if it needs any. Returns whether a closure was pushed.
+    fn load_closure(&mut self, code: &CodeObject) -> bool {
+        if code.freevars.is_empty() {
+            return false;
+        }
+        for name in &code.freevars {
+            self.emit(Instruction::LoadClosure { name: name.clone() });
+        }
+        self.emit(Instruction::BuildTuple {
+            size: code.freevars.len(),
+            unpack: false,
+            for_call: false,
+        });
+        true
+    }
+
     fn compile_statement(&mut self, statement: &ast::Statement) -> Result<(), CompileError> {
         trace!("Compiling {:?}", statement);
-        self.set_source_location(&statement.location);
+        self.set_source_location(&statement.location, &statement.end_location);
         use ast::StatementType::*;
 
         match &statement.node {
@@ -413,10 +536,11 @@ impl<O: OutputStream> Compiler<O> {
                 body,
             } => {
                 if *is_async {
-                    unimplemented!("async with");
+                    self.compile_async_with(items, body)?;
                 } else {
-                    let end_label = self.new_label();
+                    let mut end_labels = vec![];
                     for item in items {
+                        let end_label = self.new_label();
                         self.compile_expression(&item.context_expr)?;
                         self.emit(Instruction::SetupWith { end: end_label });
                         match &item.optional_vars {
@@ -427,13 +551,16 @@ impl<O: OutputStream> Compiler<O> {
                                 self.emit(Instruction::Pop);
                             }
                         }
+                        end_labels.push(end_label);
                     }
 
                     self.compile_statements(body)?;
-                    for _ in 0..items.len() {
+                    // An exception suppressed by an inner manager continues
+                    // with the exit of the outer ones:
+                    for end_label in end_labels.into_iter().rev() {
                         self.emit(Instruction::CleanupWith { end: end_label });
+                        self.set_label(end_label);
                     }
-                    self.set_label(end_label);
                 }
             }
             For {
@@ -444,7 +571,7 @@ impl<O: OutputStream> Compiler<O> {
                 orelse,
             } => {
                 if *is_async {
-                    unimplemented!("async for");
+                    self.compile_async_for(target, iter, body, orelse)?
                 } else {
                     self.compile_for(target, iter, body, orelse)?
                 }
@@ -479,13 +606,7 @@ impl<O: OutputStream> Compiler<O> {
                 body,
                 decorator_list,
                 returns,
-            } => {
-                if *is_async {
-                    unimplemented!("async def");
-                } else {
-                    self.compile_function_def(name, args, body, decorator_list, returns)?
-                }
-            }
+            } => self.compile_function_def(name, args, body, decorator_list, returns, *is_async)?,
             ClassDef {
                 name,
                 body,
@@ -498,23 +619,14 @@ impl<O: OutputStream> Compiler<O> {
                 if self.optimize == 0 {
                     let end_label = self.new_label();
                     self.compile_jump_if(test, true, end_label)?;
-                    self.emit(Instruction::LoadName {
-                        name: String::from("AssertionError"),
-                        scope: bytecode::NameScope::Local,
-                    });
-                    match msg {
-                        Some(e) => {
-                            self.compile_expression(e)?;
-                            self.emit(Instruction::CallFunction {
-                                typ: CallType::Positional(1),
-                            });
-                        }
-                        None => {
-                            self.emit(Instruction::CallFunction {
-                                typ: CallType::Positional(0),
-                            });
-                        }
+                    self.emit(Instruction::LoadAssertionError);
+                    if let Some(e) = msg {
+                        self.compile_expression(e)?;
+                        self.emit(Instruction::CallFunction {
+                            typ: CallType::Positional(1),
+                        });
                     }
+                    // Without a message, raising the class instantiates it:
                     self.emit(Instruction::Raise { argc: 1 });
                     self.set_label(end_label);
                 }
@@ -595,9 +707,17 @@ impl<O: OutputStream> Compiler<O> {
     fn compile_delete(&mut self, expression: &ast::Expression) -> Result<(), CompileError> {
         match &expression.node {
             ast::ExpressionType::Identifier { name } => {
-                self.emit(Instruction::DeleteName {
-                    name: name.to_string(),
-                });
+                if let Some(idx) = self.fast_index(name) {
+                    self.emit(Instruction::DeleteFast { idx });
+                } else if self.lookup_name(name).is_deref() {
+                    self.emit(Instruction::DeleteDeref {
+                        name: name.to_string(),
+                    });
+                } else {
+                    self.emit(Instruction::DeleteName {
+                        name: name.to_string(),
+                    });
+                }
             }
             ast::ExpressionType::Attribute { value, name } => {
                 self.compile_expression(value)?;
@@ -640,6 +760,7 @@ impl<O: OutputStream> Compiler<O> {
             self.emit(Instruction::BuildTuple {
                 size,
                 unpack: false,
+                for_call: false,
             });
         }
 
@@ -659,11 +780,12 @@ impl<O: OutputStream> Compiler<O> {
             self.emit(Instruction::BuildMap {
                 size: num_kw_only_defaults,
                 unpack: false,
+                for_call: false,
             });
         }
 
         let line_number = self.get_source_line_number();
-        self.push_output(CodeObject::new(
+        let mut code = CodeObject::new(
             args.args.iter().map(|a| a.arg.clone()).collect(),
             compile_varargs(&args.vararg),
             args.kwonlyargs.iter().map(|a| a.arg.clone()).collect(),
@@ -671,7 +793,9 @@ impl<O: OutputStream> Compiler<O> {
             self.source_path.clone().unwrap(),
             line_number,
             name.to_string(),
-        ));
+        );
+        code.posonlyarg_count = args.posonlyargs_count;
+        self.push_output(code);
         self.enter_scope();
 
         let mut flags = bytecode::FunctionOpArg::empty();
@@ -712,8 +836,16 @@ impl<O: OutputStream> Compiler<O> {
         finalbody: &Option<Vec<ast::Statement>>,
     ) -> Result<(), CompileError> {
         let mut handler_label = self.new_label();
-        let finally_label = self.new_label();
+        let finally_handler_label = self.new_label();
         let else_label = self.new_label();
+
+        // Setup a finally block if we have a finally statement.
+        if finalbody.is_some() {
+            self.emit(Instruction::SetupFinally {
+                handler: finally_handler_label,
+            });
+        }
+
         // try:
         self.emit(Instruction::SetupExcept {
             handler: handler_label,
@@ -765,8 +897,15 @@ impl<O: OutputStream> Compiler<O> {
             // Handler code:
             self.compile_statements(&handler.body)?;
             self.emit(Instruction::PopException);
+
+            if finalbody.is_some() {
+                // Pop the finally block and enter it, without exception.
+                self.emit(Instruction::PopBlock);
+                self.emit(Instruction::EnterFinally);
+            }
+
             self.emit(Instruction::Jump {
-                target: finally_label,
+                target: finally_handler_label,
             });
 
             // Emit a new label for the next handler
@@ -778,13 +917,8 @@ impl<O: OutputStream> Compiler<O> {
         });
         self.set_label(handler_label);
         // If code flows here, we have an unhandled exception,
-        // emit finally code and raise again!
-        // Duplicate finally code here:
-        // TODO: this bytecode is now duplicate, could this be
-        // improved?
-        if let Some(statements) = finalbody {
-            self.compile_statements(statements)?;
-        }
+        // raise the exception again! The finally block (if any)
+        // will run while unwinding.
         self.emit(Instruction::Raise { argc: 0 });
 
         // We successfully ran the try block:
@@ -794,12 +928,19 @@ impl<O: OutputStream> Compiler<O> {
             self.compile_statements(statements)?;
         }
 
+        if finalbody.is_some() {
+            // Pop the finally block and enter it, without return / exception.
+            self.emit(Instruction::PopBlock);
+            self.emit(Instruction::EnterFinally);
+        }
+
         // finally:
-        self.set_label(finally_label);
+        self.set_label(finally_handler_label);
         if let Some(statements) = finalbody {
             self.compile_statements(statements)?;
+            self.emit(Instruction::EndFinally);
         }
-        // unimplemented!();
+
         Ok(())
     }
 
@@ -810,13 +951,16 @@ impl<O: OutputStream> Compiler<O> {
         body: &[ast::Statement],
         decorator_list: &[ast::Expression],
         returns: &Option<ast::Expression>, // TODO: use type hint somehow..
+        is_async: bool,
     ) -> Result<(), CompileError> {
         // Create bytecode for this function:
         // remember to restore self.in_loop to the original after the function is compiled
         let was_in_loop = self.in_loop;
         let was_in_function_def = self.in_function_def;
+        let was_in_async_function = self.in_async_function;
         self.in_loop = false;
         self.in_function_def = true;
+        self.in_async_function = is_async;
 
         let old_qualified_path = self.current_qualified_path.clone();
         let qualified_name = self.create_qualified_name(name, "");
@@ -824,6 +968,8 @@ impl<O: OutputStream> Compiler<O> {
 
         self.prepare_decorators(decorator_list)?;
 
+        let def_location = self.current_source_location.clone();
+        let def_end = self.current_source_end.clone();
         let mut flags = self.enter_function(name, args)?;
 
         let (new_body, doc_str) = get_doc(body);
@@ -835,45 +981,62 @@ impl<O: OutputStream> Compiler<O> {
             value: bytecode::Constant::None,
         });
         self.emit(Instruction::ReturnValue);
-        let code = self.pop_code_object();
+        let mut code = self.pop_code_object();
         self.leave_scope();
+        // The function is created on the line of the `def`, not of its last statement:
+        self.set_source_location(&def_location, &def_end);
 
-        // Prepare type annotations:
-        let mut num_annotations = 0;
+        if is_async {
+            if code.is_generator {
+                return Err(CompileError {
+                    error: CompileErrorType::SyntaxError(
+                        "asynchronous generators are not supported".to_string(),
+                    ),
+                    location: self.current_source_location.clone(),
+                });
+            }
+            code.is_coroutine = true;
+        }
 
-        // Return annotation:
-        if let Some(annotation) = returns {
+        // Prepare type annotations, in the order of the parameters:
+        let mut num_annotations = 0;
+        let mut params: Vec<&ast::Parameter> = args.args.iter().collect();
+        if let ast::Varargs::Named(arg) = &args.vararg {
+            params.push(arg);
+        }
+        params.extend(&args.kwonlyargs);
+        if let ast::Varargs::Named(arg) = &args.kwarg {
+            params.push(arg);
+        }
+        let annotations = params
+            .into_iter()
+            .filter_map(|arg| Some((arg.arg.as_str(), arg.annotation.as_ref()?.as_ref())))
+            .chain(returns.iter().map(|annotation| ("return", annotation)));
+        for (name, annotation) in annotations {
             // key:
             self.emit(Instruction::LoadConst {
                 value: bytecode::Constant::String {
-                    value: "return".to_string(),
+                    value: name.to_string(),
                 },
             });
             // value:
-            self.compile_expression(annotation)?;
+            self.compile_annotation(annotation)?;
             num_annotations += 1;
         }
 
-        for arg in args.args.iter() {
-            if let Some(annotation) = &arg.annotation {
-                self.emit(Instruction::LoadConst {
-                    value: bytecode::Constant::String {
-                        value: arg.arg.to_string(),
-                    },
-                });
-                self.compile_expression(&annotation)?;
-                num_annotations += 1;
-            }
-        }
-
         if num_annotations > 0 {
             flags |= bytecode::FunctionOpArg::HAS_ANNOTATIONS;
             self.emit(Instruction::BuildMap {
                 size: num_annotations,
                 unpack: false,
+                for_call: false,
             });
         }
 
+        if self.load_closure(&code) {
+            flags |= bytecode::FunctionOpArg::HAS_CLOSURE;
+        }
+
         self.emit(Instruction::LoadConst {
             value: bytecode::Constant::Code {
                 code: Box::new(code),
@@ -895,6 +1058,7 @@ impl<O: OutputStream> Compiler<O> {
         self.current_qualified_path = old_qualified_path;
         self.in_loop = was_in_loop;
         self.in_function_def = was_in_function_def;
+        self.in_async_function = was_in_async_function;
         Ok(())
     }
 
@@ -937,14 +1101,26 @@ impl<O: OutputStream> Compiler<O> {
             name: "__module__".to_string(),
             scope: bytecode::NameScope::Local,
         });
+        if find_ann(new_body) {
+            self.emit(Instruction::SetupAnnotations);
+        }
+        let class_location = self.current_source_location.clone();
+        let class_end = self.current_source_end.clone();
         self.compile_statements(new_body)?;
         self.emit(Instruction::LoadConst {
             value: bytecode::Constant::None,
         });
         self.emit(Instruction::ReturnValue);
 
-        let code = self.pop_code_object();
+        let mut code = self.pop_code_object();
+        code.is_class_body = true;
         self.leave_scope();
+        self.set_source_location(&class_location, &class_end);
+
+        let mut flags = bytecode::FunctionOpArg::empty();
+        if self.load_closure(&code) {
+            flags |= bytecode::FunctionOpArg::HAS_CLOSURE;
+        }
 
         self.emit(Instruction::LoadConst {
             value: bytecode::Constant::Code {
@@ -958,9 +1134,7 @@ impl<O: OutputStream> Compiler<O> {
         });
 
         // Turn code object into function object:
-        self.emit(Instruction::MakeFunction {
-            flags: bytecode::FunctionOpArg::empty(),
-        });
+        self.emit(Instruction::MakeFunction { flags });
 
         self.emit(Instruction::LoadConst {
             value: bytecode::Constant::String {
@@ -968,37 +1142,7 @@ impl<O: OutputStream> Compiler<O> {
             },
         });
 
-        for base in bases {
-            self.compile_expression(base)?;
-        }
-
-        if !keywords.is_empty() {
-            let mut kwarg_names = vec![];
-            for keyword in keywords {
-                if let Some(name) = &keyword.name {
-                    kwarg_names.push(bytecode::Constant::String {
-                        value: name.to_string(),
-                    });
-                } else {
-                    // This means **kwargs!
-                    panic!("name must be set");
-                }
-                self.compile_expression(&keyword.value)?;
-            }
-
-            self.emit(Instruction::LoadConst {
-                value: bytecode::Constant::Tuple {
-                    elements: kwarg_names,
-                },
-            });
-            self.emit(Instruction::CallFunction {
-                typ: CallType::Keyword(2 + keywords.len() + bases.len()),
-            });
-        } else {
-            self.emit(Instruction::CallFunction {
-                typ: CallType::Positional(2 + bases.len()),
-            });
-        }
+        self.compile_call_args(2, bases, keywords)?;
 
         self.store_docstring(doc_str);
         self.apply_decorators(decorator_list);
@@ -1028,6 +1172,75 @@ impl<O: OutputStream> Compiler<O> {
         }
     }
 
+    fn compile_async_with(
+        &mut self,
+        items: &[ast::WithItem],
+        body: &[ast::Statement],
+    ) -> Result<(), CompileError> {
+        if !self.in_async_function {
+            return Err(CompileError {
+                error: CompileErrorType::SyntaxError(
+                    "'async with' outside async function".to_string(),
+                ),
+                location: self.current_source_location.clone(),
+            });
+        }
+
+        let mut end_labels = vec![];
+        for item in items {
+            let end_label = self.new_label();
+            self.compile_expression(&item.context_expr)?;
+            self.emit(Instruction::BeforeAsyncWith);
+            self.compile_await();
+            self.emit(Instruction::SetupAsyncWith { end: end_label });
+            match &item.optional_vars {
+                Some(var) => {
+                    self.compile_store(var)?;
+                }
+                None => {
+                    self.emit(Instruction::Pop);
+                }
+            }
+            end_labels.push(end_label);
+        }
+
+        self.compile_statements(body)?;
+
+        // Leaving the block, normally or by unwinding, arrives at its end
+        // label with the `__aexit__` awaitable pushed and a finally handler
+        // recording why the block was left:
+        for end_label in end_labels.into_iter().rev() {
+            self.emit(Instruction::CleanupAsyncWith);
+            self.set_label(end_label);
+            self.compile_await();
+            self.emit(Instruction::EndAsyncWith);
+            self.emit(Instruction::EndFinally);
+        }
+        Ok(())
+    }
+
+    /// Await the value on top of the stack, replacing it with the result.
+    fn compile_await(&mut self) {
+        self.emit(Instruction::GetAwaitable);
+        self.compile_delegation();
+    }
+
+    /// Drive the iterator on top of the stack until it is done, yielding what
+    /// it yields and sending it what is sent in, and replace it with its
+    /// return value.
+    fn compile_delegation(&mut self) {
+        let send_label = self.new_label();
+        let end_label = self.new_label();
+        self.emit(Instruction::LoadConst {
+            value: bytecode::Constant::None,
+        });
+        self.set_label(send_label);
+        self.emit(Instruction::Send { target: end_label });
+        self.emit(Instruction::YieldValue);
+        self.emit(Instruction::Jump { target: send_label });
+        self.set_label(end_label);
+    }
+
     fn compile_while(
         &mut self,
         test: &ast::Expression,
@@ -1107,6 +1320,68 @@ impl<O: OutputStream> Compiler<O> {
         Ok(())
     }
 
+    fn compile_async_for(
+        &mut self,
+        target: &ast::Expression,
+        iter: &ast::Expression,
+        body: &[ast::Statement],
+        orelse: &Option<Vec<ast::Statement>>,
+    ) -> Result<(), CompileError> {
+        if !self.in_async_function {
+            return Err(CompileError {
+                error: CompileErrorType::SyntaxError(
+                    "'async for' outside async function".to_string(),
+                ),
+                location: self.current_source_location.clone(),
+            });
+        }
+
+        // Start loop
+        let start_label = self.new_label();
+        let handler_label = self.new_label();
+        let end_label = self.new_label();
+        self.emit(Instruction::SetupLoop {
+            start: start_label,
+            end: end_label,
+        });
+
+        // The thing iterated:
+        self.compile_expression(iter)?;
+
+        // Retrieve asynchronous iterator
+        self.emit(Instruction::GetAIter);
+
+        // Await the next value, which ends the loop with StopAsyncIteration:
+        self.set_label(start_label);
+        self.emit(Instruction::SetupExcept {
+            handler: handler_label,
+        });
+        self.emit(Instruction::GetANext);
+        self.compile_await();
+
+        // Start of loop iteration, set targets. This happens before leaving the
+        // try block, since popping the block drops the values pushed inside it:
+        self.compile_store(target)?;
+        self.emit(Instruction::PopBlock);
+
+        let was_in_loop = self.in_loop;
+        self.in_loop = true;
+        self.compile_statements(body)?;
+        self.in_loop = was_in_loop;
+
+        self.emit(Instruction::Jump {
+            target: start_label,
+        });
+        self.set_label(handler_label);
+        self.emit(Instruction::EndAsyncFor);
+        self.emit(Instruction::PopBlock);
+        if let Some(orelse) = orelse {
+            self.compile_statements(orelse)?;
+        }
+        self.set_label(end_label);
+        Ok(())
+    }
+
     fn compile_chained_comparison(
         &mut self,
         vals: &[ast::Expression],
@@ -1177,6 +1452,21 @@ impl<O: OutputStream> Compiler<O> {
         Ok(())
     }
 
+    /// Evaluate an annotation, or only load its source under
+    /// `from __future__ import annotations`.
+    fn compile_annotation(&mut self, annotation: &ast::Expression) -> Result<(), CompileError> {
+        if self.future_annotations {
+            self.emit(Instruction::LoadConst {
+                value: bytecode::Constant::String {
+                    value: unparse_annotation(annotation),
+                },
+            });
+            Ok(())
+        } else {
+            self.compile_expression(annotation)
+        }
+    }
+
     fn compile_annotated_assign(
         &mut self,
         target: &ast::Expression,
@@ -1188,8 +1478,13 @@ impl<O: OutputStream> Compiler<O> {
             self.compile_store(target)?;
         }
 
+        // Like CPython, annotations for locals of a function are never evaluated.
+        if self.in_function_scope() {
+            return Ok(());
+        }
+
         // Compile annotation:
-        self.compile_expression(annotation)?;
+        self.compile_annotation(annotation)?;
 
         if let ast::ExpressionType::Identifier { name } = &target.node {
             // Store as dict entry in __annotations__ dict:
@@ -1407,7 +1702,11 @@ impl<O: OutputStream> Compiler<O> {
 
     fn compile_expression(&mut self, expression: &ast::Expression) -> Result<(), CompileError> {
         trace!("Compiling {:?}", expression);
-        self.set_source_location(&expression.location);
+        // Instructions emitted for the node itself get its whole span, not
+        // the span of whichever operand was compiled last:
+        let parent_location = self.current_source_location.clone();
+        let parent_end = self.current_source_end.clone();
+        self.set_source_location(expression_start(expression), &expression.end_location);
 
         use ast::ExpressionType::*;
         match &expression.node {
@@ -1427,10 +1726,7 @@ impl<O: OutputStream> Compiler<O> {
             Subscript { a, b } => {
                 self.compile_expression(a)?;
                 self.compile_expression(b)?;
-                self.emit(Instruction::BinaryOperation {
-                    op: bytecode::BinaryOperator::Subscript,
-                    inplace: false,
-                });
+                self.emit(Instruction::BinarySubscr);
             }
             Unop { op, a } => {
                 self.compile_expression(a)?;
@@ -1455,16 +1751,9 @@ impl<O: OutputStream> Compiler<O> {
                 self.compile_chained_comparison(vals, ops)?;
             }
             Number { value } => {
-                let const_value = match value {
-                    ast::Number::Integer { value } => bytecode::Constant::Integer {
-                        value: value.clone(),
-                    },
-                    ast::Number::Float { value } => bytecode::Constant::Float { value: *value },
-                    ast::Number::Complex { real, imag } => bytecode::Constant::Complex {
-                        value: Complex64::new(*real, *imag),
-                    },
-                };
-                self.emit(Instruction::LoadConst { value: const_value });
+                self.emit(Instruction::LoadConst {
+                    value: number_constant(value),
+                });
             }
             List { elements } => {
                 let size = elements.len();
@@ -1480,6 +1769,7 @@ impl<O: OutputStream> Compiler<O> {
                 self.emit(Instruction::BuildTuple {
                     size,
                     unpack: must_unpack,
+                    for_call: false,
                 });
             }
             Set { elements } => {
@@ -1493,25 +1783,42 @@ impl<O: OutputStream> Compiler<O> {
             Dict { elements } => {
                 let size = elements.len();
                 let has_double_star = elements.iter().any(|e| e.0.is_none());
-                for (key, value) in elements {
-                    if let Some(key) = key {
-                        self.compile_expression(key)?;
+                // When every key is a literal, they are loaded together as one tuple:
+                let const_keys: Option<Vec<_>> = elements
+                    .iter()
+                    .map(|(key, _)| key.as_ref().and_then(try_get_constant))
+                    .collect();
+                if let (Some(keys), true) = (const_keys, size > 1) {
+                    for (_, value) in elements {
                         self.compile_expression(value)?;
-                        if has_double_star {
-                            self.emit(Instruction::BuildMap {
-                                size: 1,
-                                unpack: false,
-                            });
+                    }
+                    self.emit(Instruction::LoadConst {
+                        value: bytecode::Constant::Tuple { elements: keys },
+                    });
+                    self.emit(Instruction::BuildConstKeyMap { size });
+                } else {
+                    for (key, value) in elements {
+                        if let Some(key) = key {
+                            self.compile_expression(key)?;
+                            self.compile_expression(value)?;
+                            if has_double_star {
+                                self.emit(Instruction::BuildMap {
+                                    size: 1,
+                                    unpack: false,
+                                    for_call: false,
+                                });
+                            }
+                        } else {
+                            // dict unpacking
+                            self.compile_expression(value)?;
                         }
-                    } else {
-                        // dict unpacking
-                        self.compile_expression(value)?;
                     }
+                    self.emit(Instruction::BuildMap {
+                        size,
+                        unpack: has_double_star,
+                        for_call: false,
+                    });
                 }
-                self.emit(Instruction::BuildMap {
-                    size,
-                    unpack: has_double_star,
-                });
             }
             Slice { elements } => {
                 let size = elements.len();
@@ -1536,17 +1843,21 @@ impl<O: OutputStream> Compiler<O> {
                 };
                 self.emit(Instruction::YieldValue);
             }
-            Await { .. } => {
-                unimplemented!("await");
+            Await { value } => {
+                if !self.in_async_function {
+                    return Err(CompileError {
+                        error: CompileErrorType::InvalidAwait,
+                        location: self.current_source_location.clone(),
+                    });
+                }
+                self.compile_expression(value)?;
+                self.compile_await();
             }
             YieldFrom { value } => {
                 self.mark_generator();
                 self.compile_expression(value)?;
-                self.emit(Instruction::GetIter);
-                self.emit(Instruction::LoadConst {
-                    value: bytecode::Constant::None,
-                });
-                self.emit(Instruction::YieldFrom);
+                self.emit(Instruction::GetYieldFromIter);
+                self.compile_delegation();
             }
             True => {
                 self.emit(Instruction::LoadConst {
@@ -1584,11 +1895,17 @@ impl<O: OutputStream> Compiler<O> {
             Lambda { args, body } => {
                 let name = "<lambda>".to_string();
                 // no need to worry about the self.loop_depth because there are no loops in lambda expressions
-                let flags = self.enter_function(&name, args)?;
+                let was_in_async_function = self.in_async_function;
+                self.in_async_function = false;
+                let mut flags = self.enter_function(&name, args)?;
                 self.compile_expression(body)?;
                 self.emit(Instruction::ReturnValue);
                 let code = self.pop_code_object();
                 self.leave_scope();
+                self.in_async_function = was_in_async_function;
+                if self.load_closure(&code) {
+                    flags |= bytecode::FunctionOpArg::HAS_CLOSURE;
+                }
                 self.emit(Instruction::LoadConst {
                     value: bytecode::Constant::Code {
                         code: Box::new(code),
@@ -1603,10 +1920,13 @@ impl<O: OutputStream> Compiler<O> {
             Comprehension { kind, generators } => {
                 self.compile_comprehension(kind, generators)?;
             }
-            Starred { value } => {
-                self.compile_expression(value)?;
-                self.emit(Instruction::Unpack);
-                panic!("We should not just unpack a starred args, since the size is unknown.");
+            Starred { .. } => {
+                return Err(CompileError {
+                    error: CompileErrorType::SyntaxError(
+                        "can't use starred expression here".to_string(),
+                    ),
+                    location: self.current_source_location.clone(),
+                });
             }
             IfExpression { test, body, orelse } => {
                 let no_label = self.new_label();
@@ -1622,6 +1942,7 @@ impl<O: OutputStream> Compiler<O> {
                 self.set_label(end_label);
             }
         }
+        self.set_source_location(&parent_location, &parent_end);
         Ok(())
     }
 
@@ -1631,18 +1952,77 @@ impl<O: OutputStream> Compiler<O> {
         args: &[ast::Expression],
         keywords: &[ast::Keyword],
     ) -> Result<(), CompileError> {
+        // Plain positional method calls avoid creating a bound method:
+        if let ast::ExpressionType::Attribute { value, name } = &function.node {
+            let has_stars = args.iter().any(|arg| {
+                if let ast::ExpressionType::Starred { .. } = &arg.node {
+                    true
+                } else {
+                    false
+                }
+            });
+            if keywords.is_empty() && !has_stars {
+                self.compile_expression(value)?;
+                // A missing method is reported at the attribute, not the call:
+                let call_location = self.current_source_location.clone();
+                let call_end = self.current_source_end.clone();
+                self.set_source_location(expression_start(function), &function.end_location);
+                self.emit(Instruction::LoadMethod {
+                    name: name.to_string(),
+                });
+                self.set_source_location(&call_location, &call_end);
+                for arg in args {
+                    self.compile_expression(arg)?;
+                }
+                self.emit(Instruction::CallMethod { amount: args.len() });
+                return Ok(());
+            }
+        }
+
         self.compile_expression(function)?;
-        let count = args.len() + keywords.len();
+        self.compile_call_args(0, args, keywords)
+    }
 
-        // Normal arguments:
-        let must_unpack = self.gather_elements(args)?;
+    /// Compile the arguments of a call and the call itself. The callable and
+    /// `extra_positional` leading positional arguments are already on the stack.
+    fn compile_call_args(
+        &mut self,
+        extra_positional: usize,
+        args: &[ast::Expression],
+        keywords: &[ast::Keyword],
+    ) -> Result<(), CompileError> {
+        let count = extra_positional + args.len() + keywords.len();
+        let must_unpack = args.iter().any(|arg| {
+            if let ast::ExpressionType::Starred { .. } = &arg.node {
+                true
+            } else {
+                false
+            }
+        });
         let has_double_star = keywords.iter().any(|k| k.name.is_none());
 
         if must_unpack || has_double_star {
+            // The leading arguments are unpacked along with the starred ones:
+            let mut size = args.len();
+            if extra_positional > 0 {
+                if must_unpack {
+                    self.emit(Instruction::BuildTuple {
+                        size: extra_positional,
+                        unpack: false,
+                        for_call: false,
+                    });
+                    size += 1;
+                } else {
+                    size += extra_positional;
+                }
+            }
+
             // Create a tuple with positional args:
+            self.gather_elements(args)?;
             self.emit(Instruction::BuildTuple {
-                size: args.len(),
+                size,
                 unpack: must_unpack,
+                for_call: true,
             });
 
             // Create an optional map with kw-args:
@@ -1659,6 +2039,7 @@ impl<O: OutputStream> Compiler<O> {
                             self.emit(Instruction::BuildMap {
                                 size: 1,
                                 unpack: false,
+                                for_call: false,
                             });
                         }
                     } else {
@@ -1670,6 +2051,7 @@ impl<O: OutputStream> Compiler<O> {
                 self.emit(Instruction::BuildMap {
                     size: keywords.len(),
                     unpack: has_double_star,
+                    for_call: true,
                 });
 
                 self.emit(Instruction::CallFunction {
@@ -1681,6 +2063,9 @@ impl<O: OutputStream> Compiler<O> {
                 });
             }
         } else {
+            // Normal arguments:
+            self.gather_elements(args)?;
+
             // Keyword arguments:
             if !keywords.is_empty() {
                 let mut kwarg_names = vec![];
@@ -1689,9 +2074,6 @@ impl<O: OutputStream> Compiler<O> {
                         kwarg_names.push(bytecode::Constant::String {
                             value: name.to_string(),
                         });
-                    } else {
-                        // This means **kwargs!
-                        panic!("name must be set");
                     }
                     self.compile_expression(&keyword.value)?;
                 }
@@ -1713,8 +2095,6 @@ impl<O: OutputStream> Compiler<O> {
         Ok(())
     }
 
-    // Given a vector of expr / star expr generate code which gives either
-    // a list of expressions on the stack, or a list of tuples.
     fn gather_elements(&mut self, elements: &[ast::Expression]) -> Result<bool, CompileError> {
         // First determine if we have starred elements:
         let has_stars = elements.iter().any(|e| {
@@ -1734,6 +2114,7 @@ impl<O: OutputStream> Compiler<O> {
                     self.emit(Instruction::BuildTuple {
                         size: 1,
                         unpack: false,
+                        for_call: false,
                     });
                 }
             }
@@ -1769,10 +2150,19 @@ impl<O: OutputStream> Compiler<O> {
             line_number,
             name.clone(),
         ));
+        self.enter_scope();
+        // The comprehension runs as a plain function, which can't await:
+        let was_in_async_function = self.in_async_function;
+        self.in_async_function = false;
 
         // Create empty object of proper type:
         match kind {
-            ast::ComprehensionKind::GeneratorExpression { .. } => {}
+            ast::ComprehensionKind::GeneratorExpression { .. } => {
+                // Generator expressions return None when exhausted:
+                self.emit(Instruction::LoadConst {
+                    value: bytecode::Constant::None,
+                });
+            }
             ast::ComprehensionKind::List { .. } => {
                 self.emit(Instruction::BuildList {
                     size: 0,
@@ -1789,6 +2179,7 @@ impl<O: OutputStream> Compiler<O> {
                 self.emit(Instruction::BuildMap {
                     size: 0,
                     unpack: false,
+                    for_call: false,
                 });
             }
         }
@@ -1796,15 +2187,25 @@ impl<O: OutputStream> Compiler<O> {
         let mut loop_labels = vec![];
         for generator in generators {
             if generator.is_async {
-                unimplemented!("async for comprehensions");
+                return Err(CompileError {
+                    error: CompileErrorType::SyntaxError(
+                        "asynchronous comprehensions are not supported".to_string(),
+                    ),
+                    location: self.current_source_location.clone(),
+                });
             }
 
+            // Setup for loop:
+            let start_label = self.new_label();
+            let end_label = self.new_label();
+            self.emit(Instruction::SetupLoop {
+                start: start_label,
+                end: end_label,
+            });
+
             if loop_labels.is_empty() {
                 // Load iterator onto stack (passed as first argument):
-                self.emit(Instruction::LoadName {
-                    name: String::from(".0"),
-                    scope: bytecode::NameScope::Local,
-                });
+                self.load_name(".0");
             } else {
                 // Evaluate iterated item:
                 self.compile_expression(&generator.iter)?;
@@ -1813,14 +2214,7 @@ impl<O: OutputStream> Compiler<O> {
                 self.emit(Instruction::GetIter);
             }
 
-            // Setup for loop:
-            let start_label = self.new_label();
-            let end_label = self.new_label();
             loop_labels.push((start_label, end_label));
-            self.emit(Instruction::SetupLoop {
-                start: start_label,
-                end: end_label,
-            });
             self.set_label(start_label);
             self.emit(Instruction::ForIter { target: end_label });
 
@@ -1877,6 +2271,13 @@ impl<O: OutputStream> Compiler<O> {
 
         // Fetch code for listcomp function:
         let code = self.pop_code_object();
+        self.leave_scope();
+        self.in_async_function = was_in_async_function;
+
+        let mut flags = bytecode::FunctionOpArg::empty();
+        if self.load_closure(&code) {
+            flags |= bytecode::FunctionOpArg::HAS_CLOSURE;
+        }
 
         // List comprehension code:
         self.emit(Instruction::LoadConst {
@@ -1891,9 +2292,7 @@ impl<O: OutputStream> Compiler<O> {
         });
 
         // Turn code object into function object:
-        self.emit(Instruction::MakeFunction {
-            flags: bytecode::FunctionOpArg::empty(),
-        });
+        self.emit(Instruction::MakeFunction { flags });
 
         // Evaluate iterated item:
         self.compile_expression(&generators[0].iter)?;
@@ -1934,9 +2333,16 @@ impl<O: OutputStream> Compiler<O> {
                     spec,
                 } => {
                     self.compile_expression(value)?;
+                    match spec {
+                        Some(spec) => self.compile_string(spec)?,
+                        None => self.emit(Instruction::LoadConst {
+                            value: bytecode::Constant::String {
+                                value: String::new(),
+                            },
+                        }),
+                    }
                     self.emit(Instruction::FormatValue {
                         conversion: conversion.map(compile_conversion_flag),
-                        spec: spec.clone(),
                     });
                 }
             }
@@ -1973,7 +2379,7 @@ impl<O: OutputStream> Compiler<O> {
 
     // Low level helper functions:
     fn emit(&mut self, instruction: Instruction) {
-        let location = compile_location(&self.current_source_location);
+        let location = compile_location(&self.current_source_location, &self.current_source_end);
         // TODO: insert source filename
         self.current_output().emit(instruction, location);
     }
@@ -1996,8 +2402,9 @@ impl<O: OutputStream> Compiler<O> {
         self.current_output().set_label(label)
     }
 
-    fn set_source_location(&mut self, location: &ast::Location) {
+    fn set_source_location(&mut self, location: &ast::Location, end_location: &ast::Location) {
         self.current_source_location = location.clone();
+        self.current_source_end = end_location.clone();
     }
 
     fn get_source_line_number(&mut self) -> usize {
@@ -2030,6 +2437,31 @@ fn get_doc(body: &[ast::Statement]) -> (&[ast::Statement], Option<String>) {
     (body, None)
 }
 
+/// Whether a module or class body has annotations of its own, for which it
+/// needs an `__annotations__` dict. Nested functions and classes don't count.
+fn find_ann(body: &[ast::Statement]) -> bool {
+    use ast::StatementType::*;
+    body.iter().any(|statement| match &statement.node {
+        AnnAssign { .. } => true,
+        If { body, orelse, .. } | While { body, orelse, .. } | For { body, orelse, .. } => {
+            find_ann(body) || orelse.as_ref().map_or(false, |orelse| find_ann(orelse))
+        }
+        With { body, .. } => find_ann(body),
+        Try {
+            body,
+            handlers,
+            orelse,
+            finalbody,
+        } => {
+            find_ann(body)
+                || handlers.iter().any(|handler| find_ann(&handler.body))
+                || orelse.as_ref().map_or(false, |orelse| find_ann(orelse))
+                || finalbody.as_ref().map_or(false, |body| find_ann(body))
+        }
+        _ => false,
+    })
+}
+
 fn try_get_constant_string(string: &ast::StringGroup) -> Option<String> {
     fn get_constant_string_inner(out_string: &mut String, string: &ast::StringGroup) -> bool {
         match string {
@@ -2051,8 +2483,58 @@ fn try_get_constant_string(string: &ast::StringGroup) -> Option<String> {
     }
 }
 
-fn compile_location(location: &ast::Location) -> bytecode::Location {
+fn number_constant(number: &ast::Number) -> bytecode::Constant {
+    match number {
+        ast::Number::Integer { value } => bytecode::Constant::Integer {
+            value: value.clone(),
+        },
+        ast::Number::Float { value } => bytecode::Constant::Float { value: *value },
+        ast::Number::Complex { real, imag } => bytecode::Constant::Complex {
+            value: Complex64::new(*real, *imag),
+        },
+    }
+}
+
+/// The value of a literal expression, if it is one.
+fn try_get_constant(expression: &ast::Expression) -> Option<bytecode::Constant> {
+    use ast::ExpressionType::*;
+    let value = match &expression.node {
+        Number { value } => number_constant(value),
+        String { value } => bytecode::Constant::String {
+            value: try_get_constant_string(value)?,
+        },
+        Bytes { value } => bytecode::Constant::Bytes {
+            value: value.clone(),
+        },
+        True => bytecode::Constant::Boolean { value: true },
+        False => bytecode::Constant::Boolean { value: false },
+        None => bytecode::Constant::None,
+        Ellipsis => bytecode::Constant::Ellipsis,
+        _ => return Option::None,
+    };
+    Some(value)
+}
+
+fn compile_location(location: &ast::Location, end_location: &ast::Location) -> bytecode::Location {
     bytecode::Location::new(location.row(), location.column())
+        .with_end(end_location.row(), end_location.column())
+}
+
+/// Where the source of an expression starts. Operators, calls, subscripts
+/// and attribute accesses are located at their operator token, so follow
+/// their leftmost operand back to the first token of the expression.
+fn expression_start(expression: &ast::Expression) -> &ast::Location {
+    use ast::ExpressionType::*;
+    match &expression.node {
+        Binop { a, .. } | Subscript { a, .. } => expression_start(a),
+        Attribute { value, .. } => expression_start(value),
+        Call { function, .. } => expression_start(function),
+        IfExpression { body, .. } => expression_start(body),
+        BoolOp { values, .. } | Compare { vals: values, .. } | Slice { elements: values } => values
+            .first()
+            .map_or(&expression.location, expression_start),
+        _ => &expression.location,
+    }
 }
 
 fn compile_varargs(varargs: &ast::Varargs) -> bytecode::Varargs {
@@ -2071,42 +2553,129 @@ fn compile_conversion_flag(conversion_flag: ast::ConversionFlag) -> bytecode::Co
     }
 }
 
+/// Compute the deepest the value stack gets when running `code`, by following
+/// every path through its instructions.
+fn max_stackdepth(code: &CodeObject) -> usize {
+    let mut max_depth = 0;
+    let mut visited: Vec<Option<isize>> = vec![None; code.instructions.len()];
+    let mut pending = vec![(0, 0)];
+    while let Some((mut offset, mut depth)) = pending.pop() {
+        while offset < code.instructions.len() {
+            match visited[offset] {
+                Some(seen) if seen >= depth => break,
+                _ => visited[offset] = Some(depth),
+            }
+            let instruction = &code.instructions[offset];
+            let target = match instruction {
+                Instruction::Jump { target }
+                | Instruction::JumpIfTrue { target }
+                | Instruction::JumpIfFalse { target }
+                | Instruction::JumpIfTrueOrPop { target }
+                | Instruction::JumpIfFalseOrPop { target }
+                | Instruction::ForIter { target }
+                | Instruction::Send { target }
+                | Instruction::SetupExcept { handler: target }
+                | Instruction::SetupFinally { handler: target }
+                | Instruction::SetupWith { end: target }
+                | Instruction::SetupAsyncWith { end: target }
+                | Instruction::SetupLoop { end: target, .. } => Some(target),
+                _ => None,
+            };
+            if let Some(target) = target {
+                let target_depth = depth + instruction.stack_effect(true);
+                max_depth = max_depth.max(target_depth);
+                pending.push((code.label_map[target], target_depth));
+            }
+            depth += instruction.stack_effect(false);
+            debug_assert!(depth >= 0, "negative stack depth in {}", code.obj_name);
+            max_depth = max_depth.max(depth);
+            match instruction {
+                Instruction::Jump { .. }
+                | Instruction::ReturnValue
+                | Instruction::Raise { .. }
+                | Instruction::Break
+                | Instruction::Continue => break,
+                _ => offset += 1,
+            }
+        }
+    }
+    max_depth as usize
+}
+
+/// Replace every label operand by the offset of the instruction it targets,
+/// so the frame can jump without looking anything up. `label_map` is left
+/// mapping each offset to itself, for the disassembler.
+fn resolve_labels(code: &mut CodeObject) {
+    let label_map = std::mem::replace(&mut code.label_map, HashMap::new());
+    for instruction in &mut code.instructions {
+        match instruction {
+            Instruction::Jump { target }
+            | Instruction::JumpIfTrue { target }
+            | Instruction::JumpIfFalse { target }
+            | Instruction::JumpIfTrueOrPop { target }
+            | Instruction::JumpIfFalseOrPop { target }
+            | Instruction::ForIter { target }
+            | Instruction::Send { target }
+            | Instruction::SetupExcept { handler: target }
+            | Instruction::SetupFinally { handler: target }
+            | Instruction::SetupWith { end: target }
+            | Instruction::CleanupWith { end: target }
+            | Instruction::SetupAsyncWith { end: target } => *target = label_map[target],
+            Instruction::SetupLoop { start, end } => {
+                *start = label_map[start];
+                *end = label_map[end];
+            }
+            _ => {}
+        }
+    }
+    code.label_map = label_map.values().map(|&offset| (offset, offset)).collect();
+}
+
 #[cfg(test)]
 mod tests {
     use super::Compiler;
+    use crate::error::{CompileError, CompileErrorType};
     use crate::symboltable::make_symbol_table;
     use rustpython_bytecode::bytecode::CodeObject;
     use rustpython_bytecode::bytecode::Constant::*;
     use rustpython_bytecode::bytecode::Instruction::*;
+    use rustpython_bytecode::bytecode::NameScope;
     use rustpython_parser::parser;
 
-    fn compile_exec(source: &str) -> CodeObject {
+    fn try_compile_exec(source: &str) -> Result<CodeObject, CompileError> {
         let mut compiler: Compiler = Default::default();
         compiler.source_path = Some("source_path".to_string());
         compiler.push_new_code_object("<module>".to_string());
         let ast = parser::parse_program(&source.to_string()).unwrap();
         let symbol_scope = make_symbol_table(&ast).unwrap();
-        compiler.compile_program(&ast, symbol_scope).unwrap();
-        compiler.pop_code_object()
+        compiler.compile_program(&ast, symbol_scope)?;
+        Ok(compiler.pop_code_object())
+    }
+
+    fn compile_exec(source: &str) -> CodeObject {
+        try_compile_exec(source).unwrap()
     }
 
     #[test]
     fn test_if_ors() {
-        let code = compile_exec("if True or False or False:\n pass\n");
+        let code = compile_exec("if a or b or c:\n pass\n");
         assert_eq!(
             vec![
-                LoadConst {
-                    value: Boolean { value: true }
+                LoadName {
+                    name: "a".to_string(),
+                    scope: NameScope::Local
                 },
-                JumpIfTrue { target: 1 },
-                LoadConst {
-                    value: Boolean { value: false }
+                JumpIfTrue { target: 6 },
+                LoadName {
+                    name: "b".to_string(),
+                    scope: NameScope::Local
                 },
-                JumpIfTrue { target: 1 },
-                LoadConst {
-                    value: Boolean { value: false }
+                JumpIfTrue { target: 6 },
+                LoadName {
+                    name: "c".to_string(),
+                    scope: NameScope::Local
                 },
-                JumpIfFalse { target: 0 },
+                JumpIfFalse { target: 7 },
                 Pass,
                 LoadConst { value: None },
                 ReturnValue
@@ -2117,21 +2686,24 @@ mod tests {
 
     #[test]
     fn test_if_ands() {
-        let code = compile_exec("if True and False and False:\n pass\n");
+        let code = compile_exec("if a and b and c:\n pass\n");
         assert_eq!(
             vec![
-                LoadConst {
-                    value: Boolean { value: true }
+                LoadName {
+                    name: "a".to_string(),
+                    scope: NameScope::Local
                 },
-                JumpIfFalse { target: 0 },
-                LoadConst {
-                    value: Boolean { value: false }
+                JumpIfFalse { target: 7 },
+                LoadName {
+                    name: "b".to_string(),
+                    scope: NameScope::Local
                 },
-                JumpIfFalse { target: 0 },
-                LoadConst {
-                    value: Boolean { value: false }
+                JumpIfFalse { target: 7 },
+                LoadName {
+                    name: "c".to_string(),
+                    scope: NameScope::Local
                 },
-                JumpIfFalse { target: 0 },
+                JumpIfFalse { target: 7 },
                 Pass,
                 LoadConst { value: None },
                 ReturnValue
@@ -2142,25 +2714,29 @@ mod tests {
 
     #[test]
     fn test_if_mixed() {
-        let code = compile_exec("if (True and False) or (False and True):\n pass\n");
+        let code = compile_exec("if (a and b) or (c and d):\n pass\n");
         assert_eq!(
             vec![
-                LoadConst {
-                    value: Boolean { value: true }
+                LoadName {
+                    name: "a".to_string(),
+                    scope: NameScope::Local
                 },
-                JumpIfFalse { target: 2 },
-                LoadConst {
-                    value: Boolean { value: false }
+                JumpIfFalse { target: 4 },
+                LoadName {
+                    name: "b".to_string(),
+                    scope: NameScope::Local
                 },
-                JumpIfTrue { target: 1 },
-                LoadConst {
-                    value: Boolean { value: false }
+                JumpIfTrue { target: 8 },
+                LoadName {
+                    name: "c".to_string(),
+                    scope: NameScope::Local
                 },
-                JumpIfFalse { target: 0 },
-                LoadConst {
-                    value: Boolean { value: true }
+                JumpIfFalse { target: 9 },
+                LoadName {
+                    name: "d".to_string(),
+                    scope: NameScope::Local
                 },
-                JumpIfFalse { target: 0 },
+                JumpIfFalse { target: 9 },
                 Pass,
                 LoadConst { value: None },
                 ReturnValue
@@ -2188,4 +2764,236 @@ mod tests {
             ]
         );
     }
+
+    #[test]
+    fn test_await_in_nested_scope() {
+        // Lambdas and comprehensions are plain functions, even in a coroutine:
+        let error = try_compile_exec("async def f():\n    return lambda: await x\n").unwrap_err();
+        match error.error {
+            CompileErrorType::InvalidAwait => {}
+            other => panic!("unexpected error {:?}", other),
+        }
+        let source = "async def f():\n    return [await x for _ in y]\n";
+        assert!(try_compile_exec(source).is_err());
+    }
+
+    #[test]
+    fn test_async_comprehension() {
+        let source = "async def f():\n    return [z async for z in g()]\n";
+        match try_compile_exec(source).unwrap_err().error {
+            CompileErrorType::SyntaxError(message) => {
+                assert_eq!(message, "asynchronous comprehensions are not supported")
+            }
+            other => panic!("unexpected error {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_method_call() {
+        let code = compile_exec("obj.meth(1)\n");
+        assert_eq!(
+            code.instructions,
+            vec![
+                LoadName {
+                    name: "obj".to_string(),
+                    scope: NameScope::Local
+                },
+                LoadMethod {
+                    name: "meth".to_string()
+                },
+                LoadConst {
+                    value: Integer { value: 1.into() }
+                },
+                CallMethod { amount: 1 },
+                Pop,
+                LoadConst { value: None },
+                ReturnValue,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_function_locals() {
+        let code = compile_exec("def f(a):\n b = a\n del b\n return a\n");
+        let function = code
+            .instructions
+            .iter()
+            .find_map(|instruction| match instruction {
+                LoadConst {
+                    value: Code { code },
+                } => Some(code),
+                _ => Option::None,
+            })
+            .unwrap();
+        assert_eq!(function.varnames, vec!["a".to_string(), "b".to_string()]);
+        assert_eq!(
+            function.instructions,
+            vec![
+                LoadFast { idx: 0 },
+                StoreFast { idx: 1 },
+                DeleteFast { idx: 1 },
+                LoadFast { idx: 0 },
+                ReturnValue,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_max_stacksize() {
+        let code = compile_exec("x = 1 + (2 + (3 + y))\n");
+        assert_eq!(code.max_stacksize, 4);
+        let code = compile_exec("for a in b:\n f(a, 1, 2)\n");
+        assert_eq!(code.max_stacksize, 5);
+    }
+
+    #[test]
+    fn test_constant_condition() {
+        let code = compile_exec("while 1:\n if not True:\n  break\n");
+        assert_eq!(
+            code.instructions,
+            vec![
+                SetupLoop { start: 1, end: 2 },
+                Jump { target: 1 },
+                LoadConst { value: None },
+                ReturnValue,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_loop_jumps_resolved() {
+        let code = compile_exec("for a in b:\n if a:\n  continue\n break\n");
+        assert_eq!(
+            code.instructions,
+            vec![
+                SetupLoop { start: 3, end: 10 },
+                LoadName {
+                    name: "b".to_string(),
+                    scope: NameScope::Local,
+                },
+                GetIter,
+                ForIter { target: 9 },
+                StoreName {
+                    name: "a".to_string(),
+                    scope: NameScope::Local,
+                },
+                LoadName {
+                    name: "a".to_string(),
+                    scope: NameScope::Local,
+                },
+                JumpIfFalse { target: 8 },
+                Continue,
+                Break,
+                PopBlock,
+                LoadConst { value: None },
+                ReturnValue,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_const_key_map() {
+        let code = compile_exec("{'a': x, 1: 2}");
+        assert_eq!(
+            code.instructions,
+            vec![
+                LoadName {
+                    name: "x".to_string(),
+                    scope: NameScope::Local,
+                },
+                LoadConst {
+                    value: Integer { value: 2.into() }
+                },
+                LoadConst {
+                    value: Tuple {
+                        elements: vec![
+                            String {
+                                value: "a".to_string()
+                            },
+                            Integer { value: 1.into() },
+                        ]
+                    }
+                },
+                BuildConstKeyMap { size: 2 },
+                Pop,
+                LoadConst { value: None },
+                ReturnValue,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_dict_with_non_constant_key() {
+        let code = compile_exec("{'a': 1, x: 2}");
+        assert!(code.instructions.contains(&BuildMap {
+            size: 2,
+            unpack: false,
+            for_call: false,
+        }));
+    }
+
+    #[test]
+    fn test_future_annotations() {
+        let code = compile_exec("from __future__ import annotations\nx: List[int]\n");
+        assert!(code.instructions.contains(&LoadConst {
+            value: String {
+                value: "List[int]".to_string()
+            }
+        }));
+        assert!(!code.instructions.contains(&LoadName {
+            name: "List".to_string(),
+            scope: NameScope::Local,
+        }));
+    }
+
+    #[test]
+    fn test_setup_annotations() {
+        let code = compile_exec("if x:\n    y: int\n");
+        assert_eq!(code.instructions.first(), Some(&SetupAnnotations));
+
+        let code = compile_exec("def f():\n    y: int\n");
+        assert!(!code.instructions.contains(&SetupAnnotations));
+    }
+    #[test]
+    fn test_yield_from() {
+        let code = compile_exec("def f():\n    yield from x\n");
+        let code = match &code.instructions[0] {
+            LoadConst {
+                value: Code { code },
+            } => code,
+            instruction => panic!("{:?} loads no code object", instruction),
+        };
+        assert_eq!(
+            code.instructions[1..6],
+            [
+                GetYieldFromIter,
+                LoadConst { value: None },
+                Send { target: 6 },
+                YieldValue,
+                Jump { target: 3 },
+            ]
+        );
+        assert_eq!(code.instructions[6], Pop);
+    }
+
+    #[test]
+    fn test_attribute_spans() {
+        let code = compile_exec("x = a.b.c\n");
+        let spans: Vec<_> = code
+            .locations
+            .iter()
+            .map(|l| (l.row(), l.column(), l.end_row(), l.end_column()))
+            .collect();
+        assert_eq!(
+            spans[0..4],
+            [(1, 5, 1, 6), (1, 5, 1, 8), (1, 5, 1, 10), (1, 1, 1, 10)]
+        );
+        assert_eq!(
+            code.instructions[3],
+            StoreName {
+                name: "x".to_string(),
+                scope: NameScope::Local
+            }
+        );
+    }
 }
@@ -38,6 +38,7 @@ pub enum CompileErrorType {
     InvalidContinue,
     InvalidReturn,
     InvalidYield,
+    InvalidAwait,
 }
 
 impl fmt::Display for CompileError {
@@ -53,6 +54,7 @@ impl fmt::Display for CompileError {
             CompileErrorType::InvalidContinue => write!(f, "'continue' outside loop"),
             CompileErrorType::InvalidReturn => write!(f, "'return' outside function"),
             CompileErrorType::InvalidYield => write!(f, "'yield' outside function"),
+            CompileErrorType::InvalidAwait => write!(f, "'await' outside async function"),
         }?;
 
         // Print line number:
@@ -11,3 +11,4 @@ pub mod error;
 pub(crate) mod output_stream;
 pub mod peephole;
 pub mod symboltable;
+pub(crate) mod unparse;
@@ -147,6 +147,11 @@ fn optimize_operator(buf: &mut impl OptimizationBuffer) {
     if let Instruction::BinaryOperation { op, inplace } = instruction {
         let (rhs, rhs_meta) = buf.pop();
         let (lhs, lhs_meta) = buf.pop();
+        if is_jump_target(&lhs_meta) || is_jump_target(&rhs_meta) {
+            buf.emit(lhs, lhs_meta);
+            buf.emit(rhs, rhs_meta);
+            return buf.emit(Instruction::BinaryOperation { op, inplace }, meta);
+        }
         macro_rules! op {
             ($op:ident) => {
                 bytecode::BinaryOperator::$op
@@ -168,10 +173,11 @@ fn optimize_operator(buf: &mut impl OptimizationBuffer) {
             (op!(Multiply), lc!(Float, lhs), lc!(Float, rhs)) => {
                 emitconst!(buf, [lhs_meta, rhs_meta], Float, lhs * rhs)
             }
-            (op!(Divide), lc!(Float, lhs), lc!(Float, rhs)) => {
+            // Division by zero and zero to a negative power raise at runtime, so leave them be.
+            (op!(Divide), lc!(Float, lhs), lc!(Float, rhs)) if rhs != 0.0 => {
                 emitconst!(buf, [lhs_meta, rhs_meta], Float, lhs / rhs)
             }
-            (op!(Power), lc!(Float, lhs), lc!(Float, rhs)) => {
+            (op!(Power), lc!(Float, lhs), lc!(Float, rhs)) if lhs != 0.0 || rhs >= 0.0 => {
                 emitconst!(buf, [lhs_meta, rhs_meta], Float, lhs.powf(rhs))
             }
             (op!(Add), lc!(String, mut lhs), lc!(String, rhs)) => {
@@ -197,7 +203,8 @@ fn optimize_unpack(buf: &mut impl OptimizationBuffer) {
             Instruction::BuildTuple {
                 size: tup_size,
                 unpack,
-            } if !unpack && tup_size == size => {
+                ..
+            } if !unpack && tup_size == size && !is_jump_target(&arg_meta) => {
                 buf.emit(
                     Instruction::Reverse { amount: size },
                     vec![arg_meta, meta].into(),
@@ -212,3 +219,366 @@ fn optimize_unpack(buf: &mut impl OptimizationBuffer) {
         buf.emit(instruction, meta)
     }
 }
+
+/// Whether code jumps to just after this instruction, in which case the
+/// values it leaves on the stack are not always the ones it pushed.
+fn is_jump_target(meta: &InstructionMetadata) -> bool {
+    !meta.labels.is_empty()
+}
+
+// WHOLE CODE OBJECT
+
+/// Run the optimizations which need to see the complete code object: folding
+/// jumps on constant conditions, dropping jumps to the next instruction,
+/// inverting jumps after a `not` and removing unreachable code. Instructions
+/// are only ever removed, so the jump targets in `label_map` are shifted
+/// down to the instruction which follows the removed ones.
+pub fn optimize_code(code: &mut CodeObject) {
+    while optimize_code_pass(code) {}
+}
+
+fn optimize_code_pass(code: &mut CodeObject) -> bool {
+    let len = code.instructions.len();
+    let mut is_target = vec![false; len + 1];
+    for label in code.instructions.iter().flat_map(jump_labels) {
+        is_target[code.label_map[&label]] = true;
+    }
+
+    let mut removed = vec![false; len];
+    let mut reachable = true;
+    for offset in 0..len {
+        if is_target[offset] {
+            reachable = true;
+        }
+        if removed[offset] {
+            continue;
+        }
+        if !reachable {
+            removed[offset] = true;
+            continue;
+        }
+        if offset + 1 < len && !is_target[offset + 1] {
+            let instructions = &code.instructions;
+            if let Some(replacement) = fold_pair(&instructions[offset], &instructions[offset + 1]) {
+                // The pair is replaced by a single instruction, or by nothing at all:
+                removed[offset + 1] = true;
+                match replacement {
+                    Some(instruction) => code.instructions[offset] = instruction,
+                    None => {
+                        removed[offset] = true;
+                        continue;
+                    }
+                }
+            }
+        }
+        match &code.instructions[offset] {
+            Instruction::Jump { target } if code.label_map[target] == offset + 1 => {
+                removed[offset] = true;
+            }
+            Instruction::Jump { .. }
+            | Instruction::ReturnValue
+            | Instruction::Raise { .. }
+            | Instruction::Break
+            | Instruction::Continue => reachable = false,
+            _ => {}
+        }
+    }
+
+    if !removed.contains(&true) {
+        return false;
+    }
+
+    // Map every old offset onto the offset of the next kept instruction:
+    let mut new_offsets = Vec::with_capacity(len + 1);
+    let mut kept = 0;
+    for is_removed in &removed {
+        new_offsets.push(kept);
+        if !is_removed {
+            kept += 1;
+        }
+    }
+    new_offsets.push(kept);
+    for offset in code.label_map.values_mut() {
+        *offset = new_offsets[*offset];
+    }
+
+    let mut is_removed = removed.iter();
+    code.instructions.retain(|_| !is_removed.next().unwrap());
+    let mut is_removed = removed.iter();
+    code.locations.retain(|_| !is_removed.next().unwrap());
+    true
+}
+
+/// The labels an instruction may continue at, other than the next instruction.
+fn jump_labels(instruction: &Instruction) -> Vec<Label> {
+    match instruction {
+        Instruction::Jump { target }
+        | Instruction::JumpIfTrue { target }
+        | Instruction::JumpIfFalse { target }
+        | Instruction::JumpIfTrueOrPop { target }
+        | Instruction::JumpIfFalseOrPop { target }
+        | Instruction::ForIter { target }
+        | Instruction::Send { target }
+        | Instruction::SetupExcept { handler: target }
+        | Instruction::SetupFinally { handler: target }
+        | Instruction::SetupWith { end: target }
+        | Instruction::SetupAsyncWith { end: target }
+        | Instruction::CleanupWith { end: target } => vec![*target],
+        Instruction::SetupLoop { start, end } => vec![*start, *end],
+        _ => vec![],
+    }
+}
+
+/// Fold a conditional jump with the instruction feeding it its condition.
+/// `Some(None)` means that the pair can be dropped entirely.
+fn fold_pair(first: &Instruction, second: &Instruction) -> Option<Option<Instruction>> {
+    let (jump_if, target) = match second {
+        Instruction::JumpIfTrue { target } => (true, *target),
+        Instruction::JumpIfFalse { target } => (false, *target),
+        _ => return None,
+    };
+    match first {
+        Instruction::LoadConst { value } => {
+            let truth = constant_truthiness(value)?;
+            Some(if truth == jump_if {
+                Some(Instruction::Jump { target })
+            } else {
+                None
+            })
+        }
+        Instruction::UnaryOperation {
+            op: bytecode::UnaryOperator::Not,
+        } => Some(Some(if jump_if {
+            Instruction::JumpIfFalse { target }
+        } else {
+            Instruction::JumpIfTrue { target }
+        })),
+        _ => None,
+    }
+}
+
+/// The truth value of a constant, if it can be known at compile time.
+fn constant_truthiness(value: &bytecode::Constant) -> Option<bool> {
+    match value {
+        bytecode::Constant::Boolean { value } => Some(*value),
+        bytecode::Constant::Integer { value } => Some(value.bits() != 0),
+        bytecode::Constant::String { value } => Some(!value.is_empty()),
+        bytecode::Constant::None => Some(false),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::optimize_code;
+    use rustpython_bytecode::bytecode::Instruction::*;
+    use rustpython_bytecode::bytecode::{
+        CallType, CodeObject, Constant, Instruction, Location, NameScope, UnaryOperator, Varargs,
+    };
+
+    fn make_code(instructions: Vec<Instruction>, labels: &[(usize, usize)]) -> CodeObject {
+        let mut code = CodeObject::new(
+            vec![],
+            Varargs::None,
+            vec![],
+            Varargs::None,
+            "source_path".to_string(),
+            1,
+            "<module>".to_string(),
+        );
+        code.locations = vec![Location::default(); instructions.len()];
+        code.instructions = instructions;
+        code.label_map = labels.iter().cloned().collect();
+        code
+    }
+
+    fn dis(code: &CodeObject) -> Vec<String> {
+        code.to_string()
+            .lines()
+            .map(|line| line.split_whitespace().collect::<Vec<_>>().join(" "))
+            .collect()
+    }
+
+    fn load_name(name: &str) -> Instruction {
+        LoadName {
+            name: name.to_string(),
+            scope: NameScope::Global,
+        }
+    }
+
+    fn call(name: &str) -> Vec<Instruction> {
+        vec![
+            load_name(name),
+            CallFunction {
+                typ: CallType::Positional(0),
+            },
+            Pop,
+        ]
+    }
+
+    fn check(mut code: CodeObject, before: &[&str], after: &[&str]) {
+        assert_eq!(dis(&code), before);
+        optimize_code(&mut code);
+        assert_eq!(dis(&code), after);
+    }
+
+    #[test]
+    fn test_constant_condition() {
+        // if False: f()
+        let mut instructions = vec![
+            LoadConst {
+                value: Constant::Boolean { value: false },
+            },
+            JumpIfFalse { target: 0 },
+        ];
+        instructions.extend(call("f"));
+        instructions.extend(vec![
+            LoadConst {
+                value: Constant::None,
+            },
+            ReturnValue,
+        ]);
+        check(
+            make_code(instructions, &[(0, 5)]),
+            &[
+                "0 LoadConst (false)",
+                "1 JumpIfFalse (5)",
+                "2 LoadName (f, Global)",
+                "3 CallFunction (Positional(0))",
+                "4 Pop",
+                ">> 5 LoadConst (None)",
+                "6 ReturnValue",
+            ],
+            &[">> 0 LoadConst (None)", "1 ReturnValue"],
+        );
+    }
+
+    #[test]
+    fn test_infinite_loop() {
+        // while 1: f()
+        let mut instructions = vec![
+            SetupLoop { start: 0, end: 1 },
+            LoadConst {
+                value: Constant::Integer { value: 1.into() },
+            },
+            JumpIfFalse { target: 2 },
+        ];
+        instructions.extend(call("f"));
+        instructions.extend(vec![
+            Jump { target: 0 },
+            PopBlock,
+            LoadConst {
+                value: Constant::None,
+            },
+            ReturnValue,
+        ]);
+        check(
+            make_code(instructions, &[(0, 1), (1, 8), (2, 7)]),
+            &[
+                "0 SetupLoop (1, 8)",
+                ">> 1 LoadConst (1)",
+                "2 JumpIfFalse (7)",
+                "3 LoadName (f, Global)",
+                "4 CallFunction (Positional(0))",
+                "5 Pop",
+                "6 Jump (1)",
+                ">> 7 PopBlock",
+                ">> 8 LoadConst (None)",
+                "9 ReturnValue",
+            ],
+            &[
+                "0 SetupLoop (1, 5)",
+                ">> 1 LoadName (f, Global)",
+                "2 CallFunction (Positional(0))",
+                "3 Pop",
+                "4 Jump (1)",
+                ">> 5 LoadConst (None)",
+                "6 ReturnValue",
+            ],
+        );
+    }
+
+    #[test]
+    fn test_not_condition() {
+        // if not a: f()
+        let mut instructions = vec![
+            load_name("a"),
+            UnaryOperation {
+                op: UnaryOperator::Not,
+            },
+            JumpIfFalse { target: 0 },
+        ];
+        instructions.extend(call("f"));
+        instructions.extend(vec![
+            LoadConst {
+                value: Constant::None,
+            },
+            ReturnValue,
+        ]);
+        check(
+            make_code(instructions, &[(0, 6)]),
+            &[
+                "0 LoadName (a, Global)",
+                "1 UnaryOperation (Not)",
+                "2 JumpIfFalse (6)",
+                "3 LoadName (f, Global)",
+                "4 CallFunction (Positional(0))",
+                "5 Pop",
+                ">> 6 LoadConst (None)",
+                "7 ReturnValue",
+            ],
+            &[
+                "0 LoadName (a, Global)",
+                "1 JumpIfTrue (5)",
+                "2 LoadName (f, Global)",
+                "3 CallFunction (Positional(0))",
+                "4 Pop",
+                ">> 5 LoadConst (None)",
+                "6 ReturnValue",
+            ],
+        );
+    }
+
+    #[test]
+    fn test_jump_to_next_and_dead_code() {
+        // if a: return b
+        // else: pass
+        // (implicit return None)
+        let instructions = vec![
+            load_name("a"),
+            JumpIfFalse { target: 0 },
+            load_name("b"),
+            ReturnValue,
+            Jump { target: 1 },
+            Pass,
+            Jump { target: 1 },
+            LoadConst {
+                value: Constant::None,
+            },
+            ReturnValue,
+        ];
+        check(
+            make_code(instructions, &[(0, 5), (1, 7)]),
+            &[
+                "0 LoadName (a, Global)",
+                "1 JumpIfFalse (5)",
+                "2 LoadName (b, Global)",
+                "3 ReturnValue",
+                "4 Jump (7)",
+                ">> 5 Pass",
+                "6 Jump (7)",
+                ">> 7 LoadConst (None)",
+                "8 ReturnValue",
+            ],
+            &[
+                "0 LoadName (a, Global)",
+                "1 JumpIfFalse (4)",
+                "2 LoadName (b, Global)",
+                "3 ReturnValue",
+                ">> 4 Pass",
+                ">> 5 LoadConst (None)",
+                "6 ReturnValue",
+            ],
+        );
+    }
+}
@@ -13,7 +13,10 @@ use rustpython_parser::ast;
 use rustpython_parser::location::Location;
 
 pub fn make_symbol_table(program: &ast::Program) -> Result<SymbolTable, SymbolTableError> {
-    let mut builder: SymbolTableBuilder = Default::default();
+    let mut builder = SymbolTableBuilder {
+        future_annotations: has_future_annotations(&program.statements),
+        ..Default::default()
+    };
     builder.prepare();
     builder.scan_program(program)?;
     builder.finish()
@@ -22,15 +25,48 @@ pub fn make_symbol_table(program: &ast::Program) -> Result<SymbolTable, SymbolTa
 pub fn statements_to_symbol_table(
     statements: &[ast::Statement],
 ) -> Result<SymbolTable, SymbolTableError> {
-    let mut builder: SymbolTableBuilder = Default::default();
+    let mut builder = SymbolTableBuilder {
+        future_annotations: has_future_annotations(statements),
+        ..Default::default()
+    };
     builder.prepare();
     builder.scan_statements(statements)?;
     builder.finish()
 }
 
+/// Whether `from __future__ import annotations` is among the future
+/// statements at the start of a module, which may follow its docstring.
+pub(crate) fn has_future_annotations(body: &[ast::Statement]) -> bool {
+    for (index, statement) in body.iter().enumerate() {
+        match &statement.node {
+            ast::StatementType::Expression {
+                expression:
+                    ast::Expression {
+                        node: ast::ExpressionType::String { .. },
+                        ..
+                    },
+            } if index == 0 => {}
+            ast::StatementType::ImportFrom {
+                level: 0,
+                module: Some(module),
+                names,
+            } if module == "__future__" => {
+                if names.iter().any(|name| name.symbol == "annotations") {
+                    return true;
+                }
+            }
+            _ => break,
+        }
+    }
+    false
+}
+
 /// Captures all symbols in the current scope, and has a list of subscopes in this scope.
-#[derive(Clone, Default)]
+#[derive(Clone)]
 pub struct SymbolTable {
+    /// The kind of block this table describes.
+    pub typ: SymbolTableType,
+
     /// A set of symbols present on this scope level.
     pub symbols: IndexMap<String, Symbol>,
 
@@ -40,21 +76,37 @@ pub struct SymbolTable {
 }
 
 impl SymbolTable {
-    fn new() -> Self {
+    fn new(typ: SymbolTableType) -> Self {
         SymbolTable {
+            typ,
             symbols: Default::default(),
             sub_tables: vec![],
         }
     }
 }
 
+/// The kind of block a symbol table is made for. Functions include lambdas
+/// and comprehensions.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SymbolTableType {
+    Module,
+    Class,
+    Function,
+}
+
 /// Indicator for a single symbol what the scope of this symbol is.
 /// The scope can be unknown, which is unfortunate, but not impossible.
+///
+/// `Free` symbols are bound in an enclosing function, and `Cell` symbols are
+/// local symbols which nested scopes refer to. Both live in cells at runtime,
+/// as do `Nonlocal` symbols.
 #[derive(Debug, Clone)]
 pub enum SymbolScope {
     Global,
     Nonlocal,
     Local,
+    Free,
+    Cell,
     Unknown,
 }
 
@@ -70,6 +122,9 @@ pub struct Symbol {
     pub is_assigned: bool,
     pub is_parameter: bool,
     pub is_free: bool,
+    /// A class local which nested scopes refer to as a free variable of an enclosing
+    /// function, so that the class must pass it on in its closure.
+    pub is_free_class: bool,
 }
 
 impl Symbol {
@@ -83,6 +138,7 @@ impl Symbol {
             is_assigned: false,
             is_parameter: false,
             is_free: false,
+            is_free_class: false,
         }
     }
 
@@ -95,10 +151,27 @@ impl Symbol {
     }
 
     pub fn is_local(&self) -> bool {
-        if let SymbolScope::Local = self.scope {
-            true
-        } else {
-            false
+        match self.scope {
+            SymbolScope::Local | SymbolScope::Cell => true,
+            _ => false,
+        }
+    }
+
+    /// Whether this symbol is accessed through a cell at runtime.
+    pub fn is_deref(&self) -> bool {
+        match self.scope {
+            SymbolScope::Nonlocal | SymbolScope::Free | SymbolScope::Cell => true,
+            _ => false,
+        }
+    }
+
+    /// Whether this symbol is bound in its own scope, so that nested scopes can
+    /// refer to it.
+    fn is_bound(&self) -> bool {
+        match self.scope {
+            SymbolScope::Global => false,
+            SymbolScope::Nonlocal => true,
+            _ => self.is_assigned || self.is_parameter,
         }
     }
 }
@@ -159,34 +232,59 @@ impl SymbolTableAnalyzer {
         // TODO: this should be improved to resolve this clone action.
         self.tables.push(symbol_table.clone());
 
-        // Analyze sub scopes:
+        // Analyze sub scopes, and gather the names they take from enclosing scopes:
+        let mut child_free = vec![];
         for sub_table in &mut symbol_table.sub_tables {
             self.analyze_symbol_table(sub_table)?;
+            for symbol in sub_table.symbols.values() {
+                if symbol.is_free || symbol.is_free_class {
+                    child_free.push(symbol.name.clone());
+                }
+            }
         }
         self.tables.pop();
 
         // Analyze symbols:
         for symbol in symbol_table.symbols.values_mut() {
-            self.analyze_symbol(symbol)?;
+            self.analyze_symbol(symbol, symbol_table.typ)?;
+        }
+
+        // Names which nested scopes take from here are either bound here, and
+        // become cells, or are passed on from an enclosing function:
+        for name in child_free {
+            let symbol = symbol_table.symbols.entry(name.clone()).or_insert_with(|| {
+                let mut symbol = Symbol::new(&name);
+                symbol.scope = SymbolScope::Free;
+                symbol.is_free = true;
+                symbol
+            });
+            if let SymbolScope::Local = symbol.scope {
+                if symbol_table.typ == SymbolTableType::Class {
+                    symbol.is_free_class = true;
+                } else {
+                    symbol.scope = SymbolScope::Cell;
+                }
+            }
         }
 
         Ok(())
     }
 
-    fn analyze_symbol(&self, symbol: &mut Symbol) -> SymbolTableResult {
+    fn analyze_symbol(&self, symbol: &mut Symbol, typ: SymbolTableType) -> SymbolTableResult {
         match symbol.scope {
             SymbolScope::Nonlocal => {
-                // check if name is defined in parent table!
+                // check if name is defined in an enclosing function!
                 let parent_symbol_table: Option<&SymbolTable> = self.tables.last();
                 // symbol.table.borrow().parent.clone();
 
-                if let Some(table) = parent_symbol_table {
-                    if !table.symbols.contains_key(&symbol.name) {
+                if parent_symbol_table.is_some() {
+                    if !self.is_bound_in_enclosing_function(&symbol.name) {
                         return Err(SymbolTableError {
                             error: format!("no binding for nonlocal '{}' found", symbol.name),
                             location: Default::default(),
                         });
                     }
+                    symbol.is_free = true;
                 } else {
                     return Err(SymbolTableError {
                         error: format!(
@@ -200,7 +298,7 @@ impl SymbolTableAnalyzer {
             SymbolScope::Global => {
                 // TODO: add more checks for globals?
             }
-            SymbolScope::Local => {
+            SymbolScope::Local | SymbolScope::Free | SymbolScope::Cell => {
                 // all is well
             }
             SymbolScope::Unknown => {
@@ -208,26 +306,41 @@ impl SymbolTableAnalyzer {
 
                 if symbol.is_assigned || symbol.is_parameter {
                     symbol.scope = SymbolScope::Local;
+                } else if typ != SymbolTableType::Module
+                    && self.is_bound_in_enclosing_function(&symbol.name)
+                {
+                    // Symbol is bound in some enclosing function.
+                    symbol.scope = SymbolScope::Free;
+                    symbol.is_free = true;
                 } else {
-                    // TODO: comment this out and make it work properly:
-                    /*
-                    let found_in_outer_scope = self
-                        .tables
-                        .iter()
-                        .any(|t| t.symbols.contains_key(&symbol.name));
-                    if found_in_outer_scope {
-                        // Symbol is in some outer scope.
-
-                    } else {
-                        // Well, it must be a global then :)
-                        // symbol.scope = SymbolScope::Global;
-                    }
-                    */
+                    // Otherwise it is a global or builtin, or it is looked up
+                    // dynamically, like names in class bodies.
                 }
             }
         }
         Ok(())
     }
+
+    /// Look for a binding of `name` in the enclosing functions, skipping class
+    /// bodies, since their names are not visible to nested scopes.
+    fn is_bound_in_enclosing_function(&self, name: &str) -> bool {
+        for table in self.tables.iter().rev() {
+            match table.typ {
+                SymbolTableType::Module => return false,
+                SymbolTableType::Class => continue,
+                SymbolTableType::Function => {}
+            }
+            if let Some(symbol) = table.symbols.get(name) {
+                if symbol.is_global() {
+                    return false;
+                }
+                if symbol.is_bound() {
+                    return true;
+                }
+            }
+        }
+        false
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -243,6 +356,9 @@ enum SymbolUsage {
 struct SymbolTableBuilder {
     // Scope stack.
     tables: Vec<SymbolTable>,
+    /// Whether `from __future__ import annotations` is in effect, so that
+    /// annotations are only stored as strings and have no scopes or names.
+    future_annotations: bool,
 }
 
 /// Enum to indicate in what mode an expression
@@ -257,7 +373,7 @@ enum ExpressionContext {
 
 impl SymbolTableBuilder {
     fn prepare(&mut self) {
-        let table = SymbolTable::new();
+        let table = SymbolTable::new(SymbolTableType::Module);
         self.tables.push(table);
     }
 
@@ -268,9 +384,9 @@ impl SymbolTableBuilder {
         Ok(symbol_table)
     }
 
-    fn enter_block(&mut self) {
+    fn enter_block(&mut self, typ: SymbolTableType) {
         // let parent = Some(self.tables.last().unwrap().clone());
-        let table = SymbolTable::new();
+        let table = SymbolTable::new(typ);
         self.tables.push(table);
     }
 
@@ -312,11 +428,19 @@ impl SymbolTableBuilder {
 
     fn scan_parameter_annotation(&mut self, parameter: &ast::Parameter) -> SymbolTableResult {
         if let Some(annotation) = &parameter.annotation {
-            self.scan_expression(&annotation, &ExpressionContext::Load)?;
+            self.scan_annotation(annotation)?;
         }
         Ok(())
     }
 
+    fn scan_annotation(&mut self, annotation: &ast::Expression) -> SymbolTableResult {
+        if self.future_annotations {
+            Ok(())
+        } else {
+            self.scan_expression(annotation, &ExpressionContext::Load)
+        }
+    }
+
     fn scan_statement(&mut self, statement: &ast::Statement) -> SymbolTableResult {
         use ast::StatementType::*;
         match &statement.node {
@@ -341,7 +465,7 @@ impl SymbolTableBuilder {
                 self.scan_expressions(decorator_list, &ExpressionContext::Load)?;
                 self.register_name(name, SymbolUsage::Assigned)?;
                 if let Some(expression) = returns {
-                    self.scan_expression(expression, &ExpressionContext::Load)?;
+                    self.scan_annotation(expression)?;
                 }
                 self.enter_function(args)?;
                 self.scan_statements(body)?;
@@ -355,7 +479,7 @@ impl SymbolTableBuilder {
                 decorator_list,
             } => {
                 self.register_name(name, SymbolUsage::Assigned)?;
-                self.enter_block();
+                self.enter_block(SymbolTableType::Class);
                 self.scan_statements(body)?;
                 self.leave_block();
                 self.scan_expressions(bases, &ExpressionContext::Load)?;
@@ -440,7 +564,12 @@ impl SymbolTableBuilder {
                 value,
             } => {
                 self.scan_expression(target, &ExpressionContext::Store)?;
-                self.scan_expression(annotation, &ExpressionContext::Load)?;
+                // Like the compiler, skip the annotations of function locals,
+                // which are never evaluated:
+                let in_function = self.tables.last().unwrap().typ == SymbolTableType::Function;
+                if !in_function {
+                    self.scan_annotation(annotation)?;
+                }
                 if let Some(value) = value {
                     self.scan_expression(value, &ExpressionContext::Load)?;
                 }
@@ -518,11 +647,11 @@ impl SymbolTableBuilder {
                 self.scan_expressions(vals, context)?;
             }
             Subscript { a, b } => {
-                self.scan_expression(a, context)?;
-                self.scan_expression(b, context)?;
+                self.scan_expression(a, &ExpressionContext::Load)?;
+                self.scan_expression(b, &ExpressionContext::Load)?;
             }
             Attribute { value, .. } => {
-                self.scan_expression(value, context)?;
+                self.scan_expression(value, &ExpressionContext::Load)?;
             }
             Dict { elements } => {
                 for (key, value) in elements {
@@ -558,6 +687,21 @@ impl SymbolTableBuilder {
                 self.scan_expressions(elements, &ExpressionContext::Load)?;
             }
             Comprehension { kind, generators } => {
+                // The comprehension is a function of its own, which gets the
+                // first iterator passed in as `.0`. Scan in the order in which
+                // the compiler visits the parts:
+                self.enter_block(SymbolTableType::Function);
+                self.register_name(".0", SymbolUsage::Parameter)?;
+                for (i, generator) in generators.iter().enumerate() {
+                    if i > 0 {
+                        self.scan_expression(&generator.iter, &ExpressionContext::Load)?;
+                    }
+                    self.scan_expression(&generator.target, &ExpressionContext::Store)?;
+                    for if_expr in &generator.ifs {
+                        self.scan_expression(if_expr, &ExpressionContext::Load)?;
+                    }
+                }
+
                 match **kind {
                     ast::ComprehensionKind::GeneratorExpression { ref element }
                     | ast::ComprehensionKind::List { ref element }
@@ -565,17 +709,14 @@ impl SymbolTableBuilder {
                         self.scan_expression(element, &ExpressionContext::Load)?;
                     }
                     ast::ComprehensionKind::Dict { ref key, ref value } => {
-                        self.scan_expression(&key, &ExpressionContext::Load)?;
                         self.scan_expression(&value, &ExpressionContext::Load)?;
+                        self.scan_expression(&key, &ExpressionContext::Load)?;
                     }
                 }
+                self.leave_block();
 
-                for generator in generators {
-                    self.scan_expression(&generator.target, &ExpressionContext::Store)?;
+                if let Some(generator) = generators.first() {
                     self.scan_expression(&generator.iter, &ExpressionContext::Load)?;
-                    for if_expr in &generator.ifs {
-                        self.scan_expression(if_expr, &ExpressionContext::Load)?;
-                    }
                 }
             }
             Call {
@@ -639,7 +780,7 @@ impl SymbolTableBuilder {
             self.scan_parameter_annotation(name)?;
         }
 
-        self.enter_block();
+        self.enter_block(SymbolTableType::Function);
 
         // Fill scope with parameter names:
         self.scan_parameters(&args.args)?;
@@ -656,8 +797,11 @@ impl SymbolTableBuilder {
     fn scan_string_group(&mut self, group: &ast::StringGroup) -> SymbolTableResult {
         match group {
             ast::StringGroup::Constant { .. } => {}
-            ast::StringGroup::FormattedValue { value, .. } => {
+            ast::StringGroup::FormattedValue { value, spec, .. } => {
                 self.scan_expression(value, &ExpressionContext::Load)?;
+                if let Some(spec) = spec {
+                    self.scan_string_group(spec)?;
+                }
             }
             ast::StringGroup::Joined { values } => {
                 for subgroup in values {
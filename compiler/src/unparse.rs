@@ -0,0 +1,562 @@
+//! Turn expressions back into source code, which is how annotations are
+//! stored under `from __future__ import annotations`.
+//!
+//! The output follows CPython's `Python/ast_unparse.c`, so that annotations
+//! read the same in both.
+
+use rustpython_parser::ast;
+use std::fmt::Write;
+
+// The binding strength of each kind of expression, weakest first:
+const PR_TUPLE: u8 = 0;
+const PR_TEST: u8 = 1;
+const PR_OR: u8 = 2;
+const PR_AND: u8 = 3;
+const PR_NOT: u8 = 4;
+const PR_CMP: u8 = 5;
+const PR_BOR: u8 = 6;
+const PR_BXOR: u8 = 7;
+const PR_BAND: u8 = 8;
+const PR_SHIFT: u8 = 9;
+const PR_ARITH: u8 = 10;
+const PR_TERM: u8 = 11;
+const PR_FACTOR: u8 = 12;
+const PR_POWER: u8 = 13;
+const PR_AWAIT: u8 = 14;
+const PR_ATOM: u8 = 15;
+
+/// The source code of an annotation.
+pub fn unparse_annotation(expression: &ast::Expression) -> String {
+    let mut unparser = Unparser {
+        output: String::new(),
+    };
+    unparser.expression(expression, PR_TEST);
+    unparser.output
+}
+
+struct Unparser {
+    output: String,
+}
+
+impl Unparser {
+    fn push(&mut self, s: &str) {
+        self.output.push_str(s);
+    }
+
+    fn push_if(&mut self, cond: bool, s: &str) {
+        if cond {
+            self.push(s);
+        }
+    }
+
+    fn expressions(&mut self, expressions: &[ast::Expression], level: u8) {
+        for (i, expression) in expressions.iter().enumerate() {
+            self.push_if(i > 0, ", ");
+            self.expression(expression, level);
+        }
+    }
+
+    fn expression(&mut self, expression: &ast::Expression, level: u8) {
+        use ast::ExpressionType::*;
+        match &expression.node {
+            BoolOp { op, values } => {
+                let (op, pr) = match op {
+                    ast::BooleanOperator::And => (" and ", PR_AND),
+                    ast::BooleanOperator::Or => (" or ", PR_OR),
+                };
+                self.push_if(level > pr, "(");
+                for (i, value) in values.iter().enumerate() {
+                    self.push_if(i > 0, op);
+                    self.expression(value, pr + 1);
+                }
+                self.push_if(level > pr, ")");
+            }
+            Binop { a, op, b } => {
+                let (op, pr) = binary_operator(op);
+                // `**` is the only right associative operator:
+                let (left, right) = if pr == PR_POWER {
+                    (pr + 1, pr)
+                } else {
+                    (pr, pr + 1)
+                };
+                self.push_if(level > pr, "(");
+                self.expression(a, left);
+                self.push(op);
+                self.expression(b, right);
+                self.push_if(level > pr, ")");
+            }
+            Unop { op, a } => {
+                let (op, pr) = match op {
+                    ast::UnaryOperator::Not => ("not ", PR_NOT),
+                    ast::UnaryOperator::Inv => ("~", PR_FACTOR),
+                    ast::UnaryOperator::Pos => ("+", PR_FACTOR),
+                    ast::UnaryOperator::Neg => ("-", PR_FACTOR),
+                };
+                self.push_if(level > pr, "(");
+                self.push(op);
+                self.expression(a, pr);
+                self.push_if(level > pr, ")");
+            }
+            Lambda { args, body } => {
+                self.push_if(level > PR_TEST, "(");
+                if parameters_are_empty(args) {
+                    self.push("lambda: ");
+                } else {
+                    self.push("lambda ");
+                    self.parameters(args);
+                    self.push(": ");
+                }
+                self.expression(body, PR_TEST);
+                self.push_if(level > PR_TEST, ")");
+            }
+            IfExpression { test, body, orelse } => {
+                self.push_if(level > PR_TEST, "(");
+                self.expression(body, PR_TEST + 1);
+                self.push(" if ");
+                self.expression(test, PR_TEST + 1);
+                self.push(" else ");
+                self.expression(orelse, PR_TEST);
+                self.push_if(level > PR_TEST, ")");
+            }
+            Compare { vals, ops } => {
+                self.push_if(level > PR_CMP, "(");
+                self.expression(&vals[0], PR_CMP + 1);
+                for (op, val) in ops.iter().zip(&vals[1..]) {
+                    self.push(comparison(op));
+                    self.expression(val, PR_CMP + 1);
+                }
+                self.push_if(level > PR_CMP, ")");
+            }
+            Await { value } => {
+                self.push_if(level > PR_AWAIT, "(");
+                self.push("await ");
+                self.expression(value, PR_ATOM);
+                self.push_if(level > PR_AWAIT, ")");
+            }
+            Yield { value } => {
+                self.push("(yield");
+                if let Some(value) = value {
+                    self.push(" ");
+                    self.expression(value, PR_TEST);
+                }
+                self.push(")");
+            }
+            YieldFrom { value } => {
+                self.push("(yield from ");
+                self.expression(value, PR_TEST);
+                self.push(")");
+            }
+            Starred { value } => {
+                self.push("*");
+                self.expression(value, PR_BOR);
+            }
+            Dict { elements } => {
+                self.push("{");
+                for (i, (key, value)) in elements.iter().enumerate() {
+                    self.push_if(i > 0, ", ");
+                    match key {
+                        Some(key) => {
+                            self.expression(key, PR_TEST);
+                            self.push(": ");
+                            self.expression(value, PR_TEST);
+                        }
+                        Option::None => {
+                            self.push("**");
+                            self.expression(value, PR_BOR);
+                        }
+                    }
+                }
+                self.push("}");
+            }
+            Set { elements } => {
+                self.push("{");
+                self.expressions(elements, PR_TEST);
+                self.push("}");
+            }
+            List { elements } => {
+                self.push("[");
+                self.expressions(elements, PR_TEST);
+                self.push("]");
+            }
+            Tuple { elements } => {
+                if elements.is_empty() {
+                    self.push("()");
+                } else {
+                    self.push_if(level > PR_TUPLE, "(");
+                    self.expressions(elements, PR_TEST);
+                    self.push_if(elements.len() == 1, ",");
+                    self.push_if(level > PR_TUPLE, ")");
+                }
+            }
+            Comprehension { kind, generators } => {
+                let close = match &**kind {
+                    ast::ComprehensionKind::GeneratorExpression { element } => {
+                        self.push("(");
+                        self.expression(element, PR_TEST);
+                        ")"
+                    }
+                    ast::ComprehensionKind::List { element } => {
+                        self.push("[");
+                        self.expression(element, PR_TEST);
+                        "]"
+                    }
+                    ast::ComprehensionKind::Set { element } => {
+                        self.push("{");
+                        self.expression(element, PR_TEST);
+                        "}"
+                    }
+                    ast::ComprehensionKind::Dict { key, value } => {
+                        self.push("{");
+                        self.expression(key, PR_TEST);
+                        self.push(": ");
+                        self.expression(value, PR_TEST);
+                        "}"
+                    }
+                };
+                for generator in generators {
+                    self.push(if generator.is_async {
+                        " async for "
+                    } else {
+                        " for "
+                    });
+                    self.expression(&generator.target, PR_TUPLE);
+                    self.push(" in ");
+                    self.expression(&generator.iter, PR_TEST + 1);
+                    for condition in &generator.ifs {
+                        self.push(" if ");
+                        self.expression(condition, PR_TEST + 1);
+                    }
+                }
+                self.push(close);
+            }
+            Call {
+                function,
+                args,
+                keywords,
+            } => {
+                self.expression(function, PR_ATOM);
+                if let ([argument], []) = (&args[..], &keywords[..]) {
+                    // A lone generator expression brings its own parentheses:
+                    if let Comprehension { kind, .. } = &argument.node {
+                        if let ast::ComprehensionKind::GeneratorExpression { .. } = **kind {
+                            self.expression(argument, PR_TEST);
+                            return;
+                        }
+                    }
+                }
+                self.push("(");
+                self.expressions(args, PR_TEST);
+                for (i, keyword) in keywords.iter().enumerate() {
+                    self.push_if(i > 0 || !args.is_empty(), ", ");
+                    match &keyword.name {
+                        Some(name) => {
+                            self.push(name);
+                            self.push("=");
+                        }
+                        Option::None => self.push("**"),
+                    }
+                    self.expression(&keyword.value, PR_TEST);
+                }
+                self.push(")");
+            }
+            Attribute { value, name } => {
+                self.expression(value, PR_ATOM);
+                // `1.real` would read as a float:
+                if let Number {
+                    value: ast::Number::Integer { .. },
+                } = value.node
+                {
+                    self.push(" ");
+                }
+                self.push(".");
+                self.push(name);
+            }
+            Subscript { a, b } => {
+                self.expression(a, PR_ATOM);
+                self.push("[");
+                match &b.node {
+                    // The elements of a tuple index don't need parentheses:
+                    Tuple { elements } if !elements.is_empty() => {
+                        self.expressions(elements, PR_TEST);
+                        self.push_if(elements.len() == 1, ",");
+                    }
+                    _ => self.expression(b, PR_TUPLE),
+                }
+                self.push("]");
+            }
+            Slice { elements } => {
+                // Parts which were left out are `None`, and so is the step:
+                for (i, element) in elements.iter().enumerate() {
+                    let given = element.node != None;
+                    if i == 2 && !given {
+                        break;
+                    }
+                    self.push_if(i > 0, ":");
+                    if given {
+                        self.expression(element, PR_TEST);
+                    }
+                }
+            }
+            Identifier { name } => self.push(name),
+            Number { value } => self.number(value),
+            String { value } => self.string_group(value),
+            Bytes { value } => {
+                self.push("b");
+                let s: std::string::String = value.iter().map(|&b| b as char).collect();
+                self.push(&quote(&s, true));
+            }
+            True => self.push("True"),
+            False => self.push("False"),
+            None => self.push("None"),
+            Ellipsis => self.push("..."),
+        }
+    }
+
+    fn parameters(&mut self, params: &ast::Parameters) {
+        let mut first = true;
+        let mut separate = |unparser: &mut Self| {
+            unparser.push_if(!first, ", ");
+            first = false;
+        };
+        let defaults_start = params.args.len() - params.defaults.len();
+        for (i, param) in params.args.iter().enumerate() {
+            separate(self);
+            self.push(&param.arg);
+            if i >= defaults_start {
+                self.push("=");
+                self.expression(&params.defaults[i - defaults_start], PR_TEST);
+            }
+            if i + 1 == params.posonlyargs_count {
+                separate(self);
+                self.push("/");
+            }
+        }
+        match &params.vararg {
+            ast::Varargs::Named(param) => {
+                separate(self);
+                self.push("*");
+                self.push(&param.arg);
+            }
+            ast::Varargs::Unnamed => {
+                separate(self);
+                self.push("*");
+            }
+            ast::Varargs::None if !params.kwonlyargs.is_empty() => {
+                separate(self);
+                self.push("*");
+            }
+            ast::Varargs::None => {}
+        }
+        for (param, default) in params.kwonlyargs.iter().zip(&params.kw_defaults) {
+            separate(self);
+            self.push(&param.arg);
+            if let Some(default) = default {
+                self.push("=");
+                self.expression(default, PR_TEST);
+            }
+        }
+        if let ast::Varargs::Named(param) = &params.kwarg {
+            separate(self);
+            self.push("**");
+            self.push(&param.arg);
+        }
+    }
+
+    fn number(&mut self, number: &ast::Number) {
+        match number {
+            ast::Number::Integer { value } => self.push(&value.to_string()),
+            ast::Number::Float { value } => self.push(&float_repr(*value)),
+            ast::Number::Complex { real, imag } => {
+                let mut imag = float_repr(*imag);
+                if imag.ends_with(".0") {
+                    imag.truncate(imag.len() - 2);
+                }
+                if *real == 0.0 {
+                    self.push(&imag);
+                    self.push("j");
+                } else {
+                    let _ = write!(self.output, "({}+{}j)", float_repr(*real), imag);
+                }
+            }
+        }
+    }
+
+    fn string_group(&mut self, group: &ast::StringGroup) {
+        if let ast::StringGroup::Constant { value } = group {
+            self.push(&quote(value, false));
+        } else {
+            let mut body = Unparser {
+                output: String::new(),
+            };
+            body.fstring_body(group);
+            self.push("f");
+            self.push(&quote(&body.output, false));
+        }
+    }
+
+    fn fstring_body(&mut self, group: &ast::StringGroup) {
+        match group {
+            ast::StringGroup::Constant { value } => {
+                self.push(&value.replace('{', "{{").replace('}', "}}"));
+            }
+            ast::StringGroup::FormattedValue {
+                value,
+                conversion,
+                spec,
+            } => {
+                let mut inner = Unparser {
+                    output: String::new(),
+                };
+                inner.expression(value, PR_TEST + 1);
+                self.push("{");
+                // `{{` would read as an escaped brace:
+                self.push_if(inner.output.starts_with('{'), " ");
+                self.push(&inner.output);
+                match conversion {
+                    Some(ast::ConversionFlag::Str) => self.push("!s"),
+                    Some(ast::ConversionFlag::Ascii) => self.push("!a"),
+                    Some(ast::ConversionFlag::Repr) => self.push("!r"),
+                    Option::None => {}
+                }
+                if let Some(spec) = spec {
+                    self.push(":");
+                    self.fstring_body(spec);
+                }
+                self.push("}");
+            }
+            ast::StringGroup::Joined { values } => {
+                for value in values {
+                    self.fstring_body(value);
+                }
+            }
+        }
+    }
+}
+
+fn parameters_are_empty(params: &ast::Parameters) -> bool {
+    params.args.is_empty()
+        && params.kwonlyargs.is_empty()
+        && params.vararg == ast::Varargs::None
+        && params.kwarg == ast::Varargs::None
+}
+
+fn binary_operator(op: &ast::Operator) -> (&'static str, u8) {
+    use ast::Operator::*;
+    match op {
+        BitOr => (" | ", PR_BOR),
+        BitXor => (" ^ ", PR_BXOR),
+        BitAnd => (" & ", PR_BAND),
+        LShift => (" << ", PR_SHIFT),
+        RShift => (" >> ", PR_SHIFT),
+        Add => (" + ", PR_ARITH),
+        Sub => (" - ", PR_ARITH),
+        Mult => (" * ", PR_TERM),
+        MatMult => (" @ ", PR_TERM),
+        Div => (" / ", PR_TERM),
+        Mod => (" % ", PR_TERM),
+        FloorDiv => (" // ", PR_TERM),
+        Pow => (" ** ", PR_POWER),
+    }
+}
+
+fn comparison(op: &ast::Comparison) -> &'static str {
+    use ast::Comparison::*;
+    match op {
+        Equal => " == ",
+        NotEqual => " != ",
+        Less => " < ",
+        LessOrEqual => " <= ",
+        Greater => " > ",
+        GreaterOrEqual => " >= ",
+        In => " in ",
+        NotIn => " not in ",
+        Is => " is ",
+        IsNot => " is not ",
+    }
+}
+
+/// Like `repr()` of a float. Infinity is written as a literal too large to
+/// represent, like CPython does.
+fn float_repr(value: f64) -> String {
+    if value.is_infinite() {
+        return "1e309".to_string();
+    }
+    let repr = format!("{:?}", value);
+    match repr.find('e') {
+        // Python writes exponents with a sign and at least two digits:
+        Some(index) => {
+            let (mantissa, exponent) = repr.split_at(index);
+            let exponent = &exponent[1..];
+            let (sign, digits) = match exponent.strip_prefix('-') {
+                Some(digits) => ("-", digits),
+                None => ("+", exponent),
+            };
+            format!("{}e{}{:0>2}", mantissa, sign, digits)
+        }
+        None => repr,
+    }
+}
+
+/// Like `repr()` of a string, or of bytes when `bytes` is set, in which case
+/// every char stands for a byte.
+fn quote(s: &str, bytes: bool) -> String {
+    let quote = if s.contains('\'') && !s.contains('"') {
+        '"'
+    } else {
+        '\''
+    };
+    let mut repr = String::with_capacity(s.len() + 2);
+    repr.push(quote);
+    for c in s.chars() {
+        match c {
+            '\\' => repr.push_str("\\\\"),
+            '\n' => repr.push_str("\\n"),
+            '\r' => repr.push_str("\\r"),
+            '\t' => repr.push_str("\\t"),
+            c if c == quote => {
+                repr.push('\\');
+                repr.push(c);
+            }
+            c if (c as u32) < 0x20 || c as u32 == 0x7f || (bytes && c as u32 > 0x7f) => {
+                let _ = write!(repr, "\\x{:02x}", c as u32);
+            }
+            c => repr.push(c),
+        }
+    }
+    repr.push(quote);
+    repr
+}
+
+#[cfg(test)]
+mod tests {
+    use super::unparse_annotation;
+    use rustpython_parser::parser;
+
+    fn check(source: &str, expected: &str) {
+        let expression = parser::parse_expression(source).unwrap();
+        assert_eq!(unparse_annotation(&expression), expected);
+    }
+
+    #[test]
+    fn test_unparse_annotation() {
+        check("List[ 'Node' ]", "List['Node']");
+        check("Dict[str,int]", "Dict[str, int]");
+        check("(1,)", "(1,)");
+        check("a+b*c", "a + b * c");
+        check("(a+b)*c", "(a + b) * c");
+        check("a**b**c", "a ** b ** c");
+        check("(a**b)**c", "(a ** b) ** c");
+        check("-x ** 2", "-x ** 2");
+        check("not a and (b or c)", "not a and (b or c)");
+        check("x if y else z", "x if y else z");
+        check("lambda a, *, b=1: a", "lambda a, *, b=1: a");
+        check("f(x for x in y)", "f(x for x in y)");
+        check("f(*a, k=1, **kw)", "f(*a, k=1, **kw)");
+        check("a[1:2, ::3]", "a[1:2, ::3]");
+        check("1 .real", "1 .real");
+        check("1.5e100", "1.5e+100");
+        check("\"it's\"", "\"it's\"");
+        check("b'\\x00a'", "b'\\x00a'");
+        check("f'{x!r:>{width}}'", "f'{x!r:>{width}}'");
+    }
+}
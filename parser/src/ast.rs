@@ -36,6 +36,8 @@ pub struct ImportSymbol {
 #[derive(Debug, PartialEq)]
 pub struct Located<T> {
     pub location: Location,
+    /// Where the source of the node ends, just after its last token.
+    pub end_location: Location,
     pub node: T,
 }
 
@@ -287,6 +289,8 @@ impl Expression {
  */
 #[derive(Debug, PartialEq, Default)]
 pub struct Parameters {
+    /// The number of leading `args` which are before a `/`, so can't be passed by keyword.
+    pub posonlyargs_count: usize,
     pub args: Vec<Parameter>,
     pub kwonlyargs: Vec<Parameter>,
     pub vararg: Varargs, // Optionally we handle optionally named '*args' or '*'
@@ -405,7 +409,7 @@ pub enum StringGroup {
     FormattedValue {
         value: Box<Expression>,
         conversion: Option<ConversionFlag>,
-        spec: String,
+        spec: Option<Box<StringGroup>>,
     },
     Joined {
         values: Vec<StringGroup>,
@@ -23,7 +23,7 @@ impl<'a> FStringParser<'a> {
 
     fn parse_formatted_value(&mut self) -> Result<StringGroup, FStringErrorType> {
         let mut expression = String::new();
-        let mut spec = String::new();
+        let mut spec = None;
         let mut delims = Vec::new();
         let mut conversion = None;
 
@@ -43,14 +43,37 @@ impl<'a> FStringParser<'a> {
                     })
                 }
                 ':' if delims.is_empty() => {
+                    // The spec may itself contain replacement fields, as in `{x:{width}}`:
+                    let mut spec_values = vec![];
+                    let mut constant = String::new();
                     while let Some(&next) = self.chars.peek() {
-                        if next != '}' {
-                            spec.push(next);
-                            self.chars.next();
-                        } else {
-                            break;
+                        match next {
+                            '{' => {
+                                self.chars.next();
+                                if !constant.is_empty() {
+                                    spec_values.push(Constant {
+                                        value: mem::replace(&mut constant, String::new()),
+                                    });
+                                }
+                                spec_values.push(self.parse_formatted_value()?);
+                            }
+                            '}' => break,
+                            _ => {
+                                constant.push(next);
+                                self.chars.next();
+                            }
                         }
                     }
+                    if !constant.is_empty() {
+                        spec_values.push(Constant { value: constant });
+                    }
+                    spec = match spec_values.len() {
+                        0 => None,
+                        1 => spec_values.pop().map(Box::new),
+                        _ => Some(Box::new(Joined {
+                            values: spec_values,
+                        })),
+                    };
                 }
                 '(' | '{' | '[' => {
                     expression.push(ch);
@@ -176,6 +199,7 @@ mod tests {
     fn mk_ident(name: &str, row: usize, col: usize) -> ast::Expression {
         ast::Expression {
             location: ast::Location::new(row, col),
+            end_location: ast::Location::new(row, col + name.len()),
             node: ast::ExpressionType::Identifier {
                 name: name.to_owned(),
             },
@@ -194,12 +218,12 @@ mod tests {
                     FormattedValue {
                         value: Box::new(mk_ident("a", 1, 1)),
                         conversion: None,
-                        spec: String::new(),
+                        spec: None,
                     },
                     FormattedValue {
                         value: Box::new(mk_ident("b", 1, 1)),
                         conversion: None,
-                        spec: String::new(),
+                        spec: None,
                     },
                     Constant {
                         value: "{foo}".to_owned()
@@ -209,6 +233,25 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_parse_fstring_nested_spec() {
+        let source = String::from("{foo:{spec}}");
+        let parse_ast = parse_fstring(&source).unwrap();
+
+        assert_eq!(
+            parse_ast,
+            FormattedValue {
+                value: Box::new(mk_ident("foo", 1, 1)),
+                conversion: None,
+                spec: Some(Box::new(FormattedValue {
+                    value: Box::new(mk_ident("spec", 1, 1)),
+                    conversion: None,
+                    spec: None,
+                })),
+            }
+        );
+    }
+
     #[test]
     fn test_parse_empty_fstring() {
         assert_eq!(
@@ -50,9 +50,11 @@ pub fn parse_statement(source: &str) -> Result<Vec<ast::Statement>, ParseError>
 ///
 /// assert_eq!(ast::Expression {
 ///         location: ast::Location::new(1, 3),
+///         end_location: ast::Location::new(1, 6),
 ///         node: ast::ExpressionType::Binop {
 ///             a: Box::new(ast::Expression {
 ///                 location: ast::Location::new(1, 1),
+///                 end_location: ast::Location::new(1, 2),
 ///                 node: ast::ExpressionType::Number {
 ///                     value: ast::Number::Integer { value: BigInt::from(1) }
 ///                 }
@@ -60,6 +62,7 @@ pub fn parse_statement(source: &str) -> Result<Vec<ast::Statement>, ParseError>
 ///             op: ast::Operator::Add,
 ///             b: Box::new(ast::Expression {
 ///                 location: ast::Location::new(1, 5),
+///                 end_location: ast::Location::new(1, 6),
 ///                 node: ast::ExpressionType::Number {
 ///                     value: ast::Number::Integer { value: BigInt::from(2) }
 ///                 }
@@ -84,6 +87,7 @@ mod tests {
     fn mk_ident(name: &str, row: usize, col: usize) -> ast::Expression {
         ast::Expression {
             location: ast::Location::new(row, col),
+            end_location: ast::Location::new(row, col + name.len()),
             node: ast::ExpressionType::Identifier {
                 name: name.to_owned(),
             },
@@ -93,6 +97,7 @@ mod tests {
     fn make_int(value: i32, row: usize, col: usize) -> ast::Expression {
         ast::Expression {
             location: ast::Location::new(row, col),
+            end_location: ast::Location::new(row, col + value.to_string().len()),
             node: ast::ExpressionType::Number {
                 value: ast::Number::Integer {
                     value: BigInt::from(value),
@@ -101,9 +106,11 @@ mod tests {
         }
     }
 
+    /// A string literal written between single quotes.
     fn make_string(value: &str, row: usize, col: usize) -> ast::Expression {
         ast::Expression {
             location: ast::Location::new(row, col),
+            end_location: ast::Location::new(row, col + value.len() + 2),
             node: ast::ExpressionType::String {
                 value: ast::StringGroup::Constant {
                     value: String::from(value),
@@ -115,6 +122,7 @@ mod tests {
     fn as_statement(expr: ast::Expression) -> ast::Statement {
         ast::Statement {
             location: expr.location.clone(),
+            end_location: expr.end_location.clone(),
             node: ast::StatementType::Expression { expression: expr },
         }
     }
@@ -134,12 +142,14 @@ mod tests {
             ast::Program {
                 statements: vec![ast::Statement {
                     location: ast::Location::new(1, 1),
+                    end_location: ast::Location::new(1, 21),
                     node: ast::StatementType::Expression {
                         expression: ast::Expression {
                             location: ast::Location::new(1, 6),
+                            end_location: ast::Location::new(1, 21),
                             node: ast::ExpressionType::Call {
                                 function: Box::new(mk_ident("print", 1, 1)),
-                                args: vec![make_string("Hello world", 1, 8)],
+                                args: vec![make_string("Hello world", 1, 7)],
                                 keywords: vec![],
                             }
                         },
@@ -158,12 +168,14 @@ mod tests {
             ast::Program {
                 statements: vec![ast::Statement {
                     location: ast::Location::new(1, 1),
+                    end_location: ast::Location::new(1, 24),
                     node: ast::StatementType::Expression {
                         expression: ast::Expression {
                             location: ast::Location::new(1, 6),
+                            end_location: ast::Location::new(1, 24),
                             node: ast::ExpressionType::Call {
                                 function: Box::new(mk_ident("print", 1, 1)),
-                                args: vec![make_string("Hello world", 1, 8), make_int(2, 1, 22),],
+                                args: vec![make_string("Hello world", 1, 7), make_int(2, 1, 22),],
                                 keywords: vec![],
                             },
                         },
@@ -182,12 +194,14 @@ mod tests {
             ast::Program {
                 statements: vec![ast::Statement {
                     location: ast::Location::new(1, 1),
+                    end_location: ast::Location::new(1, 33),
                     node: ast::StatementType::Expression {
                         expression: ast::Expression {
                             location: ast::Location::new(1, 8),
+                            end_location: ast::Location::new(1, 33),
                             node: ast::ExpressionType::Call {
                                 function: Box::new(mk_ident("my_func", 1, 1)),
-                                args: vec![make_string("positional", 1, 10)],
+                                args: vec![make_string("positional", 1, 9)],
                                 keywords: vec![ast::Keyword {
                                     name: Some("keyword".to_string()),
                                     value: make_int(2, 1, 31),
@@ -208,11 +222,13 @@ mod tests {
             parse_ast,
             vec![ast::Statement {
                 location: ast::Location::new(1, 1),
+                end_location: ast::Location::new(3, 9),
                 node: ast::StatementType::If {
                     test: make_int(1, 1, 4),
                     body: vec![as_statement(make_int(10, 1, 7))],
                     orelse: Some(vec![ast::Statement {
                         location: ast::Location::new(2, 1),
+                        end_location: ast::Location::new(3, 9),
                         node: ast::StatementType::If {
                             test: make_int(2, 2, 6),
                             body: vec![as_statement(make_int(20, 2, 9))],
@@ -232,8 +248,10 @@ mod tests {
             parse_ast,
             Ok(vec![as_statement(ast::Expression {
                 location: ast::Location::new(1, 1),
+                end_location: ast::Location::new(1, 19),
                 node: ast::ExpressionType::Lambda {
                     args: Box::new(ast::Parameters {
+                        posonlyargs_count: 0,
                         args: vec![
                             ast::Parameter {
                                 location: ast::Location::new(1, 8),
@@ -254,6 +272,7 @@ mod tests {
                     }),
                     body: Box::new(ast::Expression {
                         location: ast::Location::new(1, 16),
+                        end_location: ast::Location::new(1, 19),
                         node: ast::ExpressionType::Binop {
                             a: Box::new(mk_ident("x", 1, 14)),
                             op: ast::Operator::Mult,
@@ -273,15 +292,18 @@ mod tests {
             parse_statement(&source),
             Ok(vec![ast::Statement {
                 location: ast::Location::new(1, 1),
+                end_location: ast::Location::new(1, 12),
                 node: ast::StatementType::Assign {
                     targets: vec![ast::Expression {
                         location: ast::Location::new(1, 1),
+                        end_location: ast::Location::new(1, 5),
                         node: ast::ExpressionType::Tuple {
                             elements: vec![mk_ident("a", 1, 1), mk_ident("b", 1, 4),]
                         }
                     }],
                     value: ast::Expression {
                         location: ast::Location::new(1, 8),
+                        end_location: ast::Location::new(1, 12),
                         node: ast::ExpressionType::Tuple {
                             elements: vec![make_int(4, 1, 8), make_int(5, 1, 11),]
                         }
@@ -300,6 +322,7 @@ mod tests {
             parse_statement(&source),
             Ok(vec![ast::Statement {
                 location: ast::Location::new(1, 1),
+                end_location: ast::Location::new(5, 7),
                 node: ast::StatementType::ClassDef {
                     name: String::from("Foo"),
                     bases: vec![mk_ident("A", 1, 11), mk_ident("B", 1, 14)],
@@ -307,10 +330,12 @@ mod tests {
                     body: vec![
                         ast::Statement {
                             location: ast::Location::new(2, 2),
+                            end_location: ast::Location::new(4, 2),
                             node: ast::StatementType::FunctionDef {
                                 is_async: false,
                                 name: String::from("__init__"),
                                 args: Box::new(ast::Parameters {
+                                    posonlyargs_count: 0,
                                     args: vec![ast::Parameter {
                                         location: ast::Location::new(2, 15),
                                         arg: String::from("self"),
@@ -324,6 +349,7 @@ mod tests {
                                 }),
                                 body: vec![ast::Statement {
                                     location: ast::Location::new(3, 3),
+                                    end_location: ast::Location::new(3, 7),
                                     node: ast::StatementType::Pass,
                                 }],
                                 decorator_list: vec![],
@@ -332,10 +358,12 @@ mod tests {
                         },
                         ast::Statement {
                             location: ast::Location::new(4, 2),
+                            end_location: ast::Location::new(5, 7),
                             node: ast::StatementType::FunctionDef {
                                 is_async: false,
                                 name: String::from("method_with_default"),
                                 args: Box::new(ast::Parameters {
+                                    posonlyargs_count: 0,
                                     args: vec![
                                         ast::Parameter {
                                             location: ast::Location::new(4, 26),
@@ -351,11 +379,12 @@ mod tests {
                                     kwonlyargs: vec![],
                                     vararg: ast::Varargs::None,
                                     kwarg: ast::Varargs::None,
-                                    defaults: vec![make_string("default", 4, 37)],
+                                    defaults: vec![make_string("default", 4, 36)],
                                     kw_defaults: vec![],
                                 }),
                                 body: vec![ast::Statement {
                                     location: ast::Location::new(5, 3),
+                                    end_location: ast::Location::new(5, 7),
                                     node: ast::StatementType::Pass,
                                 }],
                                 decorator_list: vec![],
@@ -377,6 +406,7 @@ mod tests {
             parse_ast,
             ast::Expression {
                 location: ast::Location::new(1, 1),
+                end_location: ast::Location::new(1, 15),
                 node: ast::ExpressionType::Comprehension {
                     kind: Box::new(ast::ComprehensionKind::List {
                         element: mk_ident("x", 1, 2),
@@ -401,6 +431,7 @@ mod tests {
             parse_ast,
             ast::Expression {
                 location: ast::Location::new(1, 1),
+                end_location: ast::Location::new(1, 49),
                 node: ast::ExpressionType::Comprehension {
                     kind: Box::new(ast::ComprehensionKind::List {
                         element: mk_ident("x", 1, 2)
@@ -410,6 +441,7 @@ mod tests {
                             location: ast::Location::new(1, 4),
                             target: ast::Expression {
                                 location: ast::Location::new(1, 8),
+                                end_location: ast::Location::new(1, 13),
                                 node: ast::ExpressionType::Tuple {
                                     elements: vec![mk_ident("y", 1, 8), mk_ident("y2", 1, 11),],
                                 }
@@ -425,6 +457,7 @@ mod tests {
                             ifs: vec![
                                 ast::Expression {
                                     location: ast::Location::new(1, 35),
+                                    end_location: ast::Location::new(1, 38),
                                     node: ast::ExpressionType::Compare {
                                         vals: vec![mk_ident("a", 1, 33), make_int(5, 1, 37),],
                                         ops: vec![ast::Comparison::Less],
@@ -432,6 +465,7 @@ mod tests {
                                 },
                                 ast::Expression {
                                     location: ast::Location::new(1, 44),
+                                    end_location: ast::Location::new(1, 48),
                                     node: ast::ExpressionType::Compare {
                                         vals: vec![mk_ident("a", 1, 42), make_int(10, 1, 46),],
                                         ops: vec![ast::Comparison::Greater],
@@ -445,4 +479,31 @@ mod tests {
             }
         );
     }
+
+    #[test]
+    fn test_parse_positional_only_parameters() {
+        let parse_ast = parse_statement("lambda a, b=1, /, c=2, *, d: 0").unwrap();
+        if let ast::StatementType::Expression { expression } = &parse_ast[0].node {
+            if let ast::ExpressionType::Lambda { args, .. } = &expression.node {
+                assert_eq!(args.posonlyargs_count, 2);
+                let names: Vec<_> = args.args.iter().map(|arg| arg.arg.as_str()).collect();
+                assert_eq!(names, ["a", "b", "c"]);
+                assert_eq!(args.defaults.len(), 2);
+                assert_eq!(args.kwonlyargs.len(), 1);
+                return;
+            }
+        }
+        panic!("not a lambda: {:?}", parse_ast);
+    }
+
+    #[test]
+    fn test_parse_positional_only_trailing_comma() {
+        let parse_ast = parse_program("def f(a, /,): pass").unwrap();
+        if let ast::StatementType::FunctionDef { args, .. } = &parse_ast.statements[0].node {
+            assert_eq!(args.posonlyargs_count, 1);
+            assert_eq!(args.args.len(), 1);
+        } else {
+            panic!("not a function: {:?}", parse_ast);
+        }
+    }
 }
@@ -21,8 +21,27 @@ use std::env;
 use std::path::PathBuf;
 use std::process;
 use std::str::FromStr;
+use std::thread;
+
+/// Every Python call recurses through several native frames, so the main
+/// thread's stack runs out long before `sys.getrecursionlimit()` frames.
+/// The interpreter runs on a thread of its own with room for the default
+/// limit; the stack is only reserved, so unused depth costs nothing.
+const INTERPRETER_STACK_SIZE: usize = 256 * 1024 * 1024;
 
 fn main() {
+    let interpreter = thread::Builder::new()
+        .name("main".to_string())
+        .stack_size(INTERPRETER_STACK_SIZE)
+        .spawn(run_interpreter)
+        .expect("failed to spawn the interpreter thread");
+    if interpreter.join().is_err() {
+        // The panic message has already been printed by the thread.
+        process::exit(101);
+    }
+}
+
+fn run_interpreter() {
     #[cfg(feature = "flame-it")]
     let main_guard = flame::start_guard("RustPython main");
     env_logger::init();
@@ -0,0 +1,42 @@
+import sys
+
+from testutils import assertRaises
+
+with assertRaises(AssertionError):
+    assert False
+
+try:
+    assert False, "boom"
+except AssertionError as e:
+    assert e.args == ("boom",)
+    assert str(e) == "boom"
+else:
+    assert False
+
+
+# The raised exception doesn't depend on what the name is bound to:
+def shadowed():
+    AssertionError = ValueError  # noqa: F841
+    assert False
+
+
+with assertRaises(AssertionError):
+    shadowed()
+
+assert __debug__ == (sys.flags.optimize == 0)
+
+# When optimizing, assertions are compiled out and their test isn't evaluated:
+calls = []
+
+
+def check():
+    calls.append(1)
+    return False
+
+
+exec(compile("assert check(), 'boom'", "<assert>", "exec", optimize=1))
+assert calls == []
+
+with assertRaises(AssertionError):
+    exec(compile("assert check(), 'boom'", "<assert>", "exec", optimize=0))
+assert calls == [1]
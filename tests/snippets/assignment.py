@@ -60,3 +60,23 @@ assert a == 1337
 assert b == False
 
 assert __annotations__['a'] == bool
+
+def unpack_error(f):
+    try:
+        f()
+    except ValueError as ex:
+        return str(ex)
+    assert False, 'unpacking should have failed'
+
+def too_many():
+    a, b = (1, 2, 3)
+
+def not_enough():
+    a, b, c = (1, 2)
+
+def not_enough_starred():
+    a, b, *c, d = (1, 2)
+
+assert unpack_error(too_many) == 'too many values to unpack (expected 2)'
+assert unpack_error(not_enough) == 'not enough values to unpack (expected 3, got 2)'
+assert unpack_error(not_enough_starred) == 'not enough values to unpack (expected at least 3, got 2)'
@@ -0,0 +1,87 @@
+from testutils import assertRaises
+
+
+class Suspend:
+    def __await__(self):
+        yield 'suspended'
+
+
+class Counter:
+    def __init__(self, stop):
+        self.current = 0
+        self.stop = stop
+
+    def __aiter__(self):
+        return self
+
+    async def __anext__(self):
+        await Suspend()
+        if self.current >= self.stop:
+            raise StopAsyncIteration
+        self.current += 1
+        return self.current
+
+
+def run(coro):
+    suspensions = 0
+    try:
+        while True:
+            assert coro.send(None) == 'suspended'
+            suspensions += 1
+    except StopIteration as e:
+        return e.value, suspensions
+
+
+async def collect():
+    values = []
+    async for value in Counter(3):
+        values.append(value)
+    else:
+        values.append('else')
+    return values
+
+
+assert run(collect()) == ([1, 2, 3, 'else'], 4)
+
+
+async def break_out():
+    values = []
+    async for value in Counter(10):
+        if value == 2:
+            break
+        values.append(value)
+    else:
+        values.append('else')
+    return values
+
+
+assert run(break_out()) == ([1], 2)
+
+
+class Failing:
+    def __aiter__(self):
+        return self
+
+    async def __anext__(self):
+        raise ValueError('no more')
+
+
+async def failing():
+    async for value in Failing():
+        pass
+
+
+with assertRaises(ValueError):
+    run(failing())
+
+
+async def not_async_iterable():
+    async for value in [1, 2]:
+        pass
+
+
+with assertRaises(TypeError):
+    run(not_async_iterable())
+
+with assertRaises(SyntaxError):
+    exec("def not_async():\n    async for x in y:\n        pass\n")
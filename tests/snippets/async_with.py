@@ -0,0 +1,90 @@
+from testutils import assertRaises
+
+events = []
+
+
+class Suspend:
+    def __await__(self):
+        events.append('suspend')
+        yield
+
+
+class Manager:
+    def __init__(self, name, suppress=False):
+        self.name = name
+        self.suppress = suppress
+
+    async def __aenter__(self):
+        events.append(('enter', self.name))
+        await Suspend()
+        return self.name
+
+    async def __aexit__(self, exc_type, exc, tb):
+        events.append(('exit', self.name, exc_type))
+        await Suspend()
+        return self.suppress
+
+
+def run(coro):
+    try:
+        while True:
+            coro.send(None)
+    except StopIteration as e:
+        return e.value
+
+
+async def nested():
+    async with Manager('a') as a, Manager('b') as b:
+        events.append(('body', a, b))
+    return 'done'
+
+
+assert run(nested()) == 'done'
+assert events == [
+    ('enter', 'a'), 'suspend',
+    ('enter', 'b'), 'suspend',
+    ('body', 'a', 'b'),
+    ('exit', 'b', None), 'suspend',
+    ('exit', 'a', None), 'suspend',
+]
+
+
+async def suppressed():
+    async with Manager('s', suppress=True):
+        raise ValueError
+    return 'after'
+
+
+events = []
+assert run(suppressed()) == 'after'
+assert events == [('enter', 's'), 'suspend', ('exit', 's', ValueError), 'suspend']
+
+
+async def propagated():
+    async with Manager('p'):
+        raise KeyError
+
+
+events = []
+with assertRaises(KeyError):
+    run(propagated())
+assert events == [('enter', 'p'), 'suspend', ('exit', 'p', KeyError), 'suspend']
+
+
+async def returns_from_body():
+    for i in range(3):
+        async with Manager(i):
+            if i == 1:
+                return i
+            continue
+
+
+events = []
+assert run(returns_from_body()) == 1
+assert events == [
+    ('enter', 0), 'suspend', ('exit', 0, None), 'suspend',
+    ('enter', 1), 'suspend', ('exit', 1, None), 'suspend',
+]
+
+with assertRaises(SyntaxError):
+    exec("def not_async():\n    async with Manager('x'):\n        pass\n")
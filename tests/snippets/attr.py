@@ -89,3 +89,41 @@ with assertRaises(KeyError):
     getattr(GetRaise(KeyError()), 'a')
 with assertRaises(KeyError):
     getattr(GetRaise(KeyError()), 'a', 11)
+
+
+# Attribute lookups in a loop must notice changes to the class:
+class Base:
+    x = 1
+
+class Derived(Base):
+    pass
+
+def read_x(obj, n=3):
+    return [obj.x for _ in range(n)]
+
+d = Derived()
+assert read_x(d) == [1, 1, 1]
+Base.x = 2
+assert read_x(d) == [2, 2, 2]
+Derived.x = 3
+assert read_x(d) == [3, 3, 3]
+d.x = 4
+assert read_x(d) == [4, 4, 4]
+assert read_x(Derived()) == [3, 3, 3]
+
+class Descriptor:
+    def __get__(self, obj, cls):
+        return 'get'
+
+Base.y = 5
+e = Derived()
+e.y = 'instance'
+assert [e.y for _ in range(2)] == ['instance', 'instance']
+Descriptor.__set__ = lambda self, obj, value: None
+Base.y = Descriptor()
+assert [e.y for _ in range(2)] == ['get', 'get']
+
+with assertRaises(AttributeError):
+    Derived().z
+Base.__getattr__ = lambda self, name: 'fallback'
+assert [Derived().z for _ in range(2)] == ['fallback', 'fallback']
@@ -4,4 +4,9 @@ assert ascii('안녕 RustPython') == "'\\uc548\\ub155 RustPython'"
 assert ascii(5) == '5'
 assert ascii(chr(0x10001)) == "'\\U00010001'"
 assert ascii(chr(0x9999)) == "'\\u9999'"
-assert ascii(chr(0x0A)) == "'\\n'"
\ No newline at end of file
+assert ascii(chr(0x0A)) == "'\\n'"
+assert ascii(chr(0xe9)) == "'\\xe9'"
+assert ascii('\U0001f600 smile') == "'\\U0001f600 smile'"
+assert ascii(['\U0010ffff']) == "['\\U0010ffff']"
+# Escaped surrogates stay escaped, rather than being decoded:
+assert ascii('\\ud83d\\ude00') == "'\\\\ud83d\\\\ude00'"
@@ -91,6 +91,33 @@ class CustomIndex:
 
 assert c[CustomIndex(1):CustomIndex(3)] == [1, 2]
 assert d[CustomIndex(1):CustomIndex(3)] == "23"
+assert c[CustomIndex(0):CustomIndex(5):CustomIndex(2)] == [0, 2, 4]
+assert c[CustomIndex(-1)::CustomIndex(-2)] == [9, 7, 5, 3, 1]
+assert b"abcdef"[CustomIndex(1):CustomIndex(3)] == b"bc"
+assert tuple(c)[:CustomIndex(2)] == (0, 1)
+assert range(10)[CustomIndex(2):CustomIndex(4)] == range(2, 4)
+assert b"abcdef".startswith(b"cd", CustomIndex(2))
+assert not b"abcdef".endswith(b"cd", CustomIndex(0), CustomIndex(5))
+
+e = list(range(5))
+e[CustomIndex(1):CustomIndex(3)] = ["x"]
+assert e == [0, "x", 3, 4]
+del e[CustomIndex(0):CustomIndex(4):CustomIndex(2)]
+assert e == ["x", 4]
+
+assert_raises(TypeError, lambda: c[1.5:], "float start")
+assert_raises(TypeError, lambda: c[:"2"], "str stop")
+assert_raises(TypeError, lambda: b"abc".startswith(b"a", 1.0), "float start")
+
+assert slice(CustomIndex(1), CustomIndex(8), CustomIndex(2)).indices(5) == (1, 5, 2)
+assert slice(None).indices(CustomIndex(3)) == (0, 3, 1)
+assert slice(None, None, -1).indices(4) == (3, -1, -1)
+assert slice(-10, 10).indices(4) == (0, 4, 1)
+assert slice(10, -10, -2).indices(4) == (3, -1, -2)
+assert slice(-2, None).indices(5) == (3, 5, 1)
+assert_raises(ValueError, lambda: slice(None, None, 0).indices(3), "zero step")
+assert_raises(ValueError, lambda: slice(None).indices(-1), "negative length")
+assert_raises(TypeError, lambda: slice(1.5).indices(3), "float stop")
 
 
 def test_all_slices():
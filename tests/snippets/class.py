@@ -164,3 +164,91 @@ class T5(int):
 assert str(super(int, T5(5))) == "<super: <class 'int'>, <T5 object>>"
 
 #assert str(super(type, None)) == "<super: <class 'type'>, NULL>"
+
+
+class Methods:
+    def __init__(self, x):
+        self.x = x
+
+    def get(self, y=0):
+        return self.x + y
+
+    @classmethod
+    def klass(cls):
+        return cls
+
+    @staticmethod
+    def static(y):
+        return y * 2
+
+m = Methods(3)
+assert m.get() == 3
+assert m.get(4) == 7
+assert m.klass() is Methods
+assert m.static(5) == 10
+assert [1, 2].index(2) == 1
+assert None.__repr__() == 'None'
+assert Methods.get(m, 1) == 4
+
+# Attributes on the instance take priority over methods on the class:
+m.get = lambda: 'instance'
+assert m.get() == 'instance'
+
+class Intercepted(Methods):
+    def __getattribute__(self, name):
+        if name == 'get':
+            return lambda: 'intercepted'
+        return object.__getattribute__(self, name)
+
+assert Intercepted(1).get() == 'intercepted'
+assert Intercepted(1).static(2) == 4
+
+
+# Free variables in a class body are looked up in the class namespace first:
+def class_namespace_overrides_closure():
+    x = 42
+    class X:
+        locals()["x"] = 43
+        y = x
+    assert X.y == 43
+
+    class Y:
+        z = x
+        def method(self, default=x):
+            return default
+    assert Y.z == 42
+    assert Y().method() == 42
+    assert x == 42
+
+class_namespace_overrides_closure()
+
+
+# The name of a class can be changed:
+class Renamed:
+    pass
+
+Renamed.__name__ = 'NewName'
+Renamed.__qualname__ = 'Outer.NewName'
+assert Renamed.__name__ == 'NewName'
+assert Renamed.__qualname__ == 'Outer.NewName'
+
+module_global = 10
+
+def make_class_using_outer_names():
+    enclosing = 5
+    class C:
+        length = len([1, 2])
+        from_global = module_global + 1
+        from_enclosing = enclosing
+    return C
+
+C = make_class_using_outer_names()
+assert C.length == 2
+assert C.from_global == 11
+assert C.from_enclosing == 5
+
+class ShadowsBuiltin:
+    len = 3
+    shadowed = len
+
+assert ShadowsBuiltin.shadowed == 3
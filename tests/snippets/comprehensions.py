@@ -27,3 +27,34 @@ z = [(9,), (10,)]
 w = [x for x, in z]
 assert w == [9, 10]
 
+
+# Comprehensions run in their own scope, so their variables don't leak:
+leak_names = ['i', 'j', 'k', 'm']
+for name in leak_names:
+    assert name not in globals(), name
+squares = [i * i for i in range(3)]
+evens = {j for j in range(4) if j % 2 == 0}
+pairs = {k: k + 1 for k in range(2)}
+total = sum(m for m in range(3))
+assert (squares, evens, pairs, total) == ([0, 1, 4], {0, 2}, {0: 1, 1: 2}, 3)
+for name in leak_names:
+    assert name not in globals(), name
+
+# An existing variable of the same name is left alone:
+i = 'outer'
+assert [i for i in range(2)] == [0, 1]
+assert i == 'outer'
+
+def comprehension_in_function():
+    [n for n in range(2)]
+    return locals()
+
+assert comprehension_in_function() == {}
+
+# Only the first iterable is evaluated in the enclosing scope, in a class body too:
+class Body:
+    size = 3
+    values = [v for v in range(size)]
+
+assert Body.values == [0, 1, 2]
+assert not hasattr(Body, 'v')
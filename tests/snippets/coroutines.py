@@ -0,0 +1,147 @@
+import sys
+from testutils import assertRaises
+
+
+async def simple():
+    return 42
+
+
+coro = simple()
+assert type(coro).__name__ == 'coroutine'
+with assertRaises(TypeError):
+    iter(coro)
+with assertRaises(TypeError):
+    next(coro)
+try:
+    coro.send(None)
+except StopIteration as e:
+    assert e.args == (42,)
+else:
+    assert False, "coroutine should have returned"
+
+
+class Awaitable:
+    def __await__(self):
+        value = yield 'suspended'
+        return value * 2
+
+
+async def double():
+    return await Awaitable()
+
+
+async def outer():
+    first = await double()
+    second = await simple()
+    return first + second
+
+
+coro = outer()
+assert coro.send(None) == 'suspended'
+try:
+    coro.send(5)
+except StopIteration as e:
+    assert e.args == (52,)
+else:
+    assert False, "coroutine should have returned"
+
+coro = outer()
+wrapper = coro.__await__()
+assert iter(wrapper) is wrapper
+assert next(wrapper) == 'suspended'
+wrapper.close()
+
+coro = simple()
+with assertRaises(TypeError):
+    coro.send(1)
+coro.close()
+
+
+async def awaits_non_awaitable():
+    await 1
+
+
+coro = awaits_non_awaitable()
+with assertRaises(TypeError):
+    coro.send(None)
+
+with assertRaises(SyntaxError):
+    exec("def not_async():\n    await simple()\n")
+
+# A lambda in a coroutine is a plain function, so it can't await:
+with assertRaises(SyntaxError):
+    exec("async def outer():\n    return lambda: await simple()\n")
+
+if sys.implementation.name.lower() == 'rustpython':
+    # Compiling an async comprehension fails instead of aborting:
+    with assertRaises(SyntaxError):
+        exec("async def outer():\n    return [z async for z in simple()]\n")
+
+
+# Drive a coroutine by hand, like an event loop would:
+class Future:
+    def __init__(self, name):
+        self.name = name
+
+    def __await__(self):
+        result = yield self.name
+        return result
+
+
+async def fetch(name):
+    result = await Future(name)
+    return name + '=' + str(result)
+
+
+async def gather():
+    first = await fetch('a')
+    second = await fetch('b')
+    return [first, second]
+
+
+coro = gather()
+requests = []
+value = None
+while True:
+    try:
+        request = coro.send(value)
+    except StopIteration as e:
+        result = e.value
+        break
+    requests.append(request)
+    value = len(requests) * 10
+
+assert requests == ['a', 'b']
+assert result == ['a=10', 'b=20']
+
+
+class BadAwaitable:
+    def __await__(self):
+        return 42
+
+
+async def await_bad():
+    await BadAwaitable()
+
+
+coro = await_bad()
+with assertRaises(TypeError):
+    coro.send(None)
+
+
+# A finished coroutine can't be resumed, only closed:
+async def returns_one():
+    return 1
+
+coro = returns_one()
+try:
+    coro.send(None)
+except StopIteration as e:
+    assert e.value == 1
+else:
+    assert False, "StopIteration not raised"
+with assertRaises(RuntimeError):
+    coro.send(None)
+with assertRaises(RuntimeError):
+    coro.throw(ValueError)
+coro.close()
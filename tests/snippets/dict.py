@@ -223,6 +223,22 @@ z = {'c': 3, 'd': 3, 'e': 3}
 w = {1: 1, **x, 2: 2, **y, 3: 3, **z, 4: 4}
 assert w == {1: 1, 'a': 1, 'b': 2, 'c': 3, 2: 2, 'd': 3, 3: 3, 'e': 3, 4: 4}
 
+assert {**{'a': 1}, **{'a': 2}} == {'a': 2}
+
+class Mapping:
+    def keys(self):
+        return ['x', 'y']
+    def __getitem__(self, key):
+        return key * 2
+
+assert {**Mapping(), 'z': 1} == {'x': 'xx', 'y': 'yy', 'z': 1}
+
+with assertRaises(TypeError):
+    {**5}
+
+with assertRaises(TypeError):
+    {**[1, 2]}
+
 assert str({True: True, 1.0: 1.0}) == str({True: 1.0})
 
 class A:
@@ -251,3 +267,11 @@ c = {*a, *b, *x}
 assert isinstance(c, set)
 assert c == {'bla', 'c', 'd', 'f'}
 
+
+# A literal with only constant keys builds the same dict as one whose keys are computed:
+one, text, raw, empty, ellipsis = 1, 'text', b'raw', None, ...
+literal = {1: 'one', 'text': 2, b'raw': 3.5, None: [], 1.0: 'float one', ...: 'ellipsis'}
+computed = {one: 'one', text: 2, raw: 3.5, empty: [], float(one): 'float one', ellipsis: 'ellipsis'}
+assert literal == computed
+assert list(literal.items()) == list(computed.items())
+assert literal == {1: 'float one', 'text': 2, b'raw': 3.5, None: [], ...: 'ellipsis'}
@@ -0,0 +1,2 @@
+from ..relative import value as thing
+from .. import relative
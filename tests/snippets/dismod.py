@@ -32,3 +32,35 @@ class A(object):
 
 print("A.f\n")
 dis.dis(A.f)
+
+import io
+import sys
+
+
+def disassembly(obj):
+    output = io.StringIO()
+    dis.dis(obj, file=output)
+    return output.getvalue()
+
+
+lines = disassembly(lambda x: x + 1).splitlines()
+if sys.implementation.name.lower() == 'rustpython':
+    opcodes = ['LoadFast', 'LoadConst', 'BinaryOperation', 'ReturnValue']
+else:
+    opcodes = ['LOAD_FAST', 'LOAD_CONST', 'RETURN_VALUE']
+for opcode in opcodes:
+    assert any(opcode in line for line in lines), (opcode, lines)
+# Locals are shown by name:
+load_fast = [line for line in lines if opcodes[0] in line][0]
+assert 'x)' in load_fast, load_fast
+
+# Source strings are compiled, each line's first instruction shows its line
+# number, and nested code objects are shown too:
+text = disassembly("a = 1\nb = 2\ndef inner(c):\n    return c\n")
+line_numbers = []
+for line in text.split('Disassembly of')[0].splitlines():
+    tokens = [token for token in line.split(' ') if token]
+    if len(tokens) > 1 and tokens[0].isdigit() and tokens[1].isdigit():
+        line_numbers.append(int(tokens[0]))
+assert [n for n in line_numbers if n > 0] == [1, 2, 3], text
+assert 'Disassembly of <code object inner' in text, text
@@ -9,3 +9,23 @@ assert_raises(ZeroDivisionError, lambda: 5.3 // (-0.0))
 assert_raises(ZeroDivisionError, lambda: divmod(5, 0))
 
 assert issubclass(ZeroDivisionError, ArithmeticError)
+
+
+def zero_division_message(f):
+    try:
+        f()
+    except ZeroDivisionError as ex:
+        return str(ex)
+    assert False, 'expected ZeroDivisionError'
+
+assert zero_division_message(lambda: 1 / 0) == 'division by zero'
+assert zero_division_message(lambda: 1 // 0) == 'integer division or modulo by zero'
+assert zero_division_message(lambda: 1 % 0) == 'integer modulo by zero'
+assert zero_division_message(lambda: divmod(1, 0)) == 'integer division or modulo by zero'
+assert zero_division_message(lambda: 1.0 / 0.0) == 'float division by zero'
+assert zero_division_message(lambda: 1 / 0.0) == 'float division by zero'
+assert zero_division_message(lambda: 1.0 // 0.0) == 'float floor division by zero'
+assert zero_division_message(lambda: 1.0 % 0.0) == 'float modulo'
+assert zero_division_message(lambda: divmod(1.0, 0.0)) == 'float divmod()'
+assert zero_division_message(lambda: 0 ** -1) == '0.0 cannot be raised to a negative power'
+assert zero_division_message(lambda: 0.0 ** -1.0) == '0.0 cannot be raised to a negative power'
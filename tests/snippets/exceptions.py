@@ -51,3 +51,27 @@ assert exc.name == 'name'
 assert exc.path == 'path'
 assert exc.msg == 'hello'
 assert exc.args == ('hello',)
+
+# Other exceptions show a single argument with str():
+exc = ValueError('message')
+assert str(exc) == 'message'
+assert str(ValueError(A())) == 'str'
+
+# Notes (PEP 678):
+import traceback
+
+exc = ValueError('bad value')
+assert not hasattr(exc, '__notes__')
+exc.add_note('first note')
+exc.add_note('second\nnote')
+assert exc.__notes__ == ['first note', 'second\nnote']
+
+try:
+    exc.add_note(42)
+except TypeError:
+    pass
+else:
+    assert False, "TypeError not raised"
+
+lines = traceback.format_exception_only(type(exc), exc)
+assert lines == ['ValueError: bad value\n', 'first note\n', 'second\n', 'note\n'], lines
@@ -39,9 +39,22 @@ v = Value()
 assert f'{v}' == 'foo'
 assert f'{v!r}' == 'bar'
 assert f'{v!s}' == 'baz'
+assert f'{v!a}' == 'bar'
+
+assert f'{"é"!a}' == "'\\xe9'"
+smile = '\U0001f600'
+assert f'{smile!a}' == "'\\U0001f600'"
+assert f'{["ü"]!a}' == "['\\xfc']"
 
 # advanced expressions:
 
 assert f'{True or True}' == 'True'
 assert f'{1 == 1}' == 'True'
 assert f'{"0" if True else "1"}' == '0'
+
+# Format specs can contain replacement fields of their own:
+w, p = 10, 2
+assert f"{3.14159:{w}.{p}f}" == "      3.14"
+assert f"{3.14159:>{w}}" == "   3.14159"
+assert f"{'abc':{'^'}{7}}" == "  abc  "
+assert f"{42!r:{w}}" == "42        "
@@ -1,3 +1,5 @@
+from testutils import assertRaises
+
 __name__ = "function"
 
 
@@ -88,3 +90,103 @@ def f8() -> int:
     return 10
 
 assert f8() == 10
+
+
+# Locals of a function:
+def unbound_local():
+    if False:
+        x = 1
+    return x
+
+with assertRaises(UnboundLocalError):
+    unbound_local()
+
+assert issubclass(UnboundLocalError, NameError)
+
+def deleted_local():
+    x = 1
+    del x
+    return x
+
+with assertRaises(UnboundLocalError):
+    deleted_local()
+
+def double_delete():
+    x = 1
+    del x
+    del x
+
+with assertRaises(UnboundLocalError):
+    double_delete()
+
+def read_before_assignment():
+    print(x)
+    x = 1
+
+with assertRaises(UnboundLocalError):
+    read_before_assignment()
+
+# Also for locals which a nested function refers to, which live in a cell:
+def unbound_cell():
+    y = x
+    x = 1
+    return lambda: x
+
+with assertRaises(UnboundLocalError):
+    unbound_cell()
+
+# But an empty cell of an enclosing function is only a NameError:
+def unbound_free():
+    def inner():
+        return x
+    inner_value = None
+    try:
+        inner()
+    except UnboundLocalError:
+        assert False, "UnboundLocalError raised for a free variable"
+    except NameError:
+        inner_value = 'unbound'
+    x = 1
+    return inner_value
+
+assert unbound_free() == 'unbound'
+
+# Names of a module or class body are not locals of a function:
+class Body:
+    try:
+        not_yet_defined
+    except NameError as e:
+        assert type(e) is NameError
+    not_yet_defined = 1
+
+def inspected_locals(a):
+    b = a * 2
+    c = 0
+    del c
+    return locals()
+
+assert inspected_locals(3) == {'a': 3, 'b': 6}
+
+def evaluated_locals(a):
+    b = a * 2
+    return eval('a + b')
+
+assert evaluated_locals(3) == 9
+
+def starred_return(a, b):
+    return *a, *b, 3
+
+assert starred_return([1], (2,)) == (1, 2, 3)
+assert starred_return([], []) == (3,)
+
+def starred_yield(a):
+    yield *a, 4
+
+assert list(starred_yield(range(1, 4))) == [(1, 2, 3, 4)]
+
+with assertRaises(TypeError):
+    starred_return(5, [])
+
+for source in ["def f(a):\n    return *a\n", "*a"]:
+    with assertRaises(SyntaxError):
+        compile(source, "<test>", "exec")
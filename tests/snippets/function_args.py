@@ -95,3 +95,110 @@ assert kwargs == [('a', 1), ('b', 2)]
 
 kwargs = func(a=1, b=2, c=3)
 assert kwargs == [('a', 1), ('b', 2), ('c', 3)]
+
+kwargs = func(**{'a': 1}, b=2, **{'c': 3})
+assert kwargs == [('a', 1), ('b', 2), ('c', 3)]
+
+with assertRaises(TypeError):
+    func(**{'a': 1}, **{'a': 2})
+
+with assertRaises(TypeError):
+    func(a=1, **{'a': 2})
+
+with assertRaises(TypeError):
+    func(**{1: 2})
+
+with assertRaises(TypeError):
+    func(**[1, 2])
+
+with assertRaises(TypeError):
+    func(**[])
+
+
+class Mapping:
+    def keys(self):
+        return ['b', 'a']
+    def __getitem__(self, key):
+        return key.upper()
+
+kwargs = func(**Mapping())
+assert kwargs == [('b', 'B'), ('a', 'A')]
+
+
+# Any iterable can be unpacked into positional arguments:
+def positional(*args):
+    return args
+
+def gen():
+    yield 1
+    yield 2
+
+class Sequence:
+    def __getitem__(self, index):
+        if index < 2:
+            return index
+        raise IndexError
+
+assert positional(*range(3)) == (0, 1, 2)
+assert positional(*gen()) == (1, 2)
+assert positional(0, *gen(), 3, *'ab') == (0, 1, 2, 3, 'a', 'b')
+assert positional(*Sequence()) == (0, 1)
+
+for call in (lambda: positional(*5), lambda: positional(1, *None, k=2)):
+    try:
+        call()
+    except TypeError as e:
+        assert 'after * must be an iterable, not' in str(e), e
+    else:
+        assert False, "TypeError not raised"
+
+def failing_gen():
+    raise KeyError('inside')
+    yield
+
+with assertRaises(KeyError):
+    positional(*failing_gen())
+
+
+# Positional-only parameters:
+def posonly(a, b=2, /, c=3, *, d=4):
+    return a, b, c, d
+
+assert posonly(1) == (1, 2, 3, 4)
+assert posonly(1, 5, 6, d=7) == (1, 5, 6, 7)
+assert posonly(1, c=6) == (1, 2, 6, 4)
+assert posonly.__code__.co_posonlyargcount == 2
+assert posonly.__code__.co_argcount == 3
+
+for call in (lambda: posonly(a=1), lambda: posonly(1, b=2)):
+    try:
+        call()
+    except TypeError as e:
+        assert 'positional-only arguments passed as keyword arguments' in str(e), e
+    else:
+        assert False, "TypeError not raised"
+
+# With **kwargs, the positional-only names are free for keywords:
+def posonly_kwargs(a, /, **kwargs):
+    return a, kwargs
+
+assert posonly_kwargs(1, a=2) == (1, {'a': 2})
+assert (lambda x, /, y: x - y)(5, y=3) == 2
+
+def kwonly(a, *, x, y=2, z):
+    return a, x, y, z
+
+assert kwonly(1, x=3, z=4) == (1, 3, 2, 4)
+assert kwonly(1, z=4, y=5, x=3) == (1, 3, 5, 4)
+
+with assertRaises(TypeError) as cm:
+    kwonly(1, x=3)
+assert str(cm.exception) == "kwonly() missing 1 required keyword-only argument: 'z'"
+
+with assertRaises(TypeError) as cm:
+    kwonly(1)
+assert str(cm.exception) == "kwonly() missing 2 required keyword-only arguments: 'x' and 'z'"
+
+with assertRaises(TypeError) as cm:
+    (lambda *, a, b, c: None)()
+assert str(cm.exception) == "<lambda>() missing 3 required keyword-only arguments: 'a', 'b', and 'c'"
@@ -6,3 +6,116 @@ def total(a,b,c,d):
     return sum(sum(a,b),sum(c,d))
 
 assert total(1,2,3,4) == 10
+
+
+# Closures share the cells of their enclosing function:
+def make_counter(start):
+    count = start
+    def inc(step=1):
+        nonlocal count
+        count += step
+        return count
+    def get():
+        return count
+    return inc, get
+
+inc, get = make_counter(10)
+assert inc() == 11
+assert inc(5) == 16
+assert get() == 16
+assert inc.__closure__[0] is get.__closure__[0]
+assert inc.__closure__[0].cell_contents == 16
+assert make_counter.__closure__ is None
+
+# Each call of the enclosing function creates new cells:
+inc2, get2 = make_counter(0)
+assert inc2() == 1
+assert get() == 16
+
+# The cell holds the variable, not its value at definition time:
+def adders():
+    late = [lambda x: x + i for i in range(3)]
+    early = [lambda x, i=i: x + i for i in range(3)]
+    return late, early
+
+late, early = adders()
+assert [f(10) for f in late] == [12, 12, 12]
+assert [f(10) for f in early] == [10, 11, 12]
+
+# Class bodies pass free variables through to their methods:
+def make_class():
+    x = 1
+    class C:
+        y = x
+        def get_x(self):
+            return x
+    x = 2
+    return C
+
+C = make_class()
+assert C.y == 1
+assert C().get_x() == 2
+
+def unassigned():
+    def inner():
+        return value
+    try:
+        inner()
+    except NameError:
+        pass
+    else:
+        assert False, "NameError not raised"
+    value = 3
+    return inner()
+
+assert unassigned() == 3
+
+def deleted_nonlocal():
+    x = 1
+    def delete():
+        nonlocal x
+        del x
+    def read():
+        return x
+    delete()
+    for f in (read, delete):
+        try:
+            f()
+        except NameError:
+            pass
+        else:
+            assert False, "NameError not raised"
+    x = 4
+    return read()
+
+assert deleted_nonlocal() == 4
+
+# Captured names are still found by name, in the function which owns them
+# and in the one which takes them from it, but a class body leaves out the
+# names it takes from the enclosing function:
+def captured_locals():
+    c = 1
+    def inner():
+        return c, locals(), eval("c")
+    class Body:
+        y = c
+        names = sorted(locals())
+    own = locals()
+    return own, eval("c"), inner(), Body
+
+own, evaluated, (value, inner_locals, inner_evaluated), Body = captured_locals()
+assert own['c'] == 1
+assert evaluated == 1
+assert inner_locals == {'c': 1}
+assert inner_evaluated == 1
+assert 'y' in Body.names and 'c' not in Body.names
+assert not hasattr(Body, 'c')
+
+def empty_cell():
+    def inner():
+        return x
+    names = sorted(locals())
+    x = 1
+    return names
+
+assert empty_cell() == ['inner']
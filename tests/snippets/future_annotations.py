@@ -0,0 +1,40 @@
+"""Annotations are stored as strings under PEP 563, and never evaluated."""
+from __future__ import annotations
+
+def f(x: SomeForwardRef, *args: int, y: List[Node] = None, **kwargs: 1 + 2) -> Dict[str, int]:
+    pass
+
+assert f.__annotations__['x'] == 'SomeForwardRef'
+assert f.__annotations__ == {
+    'x': 'SomeForwardRef',
+    'args': 'int',
+    'y': 'List[Node]',
+    'kwargs': '1 + 2',
+    'return': 'Dict[str, int]',
+}
+assert list(f.__annotations__) == ['x', 'args', 'y', 'kwargs', 'return']
+
+x: Undefined = 1
+assert __annotations__['x'] == 'Undefined'
+
+
+class A:
+    attr: A
+    other: Optional['A']
+
+assert A.__annotations__ == {'attr': 'A', 'other': "Optional['A']"}
+
+
+# Scopes in annotations, like lambdas and comprehensions, are never created:
+def g(a: lambda x: x, b: [y for y in range(3)]) -> 1:
+    pass
+
+assert g.__annotations__ == {'a': 'lambda x: x', 'b': '[y for y in range(3)]', 'return': '1'}
+
+def h():
+    x = 1
+    def inner():
+        return x
+    return inner()
+
+assert h() == 1
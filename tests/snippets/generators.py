@@ -1,3 +1,4 @@
+import sys
 from testutils import assertRaises
 
 
@@ -88,3 +89,500 @@ l = list(g)
 # print(l)
 assert l == [99]
 assert r == ['a', 66, None]
+
+
+def receiver():
+    received = yield
+    assert received == 42
+    yield received + 1
+
+g = receiver()
+assert next(g) is None
+assert g.send(42) == 43
+
+g = receiver()
+with assertRaises(TypeError):
+    g.send(42)
+assert g.send(None) is None
+
+
+cleanup = []
+
+def cleaned_up():
+    try:
+        yield 1
+        yield 2
+    finally:
+        cleanup.append('finally')
+
+g = cleaned_up()
+assert next(g) == 1
+g.close()
+assert cleanup == ['finally']
+
+g = cleaned_up()
+g.close()
+assert cleanup == ['finally']
+
+def catches_exit():
+    try:
+        yield 1
+    except GeneratorExit:
+        cleanup.append('exit')
+
+g = catches_exit()
+next(g)
+g.close()
+assert cleanup == ['finally', 'exit']
+
+def ignores_exit():
+    try:
+        yield 1
+    except GeneratorExit:
+        yield 2
+
+g = ignores_exit()
+next(g)
+with assertRaises(RuntimeError):
+    g.close()
+
+def raises_in_close():
+    try:
+        yield 1
+    finally:
+        raise KeyError('close')
+
+g = raises_in_close()
+next(g)
+with assertRaises(KeyError):
+    g.close()
+
+
+def sub():
+    yield 1
+    return 99
+
+def outer():
+    result = yield from sub()
+    assert result == 99
+    yield result
+
+assert list(outer()) == [1, 99]
+
+def outer_list():
+    result = yield from [1, 2]
+    yield result
+
+assert list(outer_list()) == [1, 2, None]
+
+g = sub()
+next(g)
+try:
+    next(g)
+except StopIteration as ex:
+    assert ex.value == 99
+    assert ex.args == (99,)
+
+assert StopIteration().value is None
+assert StopIteration(5).value == 5
+
+def echo():
+    received = yield 'ready'
+    yield received
+
+def delegate():
+    yield from echo()
+
+g = delegate()
+assert next(g) == 'ready'
+assert g.send('hello') == 'hello'
+
+def self_advancing():
+    assert reentrant.gi_running
+    yield next(reentrant)
+
+reentrant = self_advancing()
+assert not reentrant.gi_running
+with assertRaises(ValueError):
+    next(reentrant)
+assert not reentrant.gi_running
+
+def leaks_stop_iteration():
+    yield 1
+    next(iter([]))
+    yield 2
+
+leaky = leaks_stop_iteration()
+assert next(leaky) == 1
+try:
+    next(leaky)
+except RuntimeError as ex:
+    assert str(ex) == 'generator raised StopIteration'
+    assert isinstance(ex.__cause__, StopIteration)
+else:
+    assert False, 'StopIteration should not end the generator silently'
+
+with assertRaises(RuntimeError):
+    list(leaks_stop_iteration())
+
+# yield from delegates to a generator itself, which it may have started already,
+# and to the iterator of anything else:
+def numbers():
+    yield 1
+    yield 2
+    yield 3
+
+class Countdown:
+    def __init__(self, start):
+        self.start = start
+
+    def __iter__(self):
+        return iter(range(self.start, 0, -1))
+
+def delegate_all(started):
+    yield from started
+    yield from Countdown(2)
+    yield from (x * 10 for x in range(2))
+    yield from 'ab'
+
+started = numbers()
+assert next(started) == 1
+assert list(delegate_all(started)) == [2, 3, 2, 1, 0, 10, 'a', 'b']
+
+async def coro():
+    pass
+
+def delegate_to_coroutine(awaitable):
+    yield from awaitable
+
+c = coro()
+with assertRaises(TypeError):
+    next(delegate_to_coroutine(c))
+c.close()
+
+with assertRaises(TypeError):
+    next(delegate_to_coroutine(42))
+
+# Generators are named after their function:
+def gen():
+    yield
+
+g = gen()
+assert g.__name__ == 'gen'
+assert g.__qualname__ == 'gen'
+assert repr(g).startswith('<generator object gen at 0x'), repr(g)
+assert type(g).__name__ == 'generator'
+
+class Named:
+    def method(self):
+        yield
+
+    def genexpr(self):
+        return (x for x in [])
+
+assert Named().method().__qualname__ == 'Named.method'
+assert Named().genexpr().__name__ == '<genexpr>'
+
+g.__name__ = 'renamed'
+g.__qualname__ = 'Renamed.gen'
+assert g.__name__ == 'renamed'
+assert repr(g).startswith('<generator object Renamed.gen at 0x')
+with assertRaises(TypeError):
+    g.__name__ = None
+
+async def named_coroutine():
+    pass
+
+c = named_coroutine()
+assert c.__name__ == c.__qualname__ == 'named_coroutine'
+assert repr(c).startswith('<coroutine object named_coroutine at 0x')
+c.close()
+
+cleanups = []
+
+def yields_in_try():
+    try:
+        yield 1
+        yield 2
+    finally:
+        cleanups.append("cleanup")
+
+# Suspending at a yield doesn't run the finally block, finishing does, once.
+g = yields_in_try()
+assert next(g) == 1
+assert next(g) == 2
+assert cleanups == []
+assert list(g) == []
+assert cleanups == ["cleanup"]
+
+cleanups.clear()
+g = yields_in_try()
+next(g)
+g.close()
+assert cleanups == ["cleanup"]
+g.close()
+assert cleanups == ["cleanup"]
+
+cleanups.clear()
+g = yields_in_try()
+next(g)
+with assertRaises(ValueError):
+    g.throw(ValueError, ValueError(), None)
+assert cleanups == ["cleanup"]
+
+# A generator that never started has no finally block to run.
+cleanups.clear()
+yields_in_try().close()
+assert cleanups == []
+
+def nested_try_in_loop():
+    try:
+        for i in range(3):
+            try:
+                yield i
+            finally:
+                cleanups.append(i)
+    finally:
+        cleanups.append("outer")
+
+cleanups.clear()
+g = nested_try_in_loop()
+assert next(g) == 0
+assert next(g) == 1
+g.close()
+assert cleanups == [0, 1, "outer"]
+
+def yields_in_finally():
+    try:
+        yield 1
+    finally:
+        yield 2
+        cleanups.append("cleanup")
+
+cleanups.clear()
+assert list(yields_in_finally()) == [1, 2]
+assert cleanups == ["cleanup"]
+
+
+# throw() makes the exception from a class, its arguments or an instance:
+def catcher():
+    while True:
+        try:
+            yield
+        except Exception as e:
+            yield type(e), e.args
+
+g = catcher()
+next(g)
+assert g.throw(ValueError) == (ValueError, ())
+next(g)
+assert g.throw(ValueError, 'msg') == (ValueError, ('msg',))
+next(g)
+assert g.throw(ValueError, ('a', 'b')) == (ValueError, ('a', 'b'))
+next(g)
+assert g.throw(KeyError('k')) == (KeyError, ('k',))
+next(g)
+with assertRaises(TypeError):
+    g.throw(KeyError('k'), 'v')
+with assertRaises(TypeError):
+    g.throw(42)
+with assertRaises(TypeError):
+    g.throw(ValueError, None, 'not a traceback')
+
+
+# An exception thrown into a generator suspended in a `yield from` goes to the
+# generator it delegates to:
+def delegate():
+    try:
+        yield 1
+    except ValueError as e:
+        yield 'delegate caught ' + str(e)
+    yield 2
+    return 'result'
+
+def delegator():
+    result = yield from delegate()
+    yield result
+
+g = delegator()
+assert next(g) == 1
+assert g.throw(ValueError('x')) == 'delegate caught x'
+assert list(g) == [2, 'result']
+
+# The delegate can finish because of the exception, resuming the delegator:
+def finishing_delegate():
+    try:
+        yield 1
+    except ValueError:
+        return 'finished'
+
+def finishing_delegator():
+    yield (yield from finishing_delegate())
+
+g = finishing_delegator()
+next(g)
+assert g.throw(ValueError) == 'finished'
+
+# The delegator sees what the delegate doesn't catch:
+def passing_delegator():
+    try:
+        yield from delegate()
+    except KeyError:
+        yield 'delegator caught'
+
+g = passing_delegator()
+next(g)
+assert g.throw(KeyError) == 'delegator caught'
+
+# It goes down any number of delegations:
+def deep_delegator():
+    yield from delegator()
+
+g = deep_delegator()
+next(g)
+assert g.throw(ValueError('deep')) == 'delegate caught deep'
+
+# An iterator without a throw() leaves the exception to the delegator:
+def iterator_delegator():
+    try:
+        yield from iter([1, 2])
+    except ValueError:
+        yield 'delegator caught'
+
+g = iterator_delegator()
+next(g)
+assert g.throw(ValueError) == 'delegator caught'
+
+# close() closes the delegate first:
+closed = []
+def closing_delegate():
+    try:
+        yield 1
+    finally:
+        closed.append('delegate')
+
+def closing_delegator():
+    try:
+        yield from closing_delegate()
+    finally:
+        closed.append('delegator')
+
+g = closing_delegator()
+next(g)
+g.close()
+assert closed == ['delegate', 'delegator']
+
+# Sent values go down through every delegation, and return values come back up:
+def accumulate():
+    total = 0
+    while True:
+        value = yield total
+        if value is None:
+            return total
+        total += value
+
+def twice():
+    first = yield from accumulate()
+    second = yield from accumulate()
+    return first, second
+
+def outermost():
+    result = yield from twice()
+    yield result
+
+g = outermost()
+assert next(g) == 0
+assert g.send(1) == 1
+assert g.send(2) == 3
+assert g.send(None) == 0
+assert g.send(10) == 10
+assert g.send(None) == (3, 10)
+
+# A delegation inside a loop starts over with a new iterator each time:
+def repeat(times):
+    for i in range(times):
+        result = yield from range(i)
+        assert result is None
+
+assert list(repeat(4)) == [0, 0, 1, 0, 1, 2]
+
+# An iterator without send() can only be sent None:
+def delegate_to_iterator():
+    yield from iter([1, 2])
+
+g = delegate_to_iterator()
+next(g)
+assert g.send(None) == 2
+g = delegate_to_iterator()
+next(g)
+with assertRaises(AttributeError):
+    g.send(1)
+
+# A generator which was closed, or which ended by returning or raising, is
+# never resumed:
+def once():
+    yield 1
+
+g = once()
+g.close()
+with assertRaises(StopIteration):
+    next(g)
+
+g = once()
+with assertRaises(KeyError):
+    g.throw(KeyError)
+with assertRaises(StopIteration):
+    next(g)
+
+def raise_after_yield():
+    yield 1
+    raise KeyError
+
+g = raise_after_yield()
+assert next(g) == 1
+with assertRaises(KeyError):
+    next(g)
+with assertRaises(StopIteration):
+    next(g)
+with assertRaises(StopIteration):
+    g.send(None)
+with assertRaises(ValueError):
+    g.throw(ValueError)
+g.close()
+
+g = once()
+assert list(g) == [1]
+with assertRaises(StopIteration):
+    next(g)
+
+# A thrown exception is raised at the yield, so it has a traceback entry for
+# the generator and is reported to the generator's trace function:
+def suspended():
+    yield 1
+
+g = suspended()
+next(g)
+try:
+    g.throw(ValueError('thrown'))
+except ValueError as exc:
+    tb = exc.__traceback__
+    while tb.tb_next is not None:
+        tb = tb.tb_next
+    assert tb.tb_frame.f_code.co_name == 'suspended'
+    assert tb.tb_lineno == suspended.__code__.co_firstlineno + 1
+
+exception_events = []
+def tracer(frame, event, arg):
+    if event == 'exception' and frame.f_code.co_name == 'suspended':
+        exception_events.append(arg[0])
+    return tracer
+
+sys.settrace(tracer)
+g = suspended()
+next(g)
+try:
+    g.throw(KeyError)
+except KeyError:
+    pass
+sys.settrace(None)
+assert exception_events == [KeyError], exception_events
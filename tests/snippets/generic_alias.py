@@ -0,0 +1,52 @@
+import types
+from testutils import assertRaises
+
+alias = list[int]
+assert isinstance(alias, types.GenericAlias)
+assert alias.__origin__ is list
+assert alias.__args__ == (int,)
+assert repr(alias) == 'list[int]'
+assert alias([1, 2]) == [1, 2]
+assert alias == list[int]
+assert alias != list[str]
+
+alias = dict[str, int]
+assert alias.__origin__ is dict
+assert alias.__args__ == (str, int)
+assert repr(alias) == 'dict[str, int]'
+assert repr(tuple[int, ...]) == 'tuple[int, ...]'
+assert repr(tuple[()]) == 'tuple[()]'
+assert repr(type[int]) == 'type[int]'
+
+class Custom:
+    pass
+
+assert repr(set[Custom]) == 'set[%s.Custom]' % __name__
+
+
+# __class_getitem__ is implicitly a classmethod:
+class Box:
+    def __class_getitem__(cls, item):
+        return (cls, item)
+
+assert Box[int] == (Box, int)
+
+class SubBox(Box):
+    pass
+
+assert SubBox['key'] == (SubBox, 'key')
+
+
+# A metaclass __getitem__ takes precedence:
+class Meta(type):
+    def __getitem__(cls, item):
+        return 'metaclass'
+
+class WithMeta(metaclass=Meta):
+    def __class_getitem__(cls, item):
+        return 'class'
+
+assert WithMeta[0] == 'metaclass'
+
+with assertRaises(TypeError):
+    int[0]
@@ -19,3 +19,143 @@ class TestClass():
         assert sys._getframe().f_locals['self'] == self
 
 TestClass()
+
+def frame_position():
+    frame = sys._getframe()
+    assert frame.f_code.co_name == 'frame_position'
+    assert frame.f_lineno == frame_position.__code__.co_firstlineno + 3
+    assert isinstance(frame.f_lasti, int)
+
+frame_position()
+
+def read_locals():
+    secret = 42
+    frame = sys._getframe()
+    snapshot = frame.f_locals
+    assert snapshot['secret'] == 42
+    snapshot['secret'] = 0
+    assert secret == 42
+    assert frame.f_globals is globals()
+
+read_locals()
+
+assert sys._getframe().f_locals is globals()
+
+seen = {}
+def local_trace(frame, event, arg):
+    if event == 'return':
+        seen.update(frame.f_locals)
+    return local_trace
+
+def traced():
+    hidden = 'found'
+
+sys.settrace(lambda frame, event, arg: local_trace)
+traced()
+sys.settrace(None)
+assert seen['hidden'] == 'found'
+
+def callee():
+    return sys._getframe().f_back
+
+def caller():
+    return sys._getframe(), callee()
+
+caller_frame, back = caller()
+assert back is caller_frame
+assert back.f_code.co_name == 'caller'
+assert sys._getframe().f_back is None
+
+def gen():
+    while True:
+        yield sys._getframe().f_back.f_code.co_name
+
+g = gen()
+
+def first_resumer():
+    return next(g)
+
+def second_resumer():
+    return next(g)
+
+assert first_resumer() == 'first_resumer'
+assert second_resumer() == 'second_resumer'
+
+import weakref
+from testutils import assertRaises
+
+class Big:
+    pass
+
+def hold_big():
+    big = Big()
+    big_ref = weakref.ref(big)
+    raise ValueError(big_ref)
+
+try:
+    hold_big()
+except ValueError as ex:
+    big_ref = ex.args[0]
+    held_frame = ex.__traceback__.tb_next.tb_frame
+
+assert big_ref() is not None
+held_frame.clear()
+assert big_ref() is None
+
+with assertRaises(RuntimeError):
+    sys._getframe().clear()
+
+def caller_name():
+    return sys._getframe(1).f_code.co_name
+
+def named_caller():
+    return caller_name()
+
+assert named_caller() == 'named_caller'
+assert caller_name() == '<module>'
+
+with assertRaises(ValueError):
+    sys._getframe(1000)
+
+def multi_line_error():
+    return (1 +
+            2 +
+            undefined_name)
+
+try:
+    multi_line_error()
+except NameError as ex:
+    tb = ex.__traceback__.tb_next
+    assert tb.tb_lineno == multi_line_error.__code__.co_firstlineno + 3
+
+def caller_lineno():
+    return sys._getframe(1).f_lineno
+
+def multi_line_caller():
+    return [caller_lineno(),
+            'next line'][0]
+
+assert multi_line_caller() == multi_line_caller.__code__.co_firstlineno + 1
+
+def finished_frame():
+    return sys._getframe()
+
+assert finished_frame().f_lineno == finished_frame.__code__.co_firstlineno + 1
+
+def patch_locals(frame, event, arg):
+    if event == 'line' and frame.f_code.co_name == 'patched':
+        if frame.f_lineno == patched.__code__.co_firstlineno + 3:
+            f_locals = frame.f_locals
+            f_locals['value'] = 5
+            del f_locals['unbound']
+    return patch_locals
+
+def patched():
+    value = 1
+    unbound = 2
+    return value, 'unbound' in locals()
+
+sys.settrace(patch_locals)
+result = patched()
+sys.settrace(None)
+assert result == (5, False), result
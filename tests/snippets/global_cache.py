@@ -0,0 +1,86 @@
+# Global lookups are cached per instruction, and must see every change to the
+# globals and the builtins.
+import builtins
+
+
+def get_value():
+    return value
+
+
+value = 1
+assert get_value() == 1
+value = 2
+assert get_value() == 2
+del value
+try:
+    get_value()
+except NameError:
+    pass
+else:
+    assert False, "deleted global should be gone"
+
+
+def count(items):
+    return len(items)
+
+
+assert count([1, 2]) == 2
+
+# A global shadows a builtin, and deleting it uncovers the builtin again:
+len = lambda items: -1
+assert count([1, 2]) == -1
+del len
+assert count([1, 2]) == 2
+
+# Changes to the builtins are seen too:
+original_len = builtins.len
+builtins.len = lambda items: 42
+try:
+    assert count([1, 2]) == 42
+finally:
+    builtins.len = original_len
+assert count([1, 2]) == 2
+
+
+def get_new_builtin():
+    return new_builtin
+
+
+builtins.new_builtin = 'hello'
+try:
+    assert get_new_builtin() == 'hello'
+finally:
+    del builtins.new_builtin
+try:
+    get_new_builtin()
+except NameError:
+    pass
+else:
+    assert False, "deleted builtin should be gone"
+
+# The same code run with different globals:
+source = "def f():\n    return x\n"
+first = {'x': 'first'}
+second = {'x': 'second'}
+exec(source, first)
+exec(source, second)
+assert first['f']() == 'first'
+assert second['f']() == 'second'
+assert first['f']() == 'first'
+second['x'] = 'changed'
+assert second['f']() == 'changed'
+
+# Globals changed from within the loop which reads them:
+def read_in_loop():
+    global counter
+    seen = []
+    for i in range(3):
+        seen.append(counter)
+        counter += 1
+    return seen
+
+
+counter = 0
+assert read_in_loop() == [0, 1, 2]
+globals()['counter'] = 10
+assert read_in_loop() == [10, 11, 12]
@@ -13,3 +13,18 @@ else:
     y += 3
 
 assert y == 3
+
+# A function with thousands of branches, far more jump targets and constants
+# than fit in a one byte operand:
+source = "def classify(n):\n"
+for i in range(3000):
+    source += "    if n == {}:\n        return {!r}\n".format(i, "value {}".format(i))
+source += "    return None\n"
+namespace = {}
+exec(source, namespace)
+classify = namespace["classify"]
+assert classify(0) == "value 0"
+assert classify(255) == "value 255"
+assert classify(256) == "value 256"
+assert classify(2999) == "value 2999"
+assert classify(3000) is None
@@ -19,6 +19,15 @@ assert import_target.Y == aliased_other_func()
 
 assert STAR_IMPORT == '123'
 
+from import_star_all import *
+assert _private == 'listed'
+assert public == 'also listed'
+try:
+    unlisted
+    raise AssertionError('`unlisted` is not in `__all__`')
+except NameError:
+    pass
+
 try:
     from import_target import func, unknown_name
     raise AssertionError('`unknown_name` does not cause an exception')
@@ -70,3 +79,112 @@ from testutils import assertRaises
 with assertRaises(SyntaxError):
 	exec('import')
 
+
+# Import hooks on sys.meta_path are asked before the default finders:
+import sys
+from _frozen_importlib import spec_from_loader
+
+
+class SourceLoader:
+    def __init__(self, source):
+        self.source = source
+
+    def create_module(self, spec):
+        return None
+
+    def exec_module(self, module):
+        exec(self.source, module.__dict__)
+
+
+class SourceFinder:
+    def __init__(self, modules):
+        self.modules = modules
+
+    def find_spec(self, name, path, target=None):
+        if name in self.modules:
+            return spec_from_loader(name, SourceLoader(self.modules[name]))
+        return None
+
+
+finder = SourceFinder({"bundled_module": "answer = 42\n", "import_target": "X = 'hooked'\n"})
+sys.meta_path.insert(0, finder)
+try:
+    import bundled_module
+    from bundled_module import answer
+    import import_target as reimported
+finally:
+    sys.meta_path.remove(finder)
+
+assert bundled_module.answer == answer == 42
+assert bundled_module.__spec__.loader.source == "answer = 42\n"
+assert sys.modules["bundled_module"] is bundled_module
+# Modules which were imported before are taken from sys.modules:
+assert reimported is import_target
+assert import_target.X != 'hooked'
+
+with assertRaises(ImportError):
+    import another_bundled_module
+
+# Every name in __all__ has to exist:
+import types
+broken = types.ModuleType('broken')
+broken.__all__ = ['missing']
+sys.modules['broken'] = broken
+with assertRaises(AttributeError):
+    from broken import *
+
+# A module's __getattr__ computes its missing attributes, like submodules loaded
+# lazily. An error raised by it, other than AttributeError, is not an ImportError:
+lazy = types.ModuleType('lazy')
+def lazy_getattr(name):
+    if name == 'failing':
+        raise RuntimeError('failing submodule')
+    if name == 'computed':
+        return 'value'
+    raise AttributeError(name)
+lazy.__getattr__ = lazy_getattr
+sys.modules['lazy'] = lazy
+
+from lazy import computed
+assert computed == 'value'
+try:
+    from lazy import failing
+except RuntimeError as exc:
+    assert exc.args == ('failing submodule',)
+else:
+    raise AssertionError('the error of __getattr__ was not raised')
+try:
+    from lazy import missing
+except ImportError as exc:
+    assert str(exc) == "cannot import name 'missing' from 'lazy' (unknown location)", str(exc)
+else:
+    raise AssertionError('`missing` does not cause an exception')
+
+# A submodule being imported may not be set on its package yet:
+sys.modules['half_imported'] = types.ModuleType('half_imported')
+sys.modules['half_imported.part'] = part = types.ModuleType('half_imported.part')
+from half_imported import part as imported_part
+assert imported_part is part
+
+# A submodule which raises while it is imported propagates its own error, with
+# the traceback of the failing module, rather than a misleading ImportError:
+def innermost_file(tb):
+    while tb.tb_next is not None:
+        tb = tb.tb_next
+    return tb.tb_frame.f_code.co_filename
+
+try:
+    from failing_package import broken
+except RuntimeError as exc:
+    assert exc.args == ('failing submodule',)
+    assert innermost_file(exc.__traceback__).endswith('broken.py')
+else:
+    raise AssertionError('the failing submodule was imported')
+assert 'failing_package.broken' not in sys.modules
+
+try:
+    from failing_package import missing
+except ImportError as exc:
+    assert str(exc).startswith("cannot import name 'missing' from 'failing_package' ("), str(exc)
+else:
+    raise AssertionError('`missing` does not cause an exception')
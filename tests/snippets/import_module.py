@@ -1,3 +1,12 @@
 import dir_module
 assert dir_module.value == 5
 assert dir_module.value2 == 7
+
+from testutils import assertRaises
+
+from dir_module.dir_module_inner import uses_sibling
+assert uses_sibling.thing == 5
+assert uses_sibling.relative is dir_module.relative
+
+with assertRaises(ImportError):
+    import dir_module.dir_module_inner.beyond
@@ -0,0 +1,7 @@
+# This is used by import.py; the two should be modified in concert
+
+__all__ = ['_private', 'public']
+
+_private = 'listed'
+public = 'also listed'
+unlisted = 'hidden'
@@ -6,3 +6,25 @@ assert 0O777 == 511
 assert 0xcafebabe == 3405691582
 assert 0Xcafebabe == 3405691582
 assert 0xCAFEBABE == 3405691582
+
+# Running a literal again gives the same constant object:
+def constants():
+    return 2.5, 'string', b'bytes', 100000000000000000000
+
+first, second = constants(), constants()
+assert all(a is b for a, b in zip(first, second))
+
+assert len({id(b'bytes') for _ in range(5)}) == 1
+
+# Constants are not combined across a jump which lands between them:
+c = True
+x = 'X'
+assert ((1 if c else 2), 3, 4) == (1, 3, 4)
+assert (c or 1, 2) == (True, 2)
+t = (x if c else 1, 2)
+assert t == ('X', 2)
+assert (1 if c else 2) + 3 == 4
+a, b = (1, 2) if c else (3, 4)
+assert (a, b) == (1, 2)
+a, b = (1, 2) if not c else (3, 4)
+assert (a, b) == (3, 4)
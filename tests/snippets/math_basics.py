@@ -1,4 +1,4 @@
-from testutils import assert_raises
+from testutils import assert_raises, assertRaises
 
 assert -3 // 2 == -2
 assert -3 % 2 == 1
@@ -72,3 +72,45 @@ assert_raises(
     ValueError,
     lambda: pow(2, 2, 0),
     'pow() 3rd argument cannot be 0')
+
+with assertRaises(TypeError) as cm:
+    1 @ 2
+assert str(cm.exception) == "unsupported operand type(s) for @: 'int' and 'int'"
+
+a = 1
+with assertRaises(TypeError) as cm:
+    a @= 2
+assert str(cm.exception) == "unsupported operand type(s) for @=: 'int' and 'int'"
+
+# The operands are reported in the order they were written:
+with assertRaises(TypeError) as cm:
+    1 @ "a"
+assert str(cm.exception) == "unsupported operand type(s) for @: 'int' and 'str'"
+
+with assertRaises(TypeError) as cm:
+    a @= "a"
+assert str(cm.exception) == "unsupported operand type(s) for @=: 'int' and 'str'"
+
+with assertRaises(TypeError) as cm:
+    1 - "a"
+assert str(cm.exception) == "unsupported operand type(s) for -: 'int' and 'str'"
+
+
+class Matrix:
+    def __init__(self, val):
+        self.val = val
+
+    def __matmul__(self, other):
+        return Matrix(self.val * other.val)
+
+
+class RightMatrix:
+    def __rmatmul__(self, other):
+        return 'rmatmul'
+
+
+assert (Matrix(2) @ Matrix(3)).val == 6
+m = Matrix(4)
+m @= Matrix(5)
+assert m.val == 20
+assert 1 @ RightMatrix() == 'rmatmul'
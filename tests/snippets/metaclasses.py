@@ -1,3 +1,5 @@
+from testutils import assertRaises
+
 class MC(type):
     classes = []
     count = 0
@@ -29,3 +31,82 @@ assert C().count == 2
 
 assert C.prepared
 assert D.prepared
+
+
+# Keyword arguments of the class statement:
+created = []
+
+class Meta(type):
+    @classmethod
+    def __prepare__(mcls, name, bases, **kwargs):
+        ns = super().__prepare__(name, bases)
+        ns['prepared'] = kwargs
+        return ns
+
+    def __new__(mcls, name, bases, namespace, **kwargs):
+        created.append(name)
+        return super().__new__(mcls, name, bases, namespace)
+
+    def __init__(cls, name, bases, namespace, **kwargs):
+        super().__init__(name, bases, namespace)
+        cls.init_kwargs = kwargs
+
+class A(metaclass=Meta, flavour='plain'):
+    pass
+
+assert created == ['A']
+assert type(A) is Meta
+assert A.prepared == {'flavour': 'plain'}
+assert A.init_kwargs == {'flavour': 'plain'}
+
+# Subclasses pick up the most derived metaclass from their bases:
+class B(A):
+    pass
+
+assert created == ['A', 'B']
+assert type(B) is Meta
+
+kw = {'flavour': 'spicy'}
+bases = (A,)
+class C(*bases, **kw):
+    pass
+assert created == ['A', 'B', 'C']
+assert issubclass(C, A)
+assert C.init_kwargs == {'flavour': 'spicy'}
+
+def function_meta(name, bases, namespace, **kwargs):
+    return (name, bases, sorted(namespace), kwargs)
+
+class D(metaclass=function_meta, x=1):
+    y = 2
+assert D[0] == 'D'
+assert D[3] == {'x': 1}
+assert 'y' in D[2]
+
+class OtherMeta(type):
+    pass
+
+with assertRaises(TypeError):
+    class E(A, metaclass=OtherMeta):
+        pass
+
+class Plugin:
+    plugins = []
+    def __init_subclass__(cls, name=None, **kwargs):
+        super().__init_subclass__(**kwargs)
+        cls.plugin_name = name
+        Plugin.plugins.append(cls)
+
+class Foo(Plugin, name='foo'):
+    pass
+
+class Bar(Foo):
+    pass
+
+assert Plugin.plugins == [Foo, Bar]
+assert Foo.plugin_name == 'foo'
+assert Bar.plugin_name is None
+
+with assertRaises(TypeError):
+    class F(unknown=1):
+        pass
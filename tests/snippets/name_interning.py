@@ -0,0 +1,51 @@
+# The names used by attribute and name instructions are made into strings
+# once per code object, not every time the instruction runs.
+
+
+class Recorder:
+    def __init__(self):
+        object.__setattr__(self, 'names', [])
+
+    def __setattr__(self, name, value):
+        self.names.append(name)
+        object.__setattr__(self, name, value)
+
+    def __delattr__(self, name):
+        self.names.append(name)
+        object.__delattr__(self, name)
+
+
+r = Recorder()
+for i in range(1000):
+    r.x = i
+    del r.x
+assert len(r.names) == 2000
+assert all(name is r.names[0] for name in r.names)
+assert r.names[0] == 'x'
+assert not hasattr(r, 'x')
+
+
+def set_attr(obj, value):
+    obj.y = value
+
+
+r = Recorder()
+set_attr(r, 1)
+set_attr(r, 2)
+assert r.names[0] is r.names[1] == 'y'
+assert r.y == 2
+
+# Names stored at module and class level still behave as plain strings:
+for i in range(10):
+    counter = i
+assert counter == 9
+assert globals()['counter'] == 9
+
+
+class Namespace:
+    value = 1
+    value = value + 1
+
+
+assert Namespace.value == 2
+assert 'value' in Namespace.__dict__
@@ -5,3 +5,22 @@ dumped = marshal.dumps(orig)
 loaded = marshal.loads(dumped)
 
 assert eval(loaded) == eval(orig)
+
+source = """
+total = 0
+for i in range(5):
+    try:
+        if i == 3:
+            break
+        total += i
+    finally:
+        total += 10
+"""
+namespace = {}
+exec(marshal.loads(marshal.dumps(compile(source, "", 'exec'))), namespace)
+assert namespace['total'] == 43
+
+from testutils import assertRaises
+
+with assertRaises(ValueError):
+    marshal.loads(b"not a code object")
@@ -42,3 +42,52 @@ if "win" not in sys.platform:
 
 
 
+
+	# A pending signal is handled on the back edge of a busy loop:
+	signal.signal(signal.SIGALRM, handler)
+	signal.alarm(1)
+	deadline = time.time() + 5
+	while len(signals) < 3 and time.time() < deadline:
+		pass
+	assert signals == [signal.SIGALRM] * 3
+
+	def interrupt(signum, frame):
+		raise KeyboardInterrupt
+
+	signal.signal(signal.SIGALRM, interrupt)
+	signal.alarm(1)
+	try:
+		while True:
+			pass
+	except KeyboardInterrupt:
+		pass
+
+	# Loops closed by continue, by a loop condition or by a for loop are
+	# interruptible too:
+	def spin_continue():
+		while True:
+			continue
+
+	def spin_condition():
+		running = True
+		while running:
+			pass
+
+	def spin_for():
+		import itertools
+		for _ in itertools.count():
+			pass
+
+	for spin in [spin_continue, spin_condition, spin_for]:
+		signal.alarm(1)
+		try:
+			spin()
+		except KeyboardInterrupt:
+			pass
+	signal.signal(signal.SIGALRM, signal.SIG_DFL)
+
+assert sys.getswitchinterval() == 0.005
+sys.setswitchinterval(0.001)
+assert sys.getswitchinterval() == 0.001
+assert_raises(ValueError, lambda: sys.setswitchinterval(0))
+sys.setswitchinterval(0.005)
@@ -0,0 +1,35 @@
+from testutils import assertRaises
+
+
+class Indexable:
+    def __init__(self):
+        self.keys = []
+
+    def __getitem__(self, key):
+        self.keys.append(key)
+        return key
+
+
+x = Indexable()
+assert x[21] == 21
+s = x[1:2]
+assert (s.start, s.stop, s.step) == (1, 2, None)
+assert x["a"] == "a"
+assert x[1, 2] == (1, 2)
+assert x.keys == [21, s, "a", (1, 2)]
+
+# The lookup goes through the type, not the instance:
+x.__getitem__ = lambda key: 0
+assert x[3] == 3
+
+
+class Plain:
+    pass
+
+
+with assertRaises(TypeError):
+    Plain()[0]
+
+l = [1, 2, 3]
+l[0] += 10
+assert l[0] == 11
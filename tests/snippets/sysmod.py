@@ -1,4 +1,5 @@
 import sys
+from testutils import assertRaises
 
 print('python executable:', sys.executable)
 print(sys.argv)
@@ -38,6 +39,132 @@ sys.settrace(trc)
 demo(5)
 sys.settrace(None)
 
+events = []
+
+def local_trace(frame, event, arg):
+    events.append((event, frame.f_code.co_name))
+    return local_trace
+
+def global_trace(frame, event, arg):
+    events.append((event, frame.f_code.co_name))
+    return local_trace
+
+def traced(x):
+    y = x + 1
+    return y
+
+sys.settrace(global_trace)
+traced(1)
+sys.settrace(None)
+assert events == [
+    ('call', 'traced'),
+    ('line', 'traced'),
+    ('line', 'traced'),
+    ('return', 'traced'),
+], events
+
+# Every event comes with the line of the frame, relative to its `def` line:
+def record_events(func):
+    events = []
+    def trace(frame, event, arg):
+        if frame.f_code.co_name == func.__name__:
+            line = frame.f_lineno - func.__code__.co_firstlineno
+            events.append((event, line, arg))
+        return trace
+    sys.settrace(trace)
+    result = func()
+    if hasattr(result, '__next__'):
+        list(result)
+    sys.settrace(None)
+    return events
+
+def returning():
+    x = 2
+    return x * 3
+
+def counting():
+    yield 1
+    yield 2
+
+assert record_events(returning) == [
+    ('call', 0, None),
+    ('line', 1, None),
+    ('line', 2, None),
+    ('return', 2, 6),
+]
+# A resumed generator is called again, without a line event for the line it
+# went on with:
+assert record_events(counting) == [
+    ('call', 0, None),
+    ('line', 1, None),
+    ('return', 1, 1),
+    ('call', 1, None),
+    ('line', 2, None),
+    ('return', 2, 2),
+    ('call', 2, None),
+    ('return', 2, None),
+]
+
+# Without a local trace function, no line events are reported:
+events = []
+sys.settrace(lambda frame, event, arg: events.append(event))
+traced(1)
+sys.settrace(None)
+assert events == ['call'], events
+
+# The profile function sees calls and returns, with the returned value:
+events = []
+def profiler(frame, event, arg):
+    if event in ('call', 'return'):
+        events.append((event, frame.f_code.co_name, arg))
+
+def inner():
+    return 3
+
+def outer():
+    return inner() * 2
+
+def failing():
+    raise ValueError
+
+sys.setprofile(profiler)
+outer()
+try:
+    failing()
+except ValueError:
+    pass
+sys.setprofile(None)
+assert events == [
+    ('call', 'outer', None),
+    ('call', 'inner', None),
+    ('return', 'inner', 3),
+    ('return', 'outer', 6),
+    ('call', 'failing', None),
+    ('return', 'failing', None),
+], events
+assert sys.getprofile() is None
+
+# The local trace function is told about exceptions raised in its frame:
+events = []
+def exception_trace(frame, event, arg):
+    if event == 'exception':
+        events.append((frame.f_code.co_name, arg[0], type(arg[1]), type(arg[2]).__name__))
+    return exception_trace
+
+def catching():
+    try:
+        failing()
+    except ValueError:
+        pass
+
+sys.settrace(exception_trace)
+catching()
+sys.settrace(None)
+assert events == [
+    ('failing', ValueError, ValueError, 'traceback'),
+    ('catching', ValueError, ValueError, 'traceback'),
+], events
+
 assert sys.exc_info() == (None, None, None)
 
 try:
@@ -46,3 +173,183 @@ except ZeroDivisionError as exc:
 	exc_info = sys.exc_info()
 	assert exc_info[0] == type(exc) == ZeroDivisionError
 	assert exc_info[1] == exc
+	assert exc_info[2] is exc.__traceback__
+
+assert sys.exc_info() == (None, None, None)
+
+try:
+    raise ValueError('outer')
+except ValueError as outer:
+    try:
+        raise KeyError('inner')
+    except KeyError as inner:
+        assert sys.exc_info()[1] is inner
+    assert sys.exc_info()[1] is outer
+assert sys.exc_info() == (None, None, None)
+
+
+def handled_in_function():
+    try:
+        raise TypeError
+    except TypeError:
+        return sys.exc_info()[0]
+
+
+assert handled_in_function() is TypeError
+assert sys.exc_info() == (None, None, None)
+
+for i in range(2):
+    try:
+        raise ValueError
+    except ValueError:
+        if i:
+            break
+        continue
+assert sys.exc_info() == (None, None, None)
+
+try:
+    try:
+        raise ValueError('passing')
+    finally:
+        assert sys.exc_info()[0] is ValueError
+except ValueError:
+    pass
+assert sys.exc_info() == (None, None, None)
+
+
+def handled_in_generator():
+    try:
+        raise ValueError
+    except ValueError:
+        yield sys.exc_info()[0]
+        yield sys.exc_info()[0]
+
+
+gen = handled_in_generator()
+assert next(gen) is ValueError
+# A suspended generator doesn't leak its exception to the caller:
+assert sys.exc_info() == (None, None, None)
+assert next(gen) is ValueError
+assert sys.exc_info() == (None, None, None)
+
+# Displayhook:
+
+import builtins
+
+shown = []
+sys.displayhook = shown.append
+exec(compile('1 + 2', '<displayhook>', 'single'))
+exec(compile('None', '<displayhook>', 'single'))
+assert shown == [3, None]
+
+sys.displayhook = sys.__displayhook__
+exec(compile('40 + 2', '<displayhook>', 'single'))
+assert builtins._ == 42
+exec(compile('None', '<displayhook>', 'single'))
+assert builtins._ == 42
+
+# Recursion limit:
+
+old_limit = sys.getrecursionlimit()
+assert isinstance(old_limit, int)
+
+sys.setrecursionlimit(200)
+assert sys.getrecursionlimit() == 200
+
+def recurse(n):
+    return recurse(n + 1)
+
+with assertRaises(RecursionError):
+    recurse(0)
+
+# The depth is released when frames exit via an exception:
+with assertRaises(RecursionError):
+    recurse(0)
+
+with assertRaises(ValueError):
+    sys.setrecursionlimit(0)
+
+def max_depth(n=1):
+    try:
+        return max_depth(n + 1)
+    except RecursionError:
+        return n
+
+# The limit counts every active frame, so the reachable depth is a bit lower:
+sys.setrecursionlimit(100)
+low_depth = max_depth()
+assert 50 < low_depth < 100, low_depth
+
+sys.setrecursionlimit(250)
+high_depth = max_depth()
+assert 200 < high_depth < 250, high_depth
+
+def set_limit_at_depth(depth, limit):
+    if depth > 0:
+        return set_limit_at_depth(depth - 1, limit)
+    sys.setrecursionlimit(limit)
+
+# A limit at or below the current depth is rejected:
+with assertRaises(RecursionError):
+    set_limit_at_depth(50, 30)
+assert sys.getrecursionlimit() == 250
+
+sys.setrecursionlimit(old_limit)
+assert sys.getrecursionlimit() == old_limit
+assert issubclass(RecursionError, RuntimeError)
+
+# The default limit is reached before the native stack runs out:
+with assertRaises(RecursionError):
+    recurse(0)
+
+class Recursive:
+    def __repr__(self):
+        return repr(self)
+
+with assertRaises(RecursionError):
+    repr(Recursive())
+
+default_depth = max_depth()
+assert old_limit - 50 < default_depth < old_limit, default_depth
+
+# Opcode events are only sent to frames which ask for them:
+def count_events(trace_opcodes):
+    counts = {'line': 0, 'opcode': 0}
+    def local_trace(frame, event, arg):
+        frame.f_trace_opcodes = trace_opcodes
+        if event in counts:
+            counts[event] += 1
+        return local_trace
+    sys.settrace(lambda frame, event, arg: local_trace)
+    traced(1)
+    sys.settrace(None)
+    return counts
+
+counts = count_events(False)
+assert counts == {'line': 2, 'opcode': 0}, counts
+counts = count_events(True)
+assert counts['line'] == 2, counts
+assert counts['opcode'] > counts['line'], counts
+
+breakpoint_calls = []
+
+def record_breakpoint(*args, **kwargs):
+    breakpoint_calls.append((args, kwargs))
+    return 'hooked'
+
+sys.breakpointhook = record_breakpoint
+assert breakpoint(1, 2, key='value') == 'hooked'
+assert breakpoint_calls == [((1, 2), {'key': 'value'})]
+sys.breakpointhook = sys.__breakpointhook__
+
+import os
+os.environ['PYTHONBREAKPOINT'] = '0'
+assert breakpoint() is None
+os.environ['PYTHONBREAKPOINT'] = 'builtins.abs'
+assert breakpoint(-3) == 3
+del os.environ['PYTHONBREAKPOINT']
+
+del sys.breakpointhook
+with assertRaises(RuntimeError):
+    breakpoint()
+sys.breakpointhook = sys.__breakpointhook__
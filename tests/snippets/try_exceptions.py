@@ -1,12 +1,13 @@
 from testutils import assertRaises
+import sys
 
 try:
     raise BaseException()
 except BaseException as ex:
     print(ex)
     print(type(ex))
-    # print(ex.__traceback__)
-    # print(type(ex.__traceback__))
+    print(ex.__traceback__)
+    print(type(ex.__traceback__))
 
 try:
     raise ZeroDivisionError
@@ -238,3 +239,327 @@ try:
         raise NameError from ex
 except NameError as ex2:
     pass
+
+
+l = []
+def f():
+    try:
+        return 1
+    finally:
+        l.append('x')
+        print('x')
+
+assert f() == 1
+assert l == ['x']
+
+
+def f():
+    try:
+        raise ZeroDivisionError
+    finally:
+        return 2
+
+assert f() == 2
+
+
+def f():
+    try:
+        raise ZeroDivisionError
+    finally:
+        raise NameError
+
+with assertRaises(NameError):
+    f()
+
+
+l = []
+for i in range(3):
+    try:
+        if i == 1:
+            continue
+        if i == 2:
+            break
+        l.append(i)
+    finally:
+        l.append('f{}'.format(i))
+assert l == [0, 'f0', 'f1', 'f2']
+
+l = []
+i = 0
+while i < 2:
+    i += 1
+    try:
+        try:
+            continue
+        finally:
+            l.append('inner')
+    finally:
+        l.append('outer')
+    l.append('unreachable')
+assert l == ['inner', 'outer', 'inner', 'outer']
+
+
+l = []
+for i in range(3):
+    try:
+        for j in range(3):
+            try:
+                break
+            finally:
+                l.append((i, j))
+    finally:
+        l.append(i)
+assert l == [(0, 0), 0, (1, 0), 1, (2, 0), 2]
+
+
+l = []
+def f():
+    try:
+        try:
+            return 3
+        finally:
+            l.append(1)
+    finally:
+        l.append(2)
+
+assert f() == 3
+assert l == [1, 2]
+
+
+def raise_inner():
+    raise ValueError('inner')
+
+def call_inner():
+    raise_inner()
+
+try:
+    call_inner()
+except ValueError as ex:
+    tb = ex.__traceback__
+    assert type(tb).__name__ == 'traceback'
+    assert tb.tb_frame.f_code.co_name == '<module>'
+    assert tb.tb_next.tb_frame.f_code.co_name == 'call_inner'
+    assert tb.tb_next.tb_next.tb_frame.f_code.co_name == 'raise_inner'
+    assert tb.tb_next.tb_next.tb_next is None
+    assert tb.tb_next.tb_next.tb_lineno == raise_inner.__code__.co_firstlineno + 1
+    assert isinstance(tb.tb_lasti, int)
+    if sys.implementation.name.lower() == 'rustpython':
+        # The tuples __traceback__ used to hold, innermost frame first:
+        entries = ex.__traceback_list__
+        assert [name for _, _, name in entries] == ['raise_inner', 'call_inner', '<module>']
+        assert entries[0][1] == tb.tb_next.tb_next.tb_lineno
+        assert 'try_exceptions.py' in entries[0][0]
+
+def traceback_names(tb):
+    names = []
+    while tb is not None:
+        names.append(tb.tb_frame.f_code.co_name)
+        tb = tb.tb_next
+    return names
+
+def reraise_middle():
+    try:
+        raise_inner()
+    except ValueError:
+        raise
+
+def finally_middle():
+    try:
+        raise_inner()
+    finally:
+        pass
+
+for middle in [call_inner, reraise_middle, finally_middle]:
+    def outer():
+        try:
+            middle()
+        except ValueError as ex:
+            return ex
+    assert traceback_names(outer().__traceback__) == ['outer', middle.__name__, 'raise_inner']
+
+assert ValueError().__traceback__ is None
+
+# with_traceback:
+
+try:
+    raise_inner()
+except ValueError as ex:
+    saved_tb = ex.__traceback__
+
+exc = KeyError('rewrapped')
+assert exc.with_traceback(saved_tb) is exc
+assert exc.__traceback__ is saved_tb
+
+try:
+    raise exc
+except KeyError as ex:
+    assert ex.__traceback__.tb_next is saved_tb
+
+assert exc.with_traceback(None).__traceback__ is None
+
+with assertRaises(TypeError):
+    exc.with_traceback(42)
+
+# Implicit and explicit exception chaining:
+
+try:
+    try:
+        raise ValueError('first')
+    except ValueError as first:
+        raise KeyError('second')
+except KeyError as second:
+    assert isinstance(second.__context__, ValueError)
+    assert second.__cause__ is None
+    assert not second.__suppress_context__
+
+try:
+    try:
+        raise ValueError('first')
+    except ValueError:
+        1 / 0
+except ZeroDivisionError as ex:
+    assert isinstance(ex.__context__, ValueError)
+
+try:
+    try:
+        raise ValueError('first')
+    except ValueError:
+        raise KeyError('second') from None
+except KeyError as ex:
+    assert isinstance(ex.__context__, ValueError)
+    assert ex.__cause__ is None
+    assert ex.__suppress_context__
+
+cause = TypeError('cause')
+try:
+    try:
+        raise ValueError('first')
+    except ValueError:
+        try:
+            raise KeyError('second') from cause
+        except KeyError:
+            raise
+except KeyError as ex:
+    assert ex.__cause__ is cause
+    assert ex.__suppress_context__
+    assert isinstance(ex.__context__, ValueError)
+
+try:
+    try:
+        raise ValueError('same')
+    except ValueError as same:
+        raise same
+except ValueError as ex:
+    assert ex.__context__ is None
+
+
+def return_in_finally():
+    try:
+        raise ValueError
+    finally:
+        return 5
+
+assert return_in_finally() == 5
+assert sys.exc_info() == (None, None, None)
+
+
+def return_overrides_return():
+    try:
+        return 1
+    finally:
+        return 2
+
+assert return_overrides_return() == 2
+
+
+def break_in_finally():
+    for i in range(3):
+        try:
+            raise ValueError
+        finally:
+            break
+    return i
+
+assert break_in_finally() == 0
+assert sys.exc_info() == (None, None, None)
+
+
+def traceback_lines(tb):
+    lines = []
+    while tb is not None:
+        lines.append(tb.tb_lineno - tb.tb_frame.f_code.co_firstlineno)
+        tb = tb.tb_next
+    return lines
+
+def reraise_outer():
+    try:
+        raise ValueError('outer')
+    except ValueError:
+        try:
+            raise KeyError('inner')
+        except KeyError:
+            pass
+        raise
+
+try:
+    reraise_outer()
+except ValueError as ex:
+    assert ex.args == ('outer',)
+    assert ex.__context__ is None
+    assert traceback_lines(ex.__traceback__.tb_next) == [2]
+
+def reraise_inner():
+    try:
+        raise ValueError('outer')
+    except ValueError:
+        try:
+            raise KeyError('inner')
+        except KeyError:
+            raise
+
+try:
+    reraise_inner()
+except KeyError as ex:
+    assert ex.args == ('inner',)
+    assert isinstance(ex.__context__, ValueError)
+    assert traceback_lines(ex.__traceback__.tb_next) == [5]
+
+with assertRaises(RuntimeError):
+    raise
+
+
+# Column ranges point at the failing part of an expression:
+
+class Node:
+    pass
+
+a = Node()
+a.b = Node()
+
+def chained():
+    return a.b.c.d
+
+def failing_position(ex):
+    tb = ex.__traceback__
+    while tb.tb_next is not None:
+        tb = tb.tb_next
+    positions = list(tb.tb_frame.f_code.co_positions())
+    if sys.implementation.name.lower() == 'rustpython':
+        return positions[tb.tb_lasti]
+    # CPython counts tb_lasti in bytes of two byte code units
+    return positions[tb.tb_lasti // 2]
+
+try:
+    chained()
+except AttributeError as ex:
+    line = chained.__code__.co_firstlineno + 1
+    assert failing_position(ex) == (line, line, 11, 16)
+
+def chained_method():
+    return a.b.missing()
+
+try:
+    chained_method()
+except AttributeError as ex:
+    line = chained_method.__code__.co_firstlineno + 1
+    assert failing_position(ex) == (line, line, 11, 22)
@@ -10,3 +10,46 @@ print(curry.__annotations__)
 assert curry.__annotations__['foo'] is int
 assert curry.__annotations__['return'] is float
 assert curry.__annotations__['bla'] is int
+
+# Variable annotations in a class body go to its own __annotations__:
+class Point:
+    x: int
+    y: str = 'a'
+    if True:
+        z: float
+
+assert Point.__annotations__ == {'x': int, 'y': str, 'z': float}
+assert Point.y == 'a'
+assert not hasattr(Point, 'x')
+
+class NoAnnotations:
+    a = 1
+
+assert '__annotations__' not in NoAnnotations.__dict__
+
+# Annotations of function locals are never evaluated:
+def local_annotation():
+    v: undefined_name = 3
+    return v
+
+assert local_annotation() == 3
+
+def local_scope_annotation():
+    v: (lambda: undefined_name) = 3
+    w: [x for x in undefined_name] = 4
+    def inner():
+        return v + w
+    return inner()
+
+assert local_scope_annotation() == 7
+
+module_annotation: bytes
+assert __annotations__['module_annotation'] is bytes
+assert 'x' not in __annotations__
+
+# Every parameter can be annotated, and the return annotation comes last:
+def all_params(a: int, *args: str, b: float, **kwargs: bool) -> None:
+    pass
+
+assert list(all_params.__annotations__.items()) == [
+    ('a', int), ('args', str), ('b', float), ('kwargs', bool), ('return', None)]
@@ -1,3 +1,4 @@
+import sys
 
 
 class ContextManager:
@@ -67,3 +68,237 @@ class assertRaises:
 
 with assertRaises(RuntimeError):
     raise RuntimeError('w00t')
+
+# Traceback passed to __exit__:
+class TracebackChecker:
+    def __init__(self, expect_tb):
+        self.expect_tb = expect_tb
+
+    def __enter__(self):
+        pass
+
+    def __exit__(self, exc_type, exc_val, exc_tb):
+        if self.expect_tb:
+            assert exc_tb is not None
+            assert exc_tb is exc_val.__traceback__
+            assert exc_tb.tb_frame.f_code.co_name == '<module>'
+            return True
+        else:
+            assert exc_tb is None
+
+
+with TracebackChecker(True):
+    raise ValueError('tb')
+
+with TracebackChecker(False):
+    pass
+
+
+# Several managers in one with statement are exited in reverse order:
+events = []
+
+class Recorder:
+    def __init__(self, name, fail_enter=False):
+        self.name = name
+        self.fail_enter = fail_enter
+
+    def __enter__(self):
+        events.append(('enter', self.name))
+        if self.fail_enter:
+            raise ValueError(self.name)
+        return self.name
+
+    def __exit__(self, exc_type, exc_val, exc_tb):
+        events.append(('exit', self.name, exc_type))
+
+with Recorder('a') as x, Recorder('b') as y:
+    events.append(('body', x, y))
+assert events == [('enter', 'a'), ('enter', 'b'), ('body', 'a', 'b'), ('exit', 'b', None), ('exit', 'a', None)]
+
+events = []
+with assertRaises(ValueError):
+    with Recorder('a', fail_enter=True), Recorder('b'):
+        events.append('body')
+assert events == [('enter', 'a')]
+
+events = []
+with assertRaises(ValueError):
+    with Recorder('a'), Recorder('b', fail_enter=True), Recorder('c'):
+        events.append('body')
+assert events == [('enter', 'a'), ('enter', 'b'), ('exit', 'a', ValueError)]
+
+events = []
+with assertRaises(KeyError):
+    with Recorder('a'), Recorder('b'):
+        raise KeyError
+assert events == [('enter', 'a'), ('enter', 'b'), ('exit', 'b', KeyError), ('exit', 'a', KeyError)]
+
+def leave_early():
+    for i in range(2):
+        with Recorder('a'), Recorder('b'):
+            if i == 0:
+                continue
+            break
+    with Recorder('a'), Recorder('b'):
+        return 1
+
+events = []
+assert leave_early() == 1
+assert events == [('enter', 'a'), ('enter', 'b'), ('exit', 'b', None), ('exit', 'a', None)] * 3
+
+# An exception suppressed by an inner manager still exits the outer one first:
+class Suppressor(Recorder):
+    def __exit__(self, exc_type, exc_val, exc_tb):
+        super().__exit__(exc_type, exc_val, exc_tb)
+        return True
+
+events = []
+with Recorder('a'), Suppressor('b'):
+    raise KeyError
+events.append('after')
+assert events == [('enter', 'a'), ('enter', 'b'), ('exit', 'b', KeyError), ('exit', 'a', None), 'after']
+
+# An exception raised by __exit__ replaces the one being handled, which becomes its context:
+class ExitRaiser(Recorder):
+    def __exit__(self, exc_type, exc_val, exc_tb):
+        super().__exit__(exc_type, exc_val, exc_tb)
+        assert sys.exc_info()[1] is exc_val
+        raise KeyError(self.name)
+
+events = []
+try:
+    with Recorder('a'), ExitRaiser('b'):
+        raise ValueError('body')
+except KeyError as e:
+    assert e.args == ('b',)
+    assert type(e.__context__) is ValueError
+    assert e.__context__.args == ('body',)
+else:
+    assert False, "KeyError not raised"
+assert events == [('enter', 'a'), ('enter', 'b'), ('exit', 'b', ValueError), ('exit', 'a', KeyError)]
+assert sys.exc_info() == (None, None, None)
+
+def exit_raises_in_generator():
+    try:
+        with ExitRaiser('g'):
+            yield 1
+    except KeyError as e:
+        yield e.__context__
+
+events = []
+gen = exit_raises_in_generator()
+next(gen)
+original = IndexError()
+assert gen.throw(IndexError, original, None) is original
+
+# An exception from __enter__ propagates without calling __exit__, and leaves
+# no block behind:
+class Recorder:
+    def __init__(self, fail_enter=False):
+        self.fail_enter = fail_enter
+
+    def __enter__(self):
+        events.append('enter')
+        if self.fail_enter:
+            raise KeyError('enter')
+        return self
+
+    def __exit__(self, exc_type, exc_val, exc_tb):
+        events.append(('exit', exc_type))
+
+events = []
+for i in range(2):
+    try:
+        with Recorder(fail_enter=True):
+            events.append('body')
+    except KeyError:
+        events.append('caught')
+        continue
+    assert False, "KeyError not raised"
+assert events == ['enter', 'caught', 'enter', 'caught']
+
+# A body raising after __enter__ succeeded calls __exit__ exactly once:
+events = []
+try:
+    with Recorder():
+        raise ValueError
+except ValueError:
+    events.append('propagated')
+assert events == ['enter', ('exit', ValueError), 'propagated']
+
+# __enter__ isn't called on an object which lacks either method:
+class NoExit:
+    def __enter__(self):
+        events.append('enter')
+
+class NoEnter:
+    def __exit__(self, *args):
+        events.append('exit')
+
+events = []
+for cls, message in [
+    (NoExit, "'NoExit' object does not support the context manager protocol (missed __exit__ method)"),
+    (NoEnter, "'NoEnter' object does not support the context manager protocol"),
+]:
+    try:
+        with cls():
+            events.append('body')
+    except TypeError as e:
+        assert str(e) == message, e
+    else:
+        assert False, "TypeError not raised"
+assert events == []
+
+# Leaving the body with break, continue or return calls __exit__ once with
+# no exception, and what it returns doesn't change where control goes:
+class ExitResult:
+    def __init__(self, result):
+        self.result = result
+
+    def __enter__(self):
+        return self
+
+    def __exit__(self, *args):
+        events.append(args)
+        return self.result
+
+for result in [True, False, None, 'suppress']:
+    events = []
+    for i in range(3):
+        with ExitResult(result):
+            if i == 0:
+                continue
+            break
+        events.append('unreachable')
+    assert i == 1
+    assert events == [(None, None, None)] * 2
+
+    def returns(result):
+        with ExitResult(result):
+            return 'body'
+        return 'after'
+
+    events = []
+    assert returns(result) == 'body'
+    assert events == [(None, None, None)]
+
+    def returns_through_finally(result):
+        try:
+            with ExitResult(result):
+                return 'body'
+        finally:
+            events.append('finally')
+
+    events = []
+    assert returns_through_finally(result) == 'body'
+    assert events == [(None, None, None), 'finally']
+
+    events = []
+    while True:
+        try:
+            with ExitResult(result):
+                break
+        finally:
+            events.append('finally')
+    assert events == [(None, None, None), 'finally']
+    assert sys.exc_info() == (None, None, None)
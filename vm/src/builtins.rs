@@ -17,7 +17,7 @@ use crate::obj::objdict::PyDictRef;
 use crate::obj::objint::{self, PyIntRef};
 use crate::obj::objiter;
 use crate::obj::objstr::{self, PyString, PyStringRef};
-use crate::obj::objtype::{self, PyClassRef};
+use crate::obj::objtype::{self, PyClass, PyClassRef};
 #[cfg(feature = "rustpython-compiler")]
 use rustpython_compiler::compile;
 
@@ -59,22 +59,7 @@ fn builtin_any(iterable: PyIterable<bool>, vm: &VirtualMachine) -> PyResult<bool
 }
 
 fn builtin_ascii(obj: PyObjectRef, vm: &VirtualMachine) -> PyResult<String> {
-    let repr = vm.to_repr(&obj)?;
-    let mut ascii = String::new();
-    for c in repr.value.chars() {
-        if c.is_ascii() {
-            ascii.push(c)
-        } else {
-            let c = c as i64;
-            let hex = if c < 0x10000 {
-                format!("\\u{:04x}", c)
-            } else {
-                format!("\\U{:08x}", c)
-            };
-            ascii.push_str(&hex)
-        }
-    }
-    Ok(ascii)
+    objstr::ascii(&obj, vm)
 }
 
 fn builtin_bin(x: PyIntRef, _vm: &VirtualMachine) -> String {
@@ -86,7 +71,15 @@ fn builtin_bin(x: PyIntRef, _vm: &VirtualMachine) -> String {
     }
 }
 
-// builtin_breakpoint
+fn builtin_breakpoint(vm: &VirtualMachine, args: PyFuncArgs) -> PyResult {
+    match vm.get_attribute(vm.sys_module.clone(), "breakpointhook") {
+        Ok(hook) => vm.invoke(&hook, args),
+        Err(_) => Err(vm.new_exception(
+            vm.ctx.exceptions.runtime_error.clone(),
+            "lost sys.breakpointhook".to_string(),
+        )),
+    }
+}
 
 fn builtin_callable(obj: PyObjectRef, vm: &VirtualMachine) -> bool {
     vm.is_callable(&obj)
@@ -130,7 +123,17 @@ fn builtin_compile(args: CompileArgs, vm: &VirtualMachine) -> PyResult<PyCodeRef
         .parse::<compile::Mode>()
         .map_err(|err| vm.new_value_error(err.to_string()))?;
 
-    vm.compile(&source, mode, args.filename.value.to_string())
+    // A negative level, the default, selects the interpreter's own level:
+    let optimize = match args.optimize {
+        OptionalArg::Present(level) if !level.as_bigint().is_negative() => level
+            .as_bigint()
+            .to_u8()
+            .ok_or_else(|| vm.new_value_error("compile(): invalid optimize value".to_string()))?,
+        _ => vm.settings.optimize,
+    };
+
+    compile::compile(&source, mode, args.filename.value.to_string(), optimize)
+        .map(|code| crate::obj::objcode::PyCode::new(code).into_ref(vm))
         .map_err(|err| vm.new_syntax_error(&err))
 }
 
@@ -241,6 +244,9 @@ fn make_scope(
         }
         None => None,
     };
+    if locals.is_none() && globals.is_none() {
+        vm.current_frame().unwrap().fast_to_locals(vm)?;
+    }
     let current_scope = vm.current_scope();
     let locals = match locals {
         Some(dict) => dict.clone().downcast().ok(),
@@ -828,6 +834,7 @@ pub fn make_module(vm: &VirtualMachine, module: PyObjectRef) {
         "any" => ctx.new_rustfunc(builtin_any),
         "ascii" => ctx.new_rustfunc(builtin_ascii),
         "bin" => ctx.new_rustfunc(builtin_bin),
+        "breakpoint" => ctx.new_rustfunc(builtin_breakpoint),
         "bool" => ctx.bool_type(),
         "bytearray" => ctx.bytearray_type(),
         "bytes" => ctx.bytes_type(),
@@ -905,13 +912,17 @@ pub fn make_module(vm: &VirtualMachine, module: PyObjectRef) {
         "ReferenceError" => ctx.exceptions.reference_error.clone(),
         "SyntaxError" =>  ctx.exceptions.syntax_error.clone(),
         "NotImplementedError" => ctx.exceptions.not_implemented_error.clone(),
+        "RecursionError" => ctx.exceptions.recursion_error.clone(),
         "TypeError" => ctx.exceptions.type_error.clone(),
+        "UnboundLocalError" => ctx.exceptions.unbound_local_error.clone(),
         "ValueError" => ctx.exceptions.value_error.clone(),
         "IndexError" => ctx.exceptions.index_error.clone(),
         "ImportError" => ctx.exceptions.import_error.clone(),
         "LookupError" => ctx.exceptions.lookup_error.clone(),
+        "MemoryError" => ctx.exceptions.memory_error.clone(),
         "FileNotFoundError" => ctx.exceptions.file_not_found_error.clone(),
         "FileExistsError" => ctx.exceptions.file_exists_error.clone(),
+        "StopAsyncIteration" => ctx.exceptions.stop_async_iteration.clone(),
         "StopIteration" => ctx.exceptions.stop_iteration.clone(),
         "SystemError" => ctx.exceptions.system_error.clone(),
         "PermissionError" => ctx.exceptions.permission_error.clone(),
@@ -939,6 +950,7 @@ pub fn make_module(vm: &VirtualMachine, module: PyObjectRef) {
         "UserWarning" => ctx.exceptions.user_warning.clone(),
 
         "KeyboardInterrupt" => ctx.exceptions.keyboard_interrupt.clone(),
+        "GeneratorExit" => ctx.exceptions.generator_exit.clone(),
     });
 }
 
@@ -952,29 +964,30 @@ pub fn builtin_build_class_(
     let name = qualified_name.value.split('.').next_back().unwrap();
     let name_obj = vm.new_str(name.to_string());
 
-    let mut metaclass = if let Some(metaclass) = kwargs.pop_kwarg("metaclass") {
-        PyClassRef::try_from_object(vm, metaclass)?
-    } else {
-        vm.get_type()
+    // An explicit metaclass which is not a class is called as is, otherwise the
+    // most derived metaclass of the bases is used:
+    let metaclass = match kwargs.pop_kwarg("metaclass") {
+        Some(metaclass) => match metaclass.downcast::<PyClass>() {
+            Ok(metaclass) => calculate_meta_class(metaclass, &bases, vm)?.into_object(),
+            Err(metaclass) => metaclass,
+        },
+        None => calculate_meta_class(vm.get_type(), &bases, vm)?.into_object(),
     };
 
-    for base in bases.clone() {
-        if objtype::issubclass(&base.class(), &metaclass) {
-            metaclass = base.class();
-        } else if !objtype::issubclass(&metaclass, &base.class()) {
-            return Err(vm.new_type_error(
-                "metaclass conflict: the metaclass of a derived class must be a (non-strict) \
-                 subclass of the metaclasses of all its bases"
-                    .to_owned(),
-            ));
-        }
-    }
-
     let bases = bases.into_tuple(vm);
 
+    // The remaining keywords are passed on to __prepare__ and the metaclass:
+    let kwargs: Vec<(String, PyObjectRef)> = kwargs.into_iter().collect();
+    let call_args = |args| PyFuncArgs {
+        args,
+        kwargs: kwargs.iter().cloned().collect(),
+    };
+
     // Prepare uses full __getattribute__ resolution chain.
-    let prepare = vm.get_attribute(metaclass.clone().into_object(), "__prepare__")?;
-    let namespace = vm.invoke(&prepare, vec![name_obj.clone(), bases.clone()])?;
+    let namespace = match vm.get_attribute(metaclass.clone(), "__prepare__") {
+        Ok(prepare) => vm.invoke(&prepare, call_args(vec![name_obj.clone(), bases.clone()]))?,
+        Err(_) => vm.ctx.new_dict().into_object(),
+    };
 
     let namespace: PyDictRef = TryFromObject::try_from_object(vm, namespace)?;
 
@@ -985,11 +998,34 @@ pub fn builtin_build_class_(
     namespace.set_item("__name__", name_obj.clone(), vm)?;
     namespace.set_item("__qualname__", qualified_name.into_object(), vm)?;
 
-    let class = vm.call_method(
-        metaclass.as_object(),
-        "__call__",
-        vec![name_obj, bases, namespace.into_object()],
+    let class = vm.invoke(
+        &metaclass,
+        call_args(vec![name_obj, bases, namespace.into_object()]),
     )?;
     cells.set_item("__class__", class.clone(), vm)?;
     Ok(class)
 }
+
+fn calculate_meta_class(
+    metaclass: PyClassRef,
+    bases: &Args<PyClassRef>,
+    vm: &VirtualMachine,
+) -> PyResult<PyClassRef> {
+    let mut winner = metaclass;
+    for base in bases.clone() {
+        let base_type = base.class();
+        if objtype::issubclass(&winner, &base_type) {
+            continue;
+        } else if objtype::issubclass(&base_type, &winner) {
+            winner = base_type;
+            continue;
+        }
+
+        return Err(vm.new_type_error(
+            "metaclass conflict: the metaclass of a derived class must be a (non-strict) \
+             subclass of the metaclasses of all its bases"
+                .to_owned(),
+        ));
+    }
+    Ok(winner)
+}
@@ -1,5 +1,5 @@
 use crate::obj::objbool;
-use crate::obj::objstr::PyString;
+use crate::obj::objstr::{PyString, PyStringRef};
 use crate::pyhash;
 use crate::pyobject::{IdProtocol, IntoPyObject, PyObjectRef, PyResult};
 use crate::vm::VirtualMachine;
@@ -10,6 +10,7 @@ use num_bigint::ToBigInt;
 /// And: http://code.activestate.com/recipes/578375/
 use std::collections::{hash_map::DefaultHasher, HashMap};
 use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicUsize, Ordering};
 
 /// hash value of an object returned by __hash__
 type HashValue = pyhash::PyHash;
@@ -18,11 +19,21 @@ type HashIndex = pyhash::PyHash;
 /// entry index mapped in indices
 type EntryIndex = usize;
 
+static NEXT_VERSION_TAG: AtomicUsize = AtomicUsize::new(1);
+
+/// Get a version tag which no dict has used before.
+fn next_version_tag() -> usize {
+    NEXT_VERSION_TAG.fetch_add(1, Ordering::Relaxed)
+}
+
 #[derive(Clone)]
 pub struct Dict<T = PyObjectRef> {
     size: usize,
     indices: HashMap<HashIndex, EntryIndex>,
     entries: Vec<Option<DictEntry<T>>>,
+    /// Changed whenever an entry is added, removed or replaced, so that two
+    /// equal tags always mean the same contents.
+    version_tag: usize,
 }
 
 impl<T> Default for Dict<T> {
@@ -31,6 +42,7 @@ impl<T> Default for Dict<T> {
             size: 0,
             indices: HashMap::new(),
             entries: Vec::new(),
+            version_tag: next_version_tag(),
         }
     }
 }
@@ -65,11 +77,13 @@ impl<T: Clone> Dict<T> {
         self.entries.push(Some(entry));
         self.indices.insert(hash_index, entry_index);
         self.size += 1;
+        self.version_tag = next_version_tag();
     }
 
     fn unchecked_delete(&mut self, entry_index: EntryIndex) {
         self.entries[entry_index] = None;
         self.size -= 1;
+        self.version_tag = next_version_tag();
     }
 
     /// Store a key
@@ -84,6 +98,7 @@ impl<T: Clone> Dict<T> {
                 // Update existing key
                 if let Some(ref mut entry) = self.entries[index] {
                     entry.value = value;
+                    self.version_tag = next_version_tag();
                     Ok(())
                 } else {
                     panic!("Lookup returned invalid index into entries!");
@@ -129,7 +144,8 @@ impl<T: Clone> Dict<T> {
     pub fn clear(&mut self) {
         self.entries.clear();
         self.indices.clear();
-        self.size = 0
+        self.size = 0;
+        self.version_tag = next_version_tag();
     }
 
     /// Delete a key
@@ -192,6 +208,10 @@ impl<T: Clone> Dict<T> {
         None
     }
 
+    pub fn version_tag(&self) -> usize {
+        self.version_tag
+    }
+
     pub fn has_changed_size(&self, position: &DictSize) -> bool {
         position.size != self.size || self.entries.len() != position.entries_size
     }
@@ -315,7 +335,7 @@ impl DictKey for &PyObjectRef {
 impl DictKey for &str {
     fn do_hash(self, _vm: &VirtualMachine) -> PyResult<HashValue> {
         // follow a similar route as the hashing of PyStringRef
-        let raw_hash = pyhash::hash_value(&self.to_string()).to_bigint().unwrap();
+        let raw_hash = pyhash::hash_value(&self).to_bigint().unwrap();
         let raw_hash = pyhash::hash_bigint(&raw_hash);
         let mut hasher = DefaultHasher::new();
         raw_hash.hash(&mut hasher);
@@ -366,6 +386,22 @@ impl DictKey for &String {
     }
 }
 
+/// Implement trait for str objects, so that names the VM keeps as Python
+/// strings can be used as keys without hashing through `__hash__`.
+impl DictKey for &PyStringRef {
+    fn do_hash(self, vm: &VirtualMachine) -> PyResult<HashValue> {
+        self.value.as_str().do_hash(vm)
+    }
+
+    fn do_is(self, other: &PyObjectRef) -> bool {
+        self.is(other)
+    }
+
+    fn do_eq(self, vm: &VirtualMachine, other_key: &PyObjectRef) -> PyResult<bool> {
+        self.value.as_str().do_eq(vm, other_key)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::{Dict, DictKey, VirtualMachine};
@@ -1,118 +1,210 @@
+use crate::bytecode;
 use crate::function::PyFuncArgs;
-use crate::obj::objsequence;
+use crate::obj::objbool;
+use crate::obj::objint;
+use crate::obj::objlist::PyListRef;
+use crate::obj::objtraceback::{PyTraceback, PyTracebackRef};
 use crate::obj::objtuple::{PyTuple, PyTupleRef};
 use crate::obj::objtype;
 use crate::obj::objtype::PyClassRef;
-use crate::pyobject::{IdProtocol, PyContext, PyObjectRef, PyResult, TypeProtocol};
+use crate::pyobject::{IdProtocol, PyContext, PyObjectRef, PyResult, TryFromObject, TypeProtocol};
 use crate::types::create_type;
 use crate::vm::VirtualMachine;
 use itertools::Itertools;
-use std::fs::File;
-use std::io::{BufRead, BufReader};
+use num_traits::{Signed, ToPrimitive};
+use std::io::{self, Write};
 
 fn exception_init(vm: &VirtualMachine, args: PyFuncArgs) -> PyResult {
     let exc_self = args.args[0].clone();
     let exc_args = vm.ctx.new_tuple(args.args[1..].to_vec());
     vm.set_attr(&exc_self, "args", exc_args)?;
 
-    let traceback = vm.ctx.new_list(Vec::new());
-    vm.set_attr(&exc_self, "__traceback__", traceback)?;
+    vm.set_attr(&exc_self, "__traceback__", vm.get_none())?;
     vm.set_attr(&exc_self, "__cause__", vm.get_none())?;
     vm.set_attr(&exc_self, "__context__", vm.get_none())?;
     vm.set_attr(&exc_self, "__suppress_context__", vm.new_bool(false))?;
     Ok(vm.get_none())
 }
 
+fn exception_with_traceback(
+    zelf: PyObjectRef,
+    tb: PyObjectRef,
+    vm: &VirtualMachine,
+) -> PyResult<PyObjectRef> {
+    set_traceback(vm, &zelf, tb)?;
+    Ok(zelf)
+}
+
+/// The traceback as the list of `(filename, lineno, name)` tuples, innermost
+/// frame first, which `__traceback__` held before it became a chain of
+/// traceback objects. Kept for code still reading the old form.
+fn exception_traceback_list(zelf: PyObjectRef, vm: &VirtualMachine) -> PyResult {
+    let tb = vm.get_attribute(zelf, "__traceback__")?;
+    let mut entries: Vec<_> = match tb.downcast::<PyTraceback>() {
+        Ok(tb) => tb
+            .iter()
+            .map(|tb_entry| {
+                vm.ctx.new_tuple(vec![
+                    vm.new_str(tb_entry.frame.code.source_path.clone()),
+                    vm.new_int(tb_entry.lineno),
+                    vm.new_str(tb_entry.frame.code.obj_name.clone()),
+                ])
+            })
+            .collect(),
+        Err(_) => vec![],
+    };
+    entries.reverse();
+    Ok(vm.ctx.new_list(entries))
+}
+
+/// Add a note to the exception's `__notes__`, shown after it in tracebacks (PEP 678).
+fn exception_add_note(zelf: PyObjectRef, note: PyObjectRef, vm: &VirtualMachine) -> PyResult<()> {
+    if !objtype::isinstance(&note, &vm.ctx.str_type()) {
+        return Err(vm.new_type_error(format!("note must be a str, not '{}'", note.class().name)));
+    }
+    let notes = match vm.get_attribute(zelf.clone(), "__notes__") {
+        Ok(notes) => notes,
+        Err(_) => {
+            let notes = vm.ctx.new_list(vec![]);
+            vm.set_attr(&zelf, "__notes__", notes.clone())?;
+            notes
+        }
+    };
+    let notes = PyListRef::try_from_object(vm, notes)
+        .map_err(|_| vm.new_type_error("Cannot add note: __notes__ is not a list".to_string()))?;
+    notes.append(note, vm);
+    Ok(())
+}
+
+/// Set `__traceback__` on an exception, checking that `tb` is a traceback or None.
+pub fn set_traceback(vm: &VirtualMachine, exc: &PyObjectRef, tb: PyObjectRef) -> PyResult<()> {
+    if !vm.is_none(&tb) && !objtype::isinstance(&tb, &vm.ctx.traceback_type()) {
+        return Err(vm.new_type_error("__traceback__ must be a traceback or None".to_string()));
+    }
+    vm.set_attr(exc, "__traceback__", tb)?;
+    Ok(())
+}
+
 /// Print exception chain
 pub fn print_exception(vm: &VirtualMachine, exc: &PyObjectRef) {
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+    let _ = write_exception(&mut stdout, vm, exc);
+}
+
+/// Write exception chain, rendered the way the interpreter prints it
+pub fn write_exception<W: Write>(
+    output: &mut W,
+    vm: &VirtualMachine,
+    exc: &PyObjectRef,
+) -> io::Result<()> {
     let mut had_cause = false;
     if let Ok(cause) = vm.get_attribute(exc.clone(), "__cause__") {
         if !vm.get_none().is(&cause) {
             had_cause = true;
-            print_exception(vm, &cause);
-            println!("\nThe above exception was the direct cause of the following exception:\n");
+            write_exception(output, vm, &cause)?;
+            writeln!(
+                output,
+                "\nThe above exception was the direct cause of the following exception:\n"
+            )?;
         }
     }
-    if !had_cause {
+    let suppress_context = vm
+        .get_attribute(exc.clone(), "__suppress_context__")
+        .map(|suppress| objbool::boolval(vm, suppress).unwrap_or(false))
+        .unwrap_or(false);
+    if !had_cause && !suppress_context {
         if let Ok(context) = vm.get_attribute(exc.clone(), "__context__") {
             if !vm.get_none().is(&context) {
-                print_exception(vm, &context);
-                println!("\nDuring handling of the above exception, another exception occurred:\n");
+                write_exception(output, vm, &context)?;
+                writeln!(
+                    output,
+                    "\nDuring handling of the above exception, another exception occurred:\n"
+                )?;
             }
         }
     }
-    print_exception_inner(vm, exc)
+    write_exception_inner(output, vm, exc)
 }
 
-fn print_source_line(filename: String, lineno: usize) {
-    // TODO: use io.open() method instead, when available, according to https://github.com/python/cpython/blob/master/Python/traceback.c#L393
-    // TODO: support different encodings
-    let file = match File::open(filename) {
-        Ok(file) => file,
-        Err(_) => {
-            return;
-        }
-    };
-    let file = BufReader::new(file);
-
-    for (i, line) in file.lines().enumerate() {
-        if i + 1 == lineno {
-            if let Ok(line) = line {
-                // Indented with 4 spaces
-                println!("    {}", line.trim_start());
+/// Write exception occurrence location from traceback element
+fn write_traceback_entry<W: Write>(output: &mut W, tb_entry: &PyTracebackRef) -> io::Result<()> {
+    writeln!(
+        output,
+        r##"  File "{}", line {}, in {}"##,
+        tb_entry.frame.code.source_path, tb_entry.lineno, tb_entry.frame.code.obj_name
+    )?;
+    if let Some(line) = tb_entry.frame.source_line(tb_entry.lineno) {
+        // Indented with 4 spaces
+        writeln!(output, "    {}", line.trim_start())?;
+        if let Some(location) = tb_entry.frame.code.locations.get(tb_entry.lasti) {
+            if let Some(carets) = traceback_carets(&line, location) {
+                writeln!(output, "    {}", carets)?;
             }
-            return;
         }
     }
+    Ok(())
 }
 
-/// Print exception occurrence location from traceback element
-fn print_traceback_entry(vm: &VirtualMachine, tb_entry: &PyObjectRef) {
-    if objtype::isinstance(&tb_entry, &vm.ctx.tuple_type()) {
-        let location_attrs = objsequence::get_elements_tuple(&tb_entry);
-        let filename = if let Ok(x) = vm.to_str(&location_attrs[0]) {
-            x.value.clone()
-        } else {
-            "<error>".to_string()
-        };
-
-        let lineno = if let Ok(x) = vm.to_str(&location_attrs[1]) {
-            x.value.clone()
-        } else {
-            "<error>".to_string()
-        };
-
-        let obj_name = if let Ok(x) = vm.to_str(&location_attrs[2]) {
-            x.value.clone()
-        } else {
-            "<error>".to_string()
-        };
-
-        println!(
-            r##"  File "{}", line {}, in {}"##,
-            filename, lineno, obj_name
-        );
-        print_source_line(filename, lineno.parse().unwrap());
+/// Underline the span of `location` on its first source line, unless the
+/// span covers the whole line, in which case the carets tell nothing new.
+fn traceback_carets(line: &str, location: &bytecode::Location) -> Option<String> {
+    let indent = line.chars().count() - line.trim_start().chars().count();
+    let start = location.column().checked_sub(1)?;
+    let end = if location.end_row() == location.row() {
+        location.end_column().checked_sub(1)?
     } else {
-        println!("  File ??");
-        return;
+        line.trim_end().chars().count()
+    };
+    if start < indent || end <= start || end - start >= line.trim().chars().count() {
+        return None;
     }
+    Some(format!(
+        "{}{}",
+        " ".repeat(start - indent),
+        "^".repeat(end - start)
+    ))
 }
 
-/// Print exception with traceback
-pub fn print_exception_inner(vm: &VirtualMachine, exc: &PyObjectRef) {
-    if let Ok(tb) = vm.get_attribute(exc.clone(), "__traceback__") {
-        println!("Traceback (most recent call last):");
-        if objtype::isinstance(&tb, &vm.ctx.list_type()) {
-            let mut tb_entries = objsequence::get_elements_list(&tb).to_vec();
-            tb_entries.reverse();
+/// The number of most recent traceback entries to show, as set by `sys.tracebacklimit`.
+fn traceback_limit(vm: &VirtualMachine) -> Option<usize> {
+    let limit = vm
+        .get_attribute(vm.sys_module.clone(), "tracebacklimit")
+        .ok()?;
+    if !objtype::isinstance(&limit, &vm.ctx.int_type()) {
+        return None;
+    }
+    let limit = objint::get_value(&limit);
+    Some(if limit.is_negative() {
+        0
+    } else {
+        limit.to_usize().unwrap_or(std::usize::MAX)
+    })
+}
 
-            for exc_location in tb_entries.iter() {
-                print_traceback_entry(vm, exc_location);
+/// Write exception with traceback
+pub fn write_exception_inner<W: Write>(
+    output: &mut W,
+    vm: &VirtualMachine,
+    exc: &PyObjectRef,
+) -> io::Result<()> {
+    let limit = traceback_limit(vm);
+    if let Ok(tb) = vm.get_attribute(exc.clone(), "__traceback__") {
+        if limit != Some(0) {
+            writeln!(output, "Traceback (most recent call last):")?;
+        }
+        if let Ok(tb) = tb.downcast::<PyTraceback>() {
+            let tb_entries: Vec<_> = tb.iter().collect();
+            let skipped = limit.map_or(0, |limit| tb_entries.len().saturating_sub(limit));
+            if skipped > 0 && skipped < tb_entries.len() {
+                writeln!(output, "  ... {} earlier frames omitted", skipped)?;
+            }
+            for tb_entry in &tb_entries[skipped..] {
+                write_traceback_entry(output, tb_entry)?;
             }
         }
     } else {
-        println!("No traceback set on exception");
+        writeln!(output, "No traceback set on exception")?;
     }
 
     let varargs = vm
@@ -124,10 +216,40 @@ pub fn print_exception_inner(vm: &VirtualMachine, exc: &PyObjectRef) {
 
     let exc_name = exc.class().name.clone();
     match args_repr.len() {
-        0 => println!("{}", exc_name),
-        1 => println!("{}: {}", exc_name, args_repr[0]),
-        _ => println!("{}: ({})", exc_name, args_repr.into_iter().format(", ")),
+        0 => writeln!(output, "{}", exc_name),
+        1 => writeln!(output, "{}: {}", exc_name, args_repr[0]),
+        _ => writeln!(
+            output,
+            "{}: ({})",
+            exc_name,
+            args_repr.into_iter().format(", ")
+        ),
+    }?;
+    write_notes(output, vm, exc)
+}
+
+/// Write the notes added to an exception, one after the other.
+fn write_notes<W: Write>(output: &mut W, vm: &VirtualMachine, exc: &PyObjectRef) -> io::Result<()> {
+    let notes = match vm.get_attribute(exc.clone(), "__notes__") {
+        Ok(notes) if !vm.is_none(&notes) => notes,
+        _ => return Ok(()),
+    };
+    match PyListRef::try_from_object(vm, notes.clone()) {
+        Ok(notes) => {
+            let notes = notes.elements.borrow().clone();
+            for note in notes.iter() {
+                match vm.to_str(note) {
+                    Ok(note) => writeln!(output, "{}", note.as_str())?,
+                    Err(_) => writeln!(output, "<note str() failed>")?,
+                }
+            }
+        }
+        Err(_) => match vm.to_repr(&notes) {
+            Ok(notes) => writeln!(output, "{}", notes.as_str())?,
+            Err(_) => writeln!(output, "<__notes__ repr() failed>")?,
+        },
     }
+    Ok(())
 }
 
 fn exception_args_as_string(
@@ -163,6 +285,16 @@ fn exception_args_as_string(
 }
 
 fn exception_str(vm: &VirtualMachine, args: PyFuncArgs) -> PyResult {
+    exception_args_str(vm, args, true)
+}
+
+/// `KeyError` shows a lone key by its repr, so that e.g. an empty string key
+/// remains visible.
+fn key_error_str(vm: &VirtualMachine, args: PyFuncArgs) -> PyResult {
+    exception_args_str(vm, args, false)
+}
+
+fn exception_args_str(vm: &VirtualMachine, args: PyFuncArgs, str_single: bool) -> PyResult {
     arg_check!(
         vm,
         args,
@@ -173,7 +305,7 @@ fn exception_str(vm: &VirtualMachine, args: PyFuncArgs) -> PyResult {
         .unwrap()
         .downcast::<PyTuple>()
         .expect("'args' must be a tuple");
-    let args_str = exception_args_as_string(vm, args, false);
+    let args_str = exception_args_as_string(vm, args, str_single);
     let joined_str = match args_str.len() {
         0 => "".to_string(),
         1 => args_str.into_iter().next().unwrap(),
@@ -204,6 +336,18 @@ fn exception_repr(vm: &VirtualMachine, args: PyFuncArgs) -> PyResult {
     Ok(vm.new_str(joined_str))
 }
 
+fn stop_iteration_init(vm: &VirtualMachine, args: PyFuncArgs) -> PyResult {
+    exception_init(vm, args.clone())?;
+
+    let exc_self = args.args[0].clone();
+    vm.set_attr(
+        &exc_self,
+        "value",
+        args.args.get(1).cloned().unwrap_or_else(|| vm.get_none()),
+    )?;
+    Ok(vm.get_none())
+}
+
 #[derive(Debug)]
 pub struct ExceptionZoo {
     pub arithmetic_error: PyClassRef,
@@ -217,18 +361,22 @@ pub struct ExceptionZoo {
     pub index_error: PyClassRef,
     pub key_error: PyClassRef,
     pub lookup_error: PyClassRef,
+    pub memory_error: PyClassRef,
     pub module_not_found_error: PyClassRef,
     pub name_error: PyClassRef,
     pub not_implemented_error: PyClassRef,
     pub os_error: PyClassRef,
     pub overflow_error: PyClassRef,
     pub permission_error: PyClassRef,
+    pub recursion_error: PyClassRef,
     pub reference_error: PyClassRef,
     pub runtime_error: PyClassRef,
+    pub stop_async_iteration: PyClassRef,
     pub stop_iteration: PyClassRef,
     pub syntax_error: PyClassRef,
     pub system_error: PyClassRef,
     pub type_error: PyClassRef,
+    pub unbound_local_error: PyClassRef,
     pub value_error: PyClassRef,
     pub unicode_error: PyClassRef,
     pub unicode_decode_error: PyClassRef,
@@ -250,6 +398,7 @@ pub struct ExceptionZoo {
     pub user_warning: PyClassRef,
 
     pub keyboard_interrupt: PyClassRef,
+    pub generator_exit: PyClassRef,
 }
 
 impl ExceptionZoo {
@@ -264,10 +413,12 @@ impl ExceptionZoo {
         let index_error = create_type("IndexError", &type_type, &exception_type);
         let key_error = create_type("KeyError", &type_type, &exception_type);
         let lookup_error = create_type("LookupError", &type_type, &exception_type);
+        let memory_error = create_type("MemoryError", &type_type, &exception_type);
         let name_error = create_type("NameError", &type_type, &exception_type);
         let os_error = create_type("OSError", &type_type, &exception_type);
         let runtime_error = create_type("RuntimeError", &type_type, &exception_type);
         let reference_error = create_type("ReferenceError", &type_type, &exception_type);
+        let stop_async_iteration = create_type("StopAsyncIteration", &type_type, &exception_type);
         let stop_iteration = create_type("StopIteration", &type_type, &exception_type);
         let syntax_error = create_type("SyntaxError", &type_type, &exception_type);
         let system_error = create_type("SystemError", &type_type, &exception_type);
@@ -277,6 +428,8 @@ impl ExceptionZoo {
         let zero_division_error = create_type("ZeroDivisionError", &type_type, &arithmetic_error);
         let module_not_found_error = create_type("ModuleNotFoundError", &type_type, &import_error);
         let not_implemented_error = create_type("NotImplementedError", &type_type, &runtime_error);
+        let recursion_error = create_type("RecursionError", &type_type, &runtime_error);
+        let unbound_local_error = create_type("UnboundLocalError", &type_type, &name_error);
         let file_not_found_error = create_type("FileNotFoundError", &type_type, &os_error);
         let permission_error = create_type("PermissionError", &type_type, &os_error);
         let file_exists_error = create_type("FileExistsError", &type_type, &os_error);
@@ -301,6 +454,7 @@ impl ExceptionZoo {
         let user_warning = create_type("UserWarning", &type_type, &warning);
 
         let keyboard_interrupt = create_type("KeyboardInterrupt", &type_type, &base_exception_type);
+        let generator_exit = create_type("GeneratorExit", &type_type, &base_exception_type);
 
         ExceptionZoo {
             arithmetic_error,
@@ -314,17 +468,21 @@ impl ExceptionZoo {
             index_error,
             key_error,
             lookup_error,
+            memory_error,
             module_not_found_error,
             name_error,
             not_implemented_error,
             os_error,
             overflow_error,
             permission_error,
+            recursion_error,
             runtime_error,
+            stop_async_iteration,
             stop_iteration,
             syntax_error,
             system_error,
             type_error,
+            unbound_local_error,
             value_error,
             unicode_error,
             unicode_decode_error,
@@ -345,6 +503,7 @@ impl ExceptionZoo {
             reference_error,
             user_warning,
             keyboard_interrupt,
+            generator_exit,
         }
     }
 }
@@ -381,7 +540,10 @@ fn import_error_init(vm: &VirtualMachine, args: PyFuncArgs) -> PyResult {
 pub fn init(context: &PyContext) {
     let base_exception_type = &context.exceptions.base_exception_type;
     extend_class!(context, base_exception_type, {
-        "__init__" => context.new_rustfunc(exception_init)
+        "__init__" => context.new_rustfunc(exception_init),
+        "with_traceback" => context.new_rustfunc(exception_with_traceback),
+        "add_note" => context.new_rustfunc(exception_add_note),
+        "__traceback_list__" => context.new_property(exception_traceback_list),
     });
 
     let exception_type = &context.exceptions.exception_type;
@@ -390,8 +552,113 @@ pub fn init(context: &PyContext) {
         "__repr__" => context.new_rustfunc(exception_repr),
     });
 
+    let key_error_type = &context.exceptions.key_error;
+    extend_class!(context, key_error_type, {
+        "__str__" => context.new_rustfunc(key_error_str),
+    });
+
     let import_error_type = &context.exceptions.import_error;
     extend_class!(context, import_error_type, {
         "__init__" => context.new_rustfunc(import_error_init)
     });
+
+    let stop_iteration_type = &context.exceptions.stop_iteration;
+    extend_class!(context, stop_iteration_type, {
+        "__init__" => context.new_rustfunc(stop_iteration_init)
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::write_exception;
+    use crate::vm::VirtualMachine;
+    use rustpython_compiler::compile;
+
+    #[test]
+    fn test_traceback_source_line() {
+        let vm: VirtualMachine = Default::default();
+        let source = "def f():\n    return 1 / 0\nf()\n";
+        let code = vm
+            .compile(source, compile::Mode::Exec, "<unittest>".to_string())
+            .unwrap();
+        let exc = vm
+            .run_code_obj(code, vm.new_scope_with_builtins())
+            .unwrap_err();
+
+        let mut output = Vec::new();
+        write_exception(&mut output, &vm, &exc).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("  File \"<unittest>\", line 3, in <module>\n    f()\n"));
+        assert!(output.contains("  File \"<unittest>\", line 2, in f\n    return 1 / 0\n"));
+    }
+
+    #[test]
+    fn test_traceback_notes() {
+        let vm: VirtualMachine = Default::default();
+        let source =
+            "e = ValueError('value')\ne.add_note('first')\ne.add_note('second')\nraise e\n";
+        let code = vm
+            .compile(source, compile::Mode::Exec, "<unittest>".to_string())
+            .unwrap();
+        let exc = vm
+            .run_code_obj(code, vm.new_scope_with_builtins())
+            .unwrap_err();
+
+        let mut output = Vec::new();
+        write_exception(&mut output, &vm, &exc).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert!(
+            output.ends_with("ValueError: value\nfirst\nsecond\n"),
+            "{}",
+            output
+        );
+    }
+
+    #[test]
+    fn test_traceback_limit() {
+        let vm: VirtualMachine = Default::default();
+        let source = "import sys\nsys.tracebacklimit = 2\ndef f(n):\n    if n:\n        return f(n - 1)\n    raise ValueError('deep')\nf(5)\n";
+        let code = vm
+            .compile(source, compile::Mode::Exec, "<unittest>".to_string())
+            .unwrap();
+        let exc = vm
+            .run_code_obj(code, vm.new_scope_with_builtins())
+            .unwrap_err();
+
+        let mut output = Vec::new();
+        write_exception(&mut output, &vm, &exc).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert_eq!(
+            output,
+            "Traceback (most recent call last):\n  ... 5 earlier frames omitted\n  File \"<unittest>\", line 5, in f\n    return f(n - 1)\n           ^^^^^^^^\n  File \"<unittest>\", line 6, in f\n    raise ValueError('deep')\nValueError: deep\n"
+        );
+
+        // No traceback is shown at all with a limit of zero:
+        vm.set_attr(&vm.sys_module, "tracebacklimit", vm.new_int(0))
+            .unwrap();
+        let mut output = Vec::new();
+        write_exception(&mut output, &vm, &exc).unwrap();
+        assert_eq!(String::from_utf8(output).unwrap(), "ValueError: deep\n");
+    }
+
+    #[test]
+    fn test_traceback_carets() {
+        let vm: VirtualMachine = Default::default();
+        let source = "class A: pass\na = A()\na.b = A()\nx = a.b.c.d\n";
+        let code = vm
+            .compile(source, compile::Mode::Exec, "<unittest>".to_string())
+            .unwrap();
+        let exc = vm
+            .run_code_obj(code, vm.new_scope_with_builtins())
+            .unwrap_err();
+
+        let mut output = Vec::new();
+        write_exception(&mut output, &vm, &exc).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert!(
+            output.contains("line 4, in <module>\n    x = a.b.c.d\n        ^^^^^\n"),
+            "{}",
+            output
+        );
+    }
 }
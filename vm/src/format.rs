@@ -216,6 +216,7 @@ fn parse_grouping_option(text: &str) -> (Option<FormatGrouping>, &str) {
 fn parse_format_type(text: &str) -> (Option<FormatType>, &str) {
     let mut chars = text.chars();
     match chars.next() {
+        Some('s') => (Some(FormatType::String), chars.as_str()),
         Some('b') => (Some(FormatType::Binary), chars.as_str()),
         Some('c') => (Some(FormatType::Character), chars.as_str()),
         Some('d') => (Some(FormatType::Decimal), chars.as_str()),
@@ -318,7 +319,6 @@ impl FormatSpec {
     }
 
     pub fn format_int(&self, num: &BigInt) -> Result<String, &'static str> {
-        let fill_char = self.fill.unwrap_or(' ');
         let magnitude = num.abs();
         let prefix = if self.alternate_form {
             match self.format_type {
@@ -372,11 +372,6 @@ impl FormatSpec {
             prefix,
             self.add_magnitude_separators(raw_magnitude_string_result.unwrap())
         );
-        let align = self.align.unwrap_or(FormatAlign::Right);
-
-        // Use the byte length as the string length since we're in ascii
-        let num_chars = magnitude_string.len();
-
         let format_sign = self.sign.unwrap_or(FormatSign::Minus);
         let sign_str = match num.sign() {
             Sign::Minus => "-",
@@ -387,10 +382,113 @@ impl FormatSpec {
             },
         };
 
+        Ok(self.format_sign_and_align(&magnitude_string, sign_str, FormatAlign::Right))
+    }
+
+    pub fn format_float(&self, num: f64) -> Result<String, &'static str> {
+        if self.grouping_option.is_some() {
+            return Err("Grouping is not supported for float formatting");
+        }
+        let magnitude = num.abs();
+        let precision = self.precision.unwrap_or(6);
+        let magnitude_string = match self.format_type {
+            Some(FormatType::FixedPointLower) => format_fixed(precision, magnitude, false),
+            Some(FormatType::FixedPointUpper) => format_fixed(precision, magnitude, true),
+            Some(FormatType::ExponentLower) => format_exponent(precision, magnitude, false),
+            Some(FormatType::ExponentUpper) => format_exponent(precision, magnitude, true),
+            Some(FormatType::GeneralFormatLower) => {
+                format_general(precision, magnitude, false, self.alternate_form)
+            }
+            Some(FormatType::GeneralFormatUpper) => {
+                format_general(precision, magnitude, true, self.alternate_form)
+            }
+            None => match self.precision {
+                Some(precision) => {
+                    let text = format_general(precision, magnitude, false, false);
+                    if text.contains(|c| c == '.' || c == 'e' || c == 'n' || c == 'i') {
+                        text
+                    } else {
+                        format!("{}.0", text)
+                    }
+                }
+                None => format_repr(magnitude),
+            },
+            Some(FormatType::String) => {
+                return Err("Unknown format code 's' for object of type 'float'")
+            }
+            Some(FormatType::Binary) => {
+                return Err("Unknown format code 'b' for object of type 'float'")
+            }
+            Some(FormatType::Character) => {
+                return Err("Unknown format code 'c' for object of type 'float'")
+            }
+            Some(FormatType::Decimal) => {
+                return Err("Unknown format code 'd' for object of type 'float'")
+            }
+            Some(FormatType::Octal) => {
+                return Err("Unknown format code 'o' for object of type 'float'")
+            }
+            Some(FormatType::HexLower) => {
+                return Err("Unknown format code 'x' for object of type 'float'")
+            }
+            Some(FormatType::HexUpper) => {
+                return Err("Unknown format code 'X' for object of type 'float'")
+            }
+            Some(FormatType::Number) => {
+                return Err("Unknown format code 'n' for object of type 'float'")
+            }
+        };
+
+        let format_sign = self.sign.unwrap_or(FormatSign::Minus);
+        let sign_str = if num.is_sign_negative() && !num.is_nan() {
+            "-"
+        } else {
+            match format_sign {
+                FormatSign::Plus => "+",
+                FormatSign::Minus => "",
+                FormatSign::MinusOrSpace => " ",
+            }
+        };
+
+        Ok(self.format_sign_and_align(&magnitude_string, sign_str, FormatAlign::Right))
+    }
+
+    pub fn format_string(&self, s: &str) -> Result<String, &'static str> {
+        match self.format_type {
+            Some(FormatType::String) | None => {}
+            _ => return Err("Unknown format code for object of type 'str'"),
+        }
+        if self.sign.is_some() {
+            return Err("Sign not allowed in string format specifier");
+        }
+        if self.alternate_form {
+            return Err("Alternate form (#) not allowed in string format specifier");
+        }
+        if self.align == Some(FormatAlign::AfterSign) {
+            return Err("'=' alignment not allowed in string format specifier");
+        }
+        let value = match self.precision {
+            Some(precision) => s.chars().take(precision).collect::<String>(),
+            None => s.to_string(),
+        };
+        Ok(self.format_sign_and_align(&value, "", FormatAlign::Left))
+    }
+
+    fn format_sign_and_align(
+        &self,
+        magnitude_string: &str,
+        sign_str: &str,
+        default_align: FormatAlign,
+    ) -> String {
+        let fill_char = self.fill.unwrap_or(' ');
+        let align = self.align.unwrap_or(default_align);
+
+        let num_chars = magnitude_string.chars().count();
+
         let fill_chars_needed: i32 = self.width.map_or(0, |w| {
             cmp::max(0, (w as i32) - (num_chars as i32) - (sign_str.len() as i32))
         });
-        Ok(match align {
+        match align {
             FormatAlign::Left => format!(
                 "{}{}{}",
                 sign_str,
@@ -421,7 +519,94 @@ impl FormatSpec {
                     left_fill_string, sign_str, magnitude_string, right_fill_string
                 )
             }
-        })
+        }
+    }
+}
+
+fn format_non_finite(magnitude: f64, upper: bool) -> Option<String> {
+    let text = if magnitude.is_nan() {
+        "nan"
+    } else if magnitude.is_infinite() {
+        "inf"
+    } else {
+        return None;
+    };
+    Some(if upper {
+        text.to_uppercase()
+    } else {
+        text.to_string()
+    })
+}
+
+fn format_fixed(precision: usize, magnitude: f64, upper: bool) -> String {
+    format_non_finite(magnitude, upper).unwrap_or_else(|| format!("{:.*}", precision, magnitude))
+}
+
+/// Format as `d.ddde+xx`, with at least two exponent digits like CPython.
+fn format_exponent(precision: usize, magnitude: f64, upper: bool) -> String {
+    if let Some(text) = format_non_finite(magnitude, upper) {
+        return text;
+    }
+    let rust_format = format!("{:.*e}", precision, magnitude);
+    let (mantissa, exponent) = rust_format.split_at(rust_format.find('e').unwrap());
+    let exponent: i32 = exponent[1..].parse().unwrap();
+    let sign = if exponent < 0 { '-' } else { '+' };
+    let e = if upper { 'E' } else { 'e' };
+    format!("{}{}{}{:02}", mantissa, e, sign, exponent.abs())
+}
+
+fn remove_trailing_zeros(text: String) -> String {
+    if !text.contains('.') {
+        return text;
+    }
+    text.trim_end_matches('0').trim_end_matches('.').to_string()
+}
+
+fn format_general(precision: usize, magnitude: f64, upper: bool, alternate_form: bool) -> String {
+    if let Some(text) = format_non_finite(magnitude, upper) {
+        return text;
+    }
+    let precision = cmp::max(precision, 1);
+    let exponent = if magnitude == 0.0 {
+        0
+    } else {
+        let rust_format = format!("{:.*e}", precision - 1, magnitude);
+        let exponent = &rust_format[rust_format.find('e').unwrap() + 1..];
+        exponent.parse::<i32>().unwrap()
+    };
+    if -4 <= exponent && exponent < precision as i32 {
+        let text = format!(
+            "{:.*}",
+            (precision as i32 - 1 - exponent) as usize,
+            magnitude
+        );
+        if alternate_form {
+            text
+        } else {
+            remove_trailing_zeros(text)
+        }
+    } else {
+        let text = format_exponent(precision - 1, magnitude, upper);
+        if alternate_form {
+            text
+        } else {
+            let (mantissa, exponent) = text.split_at(text.find(|c| c == 'e' || c == 'E').unwrap());
+            format!(
+                "{}{}",
+                remove_trailing_zeros(mantissa.to_string()),
+                exponent
+            )
+        }
+    }
+}
+
+fn format_repr(magnitude: f64) -> String {
+    if let Some(text) = format_non_finite(magnitude, false) {
+        text
+    } else if magnitude.fract() == 0.0 {
+        format!("{:.1}", magnitude)
+    } else {
+        magnitude.to_string()
     }
 }
 
@@ -690,6 +875,44 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_format_float() {
+        assert_eq!(
+            parse_format_spec("10.2f").format_float(3.14159),
+            Ok("      3.14".to_string())
+        );
+        assert_eq!(
+            parse_format_spec("e").format_float(1234.5),
+            Ok("1.234500e+03".to_string())
+        );
+        assert_eq!(
+            parse_format_spec("g").format_float(0.00001),
+            Ok("1e-05".to_string())
+        );
+        assert_eq!(
+            parse_format_spec("+.3").format_float(2.0),
+            Ok("+2.0".to_string())
+        );
+        assert_eq!(
+            parse_format_spec("<6").format_float(-1.5),
+            Ok("-1.5  ".to_string())
+        );
+        assert!(parse_format_spec("d").format_float(1.0).is_err());
+    }
+
+    #[test]
+    fn test_format_string() {
+        assert_eq!(
+            parse_format_spec("^7").format_string("abc"),
+            Ok("  abc  ".to_string())
+        );
+        assert_eq!(
+            parse_format_spec(".2s").format_string("abc"),
+            Ok("ab".to_string())
+        );
+        assert!(parse_format_spec("+").format_string("abc").is_err());
+    }
+
     #[test]
     fn test_format_parse() {
         let expected = Ok(FormatString {
@@ -1,31 +1,41 @@
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 use std::fmt;
+use std::fs;
 
 use crate::builtins;
 use crate::bytecode;
+use crate::exceptions;
 use crate::function::PyFuncArgs;
 use crate::obj::objbool;
-use crate::obj::objcode::PyCodeRef;
-use crate::obj::objdict::{PyDict, PyDictRef};
+use crate::obj::objbuiltinfunc::PyBuiltinFunction;
+use crate::obj::objcell::{PyCell, PyCellRef};
+use crate::obj::objcode::{
+    self, AttrCache, AttrCacheEntry, AttrLookup, ConstCache, GlobalCache, GlobalCacheEntry,
+    NameCache, PyCodeRef, SourceCache,
+};
+use crate::obj::objcoroutine::PyCoroutine;
+use crate::obj::objdict::PyDict;
+use crate::obj::objfunction::PyFunction;
+use crate::obj::objgenerator::PyGenerator;
+use crate::obj::objgenericalias;
 use crate::obj::objiter;
 use crate::obj::objlist;
 use crate::obj::objslice::PySlice;
 use crate::obj::objstr;
-use crate::obj::objstr::PyString;
-use crate::obj::objtuple::PyTuple;
+use crate::obj::objstr::{PyString, PyStringRef};
+use crate::obj::objtraceback::{PyTraceback, PyTracebackRef};
+use crate::obj::objtuple::{PyTuple, PyTupleRef};
 use crate::obj::objtype;
 use crate::obj::objtype::PyClassRef;
 use crate::pyobject::{
     IdProtocol, ItemProtocol, PyObjectRef, PyRef, PyResult, PyValue, TryFromObject, TypeProtocol,
 };
 use crate::scope::{NameProtocol, Scope};
-use crate::vm::VirtualMachine;
+use crate::sysmodule;
+use crate::vm::{TraceEvent, VirtualMachine};
 use indexmap::IndexMap;
 use itertools::Itertools;
 
-#[cfg(not(target_arch = "wasm32"))]
-use crate::stdlib::signal::check_signals;
-
 #[derive(Clone, Debug)]
 struct Block {
     /// The type of block.
@@ -43,13 +53,42 @@ enum BlockType {
     TryExcept {
         handler: bytecode::Label,
     },
+    Finally {
+        handler: bytecode::Label,
+    },
+    /// Active finally sequence
+    FinallyHandler {
+        reason: Option<UnwindReason>,
+    },
     With {
         end: bytecode::Label,
         context_manager: PyObjectRef,
     },
+    AsyncWith {
+        end: bytecode::Label,
+        context_manager: PyObjectRef,
+    },
     ExceptHandler,
 }
 
+/// The reason why we might be unwinding a block.
+/// This could be return of function, exception being
+/// raised, a break or continue being hit, etc..
+#[derive(Clone, Debug)]
+enum UnwindReason {
+    /// We are returning a value from a return statement.
+    Returning { value: PyObjectRef },
+
+    /// We hit an exception, so unwind any try-except and finally blocks.
+    Raising { exception: PyObjectRef },
+
+    /// We are unwinding blocks, since we hit break
+    Break,
+
+    /// We are unwinding blocks since we hit a continue statements.
+    Continue,
+}
+
 pub type FrameRef = PyRef<Frame>;
 
 pub struct Frame {
@@ -59,6 +98,15 @@ pub struct Frame {
     blocks: RefCell<Vec<Block>>,      // Block frames, for controlling loops and exceptions
     pub scope: Scope,                 // Variables
     pub lasti: RefCell<usize>,        // index of last instruction ran
+    pub trace: RefCell<Option<PyObjectRef>>, // local trace function (f_trace)
+    pub trace_opcodes: Cell<bool>,    // send `opcode` trace events (f_trace_opcodes)
+    const_cache: ConstCache,
+    attr_cache: AttrCache,
+    global_cache: GlobalCache,
+    source_cache: SourceCache,
+    name_cache: NameCache,
+    cells: Vec<PyCellRef>, // cellvars followed by freevars
+    fastlocals: RefCell<Vec<Option<PyObjectRef>>>, // values of the code's varnames
 }
 
 impl PyValue for Frame {
@@ -77,7 +125,12 @@ pub enum ExecutionResult {
 pub type FrameResult = PyResult<Option<ExecutionResult>>;
 
 impl Frame {
-    pub fn new(code: PyCodeRef, scope: Scope) -> Frame {
+    pub fn new(
+        code: PyCodeRef,
+        scope: Scope,
+        closure: Option<&PyTupleRef>,
+        vm: &VirtualMachine,
+    ) -> Frame {
         //populate the globals and locals
         //TODO: This is wrong, check https://github.com/nedbat/byterun/blob/31e6c4a8212c35b5157919abff43a7daa0f377c6/byterun/pyvm2.py#L95
         /*
@@ -89,74 +142,293 @@ impl Frame {
         // let locals = globals;
         // locals.extend(callargs);
 
+        // Cell variables start out with the value of the same-named local,
+        // which is how arguments captured by an inner function get their value.
+        let locals = scope.get_locals();
+        let cellvars = code.code.cellvars.iter().map(|name| {
+            let value = locals.get_item_option(name.as_str(), vm).unwrap_or(None);
+            PyCell::new(value).into_ref(vm)
+        });
+        let freevars = code.code.freevars.iter().enumerate().map(|(i, _)| {
+            closure
+                .and_then(|closure| closure.elements.get(i).cloned())
+                .and_then(|cell| cell.downcast::<PyCell>().ok())
+                .unwrap_or_else(|| PyCell::default().into_ref(vm))
+        });
+        let cells = cellvars.chain(freevars).collect();
+        let fastlocals = code
+            .code
+            .varnames
+            .iter()
+            .map(|name| locals.get_item_option(name.as_str(), vm).unwrap_or(None))
+            .collect();
+
         Frame {
             code: code.code.clone(),
-            stack: RefCell::new(vec![]),
+            stack: RefCell::new(Vec::with_capacity(code.code.max_stacksize)),
             blocks: RefCell::new(vec![]),
             // save the callargs as locals
             // globals: locals.clone(),
             scope,
             lasti: RefCell::new(0),
+            trace: RefCell::new(None),
+            trace_opcodes: Cell::new(false),
+            const_cache: code.const_cache.clone(),
+            attr_cache: code.attr_cache.clone(),
+            global_cache: code.global_cache.clone(),
+            source_cache: code.source_cache.clone(),
+            name_cache: code.name_cache.clone(),
+            cells,
+            fastlocals: RefCell::new(fastlocals),
         }
     }
 
     // #[cfg_attr(feature = "flame-it", flame("Frame"))]
-    pub fn run(&self, vm: &VirtualMachine) -> PyResult<ExecutionResult> {
+    pub fn run(
+        &self,
+        vm: &VirtualMachine,
+        sent_value: Option<PyObjectRef>,
+    ) -> PyResult<ExecutionResult> {
         flame_guard!(format!("Frame::run({})", self.code.obj_name));
 
-        let filename = &self.code.source_path.to_string();
+        // A value sent into a suspended generator is the result of its `yield`:
+        if let Some(value) = sent_value {
+            self.push_value(value);
+        }
 
-        // This is the name of the object being run:
-        let run_obj_name = &self.code.obj_name.to_string();
+        // A resumed frame carries on in the middle of the line it stopped at,
+        // which doesn't make for a new `line` event:
+        let mut traced_lineno = if *self.lasti.borrow() == 0 {
+            None
+        } else {
+            Some(self.get_lineno().row())
+        };
 
         // Execute until return or exception:
         loop {
-            let lineno = self.get_lineno();
-            let result = self.execute_instruction(vm);
-            match result {
-                Ok(None) => {}
-                Ok(Some(value)) => {
-                    break Ok(value);
+            if let Some(result) = self.run_instruction(vm, &mut traced_lineno)? {
+                break Ok(result);
+            }
+        }
+    }
+
+    /// Execute the next instruction, or the handler of the exception it raised.
+    /// `traced_lineno` is the line last reported to the trace function, if any.
+    fn run_instruction(
+        &self,
+        vm: &VirtualMachine,
+        traced_lineno: &mut Option<usize>,
+    ) -> FrameResult {
+        let lasti = *self.lasti.borrow();
+        let lineno = self.code.locations[lasti].clone();
+        // Fetch first, so the trace function sees the frame at this instruction
+        // the same way the instruction itself does.
+        let instruction = self.fetch_instruction();
+        let result = if *vm.use_tracing.borrow() {
+            self.trace_instruction(vm, lineno.row(), traced_lineno)
+                .and_then(|()| self.execute_instruction(vm, instruction))
+        } else {
+            self.execute_instruction(vm, instruction)
+        };
+        match result.and_then(|result| self.check_stack_limit(vm).map(|()| result)) {
+            Ok(result) => Ok(result),
+            // Instruction raised an exception
+            Err(exception) => self.handle_exception(vm, exception, lasti, &lineno),
+        }
+    }
+
+    /// Add the current position to the traceback of an exception raised at
+    /// `lasti`, and unwind the blocks to its handler.
+    fn handle_exception(
+        &self,
+        vm: &VirtualMachine,
+        exception: PyObjectRef,
+        lasti: usize,
+        lineno: &bytecode::Location,
+    ) -> FrameResult {
+        // 1. Extract traceback from exception's '__traceback__' attr.
+        // 2. Prepend a new traceback node with the current frame and position.
+        // 3. Unwind block stack till appropriate handler is found.
+        assert!(objtype::isinstance(
+            &exception,
+            &vm.ctx.exceptions.base_exception_type
+        ));
+        if self.is_reraise(vm, lasti, &exception) {
+            // The handled exception keeps the traceback it was caught with.
+            return self.unwind_exception(vm, exception, lasti, lineno);
+        }
+        let context = vm.get_attribute(exception.clone(), "__context__")?;
+        if vm.is_none(&context) {
+            self.chain_exception(vm, &exception)?;
+        }
+        let traceback = vm
+            .get_attribute(exception.clone(), "__traceback__")
+            .unwrap();
+        vm_trace!("Adding to traceback: {:?} {:?}", traceback, lineno);
+        let next = if vm.is_none(&traceback) {
+            None
+        } else {
+            Some(PyTracebackRef::try_from_object(vm, traceback)?)
+        };
+        let frame = vm
+            .current_frame()
+            .expect("running frame must be on the frame stack")
+            .clone();
+        let new_traceback = PyTraceback::new(next, frame, lasti, lineno.row());
+        let new_traceback = new_traceback.into_ref(vm).into_object();
+        vm.set_attr(&exception, "__traceback__", new_traceback.clone())?;
+        if *vm.use_tracing.borrow() {
+            let exc_info = vm.ctx.new_tuple(vec![
+                exception.class().into_object(),
+                exception.clone(),
+                new_traceback,
+            ]);
+            vm.trace_event(TraceEvent::Exception, exc_info)?;
+        }
+        // TODO: append line number to traceback when the exception escapes?
+        self.unwind_exception(vm, exception, lasti, lineno)
+    }
+
+    fn unwind_exception(
+        &self,
+        vm: &VirtualMachine,
+        exception: PyObjectRef,
+        lasti: usize,
+        lineno: &bytecode::Location,
+    ) -> FrameResult {
+        match self.unwind_blocks(
+            vm,
+            UnwindReason::Raising {
+                exception: exception.clone(),
+            },
+        ) {
+            // A handler run while unwinding, like `__exit__`, raised another
+            // exception, which the remaining blocks may still handle:
+            Err(raised) if !raised.is(&exception) => {
+                self.handle_exception(vm, raised, lasti, lineno)
+            }
+            result => result,
+        }
+    }
+
+    /// Send the `line` and `opcode` trace events due before the next instruction.
+    fn trace_instruction(
+        &self,
+        vm: &VirtualMachine,
+        row: usize,
+        traced_lineno: &mut Option<usize>,
+    ) -> PyResult<()> {
+        if *traced_lineno != Some(row) {
+            *traced_lineno = Some(row);
+            vm.trace_event(TraceEvent::Line, vm.get_none())?;
+        }
+        if self.trace_opcodes.get() {
+            vm.trace_event(TraceEvent::Opcode, vm.get_none())?;
+        }
+        Ok(())
+    }
+
+    /// Whether `exception` is raised again at `lasti` with its traceback already
+    /// at this frame: by a bare `raise` of the exception currently being handled,
+    /// or by the end of a finally clause which carries on raising it.
+    fn is_reraise(&self, vm: &VirtualMachine, lasti: usize, exception: &PyObjectRef) -> bool {
+        match self.code.instructions.get(lasti) {
+            Some(bytecode::Instruction::Raise { argc: 0 }) => vm
+                .current_exception()
+                .map_or(false, |current| current.is(exception)),
+            Some(bytecode::Instruction::EndFinally) => {
+                let traceback = vm.get_attribute(exception.clone(), "__traceback__");
+                match (traceback, vm.current_frame()) {
+                    (Ok(traceback), Some(frame)) => traceback
+                        .payload::<PyTraceback>()
+                        .map_or(false, |traceback| traceback.frame.is(&*frame)),
+                    _ => false,
                 }
-                // Instruction raised an exception
-                Err(exception) => {
-                    // 1. Extract traceback from exception's '__traceback__' attr.
-                    // 2. Add new entry with current execution position (filename, lineno, code_object) to traceback.
-                    // 3. Unwind block stack till appropriate handler is found.
-                    assert!(objtype::isinstance(
-                        &exception,
-                        &vm.ctx.exceptions.base_exception_type
-                    ));
-                    let traceback = vm
-                        .get_attribute(exception.clone(), "__traceback__")
-                        .unwrap();
-                    vm_trace!("Adding to traceback: {:?} {:?}", traceback, lineno);
-                    let raise_location = vm.ctx.new_tuple(vec![
-                        vm.ctx.new_str(filename.clone()),
-                        vm.ctx.new_int(lineno.row()),
-                        vm.ctx.new_str(run_obj_name.clone()),
-                    ]);
-                    objlist::PyListRef::try_from_object(vm, traceback)?.append(raise_location, vm);
-                    match self.unwind_exception(vm, exception) {
-                        None => {}
-                        Some(exception) => {
-                            // TODO: append line number to traceback?
-                            // traceback.append();
-                            break Err(exception);
-                        }
-                    }
+            }
+            _ => false,
+        }
+    }
+
+    pub fn throw(
+        &self,
+        vm: &VirtualMachine,
+        mut exception: PyObjectRef,
+    ) -> PyResult<ExecutionResult> {
+        // The exception is raised at the `yield` where the frame is suspended,
+        // after it is offered to the iterator which a `yield from` delegates to:
+        if let Some(end) = self.delegation_end() {
+            let delegate = self.last_value(vm)?;
+            match self.throw_into_delegate(vm, &delegate, &exception) {
+                Some(Ok(value)) => return Ok(ExecutionResult::Yield(value)),
+                Some(Err(err)) if objtype::isinstance(&err, &vm.ctx.exceptions.stop_iteration) => {
+                    // The delegate is done, so the `yield from` evaluates to its value:
+                    self.pop_value(vm)?;
+                    self.push_value(objiter::stop_iter_value(vm, &err)?);
+                    self.jump(vm, end)?;
+                    return self.run(vm, None);
                 }
+                Some(Err(err)) => exception = err,
+                None => {}
             }
         }
+        // Like an exception raised by the `yield` itself, it gets a traceback
+        // entry for this frame and is reported to the trace function:
+        let lasti = self.lasti.borrow().saturating_sub(1);
+        let lineno = self.code.locations[lasti].clone();
+        match self.handle_exception(vm, exception, lasti, &lineno) {
+            Ok(None) => self.run(vm, None),
+            Ok(Some(result)) => Ok(result),
+            Err(exception) => Err(exception),
+        }
     }
 
-    pub fn throw(&self, vm: &VirtualMachine, exception: PyObjectRef) -> PyResult<ExecutionResult> {
-        match self.unwind_exception(vm, exception) {
-            None => self.run(vm),
-            Some(exception) => Err(exception),
+    /// Where a suspended frame continues once the iterator which it delegates
+    /// to is done, if it is suspended at the `YieldValue` following a `Send`.
+    fn delegation_end(&self) -> Option<bytecode::Label> {
+        let lasti = *self.lasti.borrow();
+        let suspended_at = |back| {
+            lasti
+                .checked_sub(back)
+                .and_then(|offset| self.code.instructions.get(offset))
+        };
+        match (suspended_at(2), suspended_at(1)) {
+            (
+                Some(bytecode::Instruction::Send { target }),
+                Some(bytecode::Instruction::YieldValue),
+            ) => Some(*target),
+            _ => None,
         }
     }
 
+    /// Pass an exception thrown into the frame on to the iterator which its
+    /// suspended `yield from` delegates to, as PEP 380 asks: the delegate is
+    /// closed for a GeneratorExit, and has the exception thrown into it
+    /// otherwise. None means that the frame has to handle the exception itself,
+    /// because the delegate has no way to take it or has just been closed.
+    fn throw_into_delegate(
+        &self,
+        vm: &VirtualMachine,
+        delegate: &PyObjectRef,
+        exception: &PyObjectRef,
+    ) -> Option<PyResult> {
+        if objtype::isinstance(exception, &vm.ctx.exceptions.generator_exit) {
+            let close = vm.get_method(delegate.clone(), "close")?;
+            match close.and_then(|close| vm.invoke(&close, vec![])) {
+                Ok(_) => None,
+                Err(err) => Some(Err(err)),
+            }
+        } else {
+            let throw = vm.get_method(delegate.clone(), "throw")?;
+            Some(throw.and_then(|throw| vm.invoke(&throw, vec![exception.clone()])))
+        }
+    }
+
+    /// The instruction which will be executed next, at offset `lasti`, or None
+    /// if there is none left.
+    pub fn next_instruction(&self) -> Option<&bytecode::Instruction> {
+        self.code.instructions.get(*self.lasti.borrow())
+    }
+
     pub fn fetch_instruction(&self) -> &bytecode::Instruction {
         let ins2 = &self.code.instructions[*self.lasti.borrow()];
         *self.lasti.borrow_mut() += 1;
@@ -165,12 +437,13 @@ impl Frame {
 
     /// Execute a single instruction.
     #[allow(clippy::cognitive_complexity)]
-    fn execute_instruction(&self, vm: &VirtualMachine) -> FrameResult {
-        #[cfg(not(target_arch = "wasm32"))]
-        {
-            check_signals(vm);
-        }
-        let instruction = self.fetch_instruction();
+    fn execute_instruction(
+        &self,
+        vm: &VirtualMachine,
+        instruction: &bytecode::Instruction,
+    ) -> FrameResult {
+        #[cfg(feature = "vm-opcode-stats")]
+        vm.count_opcode(instruction);
 
         flame_guard!(format!("Frame::execute_instruction({:?})", instruction));
 
@@ -187,9 +460,9 @@ impl Frame {
             trace!("=======");
         }
 
-        match &instruction {
+        match instruction {
             bytecode::Instruction::LoadConst { ref value } => {
-                let obj = vm.ctx.unwrap_constant(value);
+                let obj = self.load_const(vm, value);
                 self.push_value(obj);
                 Ok(None)
             }
@@ -209,46 +482,79 @@ impl Frame {
                 ref scope,
             } => self.store_name(vm, name, scope),
             bytecode::Instruction::DeleteName { ref name } => self.delete_name(vm, name),
+            bytecode::Instruction::LoadFast { idx } => {
+                let value = self.fastlocals.borrow()[*idx].clone();
+                let value = value.ok_or_else(|| self.unbound_local_error(vm, *idx))?;
+                self.push_value(value);
+                Ok(None)
+            }
+            bytecode::Instruction::StoreFast { idx } => {
+                let value = self.pop_value(vm)?;
+                self.fastlocals.borrow_mut()[*idx] = Some(value);
+                Ok(None)
+            }
+            bytecode::Instruction::DeleteFast { idx } => {
+                let value = self.fastlocals.borrow_mut()[*idx].take();
+                if value.is_none() {
+                    return Err(self.unbound_local_error(vm, *idx));
+                }
+                Ok(None)
+            }
+            bytecode::Instruction::LoadDeref { ref name } => {
+                let value = self
+                    .get_cell(vm, name)?
+                    .get()
+                    .ok_or_else(|| self.unbound_cell_error(vm, name))?;
+                self.push_value(value);
+                Ok(None)
+            }
+            bytecode::Instruction::LoadClassDeref { ref name } => {
+                let value = match self.scope.get_locals().get_item_option(name.as_str(), vm)? {
+                    Some(value) => value,
+                    None => self
+                        .get_cell(vm, name)?
+                        .get()
+                        .ok_or_else(|| self.unbound_cell_error(vm, name))?,
+                };
+                self.push_value(value);
+                Ok(None)
+            }
+            bytecode::Instruction::StoreDeref { ref name } => {
+                let value = self.pop_value(vm)?;
+                self.get_cell(vm, name)?.set(Some(value));
+                Ok(None)
+            }
+            bytecode::Instruction::DeleteDeref { ref name } => {
+                let cell = self.get_cell(vm, name)?;
+                if cell.get().is_none() {
+                    return Err(self.unbound_cell_error(vm, name));
+                }
+                cell.set(None);
+                Ok(None)
+            }
+            bytecode::Instruction::LoadClosure { ref name } => {
+                self.push_value(self.get_cell(vm, name)?.clone().into_object());
+                Ok(None)
+            }
+            bytecode::Instruction::BinarySubscr => self.execute_binary_subscript(vm),
             bytecode::Instruction::StoreSubscript => self.execute_store_subscript(vm),
             bytecode::Instruction::DeleteSubscript => self.execute_delete_subscript(vm),
             bytecode::Instruction::Pop => {
                 // Pop value from stack and ignore.
-                self.pop_value();
+                self.pop_value(vm)?;
                 Ok(None)
             }
             bytecode::Instruction::Duplicate => {
                 // Duplicate top of stack
-                let value = self.pop_value();
+                let value = self.pop_value(vm)?;
                 self.push_value(value.clone());
                 self.push_value(value);
                 Ok(None)
             }
-            bytecode::Instruction::Rotate { amount } => {
-                // Shuffles top of stack amount down
-                if *amount < 2 {
-                    panic!("Can only rotate two or more values");
-                }
-
-                let mut values = Vec::new();
-
-                // Pop all values from stack:
-                for _ in 0..*amount {
-                    values.push(self.pop_value());
-                }
-
-                // Push top of stack back first:
-                self.push_value(values.remove(0));
-
-                // Push other value back in order:
-                values.reverse();
-                for value in values {
-                    self.push_value(value);
-                }
-                Ok(None)
-            }
+            bytecode::Instruction::Rotate { amount } => self.execute_rotate(vm, *amount),
             bytecode::Instruction::BuildString { size } => {
                 let s = self
-                    .pop_multiple(*size)
+                    .pop_multiple(vm, *size)?
                     .into_iter()
                     .map(|pyobj| objstr::get_value(&pyobj))
                     .collect::<String>();
@@ -257,13 +563,13 @@ impl Frame {
                 Ok(None)
             }
             bytecode::Instruction::BuildList { size, unpack } => {
-                let elements = self.get_elements(vm, *size, *unpack)?;
+                let elements = self.get_elements(vm, *size, *unpack, false)?;
                 let list_obj = vm.ctx.new_list(elements);
                 self.push_value(list_obj);
                 Ok(None)
             }
             bytecode::Instruction::BuildSet { size, unpack } => {
-                let elements = self.get_elements(vm, *size, *unpack)?;
+                let elements = self.get_elements(vm, *size, *unpack, false)?;
                 let py_obj = vm.ctx.new_set();
                 for item in elements {
                     vm.call_method(&py_obj, "add", vec![item])?;
@@ -271,42 +577,55 @@ impl Frame {
                 self.push_value(py_obj);
                 Ok(None)
             }
-            bytecode::Instruction::BuildTuple { size, unpack } => {
-                let elements = self.get_elements(vm, *size, *unpack)?;
+            bytecode::Instruction::BuildTuple {
+                size,
+                unpack,
+                for_call,
+            } => {
+                let elements = self.get_elements(vm, *size, *unpack, *for_call)?;
                 let list_obj = vm.ctx.new_tuple(elements);
                 self.push_value(list_obj);
                 Ok(None)
             }
-            bytecode::Instruction::BuildMap { size, unpack } => {
+            bytecode::Instruction::BuildMap {
+                size,
+                unpack,
+                for_call,
+            } => self.execute_build_map(vm, *size, *unpack, *for_call),
+            bytecode::Instruction::BuildConstKeyMap { size } => {
+                let keys = self.pop_typed::<PyTuple>(vm, "a tuple of keys")?;
+                let values = self.pop_multiple(vm, *size)?;
+                if keys.elements.len() != *size {
+                    return Err(vm.new_exception(
+                        vm.ctx.exceptions.system_error.clone(),
+                        format!(
+                            "corrupt bytecode in {}: {} keys for {} values",
+                            self.code.obj_name,
+                            keys.elements.len(),
+                            size
+                        ),
+                    ));
+                }
                 let map_obj = vm.ctx.new_dict();
-                if *unpack {
-                    for obj in self.pop_multiple(*size) {
-                        // Take all key-value pairs from the dict:
-                        let dict: PyDictRef =
-                            obj.downcast().expect("Need a dictionary to build a map.");
-                        for (key, value) in dict {
-                            map_obj.set_item(&key, value, vm).unwrap();
-                        }
-                    }
-                } else {
-                    for (key, value) in self.pop_multiple(2 * size).into_iter().tuples() {
-                        map_obj.set_item(&key, value, vm).unwrap();
-                    }
+                for (key, value) in keys.elements.iter().zip(values) {
+                    map_obj.set_item(key, value, vm)?;
                 }
-
                 self.push_value(map_obj.into_object());
                 Ok(None)
             }
             bytecode::Instruction::BuildSlice { size } => {
-                assert!(*size == 2 || *size == 3);
+                if *size != 2 && *size != 3 {
+                    let problem = format!("cannot build a slice from {} values", size);
+                    return Err(self.corrupt_bytecode(vm, &problem));
+                }
 
                 let step = if *size == 3 {
-                    Some(self.pop_value())
+                    Some(self.pop_value(vm)?)
                 } else {
                     None
                 };
-                let stop = self.pop_value();
-                let start = self.pop_value();
+                let stop = self.pop_value(vm)?;
+                let start = self.pop_value(vm)?;
 
                 let obj = PySlice {
                     start: Some(start),
@@ -318,21 +637,21 @@ impl Frame {
                 Ok(None)
             }
             bytecode::Instruction::ListAppend { i } => {
-                let list_obj = self.nth_value(*i);
-                let item = self.pop_value();
+                let list_obj = self.nth_value(vm, *i)?;
+                let item = self.pop_value(vm)?;
                 objlist::PyListRef::try_from_object(vm, list_obj)?.append(item, vm);
                 Ok(None)
             }
             bytecode::Instruction::SetAdd { i } => {
-                let set_obj = self.nth_value(*i);
-                let item = self.pop_value();
+                let set_obj = self.nth_value(vm, *i)?;
+                let item = self.pop_value(vm)?;
                 vm.call_method(&set_obj, "add", vec![item])?;
                 Ok(None)
             }
             bytecode::Instruction::MapAdd { i } => {
-                let dict_obj = self.nth_value(*i + 1);
-                let key = self.pop_value();
-                let value = self.pop_value();
+                let dict_obj = self.nth_value(vm, *i + 1)?;
+                let key = self.pop_value(vm)?;
+                let value = self.pop_value(vm)?;
                 vm.call_method(&dict_obj, "__setitem__", vec![key, value])?;
                 Ok(None)
             }
@@ -340,36 +659,49 @@ impl Frame {
                 self.execute_binop(vm, op, *inplace)
             }
             bytecode::Instruction::LoadAttr { ref name } => self.load_attr(vm, name),
+            bytecode::Instruction::LoadMethod { ref name } => self.load_method(vm, name),
             bytecode::Instruction::StoreAttr { ref name } => self.store_attr(vm, name),
             bytecode::Instruction::DeleteAttr { ref name } => self.delete_attr(vm, name),
             bytecode::Instruction::UnaryOperation { ref op } => self.execute_unop(vm, op),
             bytecode::Instruction::CompareOperation { ref op } => self.execute_compare(vm, op),
             bytecode::Instruction::ReturnValue => {
-                let value = self.pop_value();
-                if let Some(exc) = self.unwind_blocks(vm) {
-                    Err(exc)
-                } else {
-                    Ok(Some(ExecutionResult::Return(value)))
-                }
+                let value = self.pop_value(vm)?;
+                self.unwind_blocks(vm, UnwindReason::Returning { value })
             }
             bytecode::Instruction::YieldValue => {
-                let value = self.pop_value();
+                let value = self.pop_value(vm)?;
                 Ok(Some(ExecutionResult::Yield(value)))
             }
-            bytecode::Instruction::YieldFrom => {
+            bytecode::Instruction::Send { target } => {
                 // Value send into iterator:
-                self.pop_value();
-
-                let top_of_stack = self.last_value();
-                let next_obj = objiter::get_next_object(vm, &top_of_stack)?;
+                let value = self.pop_value(vm)?;
 
-                match next_obj {
-                    Some(value) => {
-                        // Set back program counter:
-                        *self.lasti.borrow_mut() -= 1;
-                        Ok(Some(ExecutionResult::Yield(value)))
+                let top_of_stack = self.last_value(vm)?;
+                let result = if let Some(coro) = top_of_stack.payload::<PyCoroutine>() {
+                    coro.send(value, vm)
+                } else if vm.is_none(&value) {
+                    objiter::call_next(vm, &top_of_stack)
+                } else {
+                    vm.get_attribute(top_of_stack.clone(), "send")
+                        .and_then(|send| vm.invoke(&send, vec![value]))
+                };
+                match result {
+                    Ok(value) => {
+                        // The `YieldValue` which follows yields it:
+                        self.push_value(value);
+                        Ok(None)
+                    }
+                    Err(err) => {
+                        if objtype::isinstance(&err, &vm.ctx.exceptions.stop_iteration) {
+                            // The exhausted iterator is replaced by the StopIteration value:
+                            self.pop_value(vm)?;
+                            self.push_value(objiter::stop_iter_value(vm, &err)?);
+                            self.jump(vm, *target)?;
+                            Ok(None)
+                        } else {
+                            Err(err)
+                        }
                     }
-                    None => Ok(None),
                 }
             }
             bytecode::Instruction::SetupLoop { start, end } => {
@@ -383,10 +715,50 @@ impl Frame {
                 self.push_block(BlockType::TryExcept { handler: *handler });
                 Ok(None)
             }
+            bytecode::Instruction::SetupFinally { handler } => {
+                self.push_block(BlockType::Finally { handler: *handler });
+                Ok(None)
+            }
+            bytecode::Instruction::EnterFinally => {
+                self.push_block(BlockType::FinallyHandler { reason: None });
+                Ok(None)
+            }
+            bytecode::Instruction::EndFinally => {
+                // Pop the finally handler from the stack, and recall
+                // what was the reason we were in this finally clause.
+                let block = self.pop_block();
+                if let Some(BlockType::FinallyHandler { reason }) = block.map(|block| block.typ) {
+                    if let Some(UnwindReason::Raising { .. }) = reason {
+                        vm.pop_exception().expect("Should have exception in stack");
+                    }
+                    if let Some(reason) = reason {
+                        self.unwind_blocks(vm, reason)
+                    } else {
+                        Ok(None)
+                    }
+                } else {
+                    Err(self.corrupt_bytecode(vm, "no finally handler to end"))
+                }
+            }
             bytecode::Instruction::SetupWith { end } => {
-                let context_manager = self.pop_value();
-                // Call enter:
-                let obj = vm.call_method(&context_manager, "__enter__", vec![])?;
+                let context_manager = self.pop_value(vm)?;
+                // Like CPython, make sure that `__exit__` can be called before
+                // calling `__enter__`. The block is only pushed once `__enter__`
+                // has succeeded, so an exception from it doesn't call `__exit__`.
+                let unsupported = || {
+                    format!(
+                        "'{}' object does not support the context manager protocol",
+                        context_manager.class().name
+                    )
+                };
+                let enter =
+                    vm.get_method_or_type_error(context_manager.clone(), "__enter__", unsupported)?;
+                if !objtype::class_has_attr(&context_manager.class(), "__exit__") {
+                    return Err(
+                        vm.new_type_error(format!("{} (missed __exit__ method)", unsupported()))
+                    );
+                }
+                let obj = vm.invoke(&enter, vec![])?;
                 self.push_block(BlockType::With {
                     end: *end,
                     context_manager: context_manager.clone(),
@@ -395,33 +767,169 @@ impl Frame {
                 Ok(None)
             }
             bytecode::Instruction::CleanupWith { end: end1 } => {
-                let block = self.pop_block().unwrap();
-                if let BlockType::With {
+                let block = self.pop_block();
+                if let Some(BlockType::With {
                     end: end2,
                     context_manager,
-                } = &block.typ
+                }) = block.map(|block| block.typ)
                 {
-                    debug_assert!(end1 == end2);
+                    debug_assert!(*end1 == end2);
                     self.call_context_manager_exit_no_exception(vm, &context_manager)?;
                 } else {
-                    unreachable!("Block stack is incorrect, expected a with block");
+                    return Err(self.corrupt_bytecode(vm, "no with block to clean up"));
                 }
 
                 Ok(None)
             }
+            bytecode::Instruction::BeforeAsyncWith => {
+                let context_manager = self.last_value(vm)?;
+                let enter = vm.call_method(&context_manager, "__aenter__", vec![])?;
+                self.push_value(enter);
+                Ok(None)
+            }
+            bytecode::Instruction::SetupAsyncWith { end } => {
+                let obj = self.pop_value(vm)?;
+                let context_manager = self.pop_value(vm)?;
+                self.push_block(BlockType::AsyncWith {
+                    end: *end,
+                    context_manager,
+                });
+                self.push_value(obj);
+                Ok(None)
+            }
+            bytecode::Instruction::CleanupAsyncWith => {
+                let block = self.pop_block();
+                if let Some(BlockType::AsyncWith {
+                    context_manager, ..
+                }) = block.map(|block| block.typ)
+                {
+                    self.push_block(BlockType::FinallyHandler { reason: None });
+                    let exit = self.call_context_manager_aexit(vm, &context_manager, None)?;
+                    self.push_value(exit);
+                } else {
+                    return Err(self.corrupt_bytecode(vm, "no async with block to clean up"));
+                }
+                Ok(None)
+            }
+            bytecode::Instruction::EndAsyncWith => {
+                let exit_result = self.pop_value(vm)?;
+                let block = self
+                    .current_block()
+                    .ok_or_else(|| self.corrupt_bytecode(vm, "no finally handler to end"))?;
+                if let BlockType::FinallyHandler {
+                    reason: Some(UnwindReason::Raising { .. }),
+                } = block.typ
+                {
+                    // If __aexit__ returned True, suppress the exception and continue execution.
+                    if objbool::boolval(vm, exit_result)? {
+                        self.pop_block();
+                        vm.pop_exception().expect("Should have exception in stack");
+                        self.push_block(BlockType::FinallyHandler { reason: None });
+                    }
+                }
+                Ok(None)
+            }
             bytecode::Instruction::PopBlock => {
-                self.pop_block().expect("no pop to block");
+                self.pop_block()
+                    .ok_or_else(|| self.corrupt_bytecode(vm, "no block to pop"))?;
                 Ok(None)
             }
+            bytecode::Instruction::GetAwaitable => {
+                let awaited = self.pop_value(vm)?;
+                let awaitable = if awaited.payload_is::<PyCoroutine>() {
+                    awaited
+                } else {
+                    let await_method =
+                        vm.get_method_or_type_error(awaited.clone(), "__await__", || {
+                            format!(
+                                "object {} can't be used in 'await' expression",
+                                awaited.class().name
+                            )
+                        })?;
+                    let iterator = vm.invoke(&await_method, vec![])?;
+                    if iterator.payload_is::<PyCoroutine>() {
+                        return Err(
+                            vm.new_type_error("__await__() returned a coroutine".to_string())
+                        );
+                    }
+                    if objtype::class_get_attr(&iterator.class(), "__next__").is_none() {
+                        return Err(vm.new_type_error(format!(
+                            "__await__() returned non-iterator of type '{}'",
+                            iterator.class().name
+                        )));
+                    }
+                    iterator
+                };
+                self.push_value(awaitable);
+                Ok(None)
+            }
+            bytecode::Instruction::GetAIter => {
+                let iterable = self.pop_value(vm)?;
+                let aiter_method =
+                    vm.get_method_or_type_error(iterable.clone(), "__aiter__", || {
+                        format!(
+                            "'async for' requires an object with __aiter__ method, got {}",
+                            iterable.class().name
+                        )
+                    })?;
+                let aiter = vm.invoke(&aiter_method, vec![])?;
+                self.push_value(aiter);
+                Ok(None)
+            }
+            bytecode::Instruction::GetANext => {
+                let aiter = self.last_value(vm)?;
+                let anext_method =
+                    vm.get_method_or_type_error(aiter.clone(), "__anext__", || {
+                        format!(
+                            "'async for' requires an iterator with __anext__ method, got {}",
+                            aiter.class().name
+                        )
+                    })?;
+                let awaitable = vm.invoke(&anext_method, vec![])?;
+                self.push_value(awaitable);
+                Ok(None)
+            }
+            bytecode::Instruction::EndAsyncFor => {
+                let exception = self.pop_value(vm)?;
+                if objtype::isinstance(&exception, &vm.ctx.exceptions.stop_async_iteration) {
+                    // End of async for loop, leave the handler and drop the iterator:
+                    self.pop_block();
+                    vm.pop_exception().expect("Should have exception in stack");
+                    self.pop_value(vm)?;
+                    Ok(None)
+                } else {
+                    Err(exception)
+                }
+            }
             bytecode::Instruction::GetIter => {
-                let iterated_obj = self.pop_value();
+                let iterated_obj = self.pop_value(vm)?;
                 let iter_obj = objiter::get_iter(vm, &iterated_obj)?;
                 self.push_value(iter_obj);
                 Ok(None)
             }
+            bytecode::Instruction::GetYieldFromIter => {
+                let iterable = self.pop_value(vm)?;
+                // A generator is delegated to as it is, and so is a coroutine,
+                // but only by a coroutine.
+                let iterator = if iterable.payload_is::<PyCoroutine>() {
+                    if !self.code.is_coroutine {
+                        return Err(vm.new_type_error(
+                            "cannot 'yield from' a coroutine object in a non-coroutine generator"
+                                .to_string(),
+                        ));
+                    }
+                    iterable
+                } else if iterable.payload_is::<PyGenerator>() {
+                    iterable
+                } else {
+                    objiter::get_iter(vm, &iterable)?
+                };
+                self.push_value(iterator);
+                Ok(None)
+            }
             bytecode::Instruction::ForIter { target } => {
                 // The top of stack contains the iterator, lets push it forward:
-                let top_of_stack = self.last_value();
+                let top_of_stack = self.last_value(vm)?;
                 let next_obj = objiter::get_next_object(vm, &top_of_stack);
 
                 // Check the next object:
@@ -432,15 +940,15 @@ impl Frame {
                     }
                     Ok(None) => {
                         // Pop iterator from stack:
-                        self.pop_value();
+                        self.pop_value(vm)?;
 
                         // End of for loop
-                        self.jump(*target);
+                        self.jump(vm, *target)?;
                         Ok(None)
                     }
                     Err(next_error) => {
                         // Pop iterator from stack:
-                        self.pop_value();
+                        self.pop_value(vm)?;
                         Err(next_error)
                     }
                 }
@@ -449,15 +957,15 @@ impl Frame {
             bytecode::Instruction::CallFunction { typ } => {
                 let args = match typ {
                     bytecode::CallType::Positional(count) => {
-                        let args: Vec<PyObjectRef> = self.pop_multiple(*count);
+                        let args: Vec<PyObjectRef> = self.pop_multiple(vm, *count)?;
                         PyFuncArgs {
                             args,
                             kwargs: IndexMap::new(),
                         }
                     }
                     bytecode::CallType::Keyword(count) => {
-                        let kwarg_names = self.pop_value();
-                        let args: Vec<PyObjectRef> = self.pop_multiple(*count);
+                        let kwarg_names = self.pop_value(vm)?;
+                        let args: Vec<PyObjectRef> = self.pop_multiple(vm, *count)?;
 
                         let kwarg_names = vm
                             .extract_elements(&kwarg_names)?
@@ -468,74 +976,101 @@ impl Frame {
                     }
                     bytecode::CallType::Ex(has_kwargs) => {
                         let kwargs = if *has_kwargs {
-                            let kw_dict: PyDictRef =
-                                self.pop_value().downcast().expect("Kwargs must be a dict.");
-                            kw_dict
-                                .into_iter()
-                                .map(|elem| (objstr::get_value(&elem.0), elem.1))
-                                .collect()
+                            let kw_obj = self.pop_value(vm)?;
+                            let mut kwargs = IndexMap::new();
+                            for (key, value) in self.mapping_items(vm, kw_obj, true)? {
+                                if !objtype::isinstance(&key, &vm.ctx.str_type()) {
+                                    return Err(
+                                        vm.new_type_error("keywords must be strings".to_string())
+                                    );
+                                }
+                                kwargs.insert(objstr::get_value(&key), value);
+                            }
+                            kwargs
                         } else {
                             IndexMap::new()
                         };
-                        let args = self.pop_value();
+                        let args = self.pop_value(vm)?;
                         let args = vm.extract_elements(&args)?;
                         PyFuncArgs { args, kwargs }
                     }
                 };
 
                 // Call function:
-                let func_ref = self.pop_value();
+                let func_ref = self.pop_value(vm)?;
                 let value = vm.invoke(&func_ref, args)?;
                 self.push_value(value);
                 Ok(None)
             }
+            bytecode::Instruction::CallMethod { amount } => {
+                let mut args = self.pop_multiple(vm, *amount)?;
+                let self_or_attr = self.pop_value(vm)?;
+                let func_ref = self.pop_value(vm)?;
+                let value = if vm.is_none(&func_ref) {
+                    vm.invoke(&self_or_attr, args)?
+                } else {
+                    args.insert(0, self_or_attr);
+                    vm.invoke(&func_ref, args)?
+                };
+                self.push_value(value);
+                Ok(None)
+            }
             bytecode::Instruction::Jump { target } => {
-                self.jump(*target);
+                self.jump(vm, *target)?;
                 Ok(None)
             }
             bytecode::Instruction::JumpIfTrue { target } => {
-                let obj = self.pop_value();
+                let obj = self.pop_value(vm)?;
                 let value = objbool::boolval(vm, obj)?;
                 if value {
-                    self.jump(*target);
+                    self.jump(vm, *target)?;
                 }
                 Ok(None)
             }
 
             bytecode::Instruction::JumpIfFalse { target } => {
-                let obj = self.pop_value();
+                let obj = self.pop_value(vm)?;
                 let value = objbool::boolval(vm, obj)?;
                 if !value {
-                    self.jump(*target);
+                    self.jump(vm, *target)?;
                 }
                 Ok(None)
             }
 
             bytecode::Instruction::JumpIfTrueOrPop { target } => {
-                let obj = self.last_value();
+                let obj = self.last_value(vm)?;
                 let value = objbool::boolval(vm, obj)?;
                 if value {
-                    self.jump(*target);
+                    self.jump(vm, *target)?;
                 } else {
-                    self.pop_value();
+                    self.pop_value(vm)?;
                 }
                 Ok(None)
             }
 
             bytecode::Instruction::JumpIfFalseOrPop { target } => {
-                let obj = self.last_value();
+                let obj = self.last_value(vm)?;
                 let value = objbool::boolval(vm, obj)?;
                 if !value {
-                    self.jump(*target);
+                    self.jump(vm, *target)?;
                 } else {
-                    self.pop_value();
+                    self.pop_value(vm)?;
                 }
                 Ok(None)
             }
 
+            bytecode::Instruction::LoadAssertionError => {
+                let assertion_error = vm.ctx.exceptions.assertion_error.clone();
+                self.push_value(assertion_error.into_object());
+                Ok(None)
+            }
             bytecode::Instruction::Raise { argc } => {
+                let traceback = match argc {
+                    3 => Some(self.pop_value(vm)?),
+                    _ => None,
+                };
                 let cause = match argc {
-                    2 => self.get_exception(vm, true)?,
+                    2 | 3 => self.get_exception(vm, true)?,
                     _ => vm.get_none(),
                 };
                 let exception = match argc {
@@ -548,57 +1083,48 @@ impl Frame {
                             ));
                         }
                     },
-                    1 | 2 => self.get_exception(vm, false)?,
-                    3 => panic!("Not implemented!"),
-                    _ => panic!("Invalid parameter for RAISE_VARARGS, must be between 0 to 3"),
-                };
-                let context = match argc {
-                    0 => vm.get_none(), // We have already got the exception,
-                    _ => match vm.current_exception() {
-                        Some(exc) => exc,
-                        None => vm.get_none(),
-                    },
+                    1 | 2 | 3 => self.get_exception(vm, false)?,
+                    _ => {
+                        let problem = format!("cannot raise with {} arguments", argc);
+                        return Err(self.corrupt_bytecode(vm, &problem));
+                    }
                 };
-                info!(
-                    "Exception raised: {:?} with cause: {:?} and context: {:?}",
-                    exception, cause, context
-                );
-                vm.set_attr(&exception, vm.new_str("__cause__".to_string()), cause)?;
-                vm.set_attr(&exception, vm.new_str("__context__".to_string()), context)?;
+                info!("Exception raised: {:?} with cause: {:?}", exception, cause);
+                if *argc >= 2 {
+                    // `raise ... from ...` sets the cause and hides the context:
+                    vm.set_attr(&exception, "__cause__", cause)?;
+                    vm.set_attr(&exception, "__suppress_context__", vm.new_bool(true))?;
+                }
+                if *argc >= 1 {
+                    // A bare re-raise keeps the chain the exception already has.
+                    self.chain_exception(vm, &exception)?;
+                }
+                if let Some(traceback) = traceback {
+                    exceptions::set_traceback(vm, &exception, traceback)?;
+                }
                 Err(exception)
             }
 
-            bytecode::Instruction::Break => {
-                let block = self.unwind_loop(vm);
-                if let BlockType::Loop { end, .. } = block.typ {
-                    self.pop_block();
-                    self.jump(end);
-                } else {
-                    unreachable!()
-                }
-                Ok(None)
-            }
+            bytecode::Instruction::Break => self.unwind_blocks(vm, UnwindReason::Break),
             bytecode::Instruction::Pass => {
                 // Ah, this is nice, just relax!
                 Ok(None)
             }
-            bytecode::Instruction::Continue => {
-                let block = self.unwind_loop(vm);
-                if let BlockType::Loop { start, .. } = block.typ {
-                    self.jump(start);
-                } else {
-                    unreachable!();
+            bytecode::Instruction::Continue => self.unwind_blocks(vm, UnwindReason::Continue),
+            bytecode::Instruction::PrintExpr => {
+                let expr = self.pop_value(vm)?;
+                match vm.get_attribute(vm.sys_module.clone(), "displayhook") {
+                    Ok(displayhook) => {
+                        vm.invoke(&displayhook, vec![expr])?;
+                    }
+                    Err(_) => sysmodule::sys_displayhook(expr, vm)?,
                 }
                 Ok(None)
             }
-            bytecode::Instruction::PrintExpr => {
-                let expr = self.pop_value();
-                if !expr.is(&vm.get_none()) {
-                    let repr = vm.to_repr(&expr)?;
-                    // TODO: implement sys.displayhook
-                    if let Ok(ref print) = vm.get_attribute(vm.builtins.clone(), "print") {
-                        vm.invoke(print, vec![repr.into_object()])?;
-                    }
+            bytecode::Instruction::SetupAnnotations => {
+                let locals = self.scope.get_locals();
+                if !locals.contains_key("__annotations__", vm) {
+                    locals.set_item("__annotations__", vm.ctx.new_dict().into_object(), vm)?;
                 }
                 Ok(None)
             }
@@ -607,10 +1133,17 @@ impl Frame {
                 Ok(None)
             }
             bytecode::Instruction::UnpackSequence { size } => {
-                let value = self.pop_value();
+                let value = self.pop_value(vm)?;
                 let elements = vm.extract_elements(&value)?;
-                if elements.len() != *size {
-                    Err(vm.new_value_error("Wrong number of values to unpack".to_string()))
+                if elements.len() > *size {
+                    Err(vm
+                        .new_value_error(format!("too many values to unpack (expected {})", size)))
+                } else if elements.len() < *size {
+                    Err(vm.new_value_error(format!(
+                        "not enough values to unpack (expected {}, got {})",
+                        size,
+                        elements.len()
+                    )))
                 } else {
                     for element in elements.into_iter().rev() {
                         self.push_value(element);
@@ -619,12 +1152,12 @@ impl Frame {
                 }
             }
             bytecode::Instruction::UnpackEx { before, after } => {
-                let value = self.pop_value();
+                let value = self.pop_value(vm)?;
                 let elements = vm.extract_elements(&value)?;
                 let min_expected = *before + *after;
                 if elements.len() < min_expected {
                     Err(vm.new_value_error(format!(
-                        "Not enough values to unpack (expected at least {}, got {}",
+                        "not enough values to unpack (expected at least {}, got {})",
                         min_expected,
                         elements.len()
                     )))
@@ -654,39 +1187,43 @@ impl Frame {
                 }
             }
             bytecode::Instruction::Unpack => {
-                let value = self.pop_value();
+                let value = self.pop_value(vm)?;
                 let elements = vm.extract_elements(&value)?;
                 for element in elements.into_iter().rev() {
                     self.push_value(element);
                 }
                 Ok(None)
             }
-            bytecode::Instruction::FormatValue { conversion, spec } => {
+            bytecode::Instruction::FormatValue { conversion } => {
                 use bytecode::ConversionFlag::*;
+                let spec = self.pop_value(vm)?;
                 let value = match conversion {
-                    Some(Str) => vm.to_str(&self.pop_value())?.into_object(),
-                    Some(Repr) => vm.to_repr(&self.pop_value())?.into_object(),
-                    Some(Ascii) => self.pop_value(), // TODO
-                    None => self.pop_value(),
+                    Some(Str) => vm.to_str(&self.pop_value(vm)?)?.into_object(),
+                    Some(Repr) => vm.to_repr(&self.pop_value(vm)?)?.into_object(),
+                    Some(Ascii) => vm.new_str(objstr::ascii(&self.pop_value(vm)?, vm)?),
+                    None => self.pop_value(vm)?,
                 };
 
-                let spec = vm.new_str(spec.clone());
                 let formatted = vm.call_method(&value, "__format__", vec![spec])?;
                 self.push_value(formatted);
                 Ok(None)
             }
             bytecode::Instruction::PopException {} => {
-                let block = self.pop_block().unwrap(); // this asserts that the block is_some.
-                if let BlockType::ExceptHandler = block.typ {
+                let block = self.pop_block();
+                if let Some(BlockType::ExceptHandler) = block.map(|block| block.typ) {
                     vm.pop_exception().expect("Should have exception in stack");
                     Ok(None)
                 } else {
-                    panic!("Block type must be ExceptHandler here.")
+                    Err(self.corrupt_bytecode(vm, "no except handler to pop"))
                 }
             }
             bytecode::Instruction::Reverse { amount } => {
                 let mut stack = self.stack.borrow_mut();
                 let stack_len = stack.len();
+                if *amount > stack_len {
+                    drop(stack);
+                    return Err(self.stack_underflow(vm, *amount));
+                }
                 stack[stack_len - amount..stack_len].reverse();
                 Ok(None)
             }
@@ -699,11 +1236,21 @@ impl Frame {
         vm: &VirtualMachine,
         size: usize,
         unpack: bool,
+        for_call: bool,
     ) -> PyResult<Vec<PyObjectRef>> {
-        let elements = self.pop_multiple(size);
+        let elements = self.pop_multiple(vm, size)?;
         if unpack {
             let mut result: Vec<PyObjectRef> = vec![];
             for element in elements {
+                if for_call
+                    && !objtype::class_has_attr(&element.class(), "__iter__")
+                    && !objtype::class_has_attr(&element.class(), "__getitem__")
+                {
+                    return Err(vm.new_type_error(format!(
+                        "argument after * must be an iterable, not {}",
+                        element.class().name
+                    )));
+                }
                 result.extend(vm.extract_elements(&element)?);
             }
             Ok(result)
@@ -733,133 +1280,238 @@ impl Frame {
 
     #[cfg_attr(feature = "flame-it", flame("Frame"))]
     fn import_from(&self, vm: &VirtualMachine, name: &str) -> FrameResult {
-        let module = self.last_value();
-        // Load attribute, and transform any error into import error.
-        let obj = vm
-            .get_attribute(module, name)
-            .map_err(|_| vm.new_import_error(format!("cannot import name '{}'", name)))?;
+        let module = self.last_value(vm)?;
+        // Only a missing attribute is an import error, any other error is raised as it is.
+        let obj = match vm.get_attribute(module.clone(), name) {
+            Ok(obj) => obj,
+            Err(err) if objtype::isinstance(&err, &vm.ctx.exceptions.attribute_error) => {
+                self.import_submodule_from(vm, &module, name)?
+            }
+            Err(err) => return Err(err),
+        };
         self.push_value(obj);
         Ok(None)
     }
 
-    #[cfg_attr(feature = "flame-it", flame("Frame"))]
-    fn import_star(&self, vm: &VirtualMachine) -> FrameResult {
-        let module = self.pop_value();
-
-        // Grab all the names from the module and put them in the context
-        if let Some(dict) = &module.dict {
-            for (k, v) in dict {
-                let k = vm.to_str(&k)?;
-                let k = k.as_str();
-                if !k.starts_with('_') {
-                    self.scope.store_name(&vm, k, v);
-                }
+    /// Find a submodule which is not set as an attribute of its package yet,
+    /// as happens in circular imports.
+    fn import_submodule_from(
+        &self,
+        vm: &VirtualMachine,
+        module: &PyObjectRef,
+        name: &str,
+    ) -> PyResult {
+        let module_name = vm
+            .get_attribute(module.clone(), "__name__")
+            .ok()
+            .and_then(|module_name| PyStringRef::try_from_object(vm, module_name).ok());
+        let module_name = match module_name {
+            Some(module_name) => module_name,
+            None => {
+                return Err(vm.new_import_error(format!(
+                    "cannot import name '{}' from '<unknown module name>'",
+                    name
+                )))
             }
+        };
+        let sys_modules = vm.get_attribute(vm.sys_module.clone(), "modules")?;
+        let full_name = format!("{}.{}", module_name.as_str(), name);
+        if let Ok(submodule) = sys_modules.get_item(full_name.as_str(), vm) {
+            return Ok(submodule);
         }
-        Ok(None)
+
+        let location = match vm.get_attribute(module.clone(), "__file__") {
+            Ok(path) if !vm.is_none(&path) => vm.to_str(&path)?.as_str().to_string(),
+            _ => "unknown location".to_string(),
+        };
+        let initializing = match vm.get_attribute(module.clone(), "__spec__") {
+            Ok(spec) => match vm.get_attribute(spec, "_initializing") {
+                Ok(initializing) => objbool::boolval(vm, initializing)?,
+                Err(_) => false,
+            },
+            Err(_) => false,
+        };
+        Err(vm.new_import_error(if initializing {
+            format!(
+                "cannot import name '{}' from partially initialized module '{}' (most likely due to a circular import) ({})",
+                name,
+                module_name.as_str(),
+                location
+            )
+        } else {
+            format!(
+                "cannot import name '{}' from '{}' ({})",
+                name,
+                module_name.as_str(),
+                location
+            )
+        }))
     }
 
-    // Unwind all blocks:
     #[cfg_attr(feature = "flame-it", flame("Frame"))]
-    fn unwind_blocks(&self, vm: &VirtualMachine) -> Option<PyObjectRef> {
-        while let Some(block) = self.pop_block() {
-            match block.typ {
-                BlockType::Loop { .. } => {}
-                BlockType::TryExcept { .. } => {
-                    // TODO: execute finally handler
+    fn import_star(&self, vm: &VirtualMachine) -> FrameResult {
+        let module = self.pop_value(vm)?;
+
+        // Grab all the names from the module and put them in the context
+        if let Some(dict) = &module.dict {
+            // A module's `__all__` lists exactly the public names, underscore or not:
+            if let Some(all) = dict.get_item_option("__all__", vm)? {
+                for name in vm.extract_elements(&all)? {
+                    let name = PyStringRef::try_from_object(vm, name)?;
+                    let value = vm.get_attribute(module.clone(), name.clone())?;
+                    self.scope.store_name(&vm, name.as_str(), value);
                 }
-                BlockType::With {
-                    context_manager, ..
-                } => {
-                    match self.call_context_manager_exit_no_exception(vm, &context_manager) {
-                        Ok(..) => {}
-                        Err(exc) => {
-                            // __exit__ went wrong,
-                            return Some(exc);
-                        }
+            } else {
+                for (k, v) in dict {
+                    let k = vm.to_str(&k)?;
+                    let k = k.as_str();
+                    if !k.starts_with('_') {
+                        self.scope.store_name(&vm, k, v);
                     }
                 }
-                BlockType::ExceptHandler => {
-                    vm.pop_exception().expect("Should have exception in stack");
-                }
             }
         }
-
-        None
+        Ok(None)
     }
 
+    /// Unwind blocks.
+    /// The reason for unwinding gives a hint on what to do when
+    /// unwinding a block.
+    /// Optionally returns an exception.
     #[cfg_attr(feature = "flame-it", flame("Frame"))]
-    fn unwind_loop(&self, vm: &VirtualMachine) -> Block {
-        loop {
-            let block = self.current_block().expect("not in a loop");
+    fn unwind_blocks(&self, vm: &VirtualMachine, reason: UnwindReason) -> FrameResult {
+        // First unwind all existing blocks on the block stack:
+        while let Some(block) = self.current_block() {
             match block.typ {
-                BlockType::Loop { .. } => break block,
-                BlockType::TryExcept { .. } => {
-                    // TODO: execute finally handler
-                }
-                BlockType::With {
-                    context_manager, ..
-                } => match self.call_context_manager_exit_no_exception(vm, &context_manager) {
-                    Ok(..) => {}
-                    Err(exc) => {
-                        panic!("Exception in with __exit__ {:?}", exc);
+                BlockType::Loop { start, end } => match &reason {
+                    UnwindReason::Break => {
+                        self.pop_block();
+                        self.jump(vm, end)?;
+                        return Ok(None);
+                    }
+                    UnwindReason::Continue => {
+                        self.jump(vm, start)?;
+                        return Ok(None);
+                    }
+                    _ => {
+                        self.pop_block();
                     }
                 },
-                BlockType::ExceptHandler => {
-                    vm.pop_exception().expect("Should have exception in stack");
+                BlockType::Finally { handler } => {
+                    self.pop_block();
+                    // An exception passing through a finally clause is being handled by it:
+                    if let UnwindReason::Raising { exception } = &reason {
+                        vm.push_exception(exception.clone());
+                    }
+                    self.push_block(BlockType::FinallyHandler {
+                        reason: Some(reason.clone()),
+                    });
+                    self.jump(vm, handler)?;
+                    return Ok(None);
                 }
-            }
-
-            self.pop_block();
-        }
-    }
-
-    #[cfg_attr(feature = "flame-it", flame("Frame"))]
-    fn unwind_exception(&self, vm: &VirtualMachine, exc: PyObjectRef) -> Option<PyObjectRef> {
-        // unwind block stack on exception and find any handlers:
-        while let Some(block) = self.pop_block() {
-            match block.typ {
                 BlockType::TryExcept { handler } => {
-                    self.push_block(BlockType::ExceptHandler {});
-                    self.push_value(exc.clone());
-                    vm.push_exception(exc);
-                    self.jump(handler);
-                    return None;
+                    self.pop_block();
+                    if let UnwindReason::Raising { exception } = &reason {
+                        self.push_block(BlockType::ExceptHandler {});
+                        self.push_value(exception.clone());
+                        vm.push_exception(exception.clone());
+                        self.jump(vm, handler)?;
+                        return Ok(None);
+                    }
                 }
                 BlockType::With {
-                    end,
                     context_manager,
+                    end,
                 } => {
-                    match self.call_context_manager_exit(vm, &context_manager, exc.clone()) {
-                        Ok(exit_result_obj) => {
-                            match objbool::boolval(vm, exit_result_obj) {
-                                // If __exit__ method returned True, suppress the exception and continue execution.
-                                Ok(suppress_exception) => {
-                                    if suppress_exception {
-                                        self.jump(end);
-                                        return None;
-                                    } else {
-                                        // go on with the stack unwinding.
+                    self.pop_block();
+                    match &reason {
+                        UnwindReason::Raising { exception } => {
+                            // The exception is being handled while `__exit__` runs:
+                            vm.push_exception(exception.clone());
+                            let exit_result = self.call_context_manager_exit(
+                                vm,
+                                &context_manager,
+                                exception.clone(),
+                            );
+                            vm.pop_exception();
+                            match exit_result {
+                                Ok(exit_result_obj) => {
+                                    match objbool::boolval(vm, exit_result_obj) {
+                                        // If __exit__ method returned True, suppress the exception and continue execution.
+                                        Ok(suppress_exception) => {
+                                            if suppress_exception {
+                                                self.jump(vm, end)?;
+                                                return Ok(None);
+                                            } else {
+                                                // go on with the stack unwinding.
+                                            }
+                                        }
+                                        Err(exit_exc) => {
+                                            return Err(exit_exc);
+                                        }
                                     }
                                 }
                                 Err(exit_exc) => {
-                                    return Some(exit_exc);
+                                    let context =
+                                        vm.get_attribute(exit_exc.clone(), "__context__")?;
+                                    if vm.is_none(&context) && !exit_exc.is(exception) {
+                                        vm.set_attr(&exit_exc, "__context__", exception.clone())?;
+                                    }
+                                    return Err(exit_exc);
                                 }
                             }
                         }
-                        Err(exit_exc) => {
-                            // TODO: what about original exception?
-                            return Some(exit_exc);
+                        _ => {
+                            // A break, continue or return leaves the block without
+                            // an exception, so there is nothing for the result of
+                            // `__exit__` to suppress; only an error from it matters.
+                            self.call_context_manager_exit_no_exception(vm, &context_manager)?;
+                        }
+                    }
+                }
+                BlockType::AsyncWith {
+                    context_manager,
+                    end,
+                } => {
+                    self.pop_block();
+                    // The code at the end awaits `__aexit__`, and then carries
+                    // on the unwinding with `EndFinally`, just like a finally clause.
+                    let exception = match &reason {
+                        UnwindReason::Raising { exception } => {
+                            vm.push_exception(exception.clone());
+                            Some(exception.clone())
                         }
+                        _ => None,
+                    };
+                    self.push_block(BlockType::FinallyHandler {
+                        reason: Some(reason.clone()),
+                    });
+                    let exit = self.call_context_manager_aexit(vm, &context_manager, exception)?;
+                    self.push_value(exit);
+                    self.jump(vm, end)?;
+                    return Ok(None);
+                }
+                BlockType::FinallyHandler { reason } => {
+                    self.pop_block();
+                    if let Some(UnwindReason::Raising { .. }) = reason {
+                        vm.pop_exception().expect("Should have exception in stack");
                     }
                 }
-                BlockType::Loop { .. } => {}
                 BlockType::ExceptHandler => {
+                    self.pop_block();
                     vm.pop_exception().expect("Should have exception in stack");
                 }
             }
         }
-        Some(exc)
+
+        // We do not have any more blocks to unwind. Inspect the reason we are here:
+        match reason {
+            UnwindReason::Raising { exception } => Err(exception),
+            UnwindReason::Returning { value } => Ok(Some(ExecutionResult::Return(value))),
+            UnwindReason::Break | UnwindReason::Continue => {
+                Err(self.corrupt_bytecode(vm, "'break' or 'continue' outside a loop"))
+            }
+        }
     }
 
     fn call_context_manager_exit_no_exception(
@@ -867,12 +1519,12 @@ impl Frame {
         vm: &VirtualMachine,
         context_manager: &PyObjectRef,
     ) -> PyResult {
-        // TODO: do we want to put the exit call on the stack?
-        // TODO: what happens when we got an error during execution of __exit__?
-        vm.call_method(
+        self.call_exit(
+            vm,
             context_manager,
-            "__exit__",
-            vec![vm.ctx.none(), vm.ctx.none(), vm.ctx.none()],
+            vm.get_none(),
+            vm.get_none(),
+            vm.get_none(),
         )
     }
 
@@ -881,36 +1533,159 @@ impl Frame {
         vm: &VirtualMachine,
         context_manager: &PyObjectRef,
         exc: PyObjectRef,
+    ) -> PyResult {
+        let exc_type = exc.class().into_object();
+        let exc_tb = vm.get_attribute(exc.clone(), "__traceback__")?;
+        self.call_exit(vm, context_manager, exc_type, exc, exc_tb)
+    }
+
+    fn call_exit(
+        &self,
+        vm: &VirtualMachine,
+        context_manager: &PyObjectRef,
+        exc_type: PyObjectRef,
+        exc_val: PyObjectRef,
+        exc_tb: PyObjectRef,
     ) -> PyResult {
         // TODO: do we want to put the exit call on the stack?
         // TODO: what happens when we got an error during execution of __exit__?
-        let exc_type = exc.class().into_object();
-        let exc_val = exc.clone();
-        let exc_tb = vm.ctx.none(); // TODO: retrieve traceback?
         vm.call_method(context_manager, "__exit__", vec![exc_type, exc_val, exc_tb])
     }
 
+    fn call_context_manager_aexit(
+        &self,
+        vm: &VirtualMachine,
+        context_manager: &PyObjectRef,
+        exc: Option<PyObjectRef>,
+    ) -> PyResult {
+        let args = match exc {
+            Some(exc) => {
+                let exc_type = exc.class().into_object();
+                let exc_tb = vm.get_attribute(exc.clone(), "__traceback__")?;
+                vec![exc_type, exc, exc_tb]
+            }
+            None => vec![vm.get_none(), vm.get_none(), vm.get_none()],
+        };
+        vm.call_method(context_manager, "__aexit__", args)
+    }
+
     fn store_name(
         &self,
         vm: &VirtualMachine,
         name: &str,
         name_scope: &bytecode::NameScope,
     ) -> FrameResult {
-        let obj = self.pop_value();
+        let obj = self.pop_value(vm)?;
         match name_scope {
             bytecode::NameScope::Global => {
-                self.scope.store_global(vm, name, obj);
+                self.scope
+                    .globals
+                    .set_item(&self.interned_name(vm, name), obj, vm)?;
             }
             bytecode::NameScope::NonLocal => {
                 self.scope.store_cell(vm, name, obj);
             }
             bytecode::NameScope::Local => {
-                self.scope.store_name(vm, name, obj);
+                self.scope
+                    .get_locals()
+                    .set_item(&self.interned_name(vm, name), obj, vm)?;
             }
         }
         Ok(None)
     }
 
+    fn unbound_local_error(&self, vm: &VirtualMachine, idx: usize) -> PyObjectRef {
+        vm.new_exception(
+            vm.ctx.exceptions.unbound_local_error.clone(),
+            format!(
+                "local variable '{}' referenced before assignment",
+                self.code.varnames[idx]
+            ),
+        )
+    }
+
+    /// An empty cell is a local variable of this frame when it is one of its
+    /// `cellvars`, and a variable of an enclosing scope otherwise.
+    fn unbound_cell_error(&self, vm: &VirtualMachine, name: &str) -> PyObjectRef {
+        if self.code.cellvars.iter().any(|cell_name| cell_name == name) {
+            vm.new_exception(
+                vm.ctx.exceptions.unbound_local_error.clone(),
+                format!("local variable '{}' referenced before assignment", name),
+            )
+        } else {
+            vm.new_name_error(format!(
+                "free variable '{}' referenced before assignment in enclosing scope",
+                name
+            ))
+        }
+    }
+
+    /// Copy the fast locals and the cell variables into the locals dict, for
+    /// code which inspects the locals by name, like `locals()` and `eval`. Like
+    /// CPython, a class body leaves out the free variables it takes from the
+    /// enclosing function, which would otherwise become class attributes.
+    pub fn fast_to_locals(&self, vm: &VirtualMachine) -> PyResult<()> {
+        let locals = self.scope.get_locals();
+        let fastlocals = self.fastlocals.borrow();
+        let fast = self.code.varnames.iter().zip(fastlocals.iter().cloned());
+        let cell_count = if self.code.is_class_body {
+            self.code.cellvars.len()
+        } else {
+            self.cells.len()
+        };
+        let cells = self
+            .code
+            .cellvars
+            .iter()
+            .chain(self.code.freevars.iter())
+            .zip(self.cells.iter().map(|cell| cell.get()))
+            .take(cell_count);
+        for (name, value) in fast.chain(cells) {
+            match value {
+                Some(value) => {
+                    locals.set_item(name.as_str(), value, vm)?;
+                }
+                None => {
+                    if locals.contains_key(name.as_str(), vm) {
+                        locals.del_item(name.as_str(), vm)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Copy the locals dict back into the fast locals, after code which may have
+    /// changed it by name, like a trace function writing to `f_locals`. A name
+    /// missing from the dict unbinds its local.
+    pub fn locals_to_fast(&self, vm: &VirtualMachine) -> PyResult<()> {
+        let locals = self.scope.get_locals();
+        let values = self
+            .code
+            .varnames
+            .iter()
+            .map(|name| locals.get_item_option(name.as_str(), vm))
+            .collect::<PyResult<Vec<_>>>()?;
+        for (slot, value) in self.fastlocals.borrow_mut().iter_mut().zip(values) {
+            *slot = value;
+        }
+        Ok(())
+    }
+
+    fn get_cell(&self, vm: &VirtualMachine, name: &str) -> PyResult<&PyCellRef> {
+        let position = self
+            .code
+            .cellvars
+            .iter()
+            .chain(self.code.freevars.iter())
+            .position(|cell_name| cell_name == name)
+            .ok_or_else(|| {
+                let problem = format!("'{}' is not a cell or free variable", name);
+                self.corrupt_bytecode(vm, &problem)
+            })?;
+        Ok(&self.cells[position])
+    }
+
     fn delete_name(&self, vm: &VirtualMachine, name: &str) -> FrameResult {
         match self.scope.delete_name(vm, name) {
             Ok(_) => Ok(None),
@@ -926,7 +1701,7 @@ impl Frame {
         name_scope: &bytecode::NameScope,
     ) -> FrameResult {
         let optional_value = match name_scope {
-            bytecode::NameScope::Global => self.scope.load_global(vm, name),
+            bytecode::NameScope::Global => self.load_global(vm, name)?,
             bytecode::NameScope::NonLocal => self.scope.load_cell(vm, name),
             bytecode::NameScope::Local => self.scope.load_name(&vm, name),
         };
@@ -942,26 +1717,176 @@ impl Frame {
         Ok(None)
     }
 
+    /// Look a global name up like `Scope::load_global`, re-using the value found
+    /// the last time this instruction ran if neither the globals nor the builtins
+    /// have changed since.
+    fn load_global(&self, vm: &VirtualMachine, name: &str) -> PyResult<Option<PyObjectRef>> {
+        let globals = &self.scope.globals;
+        let builtins = match vm.builtins.dict {
+            Some(ref builtins) if globals.class().is(&vm.ctx.dict_type()) => builtins,
+            _ => return Ok(self.scope.load_global(vm, name)),
+        };
+        let offset = *self.lasti.borrow();
+        let cached = self
+            .global_cache
+            .borrow()
+            .get(&offset)
+            .and_then(|entry| entry.lookup(globals, builtins));
+        if cached.is_some() {
+            return Ok(cached);
+        }
+        let value = match globals.get_item_option(name, vm)? {
+            Some(value) => value,
+            None => match builtins.get_item_option(name, vm)? {
+                Some(value) => value,
+                None => return Ok(self.scope.load_global(vm, name)),
+            },
+        };
+        self.global_cache
+            .borrow_mut()
+            .insert(offset, GlobalCacheEntry::new(&value, globals, builtins));
+        Ok(Some(value))
+    }
+
+    /// Move the top of the stack `amount` values down. The amounts the compiler
+    /// uses are handled like CPython's `ROT_TWO`, `ROT_THREE` and `ROT_FOUR`.
+    fn execute_rotate(&self, vm: &VirtualMachine, amount: usize) -> FrameResult {
+        if amount < 2 {
+            // Only a compiler bug gets us here, but it should not take the host down:
+            return Err(vm.new_exception(
+                vm.ctx.exceptions.system_error.clone(),
+                format!("cannot rotate {} values", amount),
+            ));
+        }
+        let mut stack = self.stack.borrow_mut();
+        let len = stack.len();
+        if amount > len {
+            drop(stack);
+            return Err(self.stack_underflow(vm, amount));
+        }
+        match amount {
+            2 => stack.swap(len - 1, len - 2),
+            3 => {
+                stack.swap(len - 1, len - 2);
+                stack.swap(len - 2, len - 3);
+            }
+            4 => {
+                stack.swap(len - 1, len - 2);
+                stack.swap(len - 2, len - 3);
+                stack.swap(len - 3, len - 4);
+            }
+            _ => stack[len - amount..].rotate_right(1),
+        }
+        Ok(None)
+    }
+
+    fn execute_binary_subscript(&self, vm: &VirtualMachine) -> FrameResult {
+        let idx = self.pop_value(vm)?;
+        let obj = self.pop_value(vm)?;
+        let value = match objgenericalias::subscript_class(&obj, idx.clone(), vm) {
+            Some(value) => value?,
+            None => obj.get_item(&idx, vm)?,
+        };
+        self.push_value(value);
+        Ok(None)
+    }
+
     fn execute_store_subscript(&self, vm: &VirtualMachine) -> FrameResult {
-        let idx = self.pop_value();
-        let obj = self.pop_value();
-        let value = self.pop_value();
+        let idx = self.pop_value(vm)?;
+        let obj = self.pop_value(vm)?;
+        let value = self.pop_value(vm)?;
         obj.set_item(&idx, value, vm)?;
         Ok(None)
     }
 
     fn execute_delete_subscript(&self, vm: &VirtualMachine) -> FrameResult {
-        let idx = self.pop_value();
-        let obj = self.pop_value();
+        let idx = self.pop_value(vm)?;
+        let obj = self.pop_value(vm)?;
         obj.del_item(&idx, vm)?;
         Ok(None)
     }
 
-    fn jump(&self, label: bytecode::Label) {
-        let target_pc = self.code.label_map[&label];
+    /// Jump to `label`, which the compiler has already resolved to an
+    /// instruction offset. A jump back closes a loop, so it checks for pending
+    /// signals, which keeps every loop interruptible without checking on
+    /// straight-line code.
+    fn jump(&self, vm: &VirtualMachine, label: bytecode::Label) -> PyResult<()> {
+        let target_pc = label;
         #[cfg(feature = "vm-tracing-logging")]
         trace!("jump from {:?} to {:?}", self.lasti, target_pc);
-        *self.lasti.borrow_mut() = target_pc;
+        let lasti = self.lasti.replace(target_pc);
+        if target_pc < lasti {
+            vm.check_signals_periodically()?;
+        }
+        Ok(())
+    }
+
+    fn execute_build_map(
+        &self,
+        vm: &VirtualMachine,
+        size: usize,
+        unpack: bool,
+        for_call: bool,
+    ) -> FrameResult {
+        let map_obj = vm.ctx.new_dict();
+        if unpack {
+            for obj in self.pop_multiple(vm, size)? {
+                // Take all key-value pairs from the mapping:
+                for (key, value) in self.mapping_items(vm, obj, for_call)? {
+                    if for_call && map_obj.contains_key(key.clone(), vm) {
+                        let key_repr = vm.to_repr(&key)?;
+                        let msg = format!(
+                            "got multiple values for keyword argument {}",
+                            key_repr.as_str()
+                        );
+                        return Err(vm.new_type_error(msg));
+                    }
+                    map_obj.set_item(&key, value, vm)?;
+                }
+            }
+        } else {
+            for (key, value) in self.pop_multiple(vm, 2 * size)?.into_iter().tuples() {
+                map_obj.set_item(&key, value, vm)?;
+            }
+        }
+
+        self.push_value(map_obj.into_object());
+        Ok(None)
+    }
+
+    /// Collect the key-value pairs of a `**` unpacked mapping, using `keys()`
+    /// and `__getitem__` for objects which are not dicts.
+    fn mapping_items(
+        &self,
+        vm: &VirtualMachine,
+        obj: PyObjectRef,
+        for_call: bool,
+    ) -> PyResult<Vec<(PyObjectRef, PyObjectRef)>> {
+        let obj = match obj.downcast::<PyDict>() {
+            Ok(dict) => return Ok(dict.into_iter().collect()),
+            Err(obj) => obj,
+        };
+        let keys = match vm.get_method(obj.clone(), "keys") {
+            Some(keys) => vm.invoke(&keys?, vec![])?,
+            None => {
+                let msg = if for_call {
+                    format!(
+                        "argument after ** must be a mapping, not {}",
+                        obj.class().name
+                    )
+                } else {
+                    format!("'{}' object is not a mapping", obj.class().name)
+                };
+                return Err(vm.new_type_error(msg));
+            }
+        };
+        let iter = objiter::get_iter(vm, &keys)?;
+        let mut items = vec![];
+        while let Some(key) = objiter::get_next_object(vm, &iter)? {
+            let value = obj.get_item(&key, vm)?;
+            items.push((key, value));
+        }
+        Ok(items)
     }
 
     fn execute_make_function(
@@ -969,37 +1894,29 @@ impl Frame {
         vm: &VirtualMachine,
         flags: bytecode::FunctionOpArg,
     ) -> FrameResult {
-        let qualified_name = self
-            .pop_value()
-            .downcast::<PyString>()
-            .expect("qualified name to be a string");
-        let code_obj = self
-            .pop_value()
-            .downcast()
-            .expect("Second to top value on the stack must be a code object");
+        let qualified_name = self.pop_typed::<PyString>(vm, "a qualified name string")?;
+        let code_obj: PyCodeRef = self.pop_typed(vm, "a code object")?;
+
+        let closure = if flags.contains(bytecode::FunctionOpArg::HAS_CLOSURE) {
+            Some(self.pop_typed::<PyTuple>(vm, "a closure tuple")?)
+        } else {
+            None
+        };
 
         let annotations = if flags.contains(bytecode::FunctionOpArg::HAS_ANNOTATIONS) {
-            self.pop_value()
+            self.pop_value(vm)?
         } else {
             vm.ctx.new_dict().into_object()
         };
 
         let kw_only_defaults = if flags.contains(bytecode::FunctionOpArg::HAS_KW_ONLY_DEFAULTS) {
-            Some(
-                self.pop_value()
-                    .downcast::<PyDict>()
-                    .expect("Stack value for keyword only defaults expected to be a dict"),
-            )
+            Some(self.pop_typed::<PyDict>(vm, "a keyword-only defaults dict")?)
         } else {
             None
         };
 
         let defaults = if flags.contains(bytecode::FunctionOpArg::HAS_DEFAULTS) {
-            Some(
-                self.pop_value()
-                    .downcast::<PyTuple>()
-                    .expect("Stack value for defaults expected to be a tuple"),
-            )
+            Some(self.pop_typed::<PyTuple>(vm, "a defaults tuple")?)
         } else {
             None
         };
@@ -1009,7 +1926,7 @@ impl Frame {
         let scope = self.scope.clone();
         let func_obj = vm
             .ctx
-            .new_function(code_obj, scope, defaults, kw_only_defaults);
+            .new_function(code_obj, scope, defaults, kw_only_defaults, closure);
 
         let name = qualified_name.value.split('.').next_back().unwrap();
         vm.set_attr(&func_obj, "__name__", vm.new_str(name.to_string()))?;
@@ -1033,8 +1950,8 @@ impl Frame {
         op: &bytecode::BinaryOperator,
         inplace: bool,
     ) -> FrameResult {
-        let b_ref = self.pop_value();
-        let a_ref = self.pop_value();
+        let b_ref = self.pop_value(vm)?;
+        let a_ref = self.pop_value(vm)?;
         let value = if inplace {
             match *op {
                 bytecode::BinaryOperator::Subtract => vm._isub(a_ref, b_ref),
@@ -1044,7 +1961,6 @@ impl Frame {
                 bytecode::BinaryOperator::Power => vm._ipow(a_ref, b_ref),
                 bytecode::BinaryOperator::Divide => vm._itruediv(a_ref, b_ref),
                 bytecode::BinaryOperator::FloorDivide => vm._ifloordiv(a_ref, b_ref),
-                bytecode::BinaryOperator::Subscript => unreachable!(),
                 bytecode::BinaryOperator::Modulo => vm._imod(a_ref, b_ref),
                 bytecode::BinaryOperator::Lshift => vm._ilshift(a_ref, b_ref),
                 bytecode::BinaryOperator::Rshift => vm._irshift(a_ref, b_ref),
@@ -1061,8 +1977,6 @@ impl Frame {
                 bytecode::BinaryOperator::Power => vm._pow(a_ref, b_ref),
                 bytecode::BinaryOperator::Divide => vm._truediv(a_ref, b_ref),
                 bytecode::BinaryOperator::FloorDivide => vm._floordiv(a_ref, b_ref),
-                // TODO: Subscript should probably have its own op
-                bytecode::BinaryOperator::Subscript => a_ref.get_item(&b_ref, vm),
                 bytecode::BinaryOperator::Modulo => vm._mod(a_ref, b_ref),
                 bytecode::BinaryOperator::Lshift => vm._lshift(a_ref, b_ref),
                 bytecode::BinaryOperator::Rshift => vm._rshift(a_ref, b_ref),
@@ -1078,7 +1992,7 @@ impl Frame {
 
     #[cfg_attr(feature = "flame-it", flame("Frame"))]
     fn execute_unop(&self, vm: &VirtualMachine, op: &bytecode::UnaryOperator) -> FrameResult {
-        let a = self.pop_value();
+        let a = self.pop_value(vm)?;
         let value = match *op {
             bytecode::UnaryOperator::Minus => vm.call_method(&a, "__neg__", vec![])?,
             bytecode::UnaryOperator::Plus => vm.call_method(&a, "__pos__", vec![])?,
@@ -1123,8 +2037,8 @@ impl Frame {
         vm: &VirtualMachine,
         op: &bytecode::ComparisonOperator,
     ) -> FrameResult {
-        let b = self.pop_value();
-        let a = self.pop_value();
+        let b = self.pop_value(vm)?;
+        let a = self.pop_value(vm)?;
         let value = match *op {
             bytecode::ComparisonOperator::Equal => vm._eq(a, b)?,
             bytecode::ComparisonOperator::NotEqual => vm._ne(a, b)?,
@@ -1143,28 +2057,106 @@ impl Frame {
     }
 
     fn load_attr(&self, vm: &VirtualMachine, attr_name: &str) -> FrameResult {
-        let parent = self.pop_value();
-        let obj = vm.get_attribute(parent, attr_name)?;
+        let parent = self.pop_value(vm)?;
+        let cls = parent.class();
+        // Re-use the lookups on the class made the last time this instruction ran:
+        let offset = *self.lasti.borrow();
+        let cached = self
+            .attr_cache
+            .borrow()
+            .get(&offset)
+            .and_then(|entry| entry.lookup(&cls));
+        let lookup = match cached {
+            Some(lookup) => lookup,
+            None => {
+                let lookup = AttrLookup::new(vm, &cls, attr_name);
+                self.attr_cache
+                    .borrow_mut()
+                    .insert(offset, AttrCacheEntry::new(&lookup, &cls));
+                lookup
+            }
+        };
+        let obj = lookup.get_attribute(vm, parent, attr_name)?;
         self.push_value(obj);
         Ok(None)
     }
 
+    /// Push the function and `self` when the attribute is a plain method found on the
+    /// class, so that `CallMethod` can skip creating a bound method. Otherwise push
+    /// None and the attribute itself.
+    fn load_method(&self, vm: &VirtualMachine, method_name: &str) -> FrameResult {
+        let obj = self.pop_value(vm)?;
+        let cls = obj.class();
+        if let Some(func) = objtype::class_get_attr(&cls, method_name) {
+            let is_method =
+                func.payload_is::<PyFunction>() || func.payload_is::<PyBuiltinFunction>();
+            let generic_getattribute = match (
+                objtype::class_get_attr(&cls, "__getattribute__"),
+                objtype::class_get_attr(&vm.ctx.object(), "__getattribute__"),
+            ) {
+                (Some(getattribute), Some(object_getattribute)) => {
+                    getattribute.is(&object_getattribute)
+                }
+                _ => false,
+            };
+            let shadowed = match obj.dict {
+                Some(ref dict) => dict.contains_key(method_name, vm),
+                None => false,
+            };
+            if is_method && generic_getattribute && !shadowed {
+                self.push_value(func);
+                self.push_value(obj);
+                return Ok(None);
+            }
+        }
+        let attr = vm.get_attribute(obj, method_name)?;
+        self.push_value(vm.get_none());
+        self.push_value(attr);
+        Ok(None)
+    }
+
     fn store_attr(&self, vm: &VirtualMachine, attr_name: &str) -> FrameResult {
-        let parent = self.pop_value();
-        let value = self.pop_value();
-        vm.set_attr(&parent, vm.new_str(attr_name.to_string()), value)?;
+        let parent = self.pop_value(vm)?;
+        let value = self.pop_value(vm)?;
+        vm.set_attr(&parent, self.interned_name(vm, attr_name), value)?;
         Ok(None)
     }
 
     fn delete_attr(&self, vm: &VirtualMachine, attr_name: &str) -> FrameResult {
-        let parent = self.pop_value();
-        let name = vm.ctx.new_str(attr_name.to_string());
-        vm.del_attr(&parent, name)?;
+        let parent = self.pop_value(vm)?;
+        let name = self.interned_name(vm, attr_name);
+        vm.del_attr(&parent, name.into_object())?;
         Ok(None)
     }
 
+    /// The object for the constant of the `LoadConst` being executed, made only
+    /// the first time the instruction runs.
+    fn load_const(&self, vm: &VirtualMachine, value: &bytecode::Constant) -> PyObjectRef {
+        let offset = *self.lasti.borrow() - 1;
+        if let Some(obj) = &self.const_cache.borrow()[offset] {
+            return obj.clone();
+        }
+        let obj = vm.ctx.unwrap_constant(value);
+        self.const_cache.borrow_mut()[offset] = Some(obj.clone());
+        obj
+    }
+
+    /// The Python string for a name used by this frame's code, created only the
+    /// first time the code needs it.
+    fn interned_name(&self, vm: &VirtualMachine, name: &str) -> PyStringRef {
+        objcode::intern_name(&self.name_cache, vm, name)
+    }
+
+    /// The location of the instruction being executed, which is the one before
+    /// `lasti` since `fetch_instruction` moves past it before running it. A frame
+    /// which hasn't started reports its first instruction.
     pub fn get_lineno(&self) -> bytecode::Location {
-        self.code.locations[*self.lasti.borrow()].clone()
+        let current = self.lasti.borrow().saturating_sub(1);
+        self.code
+            .locations
+            .get(current)
+            .cloned()
+            .unwrap_or_default()
     }
 
     fn push_block(&self, typ: BlockType) {
@@ -1184,35 +2176,149 @@ impl Frame {
         self.blocks.borrow().last().cloned()
     }
 
+    /// The text of the given line of this frame's source, read from the code's
+    /// `source_path` or, when there is no such file, from the source the
+    /// compiler retained.
+    pub fn source_line(&self, lineno: usize) -> Option<String> {
+        let mut source_cache = self.source_cache.borrow_mut();
+        let lines = source_cache.get_or_insert_with(|| {
+            // TODO: support different encodings
+            fs::read_to_string(&self.code.source_path)
+                .ok()
+                .or_else(|| self.code.source.as_ref().map(|source| source.to_string()))
+                .map(|source| source.lines().map(str::to_owned).collect())
+                .unwrap_or_default()
+        });
+        lineno
+            .checked_sub(1)
+            .and_then(|index| lines.get(index))
+            .cloned()
+    }
+
+    /// The number of exceptions on the VM's exception stack which belong to
+    /// the handlers this frame is currently in.
+    pub fn handled_exception_count(&self) -> usize {
+        self.blocks
+            .borrow()
+            .iter()
+            .filter(|block| match block.typ {
+                BlockType::ExceptHandler => true,
+                BlockType::FinallyHandler {
+                    reason: Some(UnwindReason::Raising { .. }),
+                } => true,
+                _ => false,
+            })
+            .count()
+    }
+
+    /// Drop all values left on the value stack.
+    pub fn clear_stack(&self) {
+        self.stack.borrow_mut().clear();
+    }
+
+    /// Unbind all fast locals.
+    pub fn clear_fast_locals(&self) {
+        for value in self.fastlocals.borrow_mut().iter_mut() {
+            *value = None;
+        }
+    }
+
     pub fn push_value(&self, obj: PyObjectRef) {
         self.stack.borrow_mut().push(obj);
     }
 
-    fn pop_value(&self) -> PyObjectRef {
-        self.stack
-            .borrow_mut()
-            .pop()
-            .expect("Tried to pop value but there was nothing on the stack")
+    /// Raise a MemoryError if the stack holds more values than the VM's
+    /// `stack_limit` allows.
+    fn check_stack_limit(&self, vm: &VirtualMachine) -> PyResult<()> {
+        match vm.stack_limit.get() {
+            Some(limit) if self.stack.borrow().len() > limit => Err(vm.new_exception(
+                vm.ctx.exceptions.memory_error.clone(),
+                format!(
+                    "stack of {} exceeds the limit of {} values",
+                    self.code.obj_name, limit
+                ),
+            )),
+            _ => Ok(()),
+        }
     }
 
-    fn pop_multiple(&self, count: usize) -> Vec<PyObjectRef> {
+    /// The error for bytecode which expects more values on the stack than there
+    /// are, which means that the code object is corrupt.
+    fn stack_underflow(&self, vm: &VirtualMachine, needed: usize) -> PyObjectRef {
+        let problem = format!(
+            "needed {} values on the stack, but there are {}",
+            needed,
+            self.stack.borrow().len()
+        );
+        self.corrupt_bytecode(vm, &problem)
+    }
+
+    /// The SystemError for an instruction which the compiler never emits in
+    /// this frame's state, such as a bad operand or the wrong block on top.
+    fn corrupt_bytecode(&self, vm: &VirtualMachine, problem: &str) -> PyObjectRef {
+        vm.new_exception(
+            vm.ctx.exceptions.system_error.clone(),
+            format!("corrupt bytecode in {}: {}", self.code.obj_name, problem),
+        )
+    }
+
+    /// Pop a value which the bytecode guarantees to be a `T`, raising a
+    /// SystemError like `stack_underflow` if corrupt bytecode put something else there.
+    fn pop_typed<T: PyValue>(&self, vm: &VirtualMachine, expected: &str) -> PyResult<PyRef<T>> {
+        self.pop_value(vm)?.downcast().map_err(|obj| {
+            vm.new_exception(
+                vm.ctx.exceptions.system_error.clone(),
+                format!(
+                    "corrupt bytecode in {}: expected {} on the stack, but got {}",
+                    self.code.obj_name,
+                    expected,
+                    obj.class().name
+                ),
+            )
+        })
+    }
+
+    fn pop_value(&self, vm: &VirtualMachine) -> PyResult {
+        let value = self.stack.borrow_mut().pop();
+        value.ok_or_else(|| self.stack_underflow(vm, 1))
+    }
+
+    fn pop_multiple(&self, vm: &VirtualMachine, count: usize) -> PyResult<Vec<PyObjectRef>> {
         let mut stack = self.stack.borrow_mut();
         let stack_len = stack.len();
-        stack.drain(stack_len - count..stack_len).collect()
+        if count > stack_len {
+            drop(stack);
+            return Err(self.stack_underflow(vm, count));
+        }
+        Ok(stack.drain(stack_len - count..stack_len).collect())
     }
 
-    fn last_value(&self) -> PyObjectRef {
-        self.stack.borrow().last().unwrap().clone()
+    fn last_value(&self, vm: &VirtualMachine) -> PyResult {
+        self.nth_value(vm, 0)
     }
 
-    fn nth_value(&self, depth: usize) -> PyObjectRef {
+    fn nth_value(&self, vm: &VirtualMachine, depth: usize) -> PyResult {
         let stack = self.stack.borrow();
-        stack[stack.len() - depth - 1].clone()
+        if depth >= stack.len() {
+            drop(stack);
+            return Err(self.stack_underflow(vm, depth + 1));
+        }
+        Ok(stack[stack.len() - depth - 1].clone())
     }
 
     #[cfg_attr(feature = "flame-it", flame("Frame"))]
+    /// Set `__context__` of a newly raised exception to the exception being handled, if any.
+    fn chain_exception(&self, vm: &VirtualMachine, exception: &PyObjectRef) -> PyResult<()> {
+        if let Some(context) = vm.current_exception() {
+            if !context.is(exception) {
+                vm.set_attr(exception, "__context__", context)?;
+            }
+        }
+        Ok(())
+    }
+
     fn get_exception(&self, vm: &VirtualMachine, none_allowed: bool) -> PyResult {
-        let exception = self.pop_value();
+        let exception = self.pop_value(vm)?;
         if none_allowed && vm.get_none().is(&exception)
             || objtype::isinstance(&exception, &vm.ctx.exceptions.base_exception_type)
         {
@@ -1234,6 +2340,35 @@ impl Frame {
     }
 }
 
+impl FrameRef {
+    /// Execute exactly one instruction of this frame, or the exception handling
+    /// it leads to, as running the frame would. `Ok(Some(_))` means that the
+    /// frame has returned or yielded. This lets an embedder single-step a frame,
+    /// for example to build a debugger.
+    ///
+    /// The frame must not be running at the same time, which is checked, and it
+    /// must not be stepped again once it has returned. Trace functions get a
+    /// line event for every step.
+    pub fn step(&self, vm: &VirtualMachine) -> FrameResult {
+        if vm.frames.borrow().iter().any(|frame| frame.is(self)) {
+            return Err(vm.new_exception(
+                vm.ctx.exceptions.runtime_error.clone(),
+                "frame is already running".to_string(),
+            ));
+        }
+        if self.next_instruction().is_none() {
+            return Err(vm.new_exception(
+                vm.ctx.exceptions.runtime_error.clone(),
+                "frame has no instructions left".to_string(),
+            ));
+        }
+        vm.frames.borrow_mut().push(self.clone());
+        let result = self.run_instruction(vm, &mut None);
+        vm.frames.borrow_mut().pop();
+        result
+    }
+}
+
 impl fmt::Debug for Frame {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let stack_str = self
@@ -1266,3 +2401,272 @@ impl fmt::Debug for Frame {
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{ExecutionResult, Frame};
+    use crate::bytecode::{CodeObject, Constant, FunctionOpArg, Instruction, Location, Varargs};
+    use crate::obj::objfunction::PyFunction;
+    use crate::obj::{objint, objstr, objtuple, objtype};
+    use crate::pyobject::{PyResult, PyValue};
+    use crate::scope::NameProtocol;
+    use crate::vm::VirtualMachine;
+    use num_traits::ToPrimitive;
+    use rustpython_compiler::compile;
+
+    /// Run hand-assembled code in a fresh module scope.
+    fn run_instructions(vm: &VirtualMachine, instructions: Vec<Instruction>) -> PyResult {
+        let mut code = CodeObject::new(
+            vec![],
+            Varargs::None,
+            vec![],
+            Varargs::None,
+            "<unittest>".to_string(),
+            1,
+            "<module>".to_string(),
+        );
+        code.locations = vec![Location::default(); instructions.len()];
+        code.instructions = instructions;
+        // Left at 0: the computed size is only a capacity hint, so code
+        // which was never verified must still run.
+        vm.run_code_obj(vm.ctx.new_code_object(code), vm.new_scope_with_builtins())
+    }
+
+    fn load_string(value: &str) -> Instruction {
+        Instruction::LoadConst {
+            value: Constant::String {
+                value: value.to_string(),
+            },
+        }
+    }
+
+    /// Run code which pushes "a", "b", "c", "d" and "e", rotates the top
+    /// `amount` of them and returns the whole stack as a tuple.
+    fn run_rotate(vm: &VirtualMachine, amount: usize) -> PyResult {
+        let mut instructions: Vec<Instruction> = ["a", "b", "c", "d", "e"]
+            .iter()
+            .map(|value| load_string(value))
+            .collect();
+        instructions.push(Instruction::Rotate { amount });
+        instructions.push(Instruction::BuildTuple {
+            size: 5,
+            unpack: false,
+            for_call: false,
+        });
+        instructions.push(Instruction::ReturnValue);
+        run_instructions(vm, instructions)
+    }
+
+    fn stack_order(vm: &VirtualMachine, amount: usize) -> Vec<String> {
+        let result = run_rotate(vm, amount).unwrap();
+        objtuple::get_value(&result)
+            .iter()
+            .map(objstr::get_value)
+            .collect()
+    }
+
+    #[test]
+    fn test_rotate() {
+        let vm: VirtualMachine = Default::default();
+        assert_eq!(stack_order(&vm, 2), ["a", "b", "c", "e", "d"]);
+        assert_eq!(stack_order(&vm, 3), ["a", "b", "e", "c", "d"]);
+        // Like ROT_FOUR, the top value goes below the three under it:
+        assert_eq!(stack_order(&vm, 4), ["a", "e", "b", "c", "d"]);
+        assert_eq!(stack_order(&vm, 5), ["e", "a", "b", "c", "d"]);
+    }
+
+    #[test]
+    fn test_rotate_invalid_amount() {
+        let vm: VirtualMachine = Default::default();
+        for &amount in &[0, 1, 6] {
+            let exc = run_rotate(&vm, amount).unwrap_err();
+            assert!(objtype::isinstance(&exc, &vm.ctx.exceptions.system_error));
+        }
+    }
+
+    #[test]
+    fn test_stack_underflow() {
+        let vm: VirtualMachine = Default::default();
+        let corrupt_code = vec![
+            vec![Instruction::ReturnValue],
+            vec![load_string("a"), Instruction::Reverse { amount: 2 }],
+            vec![
+                load_string("a"),
+                Instruction::BuildTuple {
+                    size: 2,
+                    unpack: false,
+                    for_call: false,
+                },
+            ],
+            vec![Instruction::Duplicate],
+            vec![Instruction::ListAppend { i: 0 }],
+            vec![Instruction::Rotate { amount: 3 }],
+        ];
+        for instructions in corrupt_code {
+            let exc = run_instructions(&vm, instructions).unwrap_err();
+            assert!(objtype::isinstance(&exc, &vm.ctx.exceptions.system_error));
+        }
+    }
+
+    #[test]
+    fn test_corrupt_operands() {
+        let vm: VirtualMachine = Default::default();
+        let corrupt_code = vec![
+            vec![
+                load_string("a"),
+                load_string("b"),
+                load_string("c"),
+                load_string("d"),
+                Instruction::Raise { argc: 4 },
+            ],
+            vec![load_string("a"), Instruction::BuildSlice { size: 1 }],
+            vec![Instruction::LoadDeref {
+                name: "x".to_string(),
+            }],
+            vec![
+                load_string("a"),
+                Instruction::StoreDeref {
+                    name: "x".to_string(),
+                },
+            ],
+        ];
+        for instructions in corrupt_code {
+            let exc = run_instructions(&vm, instructions).unwrap_err();
+            assert!(objtype::isinstance(&exc, &vm.ctx.exceptions.system_error));
+        }
+    }
+
+    #[test]
+    fn test_corrupt_blocks() {
+        let vm: VirtualMachine = Default::default();
+        let setup_loop = Instruction::SetupLoop { start: 1, end: 2 };
+        let corrupt_code = vec![
+            vec![setup_loop.clone(), Instruction::PopException],
+            vec![setup_loop.clone(), Instruction::CleanupWith { end: 2 }],
+            vec![setup_loop.clone(), Instruction::CleanupAsyncWith],
+            vec![setup_loop, Instruction::EndFinally],
+            vec![Instruction::PopException],
+            vec![Instruction::PopBlock],
+            vec![Instruction::Break],
+        ];
+        for instructions in corrupt_code {
+            let exc = run_instructions(&vm, instructions).unwrap_err();
+            assert!(objtype::isinstance(&exc, &vm.ctx.exceptions.system_error));
+        }
+    }
+
+    #[test]
+    fn test_make_function_bad_stack() {
+        let vm: VirtualMachine = Default::default();
+        let load_code = Instruction::LoadConst {
+            value: Constant::Code {
+                code: Box::new(CodeObject::new(
+                    vec![],
+                    Varargs::None,
+                    vec![],
+                    Varargs::None,
+                    "<unittest>".to_string(),
+                    1,
+                    "f".to_string(),
+                )),
+            },
+        };
+        let make_function = |flags| Instruction::MakeFunction { flags };
+        let corrupt_code = vec![
+            vec![
+                load_string("not code"),
+                load_string("f"),
+                make_function(FunctionOpArg::empty()),
+            ],
+            vec![
+                load_code.clone(),
+                Instruction::LoadConst {
+                    value: Constant::Integer { value: 1.into() },
+                },
+                make_function(FunctionOpArg::empty()),
+            ],
+            vec![
+                load_string("not defaults"),
+                load_code.clone(),
+                load_string("f"),
+                make_function(FunctionOpArg::HAS_DEFAULTS),
+            ],
+            vec![
+                load_string("not kwdefaults"),
+                load_code,
+                load_string("f"),
+                make_function(FunctionOpArg::HAS_KW_ONLY_DEFAULTS),
+            ],
+        ];
+        for instructions in corrupt_code {
+            let exc = run_instructions(&vm, instructions).unwrap_err();
+            assert!(objtype::isinstance(&exc, &vm.ctx.exceptions.system_error));
+        }
+    }
+
+    #[test]
+    fn test_stack_limit() {
+        let vm: VirtualMachine = Default::default();
+        let pushes = vec![load_string("a"), load_string("b"), load_string("c")];
+        vm.stack_limit.set(Some(2));
+        let exc = run_instructions(&vm, pushes.clone()).unwrap_err();
+        assert!(objtype::isinstance(&exc, &vm.ctx.exceptions.memory_error));
+
+        // The error unwinds like any other, so the code can catch it.
+        let mut instructions = vec![Instruction::SetupExcept { handler: 5 }];
+        instructions.extend(pushes.clone());
+        instructions.extend(vec![
+            Instruction::ReturnValue,
+            Instruction::Pop,
+            Instruction::PopException,
+            load_string("caught"),
+            Instruction::ReturnValue,
+        ]);
+        let result = run_instructions(&vm, instructions).unwrap();
+        assert_eq!(objstr::get_value(&result), "caught");
+
+        vm.stack_limit.set(None);
+        let mut instructions = pushes;
+        instructions.push(Instruction::ReturnValue);
+        let result = run_instructions(&vm, instructions).unwrap();
+        assert_eq!(objstr::get_value(&result), "c");
+    }
+
+    #[test]
+    fn test_step() {
+        let vm: VirtualMachine = Default::default();
+        let source = "def f():\n    x = 2\n    return x * 3\n";
+        let code = vm
+            .compile(source, compile::Mode::Exec, "<unittest>".to_string())
+            .unwrap();
+        let scope = vm.new_scope_with_builtins();
+        vm.run_code_obj(code, scope.clone()).unwrap();
+        let function = scope.load_name(&vm, "f").unwrap();
+        let function = function.payload::<PyFunction>().unwrap();
+        let frame = Frame::new(
+            function.code.clone(),
+            function.scope.new_child_scope(&vm.ctx),
+            None,
+            &vm,
+        )
+        .into_ref(&vm);
+
+        let mut executed = vec![];
+        let result = loop {
+            assert_eq!(*frame.lasti.borrow(), executed.len());
+            executed.push(frame.next_instruction().unwrap().clone());
+            if let Some(result) = frame.step(&vm).unwrap() {
+                break result;
+            }
+        };
+        assert_eq!(executed, frame.code.instructions);
+        match result {
+            ExecutionResult::Return(value) => {
+                assert_eq!(objint::get_value(&value).to_i32(), Some(6))
+            }
+            ExecutionResult::Yield(_) => panic!("function should return"),
+        }
+        assert!(frame.next_instruction().is_none());
+        assert!(frame.step(&vm).is_err());
+    }
+}
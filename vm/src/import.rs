@@ -4,8 +4,9 @@
 use rand::Rng;
 
 use crate::bytecode::CodeObject;
-use crate::obj::{objcode, objsequence, objstr, objtype};
-use crate::pyobject::{ItemProtocol, PyObjectRef, PyResult, PyValue};
+use crate::obj::objtraceback::{PyTraceback, PyTracebackRef};
+use crate::obj::{objcode, objtype};
+use crate::pyobject::{IntoPyObject, ItemProtocol, PyObjectRef, PyResult, PyValue};
 use crate::scope::Scope;
 use crate::version::get_git_revision;
 use crate::vm::VirtualMachine;
@@ -106,18 +107,18 @@ pub fn remove_importlib_frames(vm: &VirtualMachine, exc: &PyObjectRef) -> PyObje
     let always_trim = objtype::isinstance(exc, &vm.ctx.exceptions.import_error);
 
     if let Ok(tb) = vm.get_attribute(exc.clone(), "__traceback__") {
-        if objtype::isinstance(&tb, &vm.ctx.list_type()) {
-            let tb_entries = objsequence::get_elements_list(&tb).to_vec();
+        if let Ok(tb) = tb.downcast::<PyTraceback>() {
             let mut in_importlib = false;
-            let new_tb = tb_entries
+            let tb_entries: Vec<PyTracebackRef> = tb
                 .iter()
+                .collect::<Vec<_>>()
+                .into_iter()
+                .rev()
                 .filter(|tb_entry| {
-                    let location_attrs = objsequence::get_elements_tuple(&tb_entry);
-                    let file_name = objstr::get_value(&location_attrs[0]);
+                    let file_name = tb_entry.frame.code.source_path.as_str();
                     if file_name == "_frozen_importlib" || file_name == "_frozen_importlib_external"
                     {
-                        let run_obj_name = objstr::get_value(&location_attrs[2]);
-                        if run_obj_name == "_call_with_frames_removed" {
+                        if tb_entry.frame.code.obj_name == "_call_with_frames_removed" {
                             in_importlib = true;
                         }
                         !always_trim && !in_importlib
@@ -126,9 +127,20 @@ pub fn remove_importlib_frames(vm: &VirtualMachine, exc: &PyObjectRef) -> PyObje
                         true
                     }
                 })
-                .cloned()
                 .collect();
-            vm.set_attr(exc, "__traceback__", vm.ctx.new_list(new_tb))
+            // Relink the remaining entries, outermost frame first:
+            let new_tb = tb_entries.into_iter().fold(None, |next, tb_entry| {
+                Some(
+                    PyTraceback::new(
+                        next,
+                        tb_entry.frame.clone(),
+                        tb_entry.lasti,
+                        tb_entry.lineno,
+                    )
+                    .into_ref(vm),
+                )
+            });
+            vm.set_attr(exc, "__traceback__", new_tb.into_pyobject(vm).unwrap())
                 .unwrap();
         }
     }
@@ -5,9 +5,12 @@ pub mod objbuiltinfunc;
 pub mod objbytearray;
 pub mod objbyteinner;
 pub mod objbytes;
+pub mod objcell;
 pub mod objclassmethod;
 pub mod objcode;
 pub mod objcomplex;
+pub mod objcoroinner;
+pub mod objcoroutine;
 pub mod objdict;
 pub mod objellipsis;
 pub mod objenumerate;
@@ -16,6 +19,7 @@ pub mod objfloat;
 pub mod objframe;
 pub mod objfunction;
 pub mod objgenerator;
+pub mod objgenericalias;
 pub mod objint;
 pub mod objiter;
 pub mod objlist;
@@ -34,6 +38,7 @@ pub mod objslice;
 pub mod objstaticmethod;
 pub mod objstr;
 pub mod objsuper;
+pub mod objtraceback;
 pub mod objtuple;
 pub mod objtype;
 pub mod objweakproxy;
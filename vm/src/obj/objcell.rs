@@ -0,0 +1,51 @@
+/*! The python `cell` type, which holds a variable shared between a function and
+the functions nested in it.
+
+*/
+
+use std::cell::RefCell;
+
+use crate::obj::objtype::PyClassRef;
+use crate::pyobject::{PyClassImpl, PyContext, PyObjectRef, PyRef, PyResult, PyValue};
+use crate::vm::VirtualMachine;
+
+pub type PyCellRef = PyRef<PyCell>;
+
+#[pyclass(name = "cell")]
+#[derive(Debug, Default)]
+pub struct PyCell {
+    pub contents: RefCell<Option<PyObjectRef>>,
+}
+
+impl PyValue for PyCell {
+    fn class(vm: &VirtualMachine) -> PyClassRef {
+        vm.ctx.cell_type()
+    }
+}
+
+#[pyimpl]
+impl PyCell {
+    pub fn new(contents: Option<PyObjectRef>) -> Self {
+        PyCell {
+            contents: RefCell::new(contents),
+        }
+    }
+
+    pub fn get(&self) -> Option<PyObjectRef> {
+        self.contents.borrow().clone()
+    }
+
+    pub fn set(&self, value: Option<PyObjectRef>) {
+        self.contents.replace(value);
+    }
+
+    #[pyproperty(name = "cell_contents")]
+    fn cell_contents(&self, vm: &VirtualMachine) -> PyResult {
+        self.get()
+            .ok_or_else(|| vm.new_value_error("Cell is empty".to_string()))
+    }
+}
+
+pub fn init(context: &PyContext) {
+    PyCell::extend_class(context, &context.types.cell_type);
+}
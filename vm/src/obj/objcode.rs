@@ -2,22 +2,232 @@
 
 */
 
+use std::cell::RefCell;
+use std::collections::HashMap;
 use std::fmt;
+use std::rc::Rc;
 
 use crate::bytecode;
-use crate::obj::objtype::PyClassRef;
-use crate::pyobject::{IdProtocol, PyContext, PyObjectRef, PyRef, PyResult, PyValue};
+use crate::obj::objdict::PyDictRef;
+use crate::obj::objiter;
+use crate::obj::objstr::{PyString, PyStringRef};
+use crate::obj::objtype::{self, PyClassRef};
+use crate::obj::objweakref::PyWeak;
+use crate::pyobject::{IdProtocol, PyContext, PyObjectRef, PyRef, PyResult, PyValue, TypeProtocol};
 use crate::vm::VirtualMachine;
 
 pub type PyCodeRef = PyRef<PyCode>;
 
+/// Attribute lookups cached by the `LoadAttr` instructions of a code object, by
+/// instruction offset.
+pub type AttrCache = Rc<RefCell<HashMap<usize, AttrCacheEntry>>>;
+
+/// Global names resolved by the `LoadName` instructions of a code object, by
+/// instruction offset.
+pub type GlobalCache = Rc<RefCell<HashMap<usize, GlobalCacheEntry>>>;
+
+/// The lines of a code object's source, loaded the first time one is needed.
+pub type SourceCache = Rc<RefCell<Option<Vec<String>>>>;
+
+/// The names used by the instructions of a code object, along with its own name
+/// and filename, each made into a Python string the first time it is needed and
+/// shared from then on.
+pub type NameCache = Rc<RefCell<HashMap<String, PyStringRef>>>;
+
+/// The objects made for the constants of the `LoadConst` instructions of a code
+/// object, by instruction offset. Each is made the first time it is loaded, and
+/// the same object is loaded from then on.
+pub type ConstCache = Rc<RefCell<Vec<Option<PyObjectRef>>>>;
+
 pub struct PyCode {
     pub code: bytecode::CodeObject,
+    pub const_cache: ConstCache,
+    pub attr_cache: AttrCache,
+    pub global_cache: GlobalCache,
+    pub source_cache: SourceCache,
+    pub name_cache: NameCache,
 }
 
 impl PyCode {
     pub fn new(code: bytecode::CodeObject) -> PyCode {
-        PyCode { code }
+        PyCode {
+            const_cache: Rc::new(RefCell::new(vec![None; code.instructions.len()])),
+            code,
+            attr_cache: Rc::new(RefCell::new(HashMap::new())),
+            global_cache: Rc::new(RefCell::new(HashMap::new())),
+            source_cache: Rc::new(RefCell::new(None)),
+            name_cache: Rc::new(RefCell::new(HashMap::new())),
+        }
+    }
+}
+
+/// The Python string for `name` from `cache`, made and added to it if it isn't
+/// there yet.
+pub fn intern_name(cache: &NameCache, vm: &VirtualMachine, name: &str) -> PyStringRef {
+    if let Some(interned) = cache.borrow().get(name) {
+        return interned.clone();
+    }
+    let interned = PyString::from(name).into_ref(vm);
+    cache
+        .borrow_mut()
+        .insert(name.to_string(), interned.clone());
+    interned
+}
+
+/// The lookups on the class of an object which `object.__getattribute__` makes
+/// to resolve one attribute name.
+pub struct AttrLookup {
+    /// Whether the class uses `object.__getattribute__`. If it does not, none of
+    /// the other fields are filled in.
+    generic: bool,
+    attr: Option<PyObjectRef>,
+    get: Option<PyObjectRef>,
+    is_data_descriptor: bool,
+    getattr: Option<PyObjectRef>,
+}
+
+impl AttrLookup {
+    pub fn new(vm: &VirtualMachine, cls: &PyClassRef, attr_name: &str) -> AttrLookup {
+        let generic = match (
+            objtype::class_get_attr(cls, "__getattribute__"),
+            objtype::class_get_attr(&vm.ctx.object(), "__getattribute__"),
+        ) {
+            (Some(getattribute), Some(object_getattribute)) => {
+                getattribute.is(&object_getattribute)
+            }
+            _ => false,
+        };
+        let mut lookup = AttrLookup {
+            generic,
+            attr: None,
+            get: None,
+            is_data_descriptor: false,
+            getattr: None,
+        };
+        if generic {
+            if let Some(attr) = objtype::class_get_attr(cls, attr_name) {
+                let attr_class = attr.class();
+                lookup.get = objtype::class_get_attr(&attr_class, "__get__");
+                lookup.is_data_descriptor = objtype::class_has_attr(&attr_class, "__set__");
+                lookup.attr = Some(attr);
+            }
+            lookup.getattr = objtype::class_get_attr(cls, "__getattr__");
+        }
+        lookup
+    }
+
+    /// Get the attribute of `obj` in the same way as `vm.get_attribute`.
+    pub fn get_attribute(self, vm: &VirtualMachine, obj: PyObjectRef, attr_name: &str) -> PyResult {
+        if !self.generic {
+            return vm.get_attribute(obj, attr_name);
+        }
+        if let (Some(attr), Some(get), true) = (&self.attr, &self.get, self.is_data_descriptor) {
+            let cls = obj.class().into_object();
+            return vm.invoke(get, vec![attr.clone(), obj, cls]);
+        }
+        if let Some(ref dict) = obj.dict {
+            if let Some(obj_attr) = dict.get_item_option(attr_name, vm)? {
+                return Ok(obj_attr);
+            }
+        }
+        if let Some(attr) = self.attr {
+            match self.get {
+                Some(get) => {
+                    let cls = obj.class().into_object();
+                    vm.invoke(&get, vec![attr, obj, cls])
+                }
+                None => Ok(attr),
+            }
+        } else if let Some(getattr) = self.getattr {
+            vm.invoke(&getattr, vec![obj, vm.new_str(attr_name.to_string())])
+        } else {
+            Err(vm.new_attribute_error(format!("{} has no attribute '{}'", obj, attr_name)))
+        }
+    }
+}
+
+/// An `AttrLookup` remembered by a `LoadAttr` instruction. It holds weak references,
+/// since everything it refers to is kept alive by the classes for as long as their
+/// version tags are unchanged.
+pub struct AttrCacheEntry {
+    class_version: usize,
+    attr_class_version: usize,
+    generic: bool,
+    attr: Option<PyWeak>,
+    get: Option<PyWeak>,
+    is_data_descriptor: bool,
+    getattr: Option<PyWeak>,
+}
+
+impl AttrCacheEntry {
+    pub fn new(lookup: &AttrLookup, cls: &PyClassRef) -> AttrCacheEntry {
+        AttrCacheEntry {
+            class_version: cls.version_tag.get(),
+            attr_class_version: lookup
+                .attr
+                .as_ref()
+                .map_or(0, |attr| attr.class().version_tag.get()),
+            generic: lookup.generic,
+            attr: lookup.attr.as_ref().map(PyWeak::downgrade),
+            get: lookup.get.as_ref().map(PyWeak::downgrade),
+            is_data_descriptor: lookup.is_data_descriptor,
+            getattr: lookup.getattr.as_ref().map(PyWeak::downgrade),
+        }
+    }
+
+    /// Get the cached lookup back, if it is still valid for `cls`.
+    pub fn lookup(&self, cls: &PyClassRef) -> Option<AttrLookup> {
+        if self.class_version != cls.version_tag.get() {
+            return None;
+        }
+        fn upgrade(weak: &Option<PyWeak>) -> Option<Option<PyObjectRef>> {
+            match weak {
+                Some(weak) => weak.upgrade().map(Some),
+                None => Some(None),
+            }
+        }
+        let attr = upgrade(&self.attr)?;
+        if let Some(ref attr) = attr {
+            if attr.class().version_tag.get() != self.attr_class_version {
+                return None;
+            }
+        }
+        Some(AttrLookup {
+            generic: self.generic,
+            attr,
+            get: upgrade(&self.get)?,
+            is_data_descriptor: self.is_data_descriptor,
+            getattr: upgrade(&self.getattr)?,
+        })
+    }
+}
+
+/// The value a global name had, and the version tags of the globals and builtins
+/// dicts it was looked up in. The value is held weakly, since one of the dicts
+/// keeps it alive for as long as their tags are unchanged.
+pub struct GlobalCacheEntry {
+    globals_version: usize,
+    builtins_version: usize,
+    value: PyWeak,
+}
+
+impl GlobalCacheEntry {
+    pub fn new(value: &PyObjectRef, globals: &PyDictRef, builtins: &PyDictRef) -> GlobalCacheEntry {
+        GlobalCacheEntry {
+            globals_version: globals.version_tag(),
+            builtins_version: builtins.version_tag(),
+            value: PyWeak::downgrade(value),
+        }
+    }
+
+    /// Get the cached value back, if neither dict has changed since.
+    pub fn lookup(&self, globals: &PyDictRef, builtins: &PyDictRef) -> Option<PyObjectRef> {
+        if self.globals_version != globals.version_tag()
+            || self.builtins_version != builtins.version_tag()
+        {
+            return None;
+        }
+        self.value.upgrade()
     }
 }
 
@@ -54,8 +264,8 @@ impl PyCodeRef {
         self.code.arg_names.len()
     }
 
-    fn co_filename(self, _vm: &VirtualMachine) -> String {
-        self.code.source_path.clone()
+    fn co_filename(self, vm: &VirtualMachine) -> PyStringRef {
+        intern_name(&self.name_cache, vm, &self.code.source_path)
     }
 
     fn co_firstlineno(self, _vm: &VirtualMachine) -> usize {
@@ -66,6 +276,10 @@ impl PyCodeRef {
         self.code.kwonlyarg_names.len()
     }
 
+    fn co_posonlyargcount(self, _vm: &VirtualMachine) -> usize {
+        self.code.posonlyarg_count
+    }
+
     fn co_consts(self, vm: &VirtualMachine) -> PyObjectRef {
         let consts = self
             .code
@@ -75,9 +289,56 @@ impl PyCodeRef {
         vm.ctx.new_tuple(consts)
     }
 
-    fn co_name(self, _vm: &VirtualMachine) -> String {
-        self.code.obj_name.clone()
+    fn co_name(self, vm: &VirtualMachine) -> PyStringRef {
+        intern_name(&self.name_cache, vm, &self.code.obj_name)
+    }
+
+    fn co_names(self, vm: &VirtualMachine) -> PyObjectRef {
+        names_tuple(self.code.get_names(), vm)
     }
+
+    fn co_varnames(self, vm: &VirtualMachine) -> PyObjectRef {
+        names_tuple(self.code.get_varnames(), vm)
+    }
+
+    fn co_cellvars(self, vm: &VirtualMachine) -> PyObjectRef {
+        names_tuple(self.code.cellvars.iter().map(String::as_str), vm)
+    }
+
+    fn co_freevars(self, vm: &VirtualMachine) -> PyObjectRef {
+        names_tuple(self.code.freevars.iter().map(String::as_str), vm)
+    }
+
+    fn co_stacksize(self, _vm: &VirtualMachine) -> usize {
+        self.code.max_stacksize
+    }
+
+    /// An iterator of `(lineno, end_lineno, col, end_col)` for every
+    /// instruction, with columns counted from 0 as in CPython.
+    fn co_positions(self, vm: &VirtualMachine) -> PyResult {
+        let positions = self
+            .code
+            .locations
+            .iter()
+            .map(|location| {
+                vm.ctx.new_tuple(vec![
+                    vm.new_int(location.row()),
+                    vm.new_int(location.end_row()),
+                    vm.new_int(location.column().saturating_sub(1)),
+                    vm.new_int(location.end_column().saturating_sub(1)),
+                ])
+            })
+            .collect();
+        objiter::get_iter(vm, &vm.ctx.new_list(positions))
+    }
+}
+
+fn names_tuple<'a>(names: impl IntoIterator<Item = &'a str>, vm: &VirtualMachine) -> PyObjectRef {
+    let names = names
+        .into_iter()
+        .map(|name| vm.new_str(name.to_string()))
+        .collect();
+    vm.ctx.new_tuple(names)
 }
 
 pub fn init(context: &PyContext) {
@@ -86,10 +347,17 @@ pub fn init(context: &PyContext) {
         "__repr__" => context.new_rustfunc(PyCodeRef::repr),
 
         "co_argcount" => context.new_property(PyCodeRef::co_argcount),
+        "co_cellvars" => context.new_property(PyCodeRef::co_cellvars),
         "co_consts" => context.new_property(PyCodeRef::co_consts),
         "co_filename" => context.new_property(PyCodeRef::co_filename),
         "co_firstlineno" => context.new_property(PyCodeRef::co_firstlineno),
+        "co_freevars" => context.new_property(PyCodeRef::co_freevars),
         "co_kwonlyargcount" => context.new_property(PyCodeRef::co_kwonlyargcount),
         "co_name" => context.new_property(PyCodeRef::co_name),
+        "co_names" => context.new_property(PyCodeRef::co_names),
+        "co_positions" => context.new_rustfunc(PyCodeRef::co_positions),
+        "co_posonlyargcount" => context.new_property(PyCodeRef::co_posonlyargcount),
+        "co_stacksize" => context.new_property(PyCodeRef::co_stacksize),
+        "co_varnames" => context.new_property(PyCodeRef::co_varnames),
     });
 }
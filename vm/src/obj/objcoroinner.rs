@@ -0,0 +1,282 @@
+/*
+ * The frame-driving machinery shared by generators and coroutines.
+ */
+
+use std::cell::{Cell, RefCell};
+
+use crate::exceptions;
+use crate::frame::{ExecutionResult, FrameRef};
+use crate::function::OptionalArg;
+use crate::obj::objstr::PyStringRef;
+use crate::obj::objtuple::PyTuple;
+use crate::obj::objtype::{isinstance, issubclass, PyClassRef};
+use crate::pyobject::{PyObjectRef, PyResult, TryFromObject, TypeProtocol};
+use crate::vm::VirtualMachine;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Variant {
+    Gen,
+    Coroutine,
+}
+
+impl Variant {
+    fn name(self) -> &'static str {
+        match self {
+            Variant::Gen => "generator",
+            Variant::Coroutine => "coroutine",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Coro {
+    frame: FrameRef,
+    /// The exceptions being handled by the suspended frame, which are taken
+    /// off the VM's exception stack while it isn't running.
+    exceptions: RefCell<Vec<PyObjectRef>>,
+    /// Set while the frame is executing, so that it can't be resumed from within itself.
+    running: Cell<bool>,
+    /// Set once the frame has returned or raised, after which it is never resumed again.
+    finished: Cell<bool>,
+    variant: Variant,
+    /// `__name__` and `__qualname__`, taken from the function which created it.
+    name: RefCell<PyStringRef>,
+    qualname: RefCell<PyStringRef>,
+}
+
+impl Coro {
+    pub fn new(
+        frame: FrameRef,
+        variant: Variant,
+        name: PyStringRef,
+        qualname: PyStringRef,
+    ) -> Self {
+        Coro {
+            frame,
+            exceptions: RefCell::new(vec![]),
+            running: Cell::new(false),
+            finished: Cell::new(false),
+            variant,
+            name: RefCell::new(name),
+            qualname: RefCell::new(qualname),
+        }
+    }
+
+    pub fn name(&self) -> PyStringRef {
+        self.name.borrow().clone()
+    }
+
+    pub fn set_name(&self, name: PyStringRef) {
+        self.name.replace(name);
+    }
+
+    pub fn qualname(&self) -> PyStringRef {
+        self.qualname.borrow().clone()
+    }
+
+    pub fn set_qualname(&self, qualname: PyStringRef) {
+        self.qualname.replace(qualname);
+    }
+
+    /// Like `<generator object gen at 0x...>`, for the object with the id `id`.
+    pub fn repr(&self, id: usize) -> String {
+        format!(
+            "<{} object {} at 0x{:x}>",
+            self.variant.name(),
+            self.qualname.borrow().as_str(),
+            id
+        )
+    }
+
+    fn resume(
+        &self,
+        vm: &VirtualMachine,
+        run: impl FnOnce() -> PyResult<ExecutionResult>,
+    ) -> PyResult<ExecutionResult> {
+        if self.running.get() {
+            return Err(vm.new_value_error(format!("{} already executing", self.variant.name())));
+        }
+        self.running.set(true);
+        for exception in self.exceptions.borrow_mut().drain(..) {
+            vm.push_exception(exception);
+        }
+        let result = run();
+        self.running.set(false);
+        match result {
+            Ok(ExecutionResult::Yield(_)) => {
+                let mut exceptions = self.exceptions.borrow_mut();
+                for _ in 0..self.frame.handled_exception_count() {
+                    exceptions.push(vm.pop_exception().expect("Should have exception in stack"));
+                }
+                exceptions.reverse();
+                result
+            }
+            Err(exception) if isinstance(&exception, &vm.ctx.exceptions.stop_iteration) => {
+                self.finished.set(true);
+                Err(self.stop_iteration_error(exception, vm)?)
+            }
+            _ => {
+                self.finished.set(true);
+                result
+            }
+        }
+    }
+
+    /// The error for resuming a finished coroutine. A finished generator
+    /// has nothing more to give, so it raises `exception` if there is one,
+    /// or the StopIteration of an exhausted iterator otherwise.
+    fn finished_error(&self, exception: Option<PyObjectRef>, vm: &VirtualMachine) -> PyResult {
+        match (self.variant, exception) {
+            (Variant::Coroutine, _) => Ok(vm.new_exception(
+                vm.ctx.exceptions.runtime_error.clone(),
+                "cannot reuse already awaited coroutine".to_string(),
+            )),
+            (Variant::Gen, Some(exception)) => Ok(exception),
+            (Variant::Gen, None) => {
+                vm.new_empty_exception(vm.ctx.exceptions.stop_iteration.clone())
+            }
+        }
+    }
+
+    /// PEP 479: a StopIteration escaping the frame would look like the end of
+    /// iteration to the caller, so it is turned into a RuntimeError instead.
+    fn stop_iteration_error(&self, cause: PyObjectRef, vm: &VirtualMachine) -> PyResult {
+        let error = vm.new_exception(
+            vm.ctx.exceptions.runtime_error.clone(),
+            format!("{} raised StopIteration", self.variant.name()),
+        );
+        vm.set_attr(&error, "__cause__", cause.clone())?;
+        vm.set_attr(&error, "__context__", cause)?;
+        Ok(error)
+    }
+
+    pub fn running(&self) -> bool {
+        self.running.get()
+    }
+
+    pub fn send(&self, value: PyObjectRef, vm: &VirtualMachine) -> PyResult {
+        if self.finished.get() {
+            return Err(self.finished_error(None, vm)?);
+        }
+        let result = if *self.frame.lasti.borrow() == 0 {
+            // A just-started generator has no pending yield to receive the value.
+            if !vm.is_none(&value) {
+                return Err(vm.new_type_error(format!(
+                    "can't send non-None value to a just-started {}",
+                    self.variant.name()
+                )));
+            }
+            self.resume(vm, || vm.run_frame(self.frame.clone()))?
+        } else {
+            self.resume(vm, || vm.resume_frame(self.frame.clone(), value))?
+        };
+        handle_execution_result(result, vm)
+    }
+
+    pub fn throw(
+        &self,
+        exc_type: PyObjectRef,
+        exc_val: OptionalArg,
+        exc_tb: OptionalArg,
+        vm: &VirtualMachine,
+    ) -> PyResult {
+        let exception = make_thrown_exception(exc_type, exc_val, exc_tb, vm)?;
+        if self.finished.get() {
+            return Err(self.finished_error(Some(exception), vm)?);
+        }
+        let result = self.resume(vm, || vm.frame_throw(self.frame.clone(), exception))?;
+        handle_execution_result(result, vm)
+    }
+
+    pub fn close(&self, vm: &VirtualMachine) -> PyResult<()> {
+        if self.finished.get() {
+            return Ok(());
+        }
+        let generator_exit = vm.new_empty_exception(vm.ctx.exceptions.generator_exit.clone())?;
+        match self.resume(vm, || vm.frame_throw(self.frame.clone(), generator_exit)) {
+            Ok(ExecutionResult::Yield(_)) => Err(vm.new_exception(
+                vm.ctx.exceptions.runtime_error.clone(),
+                format!("{} ignored GeneratorExit", self.variant.name()),
+            )),
+            Ok(ExecutionResult::Return(_)) => Ok(()),
+            Err(exception) => {
+                if isinstance(&exception, &vm.ctx.exceptions.generator_exit)
+                    || isinstance(&exception, &vm.ctx.exceptions.stop_iteration)
+                {
+                    Ok(())
+                } else {
+                    Err(exception)
+                }
+            }
+        }
+    }
+}
+
+/// The exception raised by `throw(type[, value[, traceback]])`, made like
+/// CPython does: a class is called with the value as its arguments, unless the
+/// value is an instance of it already, and an instance must come on its own.
+fn make_thrown_exception(
+    exc_type: PyObjectRef,
+    exc_val: OptionalArg,
+    exc_tb: OptionalArg,
+    vm: &VirtualMachine,
+) -> PyResult {
+    let exc_val = exc_val.into_option().filter(|value| !vm.is_none(value));
+    let exc_tb = exc_tb.into_option().filter(|tb| !vm.is_none(tb));
+    if let Some(tb) = &exc_tb {
+        if !isinstance(tb, &vm.ctx.traceback_type()) {
+            return Err(
+                vm.new_type_error("throw() third argument must be a traceback object".to_string())
+            );
+        }
+    }
+
+    let base_exception = &vm.ctx.exceptions.base_exception_type;
+    let exception = match PyClassRef::try_from_object(vm, exc_type.clone()) {
+        Ok(cls) if issubclass(&cls, base_exception) => match exc_val {
+            Some(value) if isinstance(&value, &cls) => value,
+            Some(value) => {
+                let args = match value.payload::<PyTuple>() {
+                    Some(tuple) => tuple.elements.clone(),
+                    None => vec![value],
+                };
+                vm.new_exception_obj(cls, args)?
+            }
+            None => vm.new_empty_exception(cls)?,
+        },
+        _ if isinstance(&exc_type, base_exception) => {
+            if exc_val.is_some() {
+                return Err(vm.new_type_error(
+                    "instance exception may not have a separate value".to_string(),
+                ));
+            }
+            exc_type
+        }
+        _ => {
+            return Err(vm.new_type_error(format!(
+                "exceptions must be classes or instances deriving from BaseException, not {}",
+                exc_type.class().name
+            )))
+        }
+    };
+    if let Some(tb) = exc_tb {
+        exceptions::set_traceback(vm, &exception, tb)?;
+    }
+    Ok(exception)
+}
+
+fn handle_execution_result(result: ExecutionResult, vm: &VirtualMachine) -> PyResult {
+    match result {
+        ExecutionResult::Yield(value) => Ok(value),
+        ExecutionResult::Return(value) => {
+            // Stop iteration!
+            let stop_iteration = vm.ctx.exceptions.stop_iteration.clone();
+            let args = if vm.is_none(&value) {
+                vec![]
+            } else {
+                vec![value]
+            };
+            Err(vm.new_exception_obj(stop_iteration, args)?)
+        }
+    }
+}
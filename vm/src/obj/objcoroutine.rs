@@ -0,0 +1,150 @@
+/*
+ * The coroutine object created by calling an `async def` function.
+ */
+
+use crate::frame::FrameRef;
+use crate::function::OptionalArg;
+use crate::obj::objcoroinner::{Coro, Variant};
+use crate::obj::objstr::PyStringRef;
+use crate::obj::objtype::PyClassRef;
+use crate::pyobject::{IdProtocol, PyClassImpl, PyContext, PyObjectRef, PyRef, PyResult, PyValue};
+use crate::vm::VirtualMachine;
+
+pub type PyCoroutineRef = PyRef<PyCoroutine>;
+
+#[pyclass(name = "coroutine")]
+#[derive(Debug)]
+pub struct PyCoroutine {
+    inner: Coro,
+}
+
+impl PyValue for PyCoroutine {
+    fn class(vm: &VirtualMachine) -> PyClassRef {
+        vm.ctx.coroutine_type()
+    }
+}
+
+#[pyimpl]
+impl PyCoroutine {
+    pub fn new(
+        frame: FrameRef,
+        name: PyStringRef,
+        qualname: PyStringRef,
+        vm: &VirtualMachine,
+    ) -> PyCoroutineRef {
+        PyCoroutine {
+            inner: Coro::new(frame, Variant::Coroutine, name, qualname),
+        }
+        .into_ref(vm)
+    }
+
+    #[pyproperty(name = "__name__")]
+    fn name(&self, _vm: &VirtualMachine) -> PyStringRef {
+        self.inner.name()
+    }
+
+    #[pyproperty(name = "__name__", setter)]
+    fn set_name(&self, name: PyStringRef, vm: &VirtualMachine) -> PyResult {
+        self.inner.set_name(name);
+        Ok(vm.get_none())
+    }
+
+    #[pyproperty(name = "__qualname__")]
+    fn qualname(&self, _vm: &VirtualMachine) -> PyStringRef {
+        self.inner.qualname()
+    }
+
+    #[pyproperty(name = "__qualname__", setter)]
+    fn set_qualname(&self, qualname: PyStringRef, vm: &VirtualMachine) -> PyResult {
+        self.inner.set_qualname(qualname);
+        Ok(vm.get_none())
+    }
+
+    #[pymethod(name = "__repr__")]
+    fn repr(zelf: PyCoroutineRef, _vm: &VirtualMachine) -> String {
+        zelf.inner.repr(zelf.get_id())
+    }
+
+    #[pymethod]
+    pub fn send(&self, value: PyObjectRef, vm: &VirtualMachine) -> PyResult {
+        self.inner.send(value, vm)
+    }
+
+    #[pymethod]
+    fn throw(
+        &self,
+        exc_type: PyObjectRef,
+        exc_val: OptionalArg,
+        exc_tb: OptionalArg,
+        vm: &VirtualMachine,
+    ) -> PyResult {
+        self.inner.throw(exc_type, exc_val, exc_tb, vm)
+    }
+
+    #[pymethod]
+    fn close(&self, vm: &VirtualMachine) -> PyResult<()> {
+        self.inner.close(vm)
+    }
+
+    #[pyproperty]
+    fn cr_running(&self, _vm: &VirtualMachine) -> bool {
+        self.inner.running()
+    }
+
+    #[pymethod(name = "__await__")]
+    fn await_(zelf: PyCoroutineRef, _vm: &VirtualMachine) -> PyCoroutineWrapper {
+        PyCoroutineWrapper { coro: zelf }
+    }
+}
+
+/// The iterator returned by `coroutine.__await__`, which drives the coroutine.
+#[pyclass(name = "coroutine_wrapper")]
+#[derive(Debug)]
+pub struct PyCoroutineWrapper {
+    coro: PyCoroutineRef,
+}
+
+impl PyValue for PyCoroutineWrapper {
+    fn class(vm: &VirtualMachine) -> PyClassRef {
+        vm.ctx.coroutine_wrapper_type()
+    }
+}
+
+#[pyimpl]
+impl PyCoroutineWrapper {
+    #[pymethod(name = "__iter__")]
+    fn iter(zelf: PyRef<Self>, _vm: &VirtualMachine) -> PyRef<Self> {
+        zelf
+    }
+
+    #[pymethod(name = "__next__")]
+    fn next(&self, vm: &VirtualMachine) -> PyResult {
+        self.coro.send(vm.get_none(), vm)
+    }
+
+    #[pymethod]
+    fn send(&self, value: PyObjectRef, vm: &VirtualMachine) -> PyResult {
+        self.coro.send(value, vm)
+    }
+
+    #[pymethod]
+    fn throw(
+        &self,
+        exc_type: PyObjectRef,
+        exc_val: OptionalArg,
+        exc_tb: OptionalArg,
+        vm: &VirtualMachine,
+    ) -> PyResult {
+        self.coro.throw(exc_type, exc_val, exc_tb, vm)
+    }
+
+    #[pymethod]
+    fn close(&self, vm: &VirtualMachine) -> PyResult<()> {
+        self.coro.close(vm)
+    }
+}
+
+pub fn init(ctx: &PyContext) {
+    PyCoroutine::extend_class(ctx, &ctx.types.coroutine_type);
+    PyCoroutineWrapper::extend_class(ctx, &ctx.types.coroutine_wrapper_type);
+}
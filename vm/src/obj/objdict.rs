@@ -174,7 +174,7 @@ impl PyDictRef {
         self.entries.borrow_mut().delete(vm, &key)
     }
 
-    fn clear(self, _vm: &VirtualMachine) {
+    pub fn clear(self, _vm: &VirtualMachine) {
         self.entries.borrow_mut().clear()
     }
 
@@ -270,7 +270,7 @@ impl PyDictRef {
         }
     }
 
-    fn copy(self, _vm: &VirtualMachine) -> PyDict {
+    pub fn copy(self, _vm: &VirtualMachine) -> PyDict {
         PyDict {
             entries: self.entries.clone(),
         }
@@ -344,6 +344,11 @@ impl PyDictRef {
         self.entries.borrow().size()
     }
 
+    /// A tag which changes whenever the contents of the dict do.
+    pub fn version_tag(&self) -> usize {
+        self.entries.borrow().version_tag()
+    }
+
     /// This function can be used to get an item without raising the
     /// KeyError, so we can simply check upon the result being Some
     /// python value, or None.
@@ -2,6 +2,7 @@ use super::objbytes;
 use super::objint;
 use super::objstr;
 use super::objtype;
+use crate::format::FormatSpec;
 use crate::function::OptionalArg;
 use crate::obj::objstr::PyStringRef;
 use crate::obj::objtype::PyClassRef;
@@ -69,7 +70,15 @@ fn inner_mod(v1: f64, v2: f64, vm: &VirtualMachine) -> PyResult<f64> {
     if v2 != 0.0 {
         Ok(v1 % v2)
     } else {
-        Err(vm.new_zero_division_error("float mod by zero".to_string()))
+        Err(vm.new_zero_division_error("float modulo".to_string()))
+    }
+}
+
+fn inner_pow(v1: f64, v2: f64, vm: &VirtualMachine) -> PyResult<f64> {
+    if v1 == 0.0 && v2 < 0.0 {
+        Err(vm.new_zero_division_error("0.0 cannot be raised to a negative power".to_string()))
+    } else {
+        Ok(v1.powf(v2))
     }
 }
 
@@ -99,7 +108,7 @@ fn inner_floordiv(v1: f64, v2: f64, vm: &VirtualMachine) -> PyResult<f64> {
     if v2 != 0.0 {
         Ok((v1 / v2).floor())
     } else {
-        Err(vm.new_zero_division_error("float floordiv by zero".to_string()))
+        Err(vm.new_zero_division_error("float floor division by zero".to_string()))
     }
 }
 
@@ -363,7 +372,7 @@ impl PyFloat {
     fn pow(&self, other: PyObjectRef, vm: &VirtualMachine) -> PyResult {
         try_float(&other, vm)?.map_or_else(
             || Ok(vm.ctx.not_implemented()),
-            |other| self.value.powf(other).into_pyobject(vm),
+            |other| inner_pow(self.value, other, vm)?.into_pyobject(vm),
         )
     }
 
@@ -371,7 +380,7 @@ impl PyFloat {
     fn rpow(&self, other: PyObjectRef, vm: &VirtualMachine) -> PyResult {
         try_float(&other, vm)?.map_or_else(
             || Ok(vm.ctx.not_implemented()),
-            |other| other.powf(self.value).into_pyobject(vm),
+            |other| inner_pow(other, self.value, vm)?.into_pyobject(vm),
         )
     }
 
@@ -400,6 +409,15 @@ impl PyFloat {
         }
     }
 
+    #[pymethod(name = "__format__")]
+    fn format(&self, spec: PyStringRef, vm: &VirtualMachine) -> PyResult<String> {
+        let format_spec = FormatSpec::parse(&spec.value);
+        match format_spec.format_float(self.value) {
+            Ok(string) => Ok(string),
+            Err(err) => Err(vm.new_value_error(err.to_string())),
+        }
+    }
+
     #[pymethod(name = "__truediv__")]
     fn truediv(&self, other: PyObjectRef, vm: &VirtualMachine) -> PyResult {
         try_float(&other, vm)?.map_or_else(
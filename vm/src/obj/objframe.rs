@@ -4,8 +4,9 @@
 
 use super::objcode::PyCodeRef;
 use super::objdict::PyDictRef;
+use super::objproperty::PropertyBuilder;
 use crate::frame::FrameRef;
-use crate::pyobject::{PyContext, PyObjectRef, PyResult};
+use crate::pyobject::{IdProtocol, PyContext, PyObjectRef, PyResult};
 use crate::vm::VirtualMachine;
 
 pub fn init(context: &PyContext) {
@@ -17,6 +18,18 @@ pub fn init(context: &PyContext) {
         "f_code" => context.new_property(FrameRef::fcode),
         "f_back" => context.new_property(FrameRef::f_back),
         "f_lasti" => context.new_property(FrameRef::f_lasti),
+        "f_lineno" => context.new_property(FrameRef::f_lineno),
+        "clear" => context.new_rustfunc(FrameRef::clear),
+        "f_trace" =>
+        PropertyBuilder::new(context)
+            .add_getter(FrameRef::f_trace)
+            .add_setter(FrameRef::set_f_trace)
+            .create(),
+        "f_trace_opcodes" =>
+        PropertyBuilder::new(context)
+            .add_getter(FrameRef::f_trace_opcodes)
+            .add_setter(FrameRef::set_f_trace_opcodes)
+            .create(),
     });
 }
 
@@ -30,24 +43,78 @@ impl FrameRef {
         "<frame object at .. >".to_string()
     }
 
+    fn clear(self, vm: &VirtualMachine) -> PyResult<()> {
+        if vm.frames.borrow().iter().any(|frame| frame.is(&self)) {
+            return Err(vm.new_exception(
+                vm.ctx.exceptions.runtime_error.clone(),
+                "cannot clear an executing frame".to_string(),
+            ));
+        }
+        if let Some(locals) = self.scope.get_only_locals() {
+            locals.clear(vm);
+        }
+        self.clear_fast_locals();
+        self.clear_stack();
+        Ok(())
+    }
+
     fn f_globals(self, _vm: &VirtualMachine) -> PyDictRef {
         self.scope.globals.clone()
     }
 
-    fn flocals(self, _vm: &VirtualMachine) -> PyDictRef {
-        self.scope.get_locals()
+    fn flocals(self, vm: &VirtualMachine) -> PyResult<PyDictRef> {
+        // Like CPython, function frames refresh their locals dict from the fast
+        // locals on each access, while module level frames share their globals.
+        // Changes made by a trace function are copied back when it returns.
+        self.fast_to_locals(vm)?;
+        Ok(self.scope.get_locals())
     }
 
     fn fcode(self, vm: &VirtualMachine) -> PyCodeRef {
         vm.ctx.new_code_object(self.code.clone())
     }
 
-    fn f_back(self, vm: &VirtualMachine) -> PyObjectRef {
-        // TODO: how to retrieve the upper stack frame??
-        vm.ctx.none()
+    fn f_back(self, vm: &VirtualMachine) -> Option<FrameRef> {
+        // The caller is the frame below this one on the active frame stack. Frames which are
+        // not running, such as suspended generators, have no caller.
+        let frames = vm.frames.borrow();
+        let position = frames.iter().rposition(|frame| frame.is(&self))?;
+        if position == 0 {
+            None
+        } else {
+            Some(frames[position - 1].clone())
+        }
     }
 
     fn f_lasti(self, vm: &VirtualMachine) -> PyObjectRef {
         vm.ctx.new_int(*self.lasti.borrow())
     }
+
+    /// A frame which hasn't started yet is at the line its code starts at, which
+    /// for a function is the `def` line, like in CPython.
+    fn f_lineno(self, _vm: &VirtualMachine) -> usize {
+        if *self.lasti.borrow() == 0 {
+            self.code.first_line_number
+        } else {
+            self.get_lineno().row()
+        }
+    }
+
+    fn f_trace(self, vm: &VirtualMachine) -> PyObjectRef {
+        self.trace.borrow().clone().unwrap_or_else(|| vm.get_none())
+    }
+
+    fn set_f_trace(self, value: PyObjectRef, vm: &VirtualMachine) -> PyResult {
+        self.trace.replace(Some(value));
+        Ok(vm.get_none())
+    }
+
+    fn f_trace_opcodes(self, _vm: &VirtualMachine) -> bool {
+        self.trace_opcodes.get()
+    }
+
+    fn set_f_trace_opcodes(self, value: bool, vm: &VirtualMachine) -> PyResult {
+        self.trace_opcodes.set(value);
+        Ok(vm.get_none())
+    }
 }
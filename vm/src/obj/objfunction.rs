@@ -16,6 +16,7 @@ pub struct PyFunction {
     pub scope: Scope,
     pub defaults: Option<PyTupleRef>,
     pub kw_only_defaults: Option<PyDictRef>,
+    pub closure: Option<PyTupleRef>,
 }
 
 impl PyFunction {
@@ -24,12 +25,14 @@ impl PyFunction {
         scope: Scope,
         defaults: Option<PyTupleRef>,
         kw_only_defaults: Option<PyDictRef>,
+        closure: Option<PyTupleRef>,
     ) -> Self {
         PyFunction {
             code,
             scope,
             defaults,
             kw_only_defaults,
+            closure,
         }
     }
 }
@@ -53,6 +56,10 @@ impl PyFunctionRef {
         self.defaults.clone()
     }
 
+    fn closure(self, _vm: &VirtualMachine) -> Option<PyTupleRef> {
+        self.closure.clone()
+    }
+
     fn kwdefaults(self, _vm: &VirtualMachine) -> Option<PyDictRef> {
         self.kw_only_defaults.clone()
     }
@@ -85,6 +92,7 @@ pub fn init(context: &PyContext) {
         "__code__" => context.new_property(PyFunctionRef::code),
         "__defaults__" => context.new_property(PyFunctionRef::defaults),
         "__kwdefaults__" => context.new_property(PyFunctionRef::kwdefaults),
+        "__closure__" => context.new_property(PyFunctionRef::closure),
     });
 
     let builtin_function_or_method_type = &context.types.builtin_function_or_method_type;
@@ -0,0 +1,143 @@
+/*! The type of parameterized classes such as `list[int]`, which `__class_getitem__` returns.
+
+*/
+
+use super::objtuple::{PyTuple, PyTupleRef};
+use super::objtype::{self, PyClassRef};
+use crate::function::PyFuncArgs;
+use crate::pyobject::{
+    IdProtocol, PyClassImpl, PyContext, PyObjectRef, PyRef, PyResult, PyValue, TryFromObject,
+    TypeProtocol,
+};
+use crate::vm::VirtualMachine;
+
+#[pyclass(name = "GenericAlias")]
+#[derive(Debug)]
+pub struct PyGenericAlias {
+    origin: PyClassRef,
+    args: PyTupleRef,
+}
+
+pub type PyGenericAliasRef = PyRef<PyGenericAlias>;
+
+impl PyValue for PyGenericAlias {
+    fn class(vm: &VirtualMachine) -> PyClassRef {
+        vm.ctx.types.generic_alias_type.clone()
+    }
+}
+
+#[pyimpl]
+impl PyGenericAlias {
+    /// Parameterize `origin` with `args`, which is a tuple of arguments or a single one.
+    pub fn new(origin: PyClassRef, args: PyObjectRef, vm: &VirtualMachine) -> PyGenericAlias {
+        let args = match args.downcast::<PyTuple>() {
+            Ok(args) => args,
+            Err(arg) => PyTuple::from(vec![arg]).into_ref(vm),
+        };
+        PyGenericAlias { origin, args }
+    }
+
+    #[pyproperty(name = "__origin__")]
+    fn origin(&self, _vm: &VirtualMachine) -> PyClassRef {
+        self.origin.clone()
+    }
+
+    #[pyproperty(name = "__args__")]
+    fn args(&self, _vm: &VirtualMachine) -> PyTupleRef {
+        self.args.clone()
+    }
+
+    #[pymethod(name = "__repr__")]
+    fn repr(&self, vm: &VirtualMachine) -> PyResult<String> {
+        let args = if self.args.elements.is_empty() {
+            "()".to_string()
+        } else {
+            self.args
+                .elements
+                .iter()
+                .map(|arg| repr_arg(arg, vm))
+                .collect::<PyResult<Vec<_>>>()?
+                .join(", ")
+        };
+        Ok(format!(
+            "{}[{}]",
+            repr_arg(self.origin.as_object(), vm)?,
+            args
+        ))
+    }
+
+    #[pymethod(name = "__call__")]
+    fn call(&self, args: PyFuncArgs, vm: &VirtualMachine) -> PyResult {
+        vm.invoke(self.origin.as_object(), args)
+    }
+
+    #[pymethod(name = "__eq__")]
+    fn eq(&self, other: PyObjectRef, vm: &VirtualMachine) -> PyResult {
+        let other = match other.payload::<PyGenericAlias>() {
+            Some(other) => other,
+            None => return Ok(vm.ctx.not_implemented()),
+        };
+        if !self.origin.is(&other.origin) {
+            return Ok(vm.new_bool(false));
+        }
+        vm._eq(
+            self.args.as_object().clone(),
+            other.args.as_object().clone(),
+        )
+    }
+}
+
+/// Classes are shown by their qualified name like in annotations, and other arguments by their repr.
+fn repr_arg(arg: &PyObjectRef, vm: &VirtualMachine) -> PyResult<String> {
+    if arg.is(&vm.ctx.ellipsis) {
+        return Ok("...".to_string());
+    }
+    let cls = match PyClassRef::try_from_object(vm, arg.clone()) {
+        Ok(cls) => cls,
+        Err(_) => return Ok(vm.to_repr(arg)?.as_str().to_string()),
+    };
+    let qualname = vm.to_str(&cls.clone().qualname(vm))?;
+    let module = vm.to_str(&cls.module(vm))?;
+    Ok(if module.as_str() == "builtins" {
+        qualname.as_str().to_string()
+    } else {
+        format!("{}.{}", module.as_str(), qualname.as_str())
+    })
+}
+
+/// `__class_getitem__` of the builtin classes which can be parameterized.
+fn class_getitem(cls: PyClassRef, args: PyObjectRef, vm: &VirtualMachine) -> PyGenericAlias {
+    PyGenericAlias::new(cls, args, vm)
+}
+
+/// Subscript a class whose metaclass has no `__getitem__`, using its `__class_getitem__`.
+pub fn subscript_class(
+    obj: &PyObjectRef,
+    idx: PyObjectRef,
+    vm: &VirtualMachine,
+) -> Option<PyResult> {
+    let cls = PyClassRef::try_from_object(vm, obj.clone()).ok()?;
+    if objtype::class_has_attr(&obj.class(), "__getitem__")
+        || !objtype::class_has_attr(&cls, "__class_getitem__")
+    {
+        return None;
+    }
+    Some(
+        vm.get_attribute(obj.clone(), "__class_getitem__")
+            .and_then(|class_getitem| vm.invoke(&class_getitem, vec![idx])),
+    )
+}
+
+pub fn init(context: &PyContext) {
+    PyGenericAlias::extend_class(context, &context.types.generic_alias_type);
+    for class in &[
+        &context.types.list_type,
+        &context.types.dict_type,
+        &context.types.tuple_type,
+        &context.types.set_type,
+        &context.types.frozenset_type,
+        &context.types.type_type,
+    ] {
+        class.set_str_attr("__class_getitem__", context.new_classmethod(class_getitem));
+    }
+}
@@ -116,6 +116,11 @@ impl_try_from_object_int!(
 #[allow(clippy::collapsible_if)]
 fn inner_pow(int1: &PyInt, int2: &PyInt, vm: &VirtualMachine) -> PyResult {
     let result = if int2.value.is_negative() {
+        if int1.value.is_zero() {
+            return Err(
+                vm.new_zero_division_error("0.0 cannot be raised to a negative power".to_string())
+            );
+        }
         let v1 = int1.float(vm)?;
         let v2 = int2.float(vm)?;
         vm.ctx.new_float(v1.pow(v2))
@@ -149,7 +154,7 @@ fn inner_mod(int1: &PyInt, int2: &PyInt, vm: &VirtualMachine) -> PyResult {
 
 fn inner_floordiv(int1: &PyInt, int2: &PyInt, vm: &VirtualMachine) -> PyResult {
     if int2.value.is_zero() {
-        Err(vm.new_zero_division_error("integer division by zero".to_string()))
+        Err(vm.new_zero_division_error("integer division or modulo by zero".to_string()))
     } else {
         Ok(vm.ctx.new_int(int1.value.div_floor(&int2.value)))
     }
@@ -778,7 +783,7 @@ pub fn get_float_value(obj: &PyObjectRef, vm: &VirtualMachine) -> PyResult<f64>
 #[inline]
 fn div_ints(vm: &VirtualMachine, i1: &BigInt, i2: &BigInt) -> PyResult {
     if i2.is_zero() {
-        return Err(vm.new_zero_division_error("integer division by zero".to_string()));
+        return Err(vm.new_zero_division_error("division by zero".to_string()));
     }
 
     if let (Some(f1), Some(f2)) = (i1.to_f64(), i2.to_f64()) {
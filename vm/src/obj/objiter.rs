@@ -75,7 +75,12 @@ pub fn get_all(vm: &VirtualMachine, iter_obj: &PyObjectRef) -> PyResult<Vec<PyOb
 
 pub fn new_stop_iteration(vm: &VirtualMachine) -> PyObjectRef {
     let stop_iteration_type = vm.ctx.exceptions.stop_iteration.clone();
-    vm.new_exception(stop_iteration_type, "End of iterator".to_string())
+    vm.new_empty_exception(stop_iteration_type).unwrap()
+}
+
+/// Get the value a StopIteration carries, i.e. the return value of an exhausted generator.
+pub fn stop_iter_value(vm: &VirtualMachine, exc: &PyObjectRef) -> PyResult {
+    vm.get_attribute(exc.clone(), "value")
 }
 
 #[pyclass]
@@ -64,13 +64,21 @@ impl PyModuleRef {
     }
 
     fn getattribute(self, name: PyStringRef, vm: &VirtualMachine) -> PyResult {
-        vm.generic_getattribute(self.as_object().clone(), name.clone())?
-            .ok_or_else(|| {
-                vm.new_attribute_error(format!(
-                    "module '{}' has no attribute '{}'",
-                    self.name, name,
-                ))
-            })
+        if let Some(value) = vm.generic_getattribute(self.as_object().clone(), name.clone())? {
+            return Ok(value);
+        }
+        // A module can compute its missing attributes, like submodules loaded lazily:
+        let getattr = match &self.as_object().dict {
+            Some(dict) => dict.get_item_option("__getattr__", vm)?,
+            None => None,
+        };
+        match getattr {
+            Some(getattr) => vm.invoke(&getattr, vec![name.into_object()]),
+            None => Err(vm.new_attribute_error(format!(
+                "module '{}' has no attribute '{}'",
+                self.name, name,
+            ))),
+        }
     }
 
     fn repr(self, vm: &VirtualMachine) -> PyResult {
@@ -2,7 +2,7 @@ use super::objdict::PyDictRef;
 use super::objlist::PyList;
 use super::objstr::PyStringRef;
 use super::objtype;
-use crate::function::PyFuncArgs;
+use crate::function::{KwArgs, PyFuncArgs};
 use crate::obj::objproperty::PropertyBuilder;
 use crate::obj::objtype::PyClassRef;
 use crate::pyhash;
@@ -122,6 +122,13 @@ fn object_subclasshook(vm: &VirtualMachine, _args: PyFuncArgs) -> PyResult {
     Ok(vm.ctx.not_implemented())
 }
 
+fn object_init_subclass(_cls: PyObjectRef, kwargs: KwArgs, vm: &VirtualMachine) -> PyResult<()> {
+    if kwargs.into_iter().next().is_some() {
+        return Err(vm.new_type_error("__init_subclass__() takes no keyword arguments".to_string()));
+    }
+    Ok(())
+}
+
 pub fn object_dir(obj: PyObjectRef, vm: &VirtualMachine) -> PyResult<PyList> {
     let attributes: PyAttributes = objtype::get_attributes(obj.class());
 
@@ -184,6 +191,7 @@ pub fn init(context: &PyContext) {
         "__format__" => context.new_rustfunc(object_format),
         "__getattribute__" => context.new_rustfunc(object_getattribute),
         "__subclasshook__" => context.new_classmethod(object_subclasshook),
+        "__init_subclass__" => context.new_classmethod(object_init_subclass),
         "__doc__" => context.new_str(object_doc.to_string()),
     });
 }
@@ -1,5 +1,4 @@
 use crate::function::OptionalArg;
-use crate::obj::objnone::PyNone;
 use std::cell::RefCell;
 use std::marker::Sized;
 use std::ops::{Deref, DerefMut, Range};
@@ -13,7 +12,7 @@ use num_traits::{One, Signed, ToPrimitive, Zero};
 use super::objbool;
 use super::objint::{PyInt, PyIntRef};
 use super::objlist::PyList;
-use super::objslice::{PySlice, PySliceRef};
+use super::objslice::{self, PySlice, PySliceRef};
 use super::objtuple::PyTuple;
 
 pub trait PySliceableSequence {
@@ -468,12 +467,7 @@ pub fn is_valid_slice_arg(
     vm: &VirtualMachine,
 ) -> Result<Option<BigInt>, PyObjectRef> {
     if let OptionalArg::Present(value) = arg {
-        match_class!(value,
-        i @ PyInt => Ok(Some(i.as_bigint().clone())),
-        _obj @ PyNone => Ok(None),
-        _=> {return Err(vm.new_type_error("slice indices must be integers or None or have an __index__ method".to_string()));}
-        // TODO: check for an __index__ method
-        )
+        objslice::to_index_value(vm, &value)
     } else {
         Ok(None)
     }
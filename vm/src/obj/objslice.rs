@@ -5,6 +5,7 @@ use crate::vm::VirtualMachine;
 use crate::obj::objint::PyInt;
 use crate::obj::objtype::{class_has_attr, PyClassRef};
 use num_bigint::BigInt;
+use num_traits::{One, Signed, Zero};
 
 #[derive(Debug)]
 pub struct PySlice {
@@ -87,9 +88,53 @@ impl PySliceRef {
             Ok(None)
         }
     }
+
+    /// The start, stop and step of the slice of a sequence of `length` items.
+    fn indices(self, length: PyObjectRef, vm: &VirtualMachine) -> PyResult {
+        let length = to_index_value(vm, &length)?.ok_or_else(|| {
+            vm.new_type_error("'NoneType' object cannot be interpreted as an integer".to_string())
+        })?;
+        if length.is_negative() {
+            return Err(vm.new_value_error("length should not be negative".to_string()));
+        }
+        let step = self.step_index(vm)?.unwrap_or_else(BigInt::one);
+        if step.is_zero() {
+            return Err(vm.new_value_error("slice step cannot be zero".to_string()));
+        }
+        // With a negative step the slice runs from the end down to before the start:
+        let (lower, upper) = if step.is_negative() {
+            (-BigInt::one(), &length - 1)
+        } else {
+            (BigInt::zero(), length.clone())
+        };
+        let clamp = |index: Option<BigInt>, default: &BigInt| match index {
+            None => default.clone(),
+            Some(index) => {
+                let index = if index.is_negative() {
+                    index + &length
+                } else {
+                    index
+                };
+                index.max(lower.clone()).min(upper.clone())
+            }
+        };
+        let (default_start, default_stop) = if step.is_negative() {
+            (&upper, &lower)
+        } else {
+            (&lower, &upper)
+        };
+        let start = clamp(self.start_index(vm)?, default_start);
+        let stop = clamp(self.stop_index(vm)?, default_stop);
+        Ok(vm.ctx.new_tuple(vec![
+            vm.ctx.new_int(start),
+            vm.ctx.new_int(stop),
+            vm.ctx.new_int(step),
+        ]))
+    }
 }
 
-fn to_index_value(vm: &VirtualMachine, obj: &PyObjectRef) -> PyResult<Option<BigInt>> {
+/// Coerce a slice index to an integer, with `__index__` if it is not an int already.
+pub fn to_index_value(vm: &VirtualMachine, obj: &PyObjectRef) -> PyResult<Option<BigInt>> {
     if obj.is(&vm.ctx.none) {
         return Ok(None);
     }
@@ -120,6 +165,7 @@ pub fn init(context: &PyContext) {
         "__new__" => context.new_rustfunc(slice_new),
         "start" => context.new_property(PySliceRef::start),
         "stop" => context.new_property(PySliceRef::stop),
-        "step" => context.new_property(PySliceRef::step)
+        "step" => context.new_property(PySliceRef::step),
+        "indices" => context.new_rustfunc(PySliceRef::indices)
     });
 }
@@ -17,7 +17,7 @@ use crate::cformat::{
     CFormatPart, CFormatPreconversor, CFormatQuantity, CFormatSpec, CFormatString, CFormatType,
     CNumberType,
 };
-use crate::format::{FormatParseError, FormatPart, FormatPreconversor, FormatString};
+use crate::format::{FormatParseError, FormatPart, FormatPreconversor, FormatSpec, FormatString};
 use crate::function::{single_or_tuple_any, OptionalArg, PyFuncArgs};
 use crate::pyhash;
 use crate::pyobject::{
@@ -292,6 +292,15 @@ impl PyString {
         zelf
     }
 
+    #[pymethod(name = "__format__")]
+    fn format_str(&self, spec: PyStringRef, vm: &VirtualMachine) -> PyResult<String> {
+        let format_spec = FormatSpec::parse(&spec.value);
+        match format_spec.format_string(&self.value) {
+            Ok(string) => Ok(string),
+            Err(err) => Err(vm.new_value_error(err.to_string())),
+        }
+    }
+
     #[pymethod(name = "__repr__")]
     fn repr(&self, _vm: &VirtualMachine) -> String {
         let value = &self.value;
@@ -1169,6 +1178,32 @@ pub fn borrow_value(obj: &PyObjectRef) -> &str {
     &obj.payload::<PyString>().unwrap().value
 }
 
+/// Equivalent of the `ascii()` builtin: the repr of `obj` with non-ASCII characters escaped.
+pub fn ascii(obj: &PyObjectRef, vm: &VirtualMachine) -> PyResult<String> {
+    let repr = vm.to_repr(obj)?;
+    Ok(escape_non_ascii(&repr.value))
+}
+
+fn escape_non_ascii(s: &str) -> String {
+    let mut ascii = String::with_capacity(s.len());
+    for c in s.chars() {
+        if c.is_ascii() {
+            ascii.push(c)
+        } else {
+            let c = c as u32;
+            let hex = if c < 0x100 {
+                format!("\\x{:02x}", c)
+            } else if c < 0x10000 {
+                format!("\\u{:04x}", c)
+            } else {
+                format!("\\U{:08x}", c)
+            };
+            ascii.push_str(&hex)
+        }
+    }
+    ascii
+}
+
 fn count_char(s: &str, c: char) -> usize {
     s.chars().filter(|x| *x == c).count()
 }
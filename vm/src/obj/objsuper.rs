@@ -100,6 +100,10 @@ fn super_getattribute(vm: &VirtualMachine, args: PyFuncArgs) -> PyResult {
                         // This is a classmethod
                         return Ok(item);
                     }
+                    if objstr::get_value(name_str) == "__new__" {
+                        // __new__ is a static method, which takes the class explicitly
+                        return Ok(item);
+                    }
                     return Ok(vm.ctx.new_bound_method(item, inst.clone()));
                 }
             }
@@ -1,6 +1,9 @@
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 use std::collections::HashMap;
 use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use indexmap::IndexMap;
 
 use crate::function::{Args, KwArgs, PyFuncArgs};
 use crate::pyobject::{
@@ -9,11 +12,13 @@ use crate::pyobject::{
 };
 use crate::vm::VirtualMachine;
 
+use super::objclassmethod::PyClassMethod;
 use super::objdict::PyDictRef;
+use super::objfunction::PyFunction;
 use super::objlist::PyList;
 use super::objmappingproxy::PyMappingProxy;
-use super::objproperty::PropertyBuilder;
-use super::objstr::PyStringRef;
+use super::objproperty::{PropertyBuilder, PyReadOnlyProperty};
+use super::objstr::{PyString, PyStringRef};
 use super::objtuple::PyTuple;
 use super::objweakref::PyWeak;
 
@@ -23,6 +28,7 @@ pub struct PyClass {
     pub mro: Vec<PyClassRef>,
     pub subclasses: RefCell<Vec<PyWeak>>,
     pub attributes: RefCell<PyAttributes>,
+    pub version_tag: Cell<usize>,
 }
 
 impl fmt::Display for PyClass {
@@ -33,6 +39,28 @@ impl fmt::Display for PyClass {
 
 pub type PyClassRef = PyRef<PyClass>;
 
+static NEXT_VERSION_TAG: AtomicUsize = AtomicUsize::new(1);
+
+/// Get a version tag which no class has used before.
+pub fn next_version_tag() -> usize {
+    NEXT_VERSION_TAG.fetch_add(1, Ordering::Relaxed)
+}
+
+impl PyClass {
+    /// Give this class and all of its subclasses a new version tag, so that caches
+    /// of attribute lookups on them are invalidated.
+    pub fn modified(&self) {
+        self.version_tag.set(next_version_tag());
+        for subclass in self.subclasses.borrow().iter() {
+            if let Some(subclass) = subclass.upgrade() {
+                if let Some(subclass) = subclass.payload::<PyClass>() {
+                    subclass.modified();
+                }
+            }
+        }
+    }
+}
+
 impl PyValue for PyClass {
     fn class(vm: &VirtualMachine) -> PyClassRef {
         vm.ctx.type_type()
@@ -100,32 +128,43 @@ impl PyClassRef {
         issubclass(&subclass, &self)
     }
 
-    fn name(self, _vm: &VirtualMachine) -> String {
-        self.name.clone()
+    fn name(self, vm: &VirtualMachine) -> PyObjectRef {
+        self.str_attribute("__name__")
+            .unwrap_or_else(|| vm.ctx.new_str(self.name.clone()))
     }
 
     fn repr(self, _vm: &VirtualMachine) -> String {
         format!("<class '{}'>", self.name)
     }
 
-    fn qualname(self, vm: &VirtualMachine) -> PyObjectRef {
+    /// The class's own string attribute `name`. The attributes of `type` itself
+    /// hold the properties which read these, rather than strings.
+    fn str_attribute(&self, name: &str) -> Option<PyObjectRef> {
         self.attributes
             .borrow()
-            .get("__qualname__")
+            .get(name)
+            .filter(|value| value.payload_is::<PyString>())
             .cloned()
+    }
+
+    pub fn qualname(self, vm: &VirtualMachine) -> PyObjectRef {
+        self.str_attribute("__qualname__")
             .unwrap_or_else(|| vm.ctx.new_str(self.name.clone()))
     }
 
-    fn module(self, vm: &VirtualMachine) -> PyObjectRef {
+    pub fn module(self, vm: &VirtualMachine) -> PyObjectRef {
         // TODO: Implement getting the actual module a builtin type is from
-        self.attributes
-            .borrow()
-            .get("__module__")
-            .cloned()
+        self.str_attribute("__module__")
             .unwrap_or_else(|| vm.ctx.new_str("builtins".to_owned()))
     }
 
-    fn prepare(_name: PyStringRef, _bases: PyObjectRef, vm: &VirtualMachine) -> PyDictRef {
+    fn prepare(
+        _cls: PyObjectRef,
+        _name: PyStringRef,
+        _bases: PyObjectRef,
+        _kwargs: KwArgs,
+        vm: &VirtualMachine,
+    ) -> PyDictRef {
         vm.ctx.new_dict()
     }
 
@@ -136,7 +175,9 @@ impl PyClassRef {
 
         if let Some(attr) = class_get_attr(&mcl, &name) {
             let attr_class = attr.class();
-            if class_has_attr(&attr_class, "__set__") {
+            // The properties of the metaclass, like `__name__`, take precedence over
+            // the class's own attributes of the same name, which are for its instances.
+            if class_has_attr(&attr_class, "__set__") || attr.payload_is::<PyReadOnlyProperty>() {
                 if let Some(ref descriptor) = class_get_attr(&attr_class, "__get__") {
                     return vm.invoke(
                         descriptor,
@@ -181,6 +222,7 @@ impl PyClassRef {
         self.attributes
             .borrow_mut()
             .insert(attr_name.to_string(), value);
+        self.modified();
         Ok(())
     }
 
@@ -189,6 +231,7 @@ impl PyClassRef {
         self.attributes
             .borrow_mut()
             .insert(attr_name.to_string(), value.into());
+        self.modified();
     }
 
     fn subclasses(self, _vm: &VirtualMachine) -> PyList {
@@ -229,7 +272,7 @@ pub fn init(ctx: &PyContext) {
         "__repr__" => ctx.new_rustfunc(PyClassRef::repr),
         "__qualname__" => ctx.new_property(PyClassRef::qualname),
         "__module__" => ctx.new_property(PyClassRef::module),
-        "__prepare__" => ctx.new_rustfunc(PyClassRef::prepare),
+        "__prepare__" => ctx.new_classmethod(PyClassRef::prepare),
         "__getattribute__" => ctx.new_rustfunc(PyClassRef::getattribute),
         "__setattr__" => ctx.new_rustfunc(PyClassRef::set_attr),
         "__subclasses__" => ctx.new_rustfunc(PyClassRef::subclasses),
@@ -260,13 +303,17 @@ pub fn issubclass(subclass: &PyClassRef, cls: &PyClassRef) -> bool {
     subclass.is(cls) || mro.iter().any(|c| c.is(cls.as_object()))
 }
 
-pub fn type_new(vm: &VirtualMachine, args: PyFuncArgs) -> PyResult {
+pub fn type_new(vm: &VirtualMachine, mut args: PyFuncArgs) -> PyResult {
     vm_trace!("type.__new__ {:?}", args);
-    if args.args.len() == 2 {
+    if args.args.len() == 2 && args.kwargs.is_empty() {
         Ok(args.args[1].class().into_object())
     } else if args.args.len() == 4 {
+        // Keywords from the class statement go to __init_subclass__:
+        let kwargs = std::mem::replace(&mut args.kwargs, Default::default());
         let (typ, name, bases, dict) = args.bind(vm)?;
-        type_new_class(vm, typ, name, bases, dict).map(PyRef::into_object)
+        let new_class = type_new_class(vm, typ, name, bases, dict)?;
+        init_subclass(&new_class, kwargs, vm)?;
+        Ok(new_class.into_object())
     } else {
         Err(vm.new_type_error("type() takes 1 or 3 arguments".to_string()))
     }
@@ -281,7 +328,51 @@ pub fn type_new_class(
 ) -> PyResult<PyClassRef> {
     let mut bases: Vec<PyClassRef> = bases.iter(vm)?.collect::<Result<Vec<_>, _>>()?;
     bases.push(vm.ctx.object());
-    new(typ.clone(), &name.value, bases, dict.to_attributes())
+    let mut attributes = dict.to_attributes();
+    // __init_subclass__ and __class_getitem__ are implicitly classmethods:
+    for name in &["__init_subclass__", "__class_getitem__"] {
+        if let Some(function) = attributes.get_mut(*name) {
+            if function.payload_is::<PyFunction>() {
+                *function = PyObject::new(
+                    PyClassMethod {
+                        callable: function.clone(),
+                    },
+                    vm.ctx.classmethod_type(),
+                    None,
+                );
+            }
+        }
+    }
+    new(typ.clone(), &name.value, bases, attributes)
+}
+
+/// Call `__init_subclass__` of the first base of a new class which defines it.
+fn init_subclass(
+    new_class: &PyClassRef,
+    kwargs: IndexMap<String, PyObjectRef>,
+    vm: &VirtualMachine,
+) -> PyResult<()> {
+    let init = new_class
+        .mro
+        .iter()
+        .find_map(|base| base.attributes.borrow().get("__init_subclass__").cloned());
+    if let Some(init) = init {
+        let init = match class_get_attr(&init.class(), "__get__") {
+            Some(descriptor) => vm.invoke(
+                &descriptor,
+                vec![init, vm.get_none(), new_class.clone().into_object()],
+            )?,
+            None => init,
+        };
+        vm.invoke(
+            &init,
+            PyFuncArgs {
+                args: vec![],
+                kwargs,
+            },
+        )?;
+    }
+    Ok(())
 }
 
 pub fn type_call(class: PyClassRef, args: Args, kwargs: KwArgs, vm: &VirtualMachine) -> PyResult {
@@ -404,6 +495,7 @@ pub fn new(
             mro,
             subclasses: RefCell::new(vec![]),
             attributes: RefCell::new(dict),
+            version_tag: Cell::new(next_version_tag()),
         },
         dict: None,
         typ,
@@ -335,6 +335,14 @@ impl PyContext {
         self.types.generator_type.clone()
     }
 
+    pub fn coroutine_type(&self) -> PyClassRef {
+        self.types.coroutine_type.clone()
+    }
+
+    pub fn coroutine_wrapper_type(&self) -> PyClassRef {
+        self.types.coroutine_wrapper_type.clone()
+    }
+
     pub fn bound_method_type(&self) -> PyClassRef {
         self.types.bound_method_type.clone()
     }
@@ -347,6 +355,14 @@ impl PyContext {
         self.types.weakproxy_type.clone()
     }
 
+    pub fn traceback_type(&self) -> PyClassRef {
+        self.types.traceback_type.clone()
+    }
+
+    pub fn cell_type(&self) -> PyClassRef {
+        self.types.cell_type.clone()
+    }
+
     pub fn type_type(&self) -> PyClassRef {
         self.types.type_type.clone()
     }
@@ -478,9 +494,10 @@ impl PyContext {
         scope: Scope,
         defaults: Option<PyTupleRef>,
         kw_only_defaults: Option<PyDictRef>,
+        closure: Option<PyTupleRef>,
     ) -> PyObjectRef {
         PyObject::new(
-            PyFunction::new(code_obj, scope, defaults, kw_only_defaults),
+            PyFunction::new(code_obj, scope, defaults, kw_only_defaults, closure),
             self.function_type(),
             Some(self.new_dict()),
         )
@@ -648,6 +665,12 @@ impl<T> IntoPyObject for PyRef<T> {
     }
 }
 
+impl<T> IntoPyObject for &PyRef<T> {
+    fn into_pyobject(self, _vm: &VirtualMachine) -> PyResult {
+        Ok(self.obj.clone())
+    }
+}
+
 impl<'a, T: PyValue> From<&'a PyRef<T>> for &'a PyObjectRef {
     fn from(obj: &'a PyRef<T>) -> Self {
         obj.as_object()
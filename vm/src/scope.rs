@@ -72,14 +72,12 @@ impl fmt::Debug for Scope {
 }
 
 impl Scope {
-    pub fn new(locals: Option<PyDictRef>, globals: PyDictRef, vm: &VirtualMachine) -> Scope {
+    pub fn new(locals: Option<PyDictRef>, globals: PyDictRef) -> Scope {
         let locals = match locals {
             Some(dict) => RcList::new().insert(dict),
             None => RcList::new(),
         };
-        let scope = Scope { locals, globals };
-        scope.store_name(vm, "__annotations__", vm.ctx.new_dict().into_object());
-        scope
+        Scope { locals, globals }
     }
 
     pub fn with_builtins(
@@ -93,7 +91,7 @@ impl Scope {
                 .set_item("__builtins__", vm.builtins.clone(), vm)
                 .unwrap();
         }
-        Scope::new(locals, globals, vm)
+        Scope::new(locals, globals)
     }
 
     pub fn get_locals(&self) -> PyDictRef {
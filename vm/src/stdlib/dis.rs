@@ -1,20 +1,67 @@
+use crate::bytecode::{CodeObject, Constant, Instruction};
 use crate::obj::objcode::PyCodeRef;
+use crate::obj::objstr::PyStringRef;
 use crate::pyobject::{PyObjectRef, PyResult, TryFromObject};
 use crate::vm::VirtualMachine;
+use rustpython_compiler::compile;
 
-fn dis_dis(obj: PyObjectRef, vm: &VirtualMachine) -> PyResult {
-    // Method or function:
-    if let Ok(co) = vm.get_attribute(obj.clone(), "__code__") {
-        return dis_disassemble(co, vm);
+#[derive(FromArgs)]
+struct DisOptions {
+    #[pyarg(keyword_only, default = "None")]
+    file: Option<PyObjectRef>,
+}
+
+/// Disassemble a function, method, code object or source string, followed by
+/// the code objects nested in it.
+fn dis_dis(obj: PyObjectRef, options: DisOptions, vm: &VirtualMachine) -> PyResult<()> {
+    let code = get_code(obj, vm)?;
+    let mut output = String::new();
+    disassemble_recursive(&code.code, &mut output);
+    write_output(output, options.file, vm)
+}
+
+fn dis_disassemble(co: PyCodeRef, options: DisOptions, vm: &VirtualMachine) -> PyResult<()> {
+    write_output(co.code.display_line_numbers().to_string(), options.file, vm)
+}
+
+fn get_code(obj: PyObjectRef, vm: &VirtualMachine) -> PyResult<PyCodeRef> {
+    if let Ok(source) = PyStringRef::try_from_object(vm, obj.clone()) {
+        // Like CPython, try the source as an expression before compiling it as statements:
+        let source_path = "<dis>".to_string();
+        return vm
+            .compile(source.as_str(), compile::Mode::Eval, source_path.clone())
+            .or_else(|_| vm.compile(source.as_str(), compile::Mode::Exec, source_path))
+            .map_err(|err| vm.new_syntax_error(&err));
     }
+    // Method or function:
+    let obj = match vm.get_attribute(obj.clone(), "__code__") {
+        Ok(code) => code,
+        Err(_) => obj,
+    };
+    PyCodeRef::try_from_object(vm, obj)
+}
 
-    dis_disassemble(obj, vm)
+fn disassemble_recursive(code: &CodeObject, output: &mut String) {
+    output.push_str(&code.display_line_numbers().to_string());
+    for instruction in &code.instructions {
+        if let Instruction::LoadConst {
+            value: Constant::Code { code },
+        } = instruction
+        {
+            output.push_str(&format!("\nDisassembly of {:?}:\n", code));
+            disassemble_recursive(code, output);
+        }
+    }
 }
 
-fn dis_disassemble(co: PyObjectRef, vm: &VirtualMachine) -> PyResult {
-    let code = &PyCodeRef::try_from_object(vm, co)?.code;
-    print!("{}", code);
-    Ok(vm.get_none())
+fn write_output(output: String, file: Option<PyObjectRef>, vm: &VirtualMachine) -> PyResult<()> {
+    match file {
+        Some(file) => {
+            vm.call_method(&file, "write", vec![vm.new_str(output)])?;
+        }
+        None => print!("{}", output),
+    }
+    Ok(())
 }
 
 pub fn make_module(vm: &VirtualMachine) -> PyObjectRef {
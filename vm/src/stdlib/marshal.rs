@@ -2,16 +2,20 @@ use crate::bytecode;
 use crate::obj::objbytes::{PyBytes, PyBytesRef};
 use crate::obj::objcode::{PyCode, PyCodeRef};
 use crate::pyobject::{PyObjectRef, PyResult};
+use crate::verify::verify;
 use crate::vm::VirtualMachine;
 
 fn marshal_dumps(co: PyCodeRef, _vm: &VirtualMachine) -> PyBytes {
-    PyBytes::new(bincode::serialize(&co.code).unwrap())
+    PyBytes::new(co.code.to_bytes())
 }
 
 fn marshal_loads(code_bytes: PyBytesRef, vm: &VirtualMachine) -> PyResult<PyCode> {
-    let code = bincode::deserialize::<bytecode::CodeObject>(&code_bytes)
-        .map_err(|_| vm.new_value_error("Couldn't deserialize python bytecode".to_owned()))?;
-    Ok(PyCode { code })
+    let code = bytecode::CodeObject::from_bytes(&code_bytes).map_err(|err| {
+        vm.new_value_error(format!("Couldn't deserialize python bytecode: {}", err))
+    })?;
+    // The frame trusts the code it runs, so reject malformed bytecode here:
+    verify(&code).map_err(|err| vm.new_value_error(format!("bad bytecode: {}", err)))?;
+    Ok(PyCode::new(code))
 }
 
 pub fn make_module(vm: &VirtualMachine) -> PyObjectRef {
@@ -28,10 +28,14 @@ const NSIG: usize = 64;
 // We cannot use the NSIG const in the arr macro. This will fail compilation if NSIG is different.
 static mut TRIGGERS: [AtomicBool; NSIG] = arr![AtomicBool::new(false); 64];
 
+// Set when any of the TRIGGERS is, so that checking for no signals is cheap.
+static ANY_TRIGGERED: AtomicBool = AtomicBool::new(false);
+
 extern "C" fn run_signal(signum: i32) {
     unsafe {
         TRIGGERS[signum as usize].store(true, Ordering::Relaxed);
     }
+    ANY_TRIGGERED.store(true, Ordering::SeqCst);
 }
 
 fn signal(
@@ -49,7 +53,7 @@ fn signal(
     let sig_dfl = vm.get_attribute(signal_module.clone(), "SIG_DFL")?;
     let sig_ign = vm.get_attribute(signal_module, "SIG_IGN")?;
     let signalnum = signalnum.as_bigint().to_i32().unwrap();
-    check_signals(vm);
+    check_signals(vm)?;
     let sig_handler = if handler.is(&sig_dfl) {
         SIG_DFL
     } else if handler.is(&sig_ign) {
@@ -81,8 +85,13 @@ fn alarm(time: PyIntRef, _vm: &VirtualMachine) -> u32 {
     prev_time.unwrap_or(0)
 }
 
+/// Run the handlers of the signals which arrived since the last check. An
+/// exception raised by a handler is raised from here.
 #[allow(clippy::needless_range_loop)]
-pub fn check_signals(vm: &VirtualMachine) {
+pub fn check_signals(vm: &VirtualMachine) -> PyResult<()> {
+    if !ANY_TRIGGERED.swap(false, Ordering::SeqCst) {
+        return Ok(());
+    }
     for signum in 1..NSIG {
         let triggerd = unsafe { TRIGGERS[signum].swap(false, Ordering::Relaxed) };
         if triggerd {
@@ -92,10 +101,14 @@ pub fn check_signals(vm: &VirtualMachine) {
                 .get(&(signum as i32))
                 .expect("Handler should be set")
                 .clone();
-            vm.invoke(&handler, vec![vm.new_int(signum), vm.get_none()])
-                .expect("Test");
+            if let Err(exc) = vm.invoke(&handler, vec![vm.new_int(signum), vm.get_none()]) {
+                // Leave the signals which are still pending for the next check:
+                ANY_TRIGGERED.store(true, Ordering::SeqCst);
+                return Err(exc);
+            }
         }
     }
+    Ok(())
 }
 
 fn stub_func(_vm: &VirtualMachine) -> PyResult {
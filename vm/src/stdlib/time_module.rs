@@ -29,6 +29,9 @@ fn time_sleep(vm: &VirtualMachine, args: PyFuncArgs) -> PyResult {
     let nanos: u32 = (seconds.fract() * 1e9) as u32;
     let duration = Duration::new(secs, nanos);
     thread::sleep(duration);
+    // Handle the signals which arrived while sleeping straight away:
+    #[cfg(not(target_arch = "wasm32"))]
+    crate::stdlib::signal::check_signals(vm)?;
     Ok(vm.get_none())
 }
 
@@ -1,5 +1,6 @@
 use crate::function::OptionalArg;
 use crate::obj::objstr::PyStringRef;
+use crate::obj::objtype::PyClass;
 use crate::pyobject::PyObjectRef;
 use crate::vm::VirtualMachine;
 
@@ -19,10 +20,17 @@ fn warnings_warn(args: WarnArgs, _vm: &VirtualMachine) {
         OptionalArg::Present(l) => l,
         OptionalArg::Missing => 1,
     };
+    let category = match args.category {
+        OptionalArg::Present(ref category) => match category.payload::<PyClass>() {
+            Some(category) => category.name.clone(),
+            None => format!("{:?}", category),
+        },
+        OptionalArg::Missing => "UserWarning".to_string(),
+    };
     eprintln!(
-        "Warning: {} , category: {:?}, level: {}",
+        "Warning: {} , category: {}, level: {}",
         args.message.as_str(),
-        args.category,
+        category,
         level
     )
 }
@@ -3,6 +3,7 @@ use std::{env, mem};
 
 use crate::frame::FrameRef;
 use crate::function::{OptionalArg, PyFuncArgs};
+use crate::obj::objfloat;
 use crate::obj::objstr::PyStringRef;
 use crate::pyobject::{
     IntoPyObject, ItemProtocol, PyClassImpl, PyContext, PyObjectRef, PyResult, TypeProtocol,
@@ -34,12 +35,11 @@ fn executable(ctx: &PyContext) -> PyObjectRef {
 
 fn getframe(offset: OptionalArg<usize>, vm: &VirtualMachine) -> PyResult<FrameRef> {
     let offset = offset.into_option().unwrap_or(0);
-    if offset > vm.frames.borrow().len() - 1 {
+    let frames = vm.frames.borrow();
+    if offset >= frames.len() {
         return Err(vm.new_value_error("call stack is not deep enough".to_string()));
     }
-    let idx = vm.frames.borrow().len() - offset - 1;
-    let frame = &vm.frames.borrow()[idx];
-    Ok(frame.clone())
+    Ok(frames[frames.len() - offset - 1].clone())
 }
 
 /// sys.flags
@@ -151,6 +151,51 @@ fn update_use_tracing(vm: &VirtualMachine) {
     vm.use_tracing.replace(tracing);
 }
 
+fn sys_getrecursionlimit(vm: &VirtualMachine) -> usize {
+    *vm.recursion_limit.borrow()
+}
+
+fn sys_setrecursionlimit(recursion_limit: i32, vm: &VirtualMachine) -> PyResult<()> {
+    if recursion_limit < 1 {
+        return Err(
+            vm.new_value_error("recursion limit must be greater or equal than 1".to_string())
+        );
+    }
+    let recursion_limit = recursion_limit as usize;
+    let depth = vm.frames.borrow().len();
+    if recursion_limit <= depth {
+        return Err(vm.new_exception(
+            vm.ctx.exceptions.recursion_error.clone(),
+            format!(
+                "cannot set the recursion limit to {} at the recursion depth {}: the limit is too low",
+                recursion_limit, depth
+            ),
+        ));
+    }
+    vm.recursion_limit.replace(recursion_limit);
+    Ok(())
+}
+
+fn sys_getswitchinterval(vm: &VirtualMachine) -> f64 {
+    *vm.switch_interval.borrow()
+}
+
+// There are no threads to switch between, but the interval also sets how
+// often pending signals are checked for.
+fn sys_setswitchinterval(interval: PyObjectRef, vm: &VirtualMachine) -> PyResult<()> {
+    let interval = objfloat::try_float(&interval, vm)?.ok_or_else(|| {
+        vm.new_type_error(format!(
+            "must be real number, not {}",
+            interval.class().name
+        ))
+    })?;
+    if interval <= 0.0 {
+        return Err(vm.new_value_error("switch interval must be strictly positive".to_string()));
+    }
+    vm.switch_interval.replace(interval);
+    Ok(())
+}
+
 // TODO implement string interning, this will be key for performance
 fn sys_intern(value: PyStringRef, _vm: &VirtualMachine) -> PyStringRef {
     value
@@ -161,12 +206,79 @@ fn sys_exc_info(vm: &VirtualMachine) -> PyResult {
         Some(exception) => vec![
             exception.class().into_object(),
             exception.clone(),
-            vm.get_none(),
+            vm.get_attribute(exception, "__traceback__")?,
         ],
         None => vec![vm.get_none(), vm.get_none(), vm.get_none()],
     }))
 }
 
+/// The number of times each kind of instruction has run, by its name.
+#[cfg(feature = "vm-opcode-stats")]
+fn sys_opcode_stats(vm: &VirtualMachine) -> PyResult<crate::obj::objdict::PyDictRef> {
+    let stats = vm.ctx.new_dict();
+    let opcode_stats = vm.opcode_stats.borrow();
+    let mut counts: Vec<_> = opcode_stats.values().collect();
+    counts.sort();
+    for (name, count) in counts {
+        stats.set_item(name.as_str(), vm.new_int(*count), vm)?;
+    }
+    Ok(stats)
+}
+
+/// Default `sys.displayhook`: print the repr of a non-None value and store it in `builtins._`.
+pub fn sys_displayhook(obj: PyObjectRef, vm: &VirtualMachine) -> PyResult<()> {
+    // Save non-None values as "_"
+    if vm.is_none(&obj) {
+        return Ok(());
+    }
+    // set to none to avoid recursion while printing
+    vm.set_attr(&vm.builtins, "_", vm.get_none())?;
+    let repr = vm.to_repr(&obj)?.into_object();
+    let print = vm.get_attribute(vm.builtins.clone(), "print")?;
+    vm.invoke(&print, vec![repr])?;
+    vm.set_attr(&vm.builtins, "_", obj)?;
+    Ok(())
+}
+
+/// Default `sys.breakpointhook`: call the function named by `$PYTHONBREAKPOINT`,
+/// `pdb.set_trace` if it is unset, or do nothing if it is `0`.
+fn sys_breakpointhook(vm: &VirtualMachine, args: PyFuncArgs) -> PyResult {
+    let hook_name = if vm.settings.ignore_environment {
+        String::new()
+    } else {
+        env::var("PYTHONBREAKPOINT").unwrap_or_default()
+    };
+    let hook_name = match hook_name.as_str() {
+        "0" => return Ok(vm.get_none()),
+        "" => "pdb.set_trace",
+        hook_name => hook_name,
+    };
+    let (module_name, attr) = match hook_name.rfind('.') {
+        Some(dot) => (&hook_name[..dot], &hook_name[dot + 1..]),
+        None => ("builtins", hook_name),
+    };
+    let from_list = vm.ctx.new_tuple(vec![vm.new_str(attr.to_string())]);
+    let hook = vm
+        .import(module_name, &from_list, 0)
+        .and_then(|module| vm.get_attribute(module, attr));
+    match hook {
+        Ok(hook) => vm.invoke(&hook, args),
+        Err(_) => {
+            let warnings = vm.import("_warnings", &vm.ctx.new_tuple(vec![]), 0)?;
+            let message = format!("Ignoring unimportable $PYTHONBREAKPOINT: \"{}\"", hook_name);
+            let warn = vm.get_attribute(warnings, "warn")?;
+            vm.invoke(
+                &warn,
+                vec![
+                    vm.new_str(message),
+                    vm.ctx.exceptions.runtime_warning.clone().into_object(),
+                ],
+            )?;
+            Ok(vm.get_none())
+        }
+    }
+}
+
 // TODO: raise a SystemExit here
 fn sys_exit(code: OptionalArg<i32>, _vm: &VirtualMachine) -> PyResult<()> {
     let code = code.unwrap_or(0);
@@ -289,10 +401,12 @@ __stdin__ -- the original stdin; don't touch!
 __stdout__ -- the original stdout; don't touch!
 __stderr__ -- the original stderr; don't touch!
 __displayhook__ -- the original displayhook; don't touch!
+__breakpointhook__ -- the original breakpointhook; don't touch!
 __excepthook__ -- the original excepthook; don't touch!
 
 Functions:
 
+breakpointhook() -- called by breakpoint(), starts the debugger
 displayhook() -- print an object to the screen, and save it in builtins._
 excepthook() -- print an exception and its traceback to sys.stderr
 exc_info() -- return thread-safe information about the current exception
@@ -302,11 +416,13 @@ getprofile() -- get the global profiling function
 getrefcount() -- return the reference count for an object (plus one :-)
 getrecursionlimit() -- return the max recursion depth for the interpreter
 getsizeof() -- return the size of an object in bytes
+getswitchinterval() -- get the interval between checks for pending signals
 gettrace() -- get the global debug tracing function
 setcheckinterval() -- control how often the interpreter checks for events
 setdlopenflags() -- set the flags to be used for dlopen() calls
 setprofile() -- set the global profiling function
 setrecursionlimit() -- set the max recursion depth for the interpreter
+setswitchinterval() -- set the interval between checks for pending signals
 settrace() -- set the global debug tracing function
 ";
     let mut module_names: Vec<String> = vm.stdlib_inits.borrow().keys().cloned().collect();
@@ -329,8 +445,12 @@ settrace() -- set the global debug tracing function
       "__name__" => ctx.new_str(String::from("sys")),
       "argv" => argv(vm),
       "builtin_module_names" => builtin_module_names,
+      "breakpointhook" => ctx.new_rustfunc(sys_breakpointhook),
+      "__breakpointhook__" => ctx.new_rustfunc(sys_breakpointhook),
       "byteorder" => ctx.new_str(bytorder),
       "copyright" => ctx.new_str(copyright.to_string()),
+      "displayhook" => ctx.new_rustfunc(sys_displayhook),
+      "__displayhook__" => ctx.new_rustfunc(sys_displayhook),
       "executable" => executable(ctx),
       "flags" => flags,
       "getrefcount" => ctx.new_rustfunc(sys_getrefcount),
@@ -339,6 +459,8 @@ settrace() -- set the global debug tracing function
       "getfilesystemencoding" => ctx.new_rustfunc(sys_getfilesystemencoding),
       "getfilesystemencodeerrors" => ctx.new_rustfunc(sys_getfilesystemencodeerrors),
       "getprofile" => ctx.new_rustfunc(sys_getprofile),
+      "getrecursionlimit" => ctx.new_rustfunc(sys_getrecursionlimit),
+      "getswitchinterval" => ctx.new_rustfunc(sys_getswitchinterval),
       "gettrace" => ctx.new_rustfunc(sys_gettrace),
       "intern" => ctx.new_rustfunc(sys_intern),
       "maxunicode" => ctx.new_int(0x0010_FFFF),
@@ -357,6 +479,8 @@ settrace() -- set the global debug tracing function
       "pycache_prefix" => vm.get_none(),
       "dont_write_bytecode" => vm.new_bool(vm.settings.dont_write_bytecode),
       "setprofile" => ctx.new_rustfunc(sys_setprofile),
+      "setrecursionlimit" => ctx.new_rustfunc(sys_setrecursionlimit),
+      "setswitchinterval" => ctx.new_rustfunc(sys_setswitchinterval),
       "settrace" => ctx.new_rustfunc(sys_settrace),
       "version" => vm.new_str(version::get_version()),
       "version_info" => version_info,
@@ -367,6 +491,11 @@ settrace() -- set the global debug tracing function
       "exit" => ctx.new_rustfunc(sys_exit),
     });
 
+    #[cfg(feature = "vm-opcode-stats")]
+    extend_module!(vm, module, {
+      "_opcode_stats" => ctx.new_rustfunc(sys_opcode_stats),
+    });
+
     modules.set_item("sys", module.clone(), vm).unwrap();
     modules.set_item("builtins", builtins.clone(), vm).unwrap();
 }
@@ -1,9 +1,11 @@
 use crate::obj::objbool;
 use crate::obj::objbytearray;
 use crate::obj::objbytes;
+use crate::obj::objcell;
 use crate::obj::objclassmethod;
 use crate::obj::objcode;
 use crate::obj::objcomplex;
+use crate::obj::objcoroutine;
 use crate::obj::objdict;
 use crate::obj::objellipsis;
 use crate::obj::objenumerate;
@@ -12,6 +14,7 @@ use crate::obj::objfloat;
 use crate::obj::objframe;
 use crate::obj::objfunction;
 use crate::obj::objgenerator;
+use crate::obj::objgenericalias;
 use crate::obj::objint;
 use crate::obj::objiter;
 use crate::obj::objlist;
@@ -29,13 +32,14 @@ use crate::obj::objslice;
 use crate::obj::objstaticmethod;
 use crate::obj::objstr;
 use crate::obj::objsuper;
+use crate::obj::objtraceback;
 use crate::obj::objtuple;
 use crate::obj::objtype::{self, PyClass, PyClassRef};
 use crate::obj::objweakproxy;
 use crate::obj::objweakref;
 use crate::obj::objzip;
 use crate::pyobject::{PyAttributes, PyContext, PyObject, PyObjectRef};
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 use std::mem;
 use std::ptr;
 
@@ -47,6 +51,7 @@ pub struct TypeZoo {
     pub bytearray_type: PyClassRef,
     pub bytearrayiterator_type: PyClassRef,
     pub bool_type: PyClassRef,
+    pub cell_type: PyClassRef,
     pub classmethod_type: PyClassRef,
     pub code_type: PyClassRef,
     pub dict_type: PyClassRef,
@@ -56,6 +61,9 @@ pub struct TypeZoo {
     pub frame_type: PyClassRef,
     pub frozenset_type: PyClassRef,
     pub generator_type: PyClassRef,
+    pub generic_alias_type: PyClassRef,
+    pub coroutine_type: PyClassRef,
+    pub coroutine_wrapper_type: PyClassRef,
     pub int_type: PyClassRef,
     pub iter_type: PyClassRef,
     pub complex_type: PyClassRef,
@@ -83,6 +91,7 @@ pub struct TypeZoo {
     pub slice_type: PyClassRef,
     pub type_type: PyClassRef,
     pub zip_type: PyClassRef,
+    pub traceback_type: PyClassRef,
     pub function_type: PyClassRef,
     pub builtin_function_or_method_type: PyClassRef,
     pub property_type: PyClassRef,
@@ -120,6 +129,11 @@ impl TypeZoo {
         let weakref_type = create_type("ref", &type_type, &object_type);
         let weakproxy_type = create_type("weakproxy", &type_type, &object_type);
         let generator_type = create_type("generator", &type_type, &object_type);
+        let generic_alias_type = create_type("GenericAlias", &type_type, &object_type);
+        let coroutine_type = create_type("coroutine", &type_type, &object_type);
+        let coroutine_wrapper_type = create_type("coroutine_wrapper", &type_type, &object_type);
+        let traceback_type = create_type("traceback", &type_type, &object_type);
+        let cell_type = create_type("cell", &type_type, &object_type);
         let bound_method_type = create_type("method", &type_type, &object_type);
         let str_type = create_type("str", &type_type, &object_type);
         let list_type = create_type("list", &type_type, &object_type);
@@ -206,6 +220,11 @@ impl TypeZoo {
             property_type,
             readonly_property_type,
             generator_type,
+            generic_alias_type,
+            coroutine_type,
+            coroutine_wrapper_type,
+            traceback_type,
+            cell_type,
             module_type,
             namespace_type,
             bound_method_type,
@@ -235,6 +254,7 @@ fn init_type_hierarchy() -> (PyClassRef, PyClassRef) {
                 mro: vec![],
                 subclasses: RefCell::new(vec![]),
                 attributes: RefCell::new(PyAttributes::new()),
+                version_tag: Cell::new(objtype::next_version_tag()),
             },
         }
         .into_ref();
@@ -247,6 +267,7 @@ fn init_type_hierarchy() -> (PyClassRef, PyClassRef) {
                 mro: vec![object_type.clone().downcast().unwrap()],
                 subclasses: RefCell::new(vec![]),
                 attributes: RefCell::new(PyAttributes::new()),
+                version_tag: Cell::new(objtype::next_version_tag()),
             },
         }
         .into_ref();
@@ -283,6 +304,7 @@ pub fn initialize_types(context: &PyContext) {
     objstaticmethod::init(&context);
     objclassmethod::init(&context);
     objgenerator::init(&context);
+    objcoroutine::init(&context);
     objint::init(&context);
     objfloat::init(&context);
     objcomplex::init(&context);
@@ -304,10 +326,13 @@ pub fn initialize_types(context: &PyContext) {
     objbool::init(&context);
     objcode::init(&context);
     objframe::init(&context);
+    objtraceback::init(&context);
+    objcell::init(&context);
     objweakref::init(&context);
     objweakproxy::init(&context);
     objnone::init(&context);
     objmodule::init(&context);
     objnamespace::init(&context);
     objmappingproxy::init(&context);
+    objgenericalias::init(&context);
 }
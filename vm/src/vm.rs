@@ -4,7 +4,7 @@
 //!   https://github.com/ProgVal/pythonvm-rust/blob/master/src/processor/mod.rs
 //!
 
-use std::cell::{Ref, RefCell};
+use std::cell::{Cell, Ref, RefCell};
 use std::collections::hash_map::HashMap;
 use std::collections::hash_set::HashSet;
 use std::fmt;
@@ -20,6 +20,7 @@ use crate::import;
 use crate::obj::objbool;
 use crate::obj::objbuiltinfunc::PyBuiltinFunction;
 use crate::obj::objcode::{PyCode, PyCodeRef};
+use crate::obj::objcoroutine::PyCoroutine;
 use crate::obj::objdict::PyDictRef;
 use crate::obj::objfunction::{PyFunction, PyMethod};
 use crate::obj::objgenerator::PyGenerator;
@@ -45,6 +46,22 @@ use rustpython_compiler::{compile, error::CompileError};
 
 // use objects::objects;
 
+/// The default maximum depth of the Python frame stack, as in CPython.
+pub const DEFAULT_RECURSION_LIMIT: usize = 1000;
+
+/// The default `sys.getswitchinterval()`, in seconds, as in CPython.
+pub const DEFAULT_SWITCH_INTERVAL: f64 = 0.005;
+
+/// How many jumps back, which is about how many loop iterations, run between
+/// checks for pending signals at the default switch interval. Other intervals
+/// scale this in proportion.
+const SIGNAL_CHECK_BACK_EDGES: f64 = 100.0;
+
+/// A function which evaluates frames in place of the interpreter loop, like
+/// a JIT would. It can hand frames it does not handle to
+/// `VirtualMachine::eval_frame_default`.
+pub type EvalFrameFunc = Rc<dyn Fn(&VirtualMachine, FrameRef) -> PyResult<ExecutionResult>>;
+
 // Objects are live when they are on stack, or referenced by a name (for now)
 
 /// Top level container of a python virtual machine. In theory you could
@@ -62,8 +79,21 @@ pub struct VirtualMachine {
     pub profile_func: RefCell<PyObjectRef>,
     pub trace_func: RefCell<PyObjectRef>,
     pub use_tracing: RefCell<bool>,
+    pub recursion_limit: RefCell<usize>,
+    /// The most values a frame may hold on its stack, to bound the memory
+    /// that untrusted code can use. `None` means no limit.
+    pub stack_limit: Cell<Option<usize>>,
     pub settings: PySettings,
     pub signal_handlers: RefCell<HashMap<i32, PyObjectRef>>,
+    pub switch_interval: RefCell<f64>,
+    /// Jumps back left before the next check for pending signals.
+    signal_countdown: Cell<usize>,
+    /// The evaluator of new frames, if not the default interpreter loop.
+    eval_frame: RefCell<Option<EvalFrameFunc>>,
+    /// How many times each kind of instruction has run, with its name.
+    #[cfg(feature = "vm-opcode-stats")]
+    pub opcode_stats:
+        RefCell<HashMap<std::mem::Discriminant<bytecode::Instruction>, (String, usize)>>,
 }
 
 /// Struct containing all kind of settings for the python vm.
@@ -103,9 +133,12 @@ pub struct PySettings {
 }
 
 /// Trace events for sys.settrace and sys.setprofile.
-enum TraceEvent {
+pub enum TraceEvent {
     Call,
+    Line,
     Return,
+    Exception,
+    Opcode,
 }
 
 impl fmt::Display for TraceEvent {
@@ -113,7 +146,10 @@ impl fmt::Display for TraceEvent {
         use TraceEvent::*;
         match self {
             Call => write!(f, "call"),
+            Line => write!(f, "line"),
             Return => write!(f, "return"),
+            Exception => write!(f, "exception"),
+            Opcode => write!(f, "opcode"),
         }
     }
 }
@@ -179,8 +215,15 @@ impl VirtualMachine {
             profile_func,
             trace_func,
             use_tracing: RefCell::new(false),
+            recursion_limit: RefCell::new(DEFAULT_RECURSION_LIMIT),
+            stack_limit: Cell::new(None),
             settings,
             signal_handlers: Default::default(),
+            switch_interval: RefCell::new(DEFAULT_SWITCH_INTERVAL),
+            signal_countdown: Cell::new(0),
+            eval_frame: RefCell::new(None),
+            #[cfg(feature = "vm-opcode-stats")]
+            opcode_stats: Default::default(),
         };
 
         objmodule::init_module_dict(
@@ -202,7 +245,7 @@ impl VirtualMachine {
     }
 
     pub fn run_code_obj(&self, code: PyCodeRef, scope: Scope) -> PyResult {
-        let frame = Frame::new(code, scope).into_ref(self);
+        let frame = Frame::new(code, scope, None, self).into_ref(self);
         self.run_frame_full(frame)
     }
 
@@ -213,11 +256,30 @@ impl VirtualMachine {
         }
     }
 
+    /// Run a frame from its start, with the evaluator set by `set_eval_frame_func`.
     pub fn run_frame(&self, frame: FrameRef) -> PyResult<ExecutionResult> {
-        self.frames.borrow_mut().push(frame.clone());
-        let result = frame.run(self);
-        self.frames.borrow_mut().pop();
-        result
+        let eval_frame = self.eval_frame.borrow().clone();
+        self.with_frame(frame, |frame| match eval_frame {
+            Some(eval_frame) => eval_frame(self, frame),
+            None => self.eval_frame_default(frame),
+        })
+    }
+
+    /// Run a frame with the interpreter loop.
+    pub fn eval_frame_default(&self, frame: FrameRef) -> PyResult<ExecutionResult> {
+        frame.run(self, None)
+    }
+
+    /// Replace the evaluator of frames which start running, or restore the
+    /// default one with `None`. Suspended generators and coroutines resume in
+    /// the interpreter loop.
+    pub fn set_eval_frame_func(&self, eval_frame: Option<EvalFrameFunc>) {
+        self.eval_frame.replace(eval_frame);
+    }
+
+    /// Resume a suspended frame, making `value` the result of the pending `yield`.
+    pub fn resume_frame(&self, frame: FrameRef, value: PyObjectRef) -> PyResult<ExecutionResult> {
+        self.with_frame(frame, |frame| frame.run(self, Some(value)))
     }
 
     pub fn frame_throw(
@@ -225,12 +287,81 @@ impl VirtualMachine {
         frame: FrameRef,
         exception: PyObjectRef,
     ) -> PyResult<ExecutionResult> {
+        self.with_frame(frame, |frame| frame.throw(self, exception))
+    }
+
+    /// Push `frame` on the frame stack while running `f`, guarding against runaway recursion.
+    fn with_frame<F: FnOnce(FrameRef) -> PyResult<ExecutionResult>>(
+        &self,
+        frame: FrameRef,
+        f: F,
+    ) -> PyResult<ExecutionResult> {
+        if self.frames.borrow().len() >= *self.recursion_limit.borrow() {
+            return Err(self.new_exception(
+                self.ctx.exceptions.recursion_error.clone(),
+                "maximum recursion depth exceeded".to_string(),
+            ));
+        }
         self.frames.borrow_mut().push(frame.clone());
-        let result = frame.throw(self, exception);
+        let result = self
+            .trace_event(TraceEvent::Call, self.get_none())
+            .and_then(|()| match f(frame) {
+                Ok(result) => {
+                    let value = match &result {
+                        ExecutionResult::Return(value) | ExecutionResult::Yield(value) => value,
+                    };
+                    self.trace_event(TraceEvent::Return, value.clone())?;
+                    Ok(result)
+                }
+                Err(exception) => {
+                    // A frame exited by an exception reports a return of None.
+                    self.trace_event(TraceEvent::Return, self.get_none())?;
+                    Err(exception)
+                }
+            });
+        // Pop the frame even when it exits via an exception:
         self.frames.borrow_mut().pop();
         result
     }
 
+    /// Run the handlers of any signals which arrived, once every few loop
+    /// iterations as set by `sys.setswitchinterval`. Frames call this when
+    /// they jump back.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn check_signals_periodically(&self) -> PyResult<()> {
+        let countdown = self.signal_countdown.get();
+        if countdown > 0 {
+            self.signal_countdown.set(countdown - 1);
+            return Ok(());
+        }
+        let interval = *self.switch_interval.borrow() / DEFAULT_SWITCH_INTERVAL;
+        let back_edges = (interval * SIGNAL_CHECK_BACK_EDGES).ceil() as usize;
+        self.signal_countdown.set(back_edges.max(1) - 1);
+        stdlib::signal::check_signals(self)
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    pub fn check_signals_periodically(&self) -> PyResult<()> {
+        Ok(())
+    }
+
+    /// Count an instruction about to be executed, for `sys._opcode_stats`.
+    #[cfg(feature = "vm-opcode-stats")]
+    pub fn count_opcode(&self, instruction: &bytecode::Instruction) {
+        self.opcode_stats
+            .borrow_mut()
+            .entry(std::mem::discriminant(instruction))
+            .or_insert_with(|| {
+                // The name of the variant is the start of its debug output:
+                let name = format!("{:?}", instruction)
+                    .chars()
+                    .take_while(|c| c.is_alphanumeric())
+                    .collect();
+                (name, 0)
+            })
+            .1 += 1;
+    }
+
     pub fn current_frame(&self) -> Option<Ref<FrameRef>> {
         let frames = self.frames.borrow();
         if frames.is_empty() {
@@ -294,7 +425,7 @@ impl VirtualMachine {
     }
 
     #[cfg_attr(feature = "flame-it", flame("VirtualMachine"))]
-    fn new_exception_obj(&self, exc_type: PyClassRef, args: Vec<PyObjectRef>) -> PyResult {
+    pub fn new_exception_obj(&self, exc_type: PyClassRef, args: Vec<PyObjectRef>) -> PyResult {
         // TODO: add repr of args into logging?
         vm_trace!("New exception created: {}", exc_type.name);
         self.invoke(&exc_type.into_object(), args)
@@ -337,7 +468,7 @@ impl VirtualMachine {
         op: &str,
     ) -> PyObjectRef {
         self.new_type_error(format!(
-            "Unsupported operand types for '{}': '{}' and '{}'",
+            "unsupported operand type(s) for {}: '{}' and '{}'",
             op,
             a.class().name,
             b.class().name
@@ -422,7 +553,12 @@ impl VirtualMachine {
     }
 
     pub fn get_locals(&self) -> PyDictRef {
-        self.current_scope().get_locals().clone()
+        let frame = self
+            .current_frame()
+            .expect("called get_locals but no frames on the stack");
+        // Assigning fast locals can't fail, a dict assignment by a str key doesn't call Python code.
+        frame.fast_to_locals(self).unwrap();
+        frame.scope.get_locals().clone()
     }
 
     pub fn context(&self) -> &PyContext {
@@ -549,17 +685,8 @@ impl VirtualMachine {
     fn _invoke(&self, func_ref: &PyObjectRef, args: PyFuncArgs) -> PyResult {
         vm_trace!("Invoke: {:?} {:?}", func_ref, args);
 
-        if let Some(PyFunction {
-            ref code,
-            ref scope,
-            ref defaults,
-            ref kw_only_defaults,
-        }) = func_ref.payload()
-        {
-            self.trace_event(TraceEvent::Call)?;
-            let res = self.invoke_python_function(code, scope, defaults, kw_only_defaults, args);
-            self.trace_event(TraceEvent::Return)?;
-            res
+        if let Some(function) = func_ref.payload::<PyFunction>() {
+            self.invoke_python_function(func_ref, function, args)
         } else if let Some(PyMethod {
             ref function,
             ref object,
@@ -585,73 +712,131 @@ impl VirtualMachine {
     }
 
     /// Call registered trace function.
-    fn trace_event(&self, event: TraceEvent) -> PyResult<()> {
+    ///
+    /// The global trace function receives `call` events, and what it returns becomes the
+    /// frame's local trace function (`f_trace`) which receives the `line`, `exception` and
+    /// `return` events, and `opcode` events if the frame's `f_trace_opcodes` is set. The
+    /// profile function only receives `call` and `return` events.
+    pub fn trace_event(&self, event: TraceEvent, arg: PyObjectRef) -> PyResult<()> {
         if *self.use_tracing.borrow() {
-            let frame = self.get_none();
-            let event = self.new_str(event.to_string());
-            let arg = self.get_none();
-            let args = vec![frame, event, arg];
+            let frame = self
+                .current_frame()
+                .expect("trace event without a running frame")
+                .clone();
+            let event_name = self.new_str(event.to_string());
+            let args = vec![frame.clone().into_object(), event_name, arg];
 
             // temporarily disable tracing, during the call to the
             // tracing function itself.
-            let trace_func = self.trace_func.borrow().clone();
-            if !self.is_none(&trace_func) {
-                self.use_tracing.replace(false);
-                let res = self.invoke(&trace_func, args.clone());
-                self.use_tracing.replace(true);
-                res?;
+            let trace_func = match event {
+                TraceEvent::Call => Some(self.trace_func.borrow().clone()),
+                TraceEvent::Line
+                | TraceEvent::Return
+                | TraceEvent::Exception
+                | TraceEvent::Opcode => frame.trace.borrow().clone(),
+            };
+            if let Some(trace_func) = trace_func {
+                if !self.is_none(&trace_func) {
+                    // The trace function may change the fast locals through `f_locals`.
+                    frame.fast_to_locals(self)?;
+                    self.use_tracing.replace(false);
+                    let res = self.invoke(&trace_func, args.clone());
+                    self.use_tracing.replace(true);
+                    let local_trace = res?;
+                    frame.locals_to_fast(self)?;
+                    match event {
+                        TraceEvent::Call
+                        | TraceEvent::Line
+                        | TraceEvent::Exception
+                        | TraceEvent::Opcode => {
+                            frame.trace.replace(Some(local_trace));
+                        }
+                        TraceEvent::Return => {}
+                    }
+                }
             }
 
             let profile_func = self.profile_func.borrow().clone();
             if !self.is_none(&profile_func) {
-                self.use_tracing.replace(false);
-                let res = self.invoke(&profile_func, args);
-                self.use_tracing.replace(true);
-                res?;
+                match event {
+                    TraceEvent::Call | TraceEvent::Return => {
+                        self.use_tracing.replace(false);
+                        let res = self.invoke(&profile_func, args);
+                        self.use_tracing.replace(true);
+                        res?;
+                    }
+                    TraceEvent::Line | TraceEvent::Exception | TraceEvent::Opcode => {}
+                }
             }
         }
         Ok(())
     }
 
+    /// Call `function`, the payload of `func_ref`.
     fn invoke_python_function(
         &self,
-        code: &PyCodeRef,
-        scope: &Scope,
-        defaults: &Option<PyTupleRef>,
-        kw_only_defaults: &Option<PyDictRef>,
+        func_ref: &PyObjectRef,
+        function: &PyFunction,
         func_args: PyFuncArgs,
     ) -> PyResult {
-        let scope = scope.new_child_scope(&self.ctx);
+        let code = &function.code;
+        let scope = function.scope.new_child_scope(&self.ctx);
         self.fill_locals_from_args(
             &code.code,
             &scope.get_locals(),
             func_args,
-            defaults,
-            kw_only_defaults,
+            &function.defaults,
+            &function.kw_only_defaults,
         )?;
 
         // Construct frame:
-        let frame = Frame::new(code.clone(), scope).into_ref(self);
-
-        // If we have a generator, create a new generator
-        if code.code.is_generator {
-            Ok(PyGenerator::new(frame, self).into_object())
+        let frame = Frame::new(code.clone(), scope, function.closure.as_ref(), self).into_ref(self);
+
+        // If we have a generator or coroutine, create it instead of running the frame
+        if code.code.is_coroutine || code.code.is_generator {
+            let name = self.function_name(func_ref, "__name__", &code.code)?;
+            let qualname = self.function_name(func_ref, "__qualname__", &code.code)?;
+            if code.code.is_coroutine {
+                Ok(PyCoroutine::new(frame, name, qualname, self).into_object())
+            } else {
+                Ok(PyGenerator::new(frame, name, qualname, self).into_object())
+            }
         } else {
             self.run_frame_full(frame)
         }
     }
 
+    /// The `__name__` or `__qualname__` of a function, which is the name of
+    /// its code if it has none.
+    fn function_name(
+        &self,
+        func_ref: &PyObjectRef,
+        attr: &str,
+        code: &bytecode::CodeObject,
+    ) -> PyResult<PyStringRef> {
+        match self.get_attribute(func_ref.clone(), attr) {
+            Ok(name) => PyStringRef::try_from_object(self, name),
+            Err(_) => Ok(PyString::from(code.obj_name.as_str()).into_ref(self)),
+        }
+    }
+
     pub fn invoke_with_locals(
         &self,
         function: &PyObjectRef,
         cells: PyDictRef,
         locals: PyDictRef,
     ) -> PyResult {
-        if let Some(PyFunction { code, scope, .. }) = &function.payload() {
+        if let Some(PyFunction {
+            code,
+            scope,
+            closure,
+            ..
+        }) = &function.payload()
+        {
             let scope = scope
                 .new_child_scope_with_locals(cells)
                 .new_child_scope_with_locals(locals);
-            let frame = Frame::new(code.clone(), scope).into_ref(self);
+            let frame = Frame::new(code.clone(), scope, closure.as_ref(), self).into_ref(self);
             return self.run_frame_full(frame);
         }
         panic!(
@@ -725,9 +910,14 @@ impl VirtualMachine {
         };
 
         // Handle keyword arguments
+        let posonly_names = &code_object.arg_names[..code_object.posonlyarg_count];
+        let mut posonly_passed_as_kwarg = vec![];
         for (name, value) in func_args.kwargs {
+            let is_posonly = posonly_names.contains(&name);
             // Check if we have a parameter with this name:
-            if code_object.arg_names.contains(&name) || code_object.kwonlyarg_names.contains(&name)
+            if !is_posonly
+                && (code_object.arg_names.contains(&name)
+                    || code_object.kwonlyarg_names.contains(&name))
             {
                 if locals.contains_key(&name, self) {
                     return Err(
@@ -738,12 +928,21 @@ impl VirtualMachine {
                 locals.set_item(&name, value, self)?;
             } else if let Some(d) = &kwargs {
                 d.set_item(&name, value, self)?;
+            } else if is_posonly {
+                posonly_passed_as_kwarg.push(name);
             } else {
                 return Err(
                     self.new_type_error(format!("Got an unexpected keyword argument '{}'", name))
                 );
             }
         }
+        if !posonly_passed_as_kwarg.is_empty() {
+            return Err(self.new_type_error(format!(
+                "{}() got some positional-only arguments passed as keyword arguments: '{}'",
+                code_object.obj_name,
+                posonly_passed_as_kwarg.join(", ")
+            )));
+        }
 
         // Add missing positional arguments, if we have fewer positional arguments than the
         // function definition calls for
@@ -781,6 +980,7 @@ impl VirtualMachine {
         };
 
         // Check if kw only arguments are all present:
+        let mut missing = vec![];
         for arg_name in &code_object.kwonlyarg_names {
             if !locals.contains_key(arg_name, self) {
                 if let Some(kw_only_defaults) = kw_only_defaults {
@@ -791,10 +991,18 @@ impl VirtualMachine {
                 }
 
                 // No default value and not specified.
-                return Err(self
-                    .new_type_error(format!("Missing required kw only argument: '{}'", arg_name)));
+                missing.push(arg_name);
             }
         }
+        if !missing.is_empty() {
+            return Err(self.new_type_error(format!(
+                "{}() missing {} required keyword-only argument{}: {}",
+                code_object.obj_name,
+                missing.len(),
+                if missing.len() == 1 { "" } else { "s" },
+                format_missing_names(&missing)
+            )));
+        }
 
         Ok(())
     }
@@ -899,7 +1107,8 @@ impl VirtualMachine {
     ///
     /// 1. Calls `__and__` with `lhs` and `rhs`.
     /// 2. If above is not implemented, calls `__rand__` with `rhs` and `lhs`.
-    /// 3. If above is not implemented, invokes `unsupported` for the result.
+    /// 3. If above is not implemented, invokes `unsupported` with `lhs` and `rhs`
+    ///    for the result.
     pub fn call_or_reflection(
         &self,
         lhs: PyObjectRef,
@@ -910,8 +1119,11 @@ impl VirtualMachine {
     ) -> PyResult {
         // Try to call the default method
         self.call_or_unsupported(lhs, rhs, default, move |vm, lhs, rhs| {
-            // Try to call the reflection method
-            vm.call_or_unsupported(rhs, lhs, reflection, unsupported)
+            // Try to call the reflection method, but report the operands
+            // to `unsupported` in their original order
+            vm.call_or_unsupported(rhs, lhs, reflection, |vm, rhs, lhs| {
+                unsupported(vm, lhs, rhs)
+            })
         })
     }
 
@@ -1239,6 +1451,18 @@ impl VirtualMachine {
     }
 }
 
+/// List the names of missing arguments like CPython does: `'a'`, `'a' and 'b'`
+/// or `'a', 'b', and 'c'`.
+fn format_missing_names(names: &[&String]) -> String {
+    let quoted: Vec<_> = names.iter().map(|name| format!("'{}'", name)).collect();
+    match quoted.split_last() {
+        Some((last, [])) => last.clone(),
+        Some((last, [first])) => format!("{} and {}", first, last),
+        Some((last, rest)) => format!("{}, and {}", rest.join(", "), last),
+        None => String::new(),
+    }
+}
+
 impl Default for VirtualMachine {
     fn default() -> Self {
         VirtualMachine::new(Default::default())
@@ -1306,4 +1530,116 @@ mod tests {
         let value = objstr::get_value(&res);
         assert_eq!(value, String::from("Hello Hello Hello Hello "))
     }
+
+    #[test]
+    fn test_eval_frame_func() {
+        use super::EvalFrameFunc;
+        use rustpython_compiler::compile;
+        use std::cell::RefCell;
+        use std::rc::Rc;
+
+        let vm: VirtualMachine = Default::default();
+        let names = Rc::new(RefCell::new(Vec::new()));
+        let logged_names = names.clone();
+        let eval_frame: EvalFrameFunc = Rc::new(move |vm, frame| {
+            logged_names.borrow_mut().push(frame.code.obj_name.clone());
+            vm.eval_frame_default(frame)
+        });
+        vm.set_eval_frame_func(Some(eval_frame));
+
+        let source = "def square(x):\n    return x * x\nresult = square(3) + square(4)\n";
+        let code = vm
+            .compile(source, compile::Mode::Exec, "<unittest>".to_string())
+            .unwrap();
+        let scope = vm.new_scope_with_builtins();
+        vm.run_code_obj(code, scope.clone()).unwrap();
+        let result = scope
+            .globals
+            .get_item_option("result", &vm)
+            .unwrap()
+            .unwrap();
+        assert_eq!(*objint::get_value(&result), 25_i32.to_bigint().unwrap());
+        assert_eq!(*names.borrow(), vec!["<module>", "square", "square"]);
+
+        vm.set_eval_frame_func(None);
+        let code = vm
+            .compile("square(5)\n", compile::Mode::Exec, "<unittest>".to_string())
+            .unwrap();
+        vm.run_code_obj(code, scope).unwrap();
+        assert_eq!(names.borrow().len(), 3);
+    }
+
+    #[test]
+    fn test_code_object_round_trip() {
+        use crate::bytecode::{CodeDeserializeError, CodeObject};
+        use rustpython_compiler::compile;
+
+        let vm: VirtualMachine = Default::default();
+        let source = "def collatz(n):\n    steps = 0\n    while n != 1:\n        n = n // 2 if n % 2 == 0 else 3 * n + 1\n        steps += 1\n    return steps\nresult = [collatz(n) for n in range(1, 8)]\n";
+        let code = vm
+            .compile(source, compile::Mode::Exec, "<unittest>".to_string())
+            .unwrap();
+        let data = code.code.to_bytes();
+        let mut loaded = CodeObject::from_bytes(&data).unwrap();
+        // Everything but the source text, which isn't serialized, round-trips.
+        assert!(loaded.source.is_none());
+        loaded.set_source(code.code.source.clone().unwrap());
+        assert!(loaded == code.code);
+
+        let scope = vm.new_scope_with_builtins();
+        vm.run_code_obj(vm.ctx.new_code_object(loaded), scope.clone())
+            .unwrap();
+        let result = scope
+            .globals
+            .get_item_option("result", &vm)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            vm.to_repr(&result).unwrap().as_str(),
+            "[0, 1, 7, 2, 5, 8, 16]"
+        );
+
+        let mut other_version = data.clone();
+        other_version[3] += 1;
+        assert_eq!(
+            CodeObject::from_bytes(&other_version),
+            Err(CodeDeserializeError::BadMagicNumber)
+        );
+        assert_eq!(
+            CodeObject::from_bytes(&data[..data.len() / 2]),
+            Err(CodeDeserializeError::Malformed)
+        );
+    }
+
+    #[cfg(feature = "vm-opcode-stats")]
+    #[test]
+    fn test_opcode_stats() {
+        use crate::obj::objdict::PyDictRef;
+        use crate::pyobject::TryFromObject;
+        use num_traits::ToPrimitive;
+        use rustpython_compiler::compile;
+
+        let vm: VirtualMachine = Default::default();
+        let source = "total = 0\nfor i in range(100):\n    total = total + i\n";
+        let code = vm
+            .compile(source, compile::Mode::Exec, "<unittest>".to_string())
+            .unwrap();
+        vm.run_code_obj(code, vm.new_scope_with_builtins()).unwrap();
+
+        let stats = vm
+            .get_attribute(vm.sys_module.clone(), "_opcode_stats")
+            .and_then(|stats| vm.invoke(&stats, vec![]))
+            .unwrap();
+        let stats = PyDictRef::try_from_object(&vm, stats).unwrap();
+        let count = |name: &str| {
+            stats
+                .get_item_option(name, &vm)
+                .unwrap()
+                .map_or(0, |count| objint::get_value(&count).to_usize().unwrap())
+        };
+        assert!(count("BinaryOperation") >= 100);
+        assert!(count("ForIter") >= 101);
+        assert!(count("LoadName") >= 200);
+        assert_eq!(count("YieldValue"), 0);
+    }
 }